@@ -0,0 +1,259 @@
+//! Derive macro generating the conversion of a type of the source schema into its interned
+//! representation, along with the `EqWith` implementation that checks this conversion.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{parenthesized, parse_macro_input, DeriveInput, Error, Ident, Path, Result, Token};
+
+/// Derives `FromSource<S>` and `EqWith<S, Arenas>`, where `S` is the source type given by the
+/// `#[intern(source = path::to::Source)]` attribute on the struct.
+///
+/// Each field is converted from the source field of the same name, according to its
+/// `#[intern(...)]` attribute:
+/// - no attribute: the value is converted with `FromSource` and compared with `EqWith`,
+/// - `string`: the string is interned in the string arena,
+/// - `<arena>`: the value is converted with `FromSource` and interned in the given arena,
+/// - `set(<kind>)`: each item is converted according to `<kind>` and collected into a set,
+/// - `set(<kind>, <arena>)`: the set of converted items is interned in the given arena,
+/// - `seq(<kind>)`, `seq(<kind>, <arena>)`: like `set`, but the items are compared in order, for
+///   sequences whose order is preserved,
+/// - `option(<kind>)`: the value, if any, is converted according to `<kind>`,
+/// - `flatten(<kind>)`: the whole source struct is converted according to `<kind>`, rather than a
+///   single field.
+///
+/// Sets are compared by looking up the handle of each source item without interning it, so their
+/// items must be of the `string` or `<arena>` kinds.
+///
+/// The generated code refers to `Arenas`, `Error`, `FromSource`, `TryIntern`, `EqWith`,
+/// `intern_from`, `find_from` and `option_eq_by`, which must be in scope. Sets and sequences must
+/// provide `try_intern`, `set_eq_by_key` and `seq_eq_by` respectively.
+#[proc_macro_derive(FromSource, attributes(intern))]
+pub fn derive_from_source(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+enum Kind {
+    Value,
+    String,
+    Arena(Ident),
+    Set(Box<Kind>, Option<Ident>),
+    Seq(Box<Kind>, Option<Ident>),
+    Option(Box<Kind>),
+}
+
+impl Kind {
+    fn parse_after(ident: Ident, input: ParseStream) -> Result<Self> {
+        if ident == "string" {
+            Ok(Kind::String)
+        } else if ident == "set" || ident == "seq" {
+            let content;
+            parenthesized!(content in input);
+            let kind = content.parse()?;
+            let arena = if content.is_empty() {
+                None
+            } else {
+                content.parse::<Token![,]>()?;
+                Some(content.parse()?)
+            };
+            if ident == "set" {
+                Ok(Kind::Set(Box::new(kind), arena))
+            } else {
+                Ok(Kind::Seq(Box::new(kind), arena))
+            }
+        } else if ident == "option" {
+            let content;
+            parenthesized!(content in input);
+            Ok(Kind::Option(Box::new(content.parse()?)))
+        } else {
+            Ok(Kind::Arena(ident))
+        }
+    }
+
+    // Expression converting the given `&S` value, propagating errors with `?`.
+    fn convert(&self, value: TokenStream2) -> TokenStream2 {
+        let result = self.try_convert(value);
+        quote!(#result?)
+    }
+
+    // Expression converting the given `&S` value into a `Result`.
+    fn try_convert(&self, value: TokenStream2) -> TokenStream2 {
+        match self {
+            Kind::Value => quote!(FromSource::from_source(arenas, #value)),
+            Kind::String => quote!(arenas.string.try_intern(#value)),
+            Kind::Arena(arena) => quote!(intern_from(&arenas.#arena, arenas, #value)),
+            Kind::Set(kind, Some(arena)) | Kind::Seq(kind, Some(arena)) => {
+                let convert = kind.try_convert(quote!(x));
+                quote!(arenas.#arena.try_intern(
+                    (#value).iter().map(|x| #convert).collect::<Result<Vec<_>, _>>()?
+                ))
+            }
+            Kind::Set(kind, None) | Kind::Seq(kind, None) => {
+                let convert = kind.try_convert(quote!(x));
+                quote!((#value).iter().map(|x| #convert).collect::<Result<_, _>>())
+            }
+            Kind::Option(kind) => {
+                let convert = kind.try_convert(quote!(x));
+                quote!((#value).as_ref().map(|x| #convert).transpose())
+            }
+        }
+    }
+
+    // Expression looking up the handle of the given `&S` value into an `Option`, without
+    // interning it.
+    fn find(&self, value: TokenStream2) -> std::result::Result<TokenStream2, &'static str> {
+        match self {
+            Kind::String => Ok(quote!(arenas.string.find(#value))),
+            Kind::Arena(arena) => Ok(quote!(find_from(&arenas.#arena, arenas, #value))),
+            _ => Err("set items must be of the `string` or `<arena>` kinds"),
+        }
+    }
+
+    // Expression comparing the given `&T` converted value with the given `&S` source value.
+    fn eq(
+        &self,
+        lhs: TokenStream2,
+        rhs: TokenStream2,
+    ) -> std::result::Result<TokenStream2, &'static str> {
+        Ok(match self {
+            Kind::Value => quote!((#lhs).eq_with(#rhs, arenas)),
+            Kind::String => quote!((#lhs).eq_with(#rhs, &arenas.string)),
+            Kind::Arena(arena) => quote!(arenas.#arena.lookup_ref(*#lhs).eq_with(#rhs, arenas)),
+            Kind::Set(kind, None) => {
+                let find = kind.find(quote!(x))?;
+                quote!((#lhs).set_eq_by_key(#rhs, |x| #find))
+            }
+            Kind::Set(kind, Some(arena)) => {
+                let find = kind.find(quote!(x))?;
+                quote!(arenas.#arena.lookup(*#lhs).set_eq_by_key(#rhs, |x| #find))
+            }
+            Kind::Seq(kind, None) => {
+                let eq = kind.eq(quote!(x), quote!(y))?;
+                quote!((#lhs).seq_eq_by(#rhs, |x, y| #eq))
+            }
+            Kind::Seq(kind, Some(arena)) => {
+                let eq = kind.eq(quote!(x), quote!(y))?;
+                quote!(arenas.#arena.lookup(*#lhs).seq_eq_by(#rhs, |x, y| #eq))
+            }
+            Kind::Option(kind) => {
+                let eq = kind.eq(quote!(x), quote!(y))?;
+                quote!(option_eq_by(#lhs, #rhs, |x, y| #eq))
+            }
+        })
+    }
+}
+
+impl Parse for Kind {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let ident = input.parse()?;
+        Kind::parse_after(ident, input)
+    }
+}
+
+struct FieldAttr {
+    kind: Kind,
+    flatten: bool,
+}
+
+impl Parse for FieldAttr {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let ident: Ident = input.parse()?;
+        if ident == "flatten" {
+            let content;
+            parenthesized!(content in input);
+            Ok(FieldAttr {
+                kind: content.parse()?,
+                flatten: true,
+            })
+        } else {
+            Ok(FieldAttr {
+                kind: Kind::parse_after(ident, input)?,
+                flatten: false,
+            })
+        }
+    }
+}
+
+fn expand(input: DeriveInput) -> Result<TokenStream2> {
+    let name = &input.ident;
+
+    let mut source: Option<Path> = None;
+    for attr in &input.attrs {
+        if attr.path().is_ident("intern") {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("source") {
+                    source = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("unsupported intern attribute"))
+                }
+            })?;
+        }
+    }
+    let source = source
+        .ok_or_else(|| Error::new_spanned(name, "missing #[intern(source = ...)] attribute"))?;
+
+    let fields = match &input.data {
+        syn::Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(Error::new_spanned(
+                name,
+                "FromSource can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut converts = Vec::new();
+    let mut eqs = Vec::new();
+    for field in fields {
+        let ident = field.ident.as_ref().ok_or_else(|| {
+            Error::new_spanned(field, "FromSource can only be derived for named fields")
+        })?;
+
+        let mut attr = FieldAttr {
+            kind: Kind::Value,
+            flatten: false,
+        };
+        for a in &field.attrs {
+            if a.path().is_ident("intern") {
+                attr = a.parse_args()?;
+            }
+        }
+
+        let (value, other) = if attr.flatten {
+            (quote!(source), quote!(other))
+        } else {
+            (quote!(&source.#ident), quote!(&other.#ident))
+        };
+        let convert = attr.kind.convert(value);
+        let eq = attr
+            .kind
+            .eq(quote!(&self.#ident), other)
+            .map_err(|message| Error::new_spanned(field, message))?;
+        converts.push(quote!(#ident: #convert));
+        eqs.push(eq);
+    }
+    if eqs.is_empty() {
+        eqs.push(quote!(true));
+    }
+
+    Ok(quote! {
+        impl FromSource<#source> for #name {
+            fn from_source(arenas: &Arenas, source: &#source) -> Result<Self, Error> {
+                Ok(Self {
+                    #(#converts,)*
+                })
+            }
+        }
+
+        impl EqWith<#source, Arenas> for #name {
+            fn eq_with(&self, other: &#source, arenas: &Arenas) -> bool {
+                #(#eqs)&&*
+            }
+        }
+    })
+}
@@ -0,0 +1,175 @@
+// Instrumented global allocator, which keeps track of the bytes currently allocated on the heap, so
+// that the sizes estimated with `GetSize` can be compared with the actual heap usage. Counting every
+// allocation has a cost, so the allocator is only installed with the `alloc-stats` feature, and
+// phases don't measure nor estimate anything otherwise.
+//
+// The requested sizes are counted, excluding the overhead of the system allocator (headers and size
+// classes), which depends on the platform.
+//
+// The peak resident memory reported by the OS is available regardless of the feature, and is logged
+// at the debug level (i.e. with `-v`) when a phase finishes and at the end of the run, so that it
+// doesn't clutter the output of every command.
+
+use tracing::debug;
+
+#[cfg(feature = "alloc-stats")]
+mod counting {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+    static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+    // Highest value of `ALLOCATED_BYTES` since the start of the run, and since the start of the
+    // current phase.
+    static PEAK_BYTES: AtomicUsize = AtomicUsize::new(0);
+    static PHASE_PEAK_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+    fn grow(bytes: usize) {
+        let allocated = ALLOCATED_BYTES.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        PEAK_BYTES.fetch_max(allocated, Ordering::Relaxed);
+        PHASE_PEAK_BYTES.fetch_max(allocated, Ordering::Relaxed);
+    }
+
+    struct Counting;
+
+    #[global_allocator]
+    static GLOBAL: Counting = Counting;
+
+    unsafe impl GlobalAlloc for Counting {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            // SAFETY: The caller upholds the contract of `GlobalAlloc::alloc`.
+            let ptr = unsafe { System.alloc(layout) };
+            if !ptr.is_null() {
+                grow(layout.size());
+                ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            }
+            ptr
+        }
+
+        unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+            // SAFETY: The caller upholds the contract of `GlobalAlloc::alloc_zeroed`.
+            let ptr = unsafe { System.alloc_zeroed(layout) };
+            if !ptr.is_null() {
+                grow(layout.size());
+                ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            }
+            ptr
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            // SAFETY: The caller upholds the contract of `GlobalAlloc::dealloc`.
+            unsafe { System.dealloc(ptr, layout) };
+            ALLOCATED_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+            ALLOCATIONS.fetch_sub(1, Ordering::Relaxed);
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            // SAFETY: The caller upholds the contract of `GlobalAlloc::realloc`.
+            let new_ptr = unsafe { System.realloc(ptr, layout, new_size) };
+            if !new_ptr.is_null() {
+                ALLOCATED_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+                grow(new_size);
+            }
+            new_ptr
+        }
+    }
+
+    /// Returns the number of bytes and of allocations currently live on the heap.
+    pub fn allocated() -> (usize, usize) {
+        (
+            ALLOCATED_BYTES.load(Ordering::Relaxed),
+            ALLOCATIONS.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Returns the highest number of bytes live on the heap since the start of the run.
+    pub fn peak() -> usize {
+        PEAK_BYTES.load(Ordering::Relaxed)
+    }
+
+    /// Returns the highest number of bytes live on the heap since the last call, and restarts from
+    /// the bytes currently live.
+    pub fn take_phase_peak() -> usize {
+        PHASE_PEAK_BYTES.swap(ALLOCATED_BYTES.load(Ordering::Relaxed), Ordering::Relaxed)
+    }
+}
+
+/// Phase of a command over which the heap growth is measured.
+#[cfg(feature = "alloc-stats")]
+pub struct Phase {
+    name: &'static str,
+    estimated: usize,
+    allocated: (usize, usize),
+}
+
+#[cfg(feature = "alloc-stats")]
+impl Phase {
+    /// Starts a phase, given the estimated size of the data structures that it modifies.
+    pub fn start(name: &'static str, estimated: impl FnOnce() -> usize) -> Self {
+        counting::take_phase_peak();
+        Self {
+            name,
+            estimated: estimated(),
+            allocated: counting::allocated(),
+        }
+    }
+
+    /// Prints the estimated growth of the data structures modified by the phase, along with the
+    /// growth of the heap actually measured and its peak during the phase.
+    pub fn finish(self, estimated: impl FnOnce() -> usize) {
+        let (bytes, allocations) = counting::allocated();
+        let peak = counting::take_phase_peak();
+        let estimated = estimated() as i64 - self.estimated as i64;
+        let measured = bytes as i64 - self.allocated.0 as i64;
+        let allocations = allocations as i64 - self.allocated.1 as i64;
+        println!(
+            "Heap usage of {}: estimated {estimated} bytes, measured {measured} bytes in {allocations} allocations (estimate = {:.02}% of measured), peak {peak} bytes live",
+            self.name,
+            estimated as f64 * 100.0 / measured as f64,
+        );
+        log_peak_resident(self.name);
+    }
+}
+
+/// Phase of a command, whose heap growth isn't measured without the `alloc-stats` feature.
+#[cfg(not(feature = "alloc-stats"))]
+pub struct Phase {
+    name: &'static str,
+}
+
+#[cfg(not(feature = "alloc-stats"))]
+impl Phase {
+    pub fn start(name: &'static str, _estimated: impl FnOnce() -> usize) -> Self {
+        Phase { name }
+    }
+
+    pub fn finish(self, _estimated: impl FnOnce() -> usize) {
+        log_peak_resident(self.name);
+    }
+}
+
+fn log_peak_resident(phase: &str) {
+    if let Some(peak_resident_bytes) = peak_resident_bytes() {
+        debug!(phase, peak_resident_bytes, "Finished phase");
+    }
+}
+
+/// Logs the high-water marks of the memory used by the whole run.
+pub fn log_peak() {
+    #[cfg(feature = "alloc-stats")]
+    tracing::info!(peak_heap_bytes = counting::peak(), "Peak heap usage");
+    if let Some(peak_resident_bytes) = peak_resident_bytes() {
+        debug!(peak_resident_bytes, "Peak resident memory");
+    }
+}
+
+// Peak resident set size of the process so far, as reported by Linux. This includes the memory
+// that the allocator retains after it's freed, as well as the memory-mapped databases.
+fn peak_resident_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))?;
+    let kilobytes: u64 = line.trim().strip_suffix("kB")?.trim().parse().ok()?;
+    Some(kilobytes * 1024)
+}
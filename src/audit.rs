@@ -0,0 +1,274 @@
+// Audit of the information lost by the optimized representation. Each input file is converted to
+// the optimized schema and back, and the regenerated JSON is compared with the original one. Both
+// sides are canonicalized by parsing them as JSON values, whose object keys are sorted, so that
+// whitespace and key order aren't reported.
+//
+// Unlike the explanation of verification failures, all the differences are collected. They are
+// identified by the path of the field with array indices elided, e.g. `disruptions[].lastUpdate`,
+// so that they can be aggregated across files.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+// Maximum length of the values printed in an example.
+const MAX_VALUE_LEN: usize = 60;
+
+/// How a field differs between the original and the regenerated JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Loss {
+    /// The value is different, e.g. a timestamp formatted differently.
+    Changed,
+    /// The array contains the same elements in another order.
+    Reordered,
+    /// The field or array element isn't regenerated.
+    Missing,
+    /// The field or array element is only in the regenerated JSON.
+    Added,
+}
+
+/// Differences found in a field of a file.
+#[derive(Debug, Serialize)]
+pub struct FieldLoss {
+    pub field: String,
+    pub loss: Loss,
+    pub count: usize,
+    /// First difference found, as `original -> regenerated`.
+    pub example: String,
+}
+
+/// Differences found in a file, if any.
+#[derive(Debug, Serialize)]
+pub struct FileAudit {
+    pub path: PathBuf,
+    pub losses: Vec<FieldLoss>,
+}
+
+impl FileAudit {
+    /// Compares the original JSON of a file with the JSON regenerated from its optimized
+    /// representation.
+    pub fn new(path: &Path, original: &Value, regenerated: &Value) -> Self {
+        let mut losses = BTreeMap::new();
+        let mut field = String::new();
+        audit(&mut field, original, regenerated, &mut losses);
+        Self {
+            path: path.to_owned(),
+            losses: losses
+                .into_iter()
+                .map(|((field, loss), (count, example))| FieldLoss {
+                    field,
+                    loss,
+                    count,
+                    example,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn is_lossless(&self) -> bool {
+        self.losses.is_empty()
+    }
+}
+
+type Losses = BTreeMap<(String, Loss), (usize, String)>;
+
+fn record(losses: &mut Losses, field: &str, loss: Loss, example: impl FnOnce() -> String) {
+    let field = if field.is_empty() { "<root>" } else { field };
+    losses
+        .entry((field.to_owned(), loss))
+        .and_modify(|(count, _)| *count += 1)
+        .or_insert_with(|| (1, example()));
+}
+
+fn audit(field: &mut String, original: &Value, regenerated: &Value, losses: &mut Losses) {
+    match (original, regenerated) {
+        (Value::Object(original), Value::Object(regenerated)) => {
+            for (key, original_value) in original {
+                let len = field.len();
+                if !field.is_empty() {
+                    field.push('.');
+                }
+                field.push_str(key);
+                match regenerated.get(key) {
+                    Some(regenerated_value) => {
+                        audit(field, original_value, regenerated_value, losses)
+                    }
+                    None => record(losses, field, Loss::Missing, || {
+                        format!("{} -> <none>", truncated(original_value))
+                    }),
+                }
+                field.truncate(len);
+            }
+            for (key, regenerated_value) in regenerated {
+                if !original.contains_key(key) {
+                    let field = if field.is_empty() {
+                        key.clone()
+                    } else {
+                        format!("{field}.{key}")
+                    };
+                    record(losses, &field, Loss::Added, || {
+                        format!("<none> -> {}", truncated(regenerated_value))
+                    });
+                }
+            }
+        }
+        (Value::Array(original), Value::Array(regenerated)) => {
+            audit_array(field, original, regenerated, losses)
+        }
+        _ if original == regenerated => (),
+        _ => record(losses, field, Loss::Changed, || {
+            format!("{} -> {}", truncated(original), truncated(regenerated))
+        }),
+    }
+}
+
+fn audit_array(field: &mut String, original: &[Value], regenerated: &[Value], losses: &mut Losses) {
+    if original == regenerated {
+        return;
+    }
+
+    // Pair the elements that are equal up to the order of nested arrays first, as sets are
+    // re-sorted by the interning, then the elements with the same ID, and then the remaining
+    // elements in order.
+    let canonical_original: Vec<Value> = original.iter().map(canonical).collect();
+    let canonical_regenerated: Vec<Value> = regenerated.iter().map(canonical).collect();
+    let mut pairs: Vec<(usize, usize)> = Vec::new();
+    let mut used = vec![false; regenerated.len()];
+    let mut unmatched = Vec::new();
+    for (i, x) in canonical_original.iter().enumerate() {
+        match (0..regenerated.len()).find(|&j| !used[j] && canonical_regenerated[j] == *x) {
+            Some(j) => {
+                used[j] = true;
+                pairs.push((i, j));
+            }
+            None => unmatched.push(i),
+        }
+    }
+    unmatched.retain(|&i| {
+        let Some(id) = original[i].get("id") else {
+            return true;
+        };
+        match (0..regenerated.len()).find(|&j| !used[j] && regenerated[j].get("id") == Some(id)) {
+            Some(j) => {
+                used[j] = true;
+                pairs.push((i, j));
+                false
+            }
+            None => true,
+        }
+    });
+    let extra: Vec<usize> = (0..regenerated.len()).filter(|&j| !used[j]).collect();
+    pairs.extend(unmatched.iter().copied().zip(extra.iter().copied()));
+    pairs.sort_unstable();
+
+    if !pairs.is_sorted_by_key(|&(_, j)| j) {
+        record(losses, field, Loss::Reordered, || {
+            let (i, j) = pairs
+                .iter()
+                .find(|(i, j)| i != j)
+                .copied()
+                .unwrap_or_default();
+            format!("{} at [{i}] -> [{j}]", truncated(&original[i]))
+        });
+    }
+
+    let len = field.len();
+    field.push_str("[]");
+    for &(i, j) in &pairs {
+        audit(field, &original[i], &regenerated[j], losses);
+    }
+    for &i in unmatched.iter().skip(extra.len()) {
+        record(losses, field, Loss::Missing, || {
+            format!("{} -> <none>", truncated(&original[i]))
+        });
+    }
+    for &j in extra.iter().skip(unmatched.len()) {
+        record(losses, field, Loss::Added, || {
+            format!("<none> -> {}", truncated(&regenerated[j]))
+        });
+    }
+    field.truncate(len);
+}
+
+// Returns the value with all its nested arrays sorted, to compare values as sets.
+fn canonical(value: &Value) -> Value {
+    match value {
+        Value::Array(values) => {
+            let mut values: Vec<Value> = values.iter().map(canonical).collect();
+            values.sort_by_cached_key(Value::to_string);
+            Value::Array(values)
+        }
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, value)| (key.clone(), canonical(value)))
+                .collect(),
+        ),
+        _ => value.clone(),
+    }
+}
+
+/// Prints the lossy fields of each file, followed by the number of files affected by each of them.
+pub fn print_audits(audits: &[FileAudit], file_count: usize) {
+    let lossy: Vec<&FileAudit> = audits.iter().filter(|x| !x.is_lossless()).collect();
+    println!(
+        "{} of {file_count} files are reproduced exactly, {} are not",
+        file_count - lossy.len(),
+        lossy.len(),
+    );
+    for audit in &lossy {
+        let fields: Vec<String> = audit
+            .losses
+            .iter()
+            .map(|x| format!("{} ({:?}, {}×)", x.field, x.loss, x.count))
+            .collect();
+        println!("- {:?}: {}", audit.path, fields.join(", "));
+    }
+
+    // Files, occurrences and an example of each kind of loss.
+    let mut summary: BTreeMap<(&str, Loss), (usize, usize, &str)> = BTreeMap::new();
+    for audit in &lossy {
+        for x in &audit.losses {
+            let entry = summary
+                .entry((&x.field, x.loss))
+                .or_insert((0, 0, &x.example));
+            entry.0 += 1;
+            entry.1 += x.count;
+        }
+    }
+    if !summary.is_empty() {
+        println!("Lossy fields:");
+    }
+    for ((field, loss), (files, count, example)) in summary {
+        println!("  {field}: {loss:?} in {files} files ({count} occurrences), e.g. {example}",);
+    }
+}
+
+/// Writes the lossy files as a JSON array to the given path, if any.
+pub fn write_audit_report(
+    path: Option<&Path>,
+    audits: &[FileAudit],
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(path) = path {
+        tracing::info!(?path, "Writing audit report");
+        let lossy: Vec<&FileAudit> = audits.iter().filter(|x| !x.is_lossless()).collect();
+        serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), &lossy)?;
+    }
+    Ok(())
+}
+
+fn truncated(value: &Value) -> String {
+    let mut value = value.to_string();
+    if value.len() > MAX_VALUE_LEN {
+        let mut end = MAX_VALUE_LEN;
+        while !value.is_char_boundary(end) {
+            end -= 1;
+        }
+        value.truncate(end);
+        value.push_str("...");
+    }
+    value
+}
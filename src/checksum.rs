@@ -0,0 +1,110 @@
+// Checksums of the sections of serialized databases, so that corruption (e.g. from an interrupted
+// write or bit rot) is reported with the section that it affects, before attempting to decode it.
+//
+// Each section is followed by the CRC32 of its bytes, as a little-endian u32. The binary formats
+// that are decoded as a whole consist of a single section covering the entire file. The stream and
+// indexed formats checksum their index, each snapshot record and the arenas separately. JSON files
+// aren't checksummed, so that they remain valid JSON documents. COBS frames of postcard embed the
+// checksum of their payload before the framing.
+
+use std::fmt::Display;
+use std::io::{self, Write};
+
+/// Length of a checksum, in bytes.
+pub const CHECKSUM_LEN: usize = 4;
+
+/// Appends the checksum of the section written since the `start` offset.
+pub fn seal(bytes: &mut Vec<u8>, start: usize) {
+    let checksum = crc32fast::hash(&bytes[start..]);
+    bytes.extend_from_slice(&checksum.to_le_bytes());
+}
+
+/// Appends the checksum of the whole buffer.
+pub fn sealed(mut bytes: Vec<u8>) -> Vec<u8> {
+    seal(&mut bytes, 0);
+    bytes
+}
+
+/// Checks a section followed by its checksum, returning the section without its checksum.
+pub fn verify(bytes: &[u8], section: impl Display) -> Result<&[u8], Box<dyn std::error::Error>> {
+    let (bytes, stored) = bytes
+        .split_last_chunk::<CHECKSUM_LEN>()
+        .ok_or_else(|| format!("Not enough bytes for the checksum of {section}"))?;
+    let stored = u32::from_le_bytes(*stored);
+    let computed = crc32fast::hash(bytes);
+    if stored != computed {
+        return Err(format!(
+            "Checksum mismatch in {section} (stored {stored:08x}, computed {computed:08x}), the database is corrupted"
+        )
+        .into());
+    }
+    Ok(bytes)
+}
+
+/// Writer that checksums the bytes written through it, to seal a section that is streamed rather
+/// than buffered.
+pub struct ChecksumWriter<W> {
+    inner: W,
+    hasher: crc32fast::Hasher,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: crc32fast::Hasher::new(),
+        }
+    }
+
+    /// Appends the checksum of the bytes written so far.
+    pub fn finish(mut self) -> io::Result<W> {
+        let checksum = self.hasher.finalize();
+        self.inner.write_all(&checksum.to_le_bytes())?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sections_are_verified() {
+        let mut bytes = b"header".to_vec();
+        bytes.extend_from_slice(b"section");
+        seal(&mut bytes, 6);
+        assert_eq!(bytes.len(), 6 + 7 + CHECKSUM_LEN);
+        assert_eq!(verify(&bytes[6..], "the section").unwrap(), b"section");
+
+        // Streaming a section through a writer seals it the same way.
+        let mut writer = ChecksumWriter::new(Vec::new());
+        writer.write_all(b"sec").unwrap();
+        writer.write_all(b"tion").unwrap();
+        assert_eq!(writer.finish().unwrap(), &bytes[6..]);
+        assert_eq!(sealed(b"section".to_vec()), &bytes[6..]);
+
+        let mut corrupted = bytes[6..].to_vec();
+        corrupted[0] ^= 1;
+        let error = verify(&corrupted, "the section").unwrap_err().to_string();
+        assert!(
+            error.starts_with("Checksum mismatch in the section"),
+            "{error}"
+        );
+        assert_eq!(
+            verify(&bytes[..3], "the section").unwrap_err().to_string(),
+            "Not enough bytes for the checksum of the section",
+        );
+    }
+}
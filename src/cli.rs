@@ -0,0 +1,739 @@
+use crate::codec;
+use crate::schema::anonymize::Anonymizer;
+use crate::schema::optimized::DEFAULT_TIMEZONE;
+use crate::schema::{Extras, Schema};
+use chrono_tz::Tz;
+use clap::builder::PossibleValuesParser;
+use clap::{Parser, Subcommand, ValueEnum};
+#[cfg(feature = "simd-json")]
+use std::cell::RefCell;
+use std::hash::{BuildHasher, RandomState};
+use std::num::NonZeroU64;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+#[cfg(not(feature = "simd-json"))]
+use std::sync::Once;
+
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Don't display a progress bar while processing input files.
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+    /// Format of the input files. By default, files with a `.ndjson` or `.jsonl` extension
+    /// (possibly followed by a compression extension) contain one snapshot per line.
+    #[arg(long, value_enum, default_value_t = InputFormat::Auto, global = true)]
+    pub input_format: InputFormat,
+    /// Increase the verbosity of the logs (can be repeated).
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    pub verbose: u8,
+    /// Format of the logs printed to stderr.
+    #[arg(long, value_enum, default_value_t = LogFormat::Text, global = true)]
+    pub log_format: LogFormat,
+    /// Timezone of the local datetimes in the input files, e.g. "America/New_York". Databases record
+    /// the timezone that they were built with, and loading them with another one is an error, as it
+    /// wouldn't regenerate the same datetimes.
+    #[arg(long, default_value_t = DEFAULT_TIMEZONE, global = true)]
+    pub timezone: Tz,
+    /// Instant chosen for the local datetimes that occur twice in the timezone, when clocks are
+    /// turned back. Either instant regenerates the same local datetime.
+    #[arg(long, value_enum, default_value_t = AmbiguousDatetimes::Earliest, global = true)]
+    pub ambiguous_datetimes: AmbiguousDatetimes,
+    /// Schema of the input files and of the databases built from them.
+    #[arg(long, value_enum, default_value_t = SchemaName::Disruptions, global = true)]
+    pub schema: SchemaName,
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Parse JSON files, intern them and serialize the resulting databases in all formats.
+    Build {
+        /// Directory where the serialized databases are written, along with their statistics in
+        /// `stats.json`. Required unless `--dry-run` is given.
+        #[arg(short, long, required_unless_present = "dry_run")]
+        output_dir: Option<PathBuf>,
+        /// Directories containing the JSON files to parse.
+        #[arg(required = true)]
+        input_dirs: Vec<PathBuf>,
+        #[command(flatten)]
+        codec_args: CodecArgs,
+        #[command(flatten)]
+        stats: StatsArgs,
+        #[command(flatten)]
+        report: ReportArgs,
+        #[command(flatten)]
+        verify: VerifyArgs,
+        #[command(flatten)]
+        parse: ParseArgs,
+        /// Record the provenance of each snapshot in the database: the checksum and modification
+        /// time of its input file, and when it was ingested.
+        #[arg(long)]
+        provenance: bool,
+        /// Parse and intern the files and print their statistics, without serializing the
+        /// databases nor writing any file.
+        #[arg(long, conflicts_with = "failure_report")]
+        dry_run: bool,
+    },
+    /// Parse arbitrary JSON files without a schema, intern their strings, arrays and objects and
+    /// serialize the resulting databases in all formats.
+    BuildJson {
+        /// Directory where the serialized databases are written, along with their statistics in
+        /// `stats.json`. Required unless `--dry-run` is given.
+        #[arg(short, long, required_unless_present = "dry_run")]
+        output_dir: Option<PathBuf>,
+        /// Directories containing the JSON files to parse.
+        #[arg(required = true)]
+        input_dirs: Vec<PathBuf>,
+        #[command(flatten)]
+        codec_args: CodecArgs,
+        #[command(flatten)]
+        stats: StatsArgs,
+        #[command(flatten)]
+        report: ReportArgs,
+        /// Parse and intern the files and print their statistics, without serializing the
+        /// databases nor writing any file.
+        #[arg(long, conflicts_with = "failure_report")]
+        dry_run: bool,
+    },
+    /// Parse JSON files, intern them and stream each snapshot to the output file as soon as it's
+    /// parsed, so that only the arenas are kept in memory.
+    Stream {
+        /// Path of the database file to create.
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Directories containing the JSON files to parse.
+        #[arg(required = true)]
+        input_dirs: Vec<PathBuf>,
+        #[command(flatten)]
+        report: ReportArgs,
+    },
+    /// Load a serialized database, add the JSON files that it doesn't contain yet and serialize the
+    /// result in all formats.
+    Append {
+        #[command(flatten)]
+        database: DatabaseArgs,
+        /// Directory where the serialized databases are written, along with their statistics in
+        /// `stats.json`.
+        #[arg(short, long)]
+        output_dir: PathBuf,
+        /// Directories containing the JSON files to parse.
+        #[arg(required = true)]
+        input_dirs: Vec<PathBuf>,
+        #[command(flatten)]
+        codec_args: CodecArgs,
+        #[command(flatten)]
+        stats: StatsArgs,
+        #[command(flatten)]
+        report: ReportArgs,
+        #[command(flatten)]
+        verify: VerifyArgs,
+        #[command(flatten)]
+        parse: ParseArgs,
+        /// Record the provenance of each snapshot in the database: the checksum and modification
+        /// time of its input file, and when it was ingested.
+        #[arg(long)]
+        provenance: bool,
+    },
+    /// Merge several serialized databases into one and serialize the result in all formats.
+    Merge {
+        /// Directory where the serialized databases are written, along with their statistics in
+        /// `stats.json`.
+        #[arg(short, long)]
+        output_dir: PathBuf,
+        /// Serialization format of the input databases. Inferred from the file names if omitted.
+        #[arg(short, long)]
+        format: Option<Format>,
+        /// Paths to the serialized databases to merge.
+        #[arg(num_args = 2.., required = true)]
+        databases: Vec<PathBuf>,
+        #[command(flatten)]
+        codec_args: CodecArgs,
+        #[command(flatten)]
+        stats: StatsArgs,
+    },
+    /// Load a serialized database and print a summary of its contents.
+    Inspect {
+        #[command(flatten)]
+        database: DatabaseArgs,
+        /// Number of values listed for each arena in the report of reference counts, and of longest
+        /// strings listed in the report of string lengths.
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+        /// Detect the templates that the disruption messages are written from, and report how
+        /// much smaller the messages would be as a template and its parameters.
+        #[arg(long)]
+        message_templates: bool,
+    },
+    /// Load a serialized database and cluster the titles and messages of the disruptions by
+    /// similarity, to measure the redundancy that interning identical strings doesn't capture.
+    Similarity {
+        #[command(flatten)]
+        database: DatabaseArgs,
+        /// Minimum similarity between two strings of a cluster, as the Jaccard index of their sets
+        /// of 3 consecutive words. A single different word in a message of 25 words gives a
+        /// similarity of about 0.77.
+        #[arg(long, default_value_t = 0.6, value_parser = probability)]
+        threshold: f64,
+        /// Number of largest clusters listed for each field.
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+    },
+    /// Load a serialized database and report, for each snapshot, how many disruptions appeared,
+    /// disappeared or changed since the previous snapshot.
+    Churn {
+        #[command(flatten)]
+        database: DatabaseArgs,
+        /// Path of a JSON file where to write the churn of each snapshot.
+        #[arg(long)]
+        churn_report: Option<PathBuf>,
+    },
+    /// Load a serialized database and write a table of the lifetime of each disruption: its first
+    /// and last appearances, its number of versions and the evolution of its application periods.
+    Lifetimes {
+        #[command(flatten)]
+        database: DatabaseArgs,
+        /// Path of the table to create.
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Format of the table. Inferred from the extension of the output file if omitted.
+        #[arg(long, value_enum)]
+        table_format: Option<TableFormat>,
+    },
+    /// Search the titles and messages of the disruptions of a serialized database, and list the
+    /// disruptions containing all the given words, with their lines and application periods.
+    Search {
+        #[command(flatten)]
+        database: DatabaseArgs,
+        /// Words to search for, matched case-insensitively.
+        #[arg(required = true)]
+        terms: Vec<String>,
+        /// Maximum number of matching disruptions listed.
+        #[arg(long, default_value_t = 20)]
+        top: usize,
+    },
+    /// Load a serialized database and explore it interactively, with commands read from the standard
+    /// input: `lines`, `disruption <uuid>`, `snapshot <n>`, `stats` and `search <text>`.
+    Repl {
+        #[command(flatten)]
+        database: DatabaseArgs,
+    },
+    /// Run a query against a serialized database.
+    Query {
+        #[command(flatten)]
+        database: DatabaseArgs,
+        #[command(subcommand)]
+        query: Query,
+    },
+    /// Export a serialized database to another format.
+    Export {
+        #[command(flatten)]
+        database: DatabaseArgs,
+        #[command(subcommand)]
+        target: ExportTarget,
+    },
+    /// Check that a serialized database matches the JSON files it was built from.
+    Verify {
+        #[command(flatten)]
+        database: DatabaseArgs,
+        /// Directories containing the JSON files that the database was built from.
+        #[arg(required = true)]
+        input_dirs: Vec<PathBuf>,
+        #[command(flatten)]
+        report: ReportArgs,
+        #[command(flatten)]
+        parse: ParseArgs,
+    },
+    /// Load a serialized database and check its structural invariants: interned IDs in bounds,
+    /// sorted sets, arenas consistent with their hash tables and valid timestamps.
+    VerifyDb {
+        #[command(flatten)]
+        database: DatabaseArgs,
+    },
+    /// Compare the contents of two serialized databases, e.g. to check that a database appended to
+    /// incrementally matches one built from scratch. Reports the snapshots present in only one of
+    /// them or with different contents, and for each arena the values present in only one of them
+    /// and the difference of sizes.
+    Diff {
+        /// Serialization format of the databases. Inferred from the file names if omitted.
+        #[arg(short, long)]
+        format: Option<Format>,
+        /// Path to the first serialized database.
+        left: PathBuf,
+        /// Path to the second serialized database.
+        right: PathBuf,
+    },
+    /// Convert the JSON files to the optimized schema and back, and report the fields that aren't
+    /// reproduced exactly.
+    Audit {
+        /// Directories containing the JSON files to audit.
+        #[arg(required = true)]
+        input_dirs: Vec<PathBuf>,
+        /// Path of a JSON file where to list the lossy fields of each input file.
+        #[arg(long)]
+        audit_report: Option<PathBuf>,
+        #[command(flatten)]
+        report: ReportArgs,
+    },
+    /// Generate a synthetic dataset of JSON snapshots, one file per snapshot.
+    Generate {
+        /// Directory where the JSON files are written.
+        #[arg(short, long)]
+        output_dir: PathBuf,
+        /// Number of snapshots to generate.
+        #[arg(long, default_value_t = 100)]
+        snapshots: usize,
+        /// Seed of the random number generator.
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// Number of lines in the network.
+        #[arg(long, default_value_t = 300)]
+        lines: usize,
+        /// Number of disruptions in each snapshot.
+        #[arg(long, default_value_t = 200)]
+        disruptions: usize,
+        /// Probability that a disruption of a snapshot is kept as is in the next one.
+        #[arg(long, default_value_t = 0.95, value_parser = probability)]
+        overlap: f64,
+        /// Probability that a free-text field reuses a text generated before.
+        #[arg(long, default_value_t = 0.5, value_parser = probability)]
+        string_reuse: f64,
+    },
+    /// Poll the disruptions API, write each response to a JSON file in the output directory and
+    /// optionally ingest it into a database.
+    #[cfg(feature = "fetch")]
+    Fetch {
+        /// URL of the disruptions endpoint.
+        #[arg(
+            long,
+            default_value = "https://prim.iledefrance-mobilites.fr/marketplace/disruptions_bulk/disruptions/v2"
+        )]
+        url: String,
+        /// API key, sent in the `apikey` header.
+        #[arg(long, env = "IDFM_API_KEY", hide_env_values = true)]
+        api_key: String,
+        /// Number of seconds between two requests.
+        #[arg(long, default_value_t = 120)]
+        interval: u64,
+        /// Directory where the JSON snapshots are written.
+        #[arg(short, long)]
+        output_dir: PathBuf,
+        /// Database into which each snapshot is also ingested, created if it doesn't exist. The
+        /// serialization format is inferred from the file name.
+        #[arg(long)]
+        database: Option<PathBuf>,
+    },
+    /// Ingest the JSON files in the input directories, then keep watching them to ingest new files
+    /// as they are created, periodically re-serializing the database. The database is loaded first
+    /// if it already exists.
+    Watch {
+        #[command(flatten)]
+        database: DatabaseArgs,
+        /// Directories to watch for new JSON files.
+        #[arg(required = true)]
+        input_dirs: Vec<PathBuf>,
+        /// Minimum number of seconds between two serializations of the database.
+        #[arg(long, default_value_t = 60)]
+        save_interval: u64,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Query {
+    /// List all snapshots with their number of disruptions and lines.
+    Snapshots,
+    /// List the disruptions that impacted the given line, across all snapshots.
+    Line {
+        /// ID of the line, e.g. "line:IDFM:C01371".
+        id: String,
+    },
+    /// Print a snapshot as JSON, in the format of the input files. Only this snapshot is decoded
+    /// from databases in the indexed format.
+    Snapshot {
+        /// Position of the snapshot in the database, as listed by the `snapshots` query.
+        index: usize,
+    },
+    /// Count and list the disruptions whose application periods cover the given instant, grouped
+    /// by line and severity, according to the snapshot updated the closest to that instant.
+    ActiveAt {
+        /// Instant in RFC 3339, e.g. "2024-01-01T10:00:00Z", or local datetime in the configured
+        /// timezone, e.g. "2024-01-01T11:00:00" or "20240101T110000".
+        timestamp: String,
+    },
+    /// List the input file of each snapshot, along with its provenance if it was recorded when
+    /// ingesting it.
+    Provenance,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ExportTarget {
+    /// Export to a SQLite file, with one table per arena and one table for the snapshots.
+    Sqlite {
+        /// Path of the SQLite file to create.
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Regenerate one JSON file per snapshot, in the format of the input files. Each file is
+    /// written at the path of its original input file, relative to the output directory.
+    Json {
+        /// Directory where the JSON files are written.
+        #[arg(short, long)]
+        output_dir: PathBuf,
+    },
+    /// Write the whole database to a single JSON file, with every interned value expanded inline.
+    /// Each snapshot is written in the format of the input files, along with the path of its input
+    /// file.
+    Resolved {
+        /// Path of the JSON file to create.
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Compress each regenerated JSON snapshot independently with zstd, using a dictionary trained
+    /// on the interned strings. Each file is written at the path of its original input file with a
+    /// `.zst` extension, and can be decompressed on its own with `zstd -d -D <output_dir>/dictionary`.
+    Zstd {
+        /// Directory where the dictionary and the compressed files are written.
+        #[arg(short, long)]
+        output_dir: PathBuf,
+        /// Maximum size in bytes of the trained dictionary.
+        #[arg(long, default_value_t = 112_640)]
+        dict_size: usize,
+        #[command(flatten)]
+        compression: CompressionArgs,
+    },
+    /// Export the disruptions as GTFS-Realtime feeds of service alerts, in protobuf. Each feed is
+    /// written at the path of its original input file with a `.pb` extension, unless the snapshots
+    /// are merged.
+    Gtfs {
+        /// Directory where the feeds are written.
+        #[arg(short, long)]
+        output_dir: PathBuf,
+        /// Write a single `alerts.pb` feed with the last version of every disruption of the
+        /// database, rather than one feed per snapshot.
+        #[arg(long)]
+        merged: bool,
+    },
+}
+
+#[derive(Debug, clap::Args)]
+pub struct DatabaseArgs {
+    /// Path to the serialized database.
+    pub path: PathBuf,
+    /// Serialization format of the database. Inferred from the file name if omitted.
+    #[arg(short, long)]
+    pub format: Option<Format>,
+}
+
+impl DatabaseArgs {
+    pub fn format(&self) -> Result<Format, Box<dyn std::error::Error>> {
+        match self.format {
+            Some(format) => Ok(format),
+            None => Format::from_path(&self.path)
+                .ok_or_else(|| format!("Cannot infer database format from {:?}", self.path).into()),
+        }
+    }
+}
+
+#[derive(Debug, clap::Args)]
+pub struct CodecArgs {
+    /// Serialization formats to write, separated by commas. All the formats are written by
+    /// default.
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_parser = PossibleValuesParser::new(codec::database_codecs().iter().map(|codec| codec.stem())),
+    )]
+    pub formats: Vec<String>,
+    /// Number of times that each encoder and decoder is run to measure its time, after a warmup
+    /// run if there are several. The median time is reported along with the fastest and slowest
+    /// runs.
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    pub bench_iterations: u32,
+    #[command(flatten)]
+    pub compression: CompressionArgs,
+}
+
+impl CodecArgs {
+    /// Whether the format with the given file stem should be written.
+    pub fn selects(&self, stem: &str) -> bool {
+        self.formats.is_empty() || self.formats.iter().any(|format| format == stem)
+    }
+}
+
+#[derive(Debug, clap::Args)]
+pub struct CompressionArgs {
+    /// Compression level passed to zstd when measuring compressed sizes.
+    #[arg(long, default_value_t = 12, value_parser = clap::value_parser!(u32).range(1..=19))]
+    pub zstd_level: u32,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct StatsArgs {
+    /// Also render the statistics in the given format, next to `stats.json`.
+    #[arg(long, value_enum)]
+    pub report: Option<ReportFormat>,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct ReportArgs {
+    /// Path of a JSON file where to list the input files that failed to be parsed or verified.
+    #[arg(long)]
+    pub failure_report: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, clap::Args)]
+pub struct ParseArgs {
+    /// Drop the fields that the schema doesn't know instead of failing to parse the file, and count
+    /// them in the statistics.
+    #[arg(long)]
+    pub tolerant: bool,
+    /// JSON parser of the input files. Tolerant mode always parses with serde_json.
+    #[arg(long, value_enum, default_value_t = JsonParser::Serde)]
+    pub parser: JsonParser,
+    /// Replace every UUID with a pseudonym derived from it, to share the database without the
+    /// upstream identifiers. Equal UUIDs get equal pseudonyms, so the interning is unaffected.
+    #[arg(long)]
+    pub anonymize: bool,
+    /// Secret from which the pseudonyms are derived. Without it, anyone can find the pseudonym of a
+    /// known UUID.
+    #[arg(long, requires = "anonymize")]
+    pub anonymize_key: Option<String>,
+    /// Also replace the messages of the disruptions with placeholders when anonymizing.
+    #[arg(long, requires = "anonymize")]
+    pub redact_messages: bool,
+}
+
+impl ParseArgs {
+    /// Parses an input file, along with the fields that the schema doesn't know in tolerant mode.
+    /// The snapshot is anonymized if requested.
+    pub fn parse<'a, S: Schema>(
+        &self,
+        bytes: &'a [u8],
+    ) -> serde_json::Result<(S::Source<'a>, Extras)> {
+        let (mut source, extras) = self.parse_raw::<S>(bytes)?;
+        if self.anonymize {
+            let key = self.anonymize_key.as_deref().unwrap_or_default();
+            S::anonymize(&mut source, &Anonymizer::new(key, self.redact_messages));
+        }
+        Ok((source, extras))
+    }
+
+    /// Whether input files can be parsed directly into the interned representation, as the options
+    /// that transform the parsed snapshots work on the source schema.
+    pub fn parses_directly(&self) -> bool {
+        !self.tolerant && !self.anonymize && self.parser == JsonParser::Serde
+    }
+
+    fn parse_raw<'a, S: Schema>(
+        &self,
+        bytes: &'a [u8],
+    ) -> serde_json::Result<(S::Source<'a>, Extras)> {
+        if self.tolerant {
+            return S::from_slice_tolerant(bytes);
+        }
+        let parsed = match self.parser {
+            JsonParser::Serde => None,
+            JsonParser::Simd => simd_from_slice::<S>(bytes),
+        };
+        match parsed {
+            Some(source) => Ok((source, Extras::new())),
+            None => S::from_slice(bytes).map(|source| (source, Extras::new())),
+        }
+    }
+}
+
+// Parses the latest version of the schema with simd-json, which parses in place, so the bytes are
+// copied into buffers reused by the next files parsed on the same thread, and the parsed snapshot
+// then copies the strings it borrows from them. Files that it rejects are parsed again with
+// serde_json, which falls back to older versions of the schema and reports the position of errors.
+#[cfg(feature = "simd-json")]
+fn simd_from_slice<'a, S: Schema>(bytes: &[u8]) -> Option<S::Source<'a>> {
+    thread_local! {
+        static BUFFERS: RefCell<(Vec<u8>, simd_json::Buffers)> = RefCell::default();
+    }
+    BUFFERS.with_borrow_mut(|(copy, buffers)| {
+        copy.clear();
+        copy.extend_from_slice(bytes);
+        simd_json::serde::from_slice_with_buffers(copy, buffers)
+            .inspect_err(|err| tracing::debug!(%err, "simd-json rejected the file"))
+            .ok()
+            .map(S::into_owned)
+    })
+}
+
+#[cfg(not(feature = "simd-json"))]
+fn simd_from_slice<'a, S: Schema>(_bytes: &[u8]) -> Option<S::Source<'a>> {
+    static UNAVAILABLE: Once = Once::new();
+    UNAVAILABLE.call_once(|| {
+        tracing::warn!("Built without the `simd-json` feature, parsing with serde_json instead");
+    });
+    None
+}
+
+#[derive(Debug, clap::Args)]
+pub struct VerifyArgs {
+    /// Which input files to check against their interned representation: `all`, `none`, or
+    /// `sample:N` to check about one in N files, chosen at random.
+    #[arg(long, default_value = "all")]
+    pub verify: VerifyMode,
+}
+
+impl VerifyArgs {
+    /// Returns a predicate deciding whether the given input file should be verified.
+    pub fn sampler(&self) -> impl Fn(&Path) -> bool + Sync {
+        // The hasher is randomly seeded, so each run verifies a different sample.
+        let state = RandomState::new();
+        let mode = self.verify;
+        move |path| match mode {
+            VerifyMode::All => true,
+            VerifyMode::None => false,
+            VerifyMode::Sample(n) => state.hash_one(path) % n.get() == 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyMode {
+    All,
+    Sample(NonZeroU64),
+    None,
+}
+
+impl FromStr for VerifyMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(VerifyMode::All),
+            "none" => Ok(VerifyMode::None),
+            _ => {
+                let n = s
+                    .strip_prefix("sample:")
+                    .ok_or_else(|| format!("expected all, none or sample:N, found {s:?}"))?;
+                let n = n
+                    .parse()
+                    .map_err(|err| format!("invalid sampling rate {n:?}: {err}"))?;
+                Ok(VerifyMode::Sample(n))
+            }
+        }
+    }
+}
+
+fn probability(s: &str) -> Result<f64, String> {
+    let p: f64 = s
+        .parse()
+        .map_err(|err| format!("invalid probability {s:?}: {err}"))?;
+    if (0.0..=1.0).contains(&p) {
+        Ok(p)
+    } else {
+        Err(format!("probability must be between 0 and 1, found {p}"))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    /// Self-contained HTML page with bar charts, written to `report.html`.
+    Html,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TableFormat {
+    /// Comma-separated values, with a header line.
+    Csv,
+    /// JSON array of objects.
+    Json,
+}
+
+impl TableFormat {
+    pub fn from_path(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        match path.extension().and_then(|x| x.to_str()) {
+            Some("csv") => Ok(TableFormat::Csv),
+            Some("json") => Ok(TableFormat::Json),
+            _ => Err(format!("Cannot infer table format from {path:?}").into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SchemaName {
+    /// Snapshots of the traffic disruptions API.
+    Disruptions,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable lines.
+    Text,
+    /// One JSON object per line.
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AmbiguousDatetimes {
+    /// The first occurrence, before clocks are turned back.
+    Earliest,
+    /// The second occurrence, after clocks are turned back.
+    Latest,
+    /// Fail to convert the snapshot.
+    Reject,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum JsonParser {
+    /// serde_json.
+    #[default]
+    Serde,
+    /// simd-json, which requires the `simd-json` feature.
+    Simd,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum InputFormat {
+    /// Detected from the extension of each file.
+    Auto,
+    /// One snapshot per file.
+    Json,
+    /// One snapshot per line (newline-delimited JSON).
+    Ndjson,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Bincode,
+    Bincode2,
+    Bitcode,
+    Cbor,
+    Json,
+    Postcard,
+    PostcardCobs,
+    #[value(name = "messagepack")]
+    MessagePack,
+    Rkyv,
+    Stream,
+    Indexed,
+    Protobuf,
+    Avro,
+}
+
+impl Format {
+    fn from_path(path: &Path) -> Option<Self> {
+        match path.file_stem()?.to_str()? {
+            "bincode" => Some(Format::Bincode),
+            "bincode2" => Some(Format::Bincode2),
+            "bitcode" => Some(Format::Bitcode),
+            "cbor" => Some(Format::Cbor),
+            "json" | "json_pretty" => Some(Format::Json),
+            "postcard" => Some(Format::Postcard),
+            "postcard_cobs" => Some(Format::PostcardCobs),
+            "messagepack" => Some(Format::MessagePack),
+            "rkyv" => Some(Format::Rkyv),
+            "stream" => Some(Format::Stream),
+            "indexed" => Some(Format::Indexed),
+            "protobuf" => Some(Format::Protobuf),
+            // The compression of Avro containers is read from their header.
+            "avro" | "avro_deflate" | "avro_snappy" => Some(Format::Avro),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,439 @@
+// Serialization formats of the databases. Each format is implemented once here and shared between
+// the round trips that measure the formats and the commands that load or save a database, so adding
+// a format to the registries below is enough for it to appear in the statistics tables.
+//
+// The optimized database is wrapped with its version and checksummed, so that it can be upgraded
+// and checked when it's loaded back. The other databases only exist for the comparison, so they're
+// serialized as is.
+
+use crate::cli::Format;
+use crate::schema::optimized::avro::{self, Compression};
+use crate::schema::optimized::protobuf;
+use crate::version::{self, Upgraded, Versioned};
+use crate::{checksum, index, Database};
+use cobs::CobsDecoder;
+use crc::{Crc, CRC_32_ISO_HDLC};
+use postcard::ser_flavors::crc::CrcModifier;
+use postcard::ser_flavors::{AllocVec, Cobs};
+use rkyv::util::AlignedVec;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::error::Error;
+
+/// Serialization format of values of type `T`.
+pub trait Codec<T> {
+    /// Name of the format in the statistics tables.
+    fn name(&self) -> &'static str;
+    /// File name of the serialized value, without the extension of the serialized type.
+    fn stem(&self) -> &'static str;
+    fn encode(&self, value: &T) -> Result<Vec<u8>, Box<dyn Error>>;
+    fn decode(&self, bytes: &[u8]) -> Result<T, Box<dyn Error>>;
+}
+
+/// Formats of the optimized database, in the order of the statistics tables.
+pub fn database_codecs() -> Vec<Box<dyn Codec<Database>>> {
+    let mut codecs: Vec<Box<dyn Codec<Database>>> = SerdeFormat::ALL
+        .into_iter()
+        .map(|format| Box::new(Sealed(format)) as Box<dyn Codec<Database>>)
+        .collect();
+    codecs.push(Box::new(Rkyv));
+    codecs.push(Box::new(Indexed));
+    codecs.push(Box::new(Protobuf));
+    for compression in [Compression::Null, Compression::Deflate, Compression::Snappy] {
+        codecs.push(Box::new(Avro(compression)));
+    }
+    codecs
+}
+
+/// Formats of the databases that are only serialized for the comparison. Pretty-printing is left
+/// out, as it adds the same whitespace to any JSON document.
+pub fn plain_codecs<T: Serialize + DeserializeOwned>() -> Vec<Box<dyn Codec<T>>> {
+    SerdeFormat::ALL
+        .into_iter()
+        .filter(|&format| format != SerdeFormat::JsonPretty)
+        .map(|format| Box::new(Plain(format)) as Box<dyn Codec<T>>)
+        .collect()
+}
+
+/// Codec of a database file in the given format, or `None` for the streaming format which is
+/// written incrementally.
+pub fn database_codec(format: Format) -> Option<Box<dyn Codec<Database>>> {
+    Some(match format {
+        Format::Bincode => Box::new(Sealed(SerdeFormat::Bincode)),
+        Format::Bincode2 => Box::new(Sealed(SerdeFormat::Bincode2)),
+        Format::Bitcode => Box::new(Sealed(SerdeFormat::Bitcode)),
+        Format::Cbor => Box::new(Sealed(SerdeFormat::Cbor)),
+        Format::Json => Box::new(Sealed(SerdeFormat::Json)),
+        Format::Postcard => Box::new(Sealed(SerdeFormat::Postcard)),
+        Format::PostcardCobs => Box::new(Sealed(SerdeFormat::PostcardCobs)),
+        Format::MessagePack => Box::new(Sealed(SerdeFormat::MessagePack)),
+        Format::Rkyv => Box::new(Rkyv),
+        Format::Indexed => Box::new(Indexed),
+        Format::Protobuf => Box::new(Protobuf),
+        Format::Avro => Box::new(Avro(Compression::Null)),
+        Format::Stream => return None,
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SerdeFormat {
+    Bincode,
+    Bincode2,
+    Bitcode,
+    Cbor,
+    Json,
+    JsonPretty,
+    Postcard,
+    PostcardCobs,
+    MessagePack,
+}
+
+impl SerdeFormat {
+    const ALL: [Self; 9] = [
+        Self::Bincode,
+        Self::Bincode2,
+        Self::Bitcode,
+        Self::Cbor,
+        Self::Json,
+        Self::JsonPretty,
+        Self::Postcard,
+        Self::PostcardCobs,
+        Self::MessagePack,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Bincode => "Bincode",
+            Self::Bincode2 => "Bincode 2",
+            Self::Bitcode => "bitcode",
+            Self::Cbor => "CBOR",
+            Self::Json => "JSON",
+            Self::JsonPretty => "JSON (pretty)",
+            Self::Postcard => "Postcard",
+            Self::PostcardCobs => "Postcard COBS",
+            Self::MessagePack => "MessagePack",
+        }
+    }
+
+    fn stem(self) -> &'static str {
+        match self {
+            Self::Bincode => "bincode",
+            Self::Bincode2 => "bincode2",
+            Self::Bitcode => "bitcode",
+            Self::Cbor => "cbor",
+            Self::Json => "json",
+            Self::JsonPretty => "json_pretty",
+            Self::Postcard => "postcard",
+            Self::PostcardCobs => "postcard_cobs",
+            Self::MessagePack => "messagepack",
+        }
+    }
+
+    // Whether the database is followed by a checksum. JSON files remain valid JSON documents, and
+    // COBS frames already embed a CRC.
+    fn is_sealed(self) -> bool {
+        !matches!(self, Self::Json | Self::JsonPretty | Self::PostcardCobs)
+    }
+
+    fn serialize<T: Serialize + ?Sized>(self, value: &T) -> Result<Vec<u8>, Box<dyn Error>> {
+        Ok(match self {
+            Self::Bincode => bincode::serialize(value)?,
+            Self::Bincode2 => bincode2::serde::encode_to_vec(value, bincode2::config::standard())?,
+            Self::Bitcode => bitcode::serialize(value)?,
+            Self::Cbor => {
+                let mut output = Vec::new();
+                ciborium::into_writer(value, &mut output)?;
+                output
+            }
+            Self::Json => serde_json::to_vec(value)?,
+            Self::JsonPretty => serde_json::to_vec_pretty(value)?,
+            Self::Postcard => postcard::to_stdvec(value)?,
+            Self::PostcardCobs => to_cobs_frame(value)?,
+            Self::MessagePack => rmp_serde::to_vec(value)?,
+        })
+    }
+
+    fn deserialize<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, Box<dyn Error>> {
+        Ok(match self {
+            Self::Bincode => bincode::deserialize(bytes)?,
+            Self::Bincode2 => {
+                bincode2::serde::decode_from_slice(bytes, bincode2::config::standard())?.0
+            }
+            Self::Bitcode => bitcode::deserialize(bytes)?,
+            Self::Cbor => ciborium::from_reader(bytes)?,
+            Self::Json | Self::JsonPretty => serde_json::from_slice(bytes)?,
+            Self::Postcard => postcard::from_bytes(bytes)?,
+            Self::PostcardCobs => from_cobs_frame(bytes)?,
+            Self::MessagePack => rmp_serde::from_slice(bytes)?,
+        })
+    }
+}
+
+/// CRC of the COBS frames, the same as the checksums of the other formats.
+const CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+/// Serializes a value with postcard as a single frame for serial links: the CRC32 of the
+/// serialized value is appended to it, and the whole is encoded with Consistent Overhead Byte
+/// Stuffing, so that the frame contains no zero byte until its terminating zero.
+pub fn to_cobs_frame<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Box<dyn Error>> {
+    let flavor = CrcModifier::new(Cobs::try_new(AllocVec::new())?, CRC32.digest());
+    Ok(postcard::serialize_with_flavor(value, flavor)?)
+}
+
+/// Deserializes a frame written by [`to_cobs_frame`], which must span the whole input, and checks
+/// its CRC.
+pub fn from_cobs_frame<T: DeserializeOwned>(frame: &[u8]) -> Result<T, Box<dyn Error>> {
+    let mut payload = vec![0; frame.len()];
+    let len = match CobsDecoder::new(&mut payload).push(frame)? {
+        Some((len, used)) if used == frame.len() => len,
+        Some((_, used)) => {
+            return Err(format!("Unexpected data after the COBS frame at byte {used}").into())
+        }
+        None => return Err("COBS frame is missing its terminating zero".into()),
+    };
+    // The CRC is checked before deserializing, as the arenas of a corrupted database may not be
+    // valid UTF-8. It has the same layout as the checksums of the other formats.
+    let payload = checksum::verify(&payload[..len], "the COBS frame")?;
+    Ok(postcard::from_bytes(payload)?)
+}
+
+// Serde format applied to the value as is.
+struct Plain(SerdeFormat);
+
+impl<T: Serialize + DeserializeOwned> Codec<T> for Plain {
+    fn name(&self) -> &'static str {
+        self.0.name()
+    }
+
+    fn stem(&self) -> &'static str {
+        self.0.stem()
+    }
+
+    fn encode(&self, value: &T) -> Result<Vec<u8>, Box<dyn Error>> {
+        self.0.serialize(value)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<T, Box<dyn Error>> {
+        self.0.deserialize(bytes)
+    }
+}
+
+// Serde format applied to the versioned database, followed by a checksum unless the format has its
+// own.
+struct Sealed(SerdeFormat);
+
+impl Codec<Database> for Sealed {
+    fn name(&self) -> &'static str {
+        self.0.name()
+    }
+
+    fn stem(&self) -> &'static str {
+        self.0.stem()
+    }
+
+    fn encode(&self, database: &Database) -> Result<Vec<u8>, Box<dyn Error>> {
+        let bytes = self.0.serialize(&Versioned(database))?;
+        Ok(if self.0.is_sealed() {
+            checksum::sealed(bytes)
+        } else {
+            bytes
+        })
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Database, Box<dyn Error>> {
+        let bytes = if self.0.is_sealed() {
+            checksum::verify(bytes, "the database")?
+        } else {
+            bytes
+        };
+        Ok(self.0.deserialize::<Upgraded>(bytes)?.0)
+    }
+}
+
+// Archive of the database, after the version header and followed by a checksum.
+struct Rkyv;
+
+impl Codec<Database> for Rkyv {
+    fn name(&self) -> &'static str {
+        "rkyv"
+    }
+
+    fn stem(&self) -> &'static str {
+        "rkyv"
+    }
+
+    fn encode(&self, database: &Database) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut output = version::header().to_vec();
+        output.extend_from_slice(&rkyv::to_bytes::<rkyv::rancor::Error>(database)?);
+        Ok(checksum::sealed(output))
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Database, Box<dyn Error>> {
+        let bytes = version::split_header(checksum::verify(bytes, "the database")?)?;
+        // Archives must be read from an aligned buffer. Memory-mapped files already are, as the
+        // header keeps the alignment of the page.
+        if bytes.as_ptr().align_offset(16) == 0 {
+            return Ok(rkyv::from_bytes::<Database, rkyv::rancor::Error>(bytes)?);
+        }
+        let mut aligned = AlignedVec::<16>::with_capacity(bytes.len());
+        aligned.extend_from_slice(bytes);
+        Ok(rkyv::from_bytes::<Database, rkyv::rancor::Error>(&aligned)?)
+    }
+}
+
+// Indexed layout, whose snapshots can be decoded individually.
+struct Indexed;
+
+impl Codec<Database> for Indexed {
+    fn name(&self) -> &'static str {
+        "Indexed"
+    }
+
+    fn stem(&self) -> &'static str {
+        "indexed"
+    }
+
+    fn encode(&self, database: &Database) -> Result<Vec<u8>, Box<dyn Error>> {
+        index::serialize(
+            &database.arenas,
+            &database.paths,
+            &database.datas,
+            &database.provenance,
+        )
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Database, Box<dyn Error>> {
+        let (arenas, records) = index::read(bytes)?;
+        Ok(Database::from_records(arenas, records))
+    }
+}
+
+/// Protobuf message of the database, whose schema is [`protobuf::SCHEMA`].
+pub struct Protobuf;
+
+impl Codec<Database> for Protobuf {
+    fn name(&self) -> &'static str {
+        "Protobuf"
+    }
+
+    fn stem(&self) -> &'static str {
+        "protobuf"
+    }
+
+    fn encode(&self, database: &Database) -> Result<Vec<u8>, Box<dyn Error>> {
+        protobuf::serialize(
+            &database.arenas,
+            &database.paths,
+            &database.datas,
+            &database.provenance,
+        )
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Database, Box<dyn Error>> {
+        let (arenas, records) = protobuf::read(bytes)?;
+        Ok(Database::from_records(arenas, records))
+    }
+}
+
+// Avro container of the database, whose blocks are compressed by Avro itself.
+struct Avro(Compression);
+
+impl Codec<Database> for Avro {
+    fn name(&self) -> &'static str {
+        match self.0 {
+            Compression::Null => "Avro",
+            Compression::Deflate => "Avro deflate",
+            Compression::Snappy => "Avro snappy",
+        }
+    }
+
+    fn stem(&self) -> &'static str {
+        match self.0 {
+            Compression::Null => "avro",
+            Compression::Deflate => "avro_deflate",
+            Compression::Snappy => "avro_snappy",
+        }
+    }
+
+    fn encode(&self, database: &Database) -> Result<Vec<u8>, Box<dyn Error>> {
+        avro::serialize(
+            &database.arenas,
+            &database.paths,
+            &database.datas,
+            &database.provenance,
+            self.0,
+        )
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Database, Box<dyn Error>> {
+        let (arenas, records) = avro::read(bytes)?;
+        Ok(Database::from_records(arenas, records))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::DatabaseArgs;
+    use crate::database::read_database;
+    use crate::schema::generate::{intern_test_snapshots, test_database};
+    use crate::schema::optimized::Arenas;
+    use std::path::PathBuf;
+
+    #[test]
+    fn cobs_frames_detect_truncation_and_corruption() {
+        let arenas = Arenas::default();
+        intern_test_snapshots(&arenas, 2);
+
+        let frame = to_cobs_frame(&arenas).unwrap();
+        let (terminator, payload) = frame.split_last().unwrap();
+        assert_eq!(*terminator, 0);
+        assert!(!payload.contains(&0));
+        assert_eq!(from_cobs_frame::<Arenas>(&frame).unwrap(), arenas);
+
+        assert!(from_cobs_frame::<Arenas>(payload).is_err());
+        let mut trailing = frame.clone();
+        trailing.push(1);
+        assert!(from_cobs_frame::<Arenas>(&trailing).is_err());
+        // Corrupting a byte without introducing a zero either breaks the encoding or fails the CRC.
+        let mut corrupted = frame.clone();
+        let i = corrupted.len() / 2;
+        corrupted[i] = if corrupted[i] == 1 {
+            2
+        } else {
+            corrupted[i] ^ 1
+        };
+        assert!(from_cobs_frame::<Arenas>(&corrupted).is_err());
+    }
+
+    // Every database written by the round trip can be loaded back, with its format inferred from
+    // the file name.
+    #[test]
+    fn database_files_load_in_their_inferred_format() {
+        // Deserializing a whole database nests deeply enough in debug builds to overflow the
+        // default stack of test threads.
+        std::thread::Builder::new()
+            .stack_size(16 << 20)
+            .spawn(|| {
+                let dir: PathBuf = std::env::temp_dir()
+                    .join(format!("rust-interning-codecs-{}", std::process::id()));
+                std::fs::create_dir_all(&dir).unwrap();
+
+                let database = test_database(3);
+
+                for codec in database_codecs() {
+                    let path = dir.join(format!("{}.db", codec.stem()));
+                    std::fs::write(&path, codec.encode(&database).unwrap()).unwrap();
+                    let args = DatabaseArgs { path, format: None };
+                    let loaded = read_database(&args)
+                        .unwrap_or_else(|e| panic!("Failed to load {}: {e}", codec.name()));
+                    assert_eq!(loaded, database, "{}", codec.name());
+                }
+
+                std::fs::remove_dir_all(&dir).unwrap();
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+}
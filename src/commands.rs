@@ -0,0 +1,9 @@
+// Implementations of the subcommands, which `run` dispatches to once the command line is parsed.
+// They are grouped by what they do with a database: building it from input files, adding files to
+// an existing one, querying it, verifying it or exporting it to other formats.
+
+pub mod build;
+pub mod export;
+pub mod ingest;
+pub mod query;
+pub mod verify;
@@ -0,0 +1,666 @@
+// Building of a database from the input directories. Input files are parsed and interned in
+// parallel into shared arenas, then each snapshot is verified against its source before the
+// database is serialized in every requested format.
+
+use crate::cli::{CodecArgs, ReportArgs, StatsArgs, VerifyArgs};
+use crate::compare::EqWith;
+use crate::database::{joptimize, joptimize_loop, Database, Jdatabase};
+use crate::input::InputFile;
+use crate::report::{
+    print_failures, print_unknown_fields, write_report, Failures, Stage, UnknownFields,
+};
+use crate::round_trip::jcodec;
+use crate::schema::optimized::Arenas;
+use crate::schema::{Disruptions, Schema};
+use crate::stats::{FileCounts, StatsReport};
+use crate::timing::Timings;
+use crate::walk::Inputs;
+use crate::{alloc, round_trip, schema, stats, stream, timing};
+use blazinterner::Interned;
+use get_size2::GetSize;
+use jinterner::{IValue, Jinterners};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+use tracing::{info, warn};
+
+// Without an output directory, i.e. for dry runs, nothing is serialized nor written once the
+// statistics of the interned databases are printed.
+pub fn build(
+    inputs: &Inputs,
+    output_dir: Option<PathBuf>,
+    codec_args: &CodecArgs,
+    stats_args: &StatsArgs,
+    report: &ReportArgs,
+    verify: &VerifyArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let should_verify = verify.sampler();
+    let ingestion = Ingestion::<Disruptions>::default();
+    let phase = alloc::Phase::start("parsing and interning", || ingestion.get_size());
+    inputs.visit(&|input| ingestion.ingest(inputs, input, &should_verify))?;
+    let (counters, mut database, jdatabase) = ingestion.finish();
+    phase.finish(|| {
+        database.get_size() + jdatabase.jinterners.get_size() + jdatabase.jvalues.get_size()
+    });
+
+    let IngestionCounters {
+        file_count,
+        verified_count,
+        failures,
+        unknown_fields,
+        total_input_bytes,
+        total_parsed_bytes,
+        source_input_bytes,
+        total_optimized_bytes,
+        total_optimized_json_bytes,
+        timings,
+    } = counters;
+    let file_count = file_count.into_inner();
+    let verified_count = verified_count.into_inner();
+    let failures = failures.into_sorted();
+    let total_input_bytes = total_input_bytes.into_inner();
+    let total_parsed_bytes = total_parsed_bytes.into_inner();
+    let source_input_bytes = source_input_bytes.into_inner();
+    let mut total_optimized_bytes = total_optimized_bytes.into_inner();
+    let total_optimized_json_bytes = total_optimized_json_bytes.into_inner();
+
+    let unknown_fields = unknown_fields.into_counts();
+    println!(
+        "Parsed {total_input_bytes} bytes from {file_count} files (+ {} failed files)",
+        failures.len(),
+    );
+    print_failures(&failures);
+    print_unknown_fields(&unknown_fields);
+    write_report(report.failure_report.as_deref(), &failures)?;
+    println!("Verified {verified_count} of the parsed files");
+    timings.print_summary(5);
+    print_duplicates(&database.datas);
+    // Only the files that were verified or converted are parsed into the source schema.
+    if source_input_bytes != 0 {
+        println!(
+            "Expanded {source_input_bytes} bytes parsed into the source schema to {total_parsed_bytes} bytes in memory (relative size = {:.02}%)",
+            total_parsed_bytes as f64 * 100.0 / source_input_bytes as f64,
+        );
+    }
+
+    // Files that failed to parse may have interned some values before the failure. Compaction also
+    // numbers the values in the order of the snapshots, regardless of the order of ingestion.
+    database.compact()?;
+    let arenas = &database.arenas;
+
+    let arenas_bytes = arenas.get_size();
+    total_optimized_bytes += arenas_bytes;
+    println!(
+        "Optimized to {total_optimized_bytes} bytes (relative size = {:.02}%)",
+        total_optimized_bytes as f64 * 100.0 / total_input_bytes as f64,
+    );
+    println!(
+        "[{:.02}%] Arenas: {arenas_bytes} bytes",
+        arenas_bytes as f64 * 100.0 / total_optimized_bytes as f64,
+    );
+    arenas.print_summary(total_optimized_bytes, &database.datas);
+
+    let mut stats = StatsReport {
+        files: Some(FileCounts {
+            unknown_fields,
+            ..FileCounts::new(file_count, verified_count, 0, &failures)
+        }),
+        interners: arenas.interner_stats(),
+        ..Default::default()
+    };
+    stats.totals.input_bytes = total_input_bytes;
+    stats.totals.parsed_bytes = (source_input_bytes != 0).then_some(total_parsed_bytes);
+    stats.totals.optimized_bytes = Some(total_optimized_bytes);
+    stats.totals.arenas_bytes = Some(arenas_bytes);
+
+    if let Some(output_dir) = &output_dir {
+        round_trip::codec(
+            database,
+            output_dir,
+            total_input_bytes,
+            codec_args,
+            &mut stats,
+        )?;
+    }
+
+    process_jdatabase(
+        jdatabase,
+        total_optimized_json_bytes,
+        total_input_bytes,
+        output_dir.as_deref(),
+        codec_args,
+        &mut stats,
+    )?;
+
+    match output_dir {
+        Some(output_dir) => stats::write_stats(&output_dir, &stats, stats_args.report),
+        None => Ok(()),
+    }
+}
+
+// Counters and timings of the input files ingested by `build`.
+#[derive(Default)]
+pub struct IngestionCounters {
+    pub file_count: AtomicUsize,
+    pub verified_count: AtomicUsize,
+    pub failures: Failures,
+    pub unknown_fields: UnknownFields,
+    pub total_input_bytes: AtomicUsize,
+    // Sizes of the snapshots parsed into the source schema, and of their input files.
+    total_parsed_bytes: AtomicUsize,
+    source_input_bytes: AtomicUsize,
+    pub total_optimized_bytes: AtomicUsize,
+    total_optimized_json_bytes: AtomicUsize,
+    pub timings: Timings,
+}
+
+// Snapshots ingested by `build`, both interned in the interners of the schema and as JSON values
+// interned in the jinterners. Files are ingested in parallel, so snapshots arrive in any order.
+pub struct Ingestion<S: Schema> {
+    counters: IngestionCounters,
+    pub arenas: S::Interners,
+    pub datas: Mutex<Vec<stream::Record<S>>>,
+    pub jinterners: Jinterners,
+    pub jvalues: Mutex<Vec<(PathBuf, IValue)>>,
+}
+
+// Implemented by hand, as deriving it would require the schema itself to implement `Default`.
+impl<S: Schema> Default for Ingestion<S> {
+    fn default() -> Self {
+        Self {
+            counters: IngestionCounters::default(),
+            arenas: S::Interners::default(),
+            datas: Mutex::default(),
+            jinterners: Jinterners::default(),
+            jvalues: Mutex::default(),
+        }
+    }
+}
+
+impl<S: Schema> Ingestion<S> {
+    pub fn get_size(&self) -> usize {
+        self.arenas.get_size() + self.jinterners.get_size()
+    }
+
+    // Parses, interns and verifies an input file. Files that fail are recorded in the failures.
+    //
+    // Files are parsed directly into the arenas. They're only parsed into the source schema to
+    // verify them, or to convert them when the parsing options work on the source schema or when
+    // they're in an older version of the schema, which the direct parser doesn't support.
+    pub fn ingest(
+        &self,
+        inputs: &Inputs,
+        input: &InputFile,
+        should_verify: &impl Fn(&Path) -> bool,
+    ) -> std::io::Result<()> {
+        let file_path = input.path();
+        let mut timer = self.counters.timings.file(file_path);
+        let bytes = timer.time(timing::Phase::Read, || input.read())?;
+        timer.set_bytes(bytes.len());
+        self.counters
+            .total_input_bytes
+            .fetch_add(bytes.len(), Ordering::Relaxed);
+
+        let mut direct = None;
+        if inputs.parsing.parses_directly() {
+            direct = timer.time(timing::Phase::Parse, || {
+                S::from_slice_interned(&self.arenas, &bytes)
+            });
+        }
+
+        let verify = should_verify(file_path);
+        let source = if direct.is_none() || verify {
+            let parsed = timer.time(timing::Phase::Parse, || inputs.parse::<S>(&bytes));
+            let (data, extras) = match parsed {
+                Ok(parsed) => parsed,
+                Err(err) => {
+                    self.counters
+                        .failures
+                        .record_json_error(file_path, &bytes, &err);
+                    return Ok(());
+                }
+            };
+            self.counters.unknown_fields.record(file_path, &extras);
+            let parsed_bytes = timer.time(timing::Phase::Estimate, || data.get_size());
+            self.counters
+                .total_parsed_bytes
+                .fetch_add(parsed_bytes, Ordering::Relaxed);
+            self.counters
+                .source_input_bytes
+                .fetch_add(bytes.len(), Ordering::Relaxed);
+            Some(data)
+        } else {
+            None
+        };
+
+        let optimized = match (direct, &source) {
+            (Some(optimized), _) => optimized,
+            (None, Some(data)) => {
+                let converted = timer.time(timing::Phase::Convert, || {
+                    convert::<S>(&self.arenas, file_path, data, &self.counters.failures)
+                });
+                let Some(optimized) = converted else {
+                    return Ok(());
+                };
+                optimized
+            }
+            (None, None) => {
+                unreachable!("Files not parsed directly are parsed into the source schema")
+            }
+        };
+
+        if let Some(data) = source.as_ref().filter(|_| verify) {
+            self.counters.verified_count.fetch_add(1, Ordering::Relaxed);
+            let verified = timer.time(timing::Phase::Verify, || {
+                check_conversion::<S>(
+                    &self.arenas,
+                    file_path,
+                    &optimized,
+                    data,
+                    &self.counters.failures,
+                )
+            });
+            if !verified {
+                return Ok(());
+            }
+        }
+        let optimized = match timer.time(timing::Phase::Convert, || {
+            S::intern(&self.arenas, optimized)
+        }) {
+            Ok(optimized) => optimized,
+            Err(err) => {
+                self.counters
+                    .failures
+                    .record(file_path, Stage::Conversion, err);
+                return Ok(());
+            }
+        };
+        let optimized_bytes = timer.time(timing::Phase::Estimate, || optimized.get_size());
+        self.counters
+            .total_optimized_bytes
+            .fetch_add(optimized_bytes, Ordering::Relaxed);
+        let provenance = inputs.provenance(input, &bytes)?;
+
+        self.datas
+            .lock()
+            .unwrap()
+            .push((file_path.to_owned(), optimized, provenance));
+        self.counters.file_count.fetch_add(1, Ordering::Relaxed);
+
+        // Anonymized snapshots are converted back to JSON, so that the JSON databases don't contain
+        // the upstream identifiers either.
+        let value: Result<serde_json::Value, _> =
+            timer.time(timing::Phase::Json, || match &source {
+                Some(data) if inputs.parsing.anonymize => serde_json::to_value(data),
+                _ => serde_json::from_slice(&bytes),
+            });
+        let value = match value {
+            Ok(value) => value,
+            Err(err) => {
+                warn!(%err, "Error parsing JSON");
+                return Ok(());
+            }
+        };
+
+        let jvalue = timer.time(timing::Phase::Json, || {
+            let jvalue = self.jinterners.intern_ref(&value);
+            assert_eq!(
+                jvalue.lookup(&self.jinterners),
+                value,
+                "Optimized JSON data didn't match original for file: {file_path:?}"
+            );
+            jvalue
+        });
+        let jvalue_bytes = timer.time(timing::Phase::Estimate, || jvalue.get_size());
+        self.counters
+            .total_optimized_json_bytes
+            .fetch_add(jvalue_bytes, Ordering::Relaxed);
+
+        self.jvalues
+            .lock()
+            .unwrap()
+            .push((file_path.to_owned(), jvalue));
+
+        Ok(())
+    }
+
+    // Returns the databases of the ingested snapshots, sorted by path. The arenas still depend on the
+    // order in which files were ingested until the database is compacted.
+    pub fn finish(self) -> (IngestionCounters, Database<S>, Jdatabase) {
+        let mut records = self.datas.into_inner().unwrap();
+        records.sort_unstable_by(|(x, _, _), (y, _, _)| x.cmp(y));
+        let database = Database::from_records(self.arenas, records);
+        let jdatabase =
+            Jdatabase::from_ingested(&self.jinterners, self.jvalues.into_inner().unwrap());
+
+        (self.counters, database, jdatabase)
+    }
+}
+
+// Converts a parsed snapshot with the schema, recording the file as failed if it can't be,
+// including when the interners are full.
+pub fn convert<S: Schema>(
+    interners: &S::Interners,
+    file_path: &Path,
+    source: &S::Source<'_>,
+    failures: &Failures,
+) -> Option<S::Optimized> {
+    match S::from_source(interners, source) {
+        Ok(optimized) => Some(optimized),
+        Err(err) => {
+            failures.record(file_path, Stage::Conversion, err);
+            None
+        }
+    }
+}
+
+// Checks that a converted snapshot matches the parsed one, recording the file as failed otherwise.
+pub fn check_conversion<S: Schema>(
+    interners: &S::Interners,
+    file_path: &Path,
+    optimized: &S::Optimized,
+    source: &S::Source<'_>,
+    failures: &Failures,
+) -> bool {
+    let matches = optimized.eq_with(source, interners);
+    if !matches {
+        failures.record(
+            file_path,
+            Stage::Verification,
+            format!(
+                "optimized data didn't match original: {}",
+                S::explain(optimized, source, interners)
+            ),
+        );
+    }
+    matches
+}
+
+// Like `build`, dry runs have no output directory.
+pub fn build_json(
+    inputs: &Inputs,
+    output_dir: Option<PathBuf>,
+    codec_args: &CodecArgs,
+    stats_args: &StatsArgs,
+    report: &ReportArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let file_count = AtomicUsize::new(0);
+    let failures = Failures::default();
+    let total_input_bytes = AtomicUsize::new(0);
+    let total_optimized_json_bytes = AtomicUsize::new(0);
+
+    let jinterners = Jinterners::default();
+    let jvalues = Mutex::new(Vec::new());
+
+    inputs.visit(&|input| {
+        let file_path = input.path();
+        let bytes = input.read()?;
+        total_input_bytes.fetch_add(bytes.len(), Ordering::Relaxed);
+
+        let value: Result<serde_json::Value, _> = serde_json::from_slice(&bytes);
+        let value = match value {
+            Ok(value) => value,
+            Err(err) => {
+                failures.record_json_error(file_path, &bytes, &err);
+                return Ok(());
+            }
+        };
+
+        let jvalue = jinterners.intern_ref(&value);
+        total_optimized_json_bytes.fetch_add(jvalue.get_size(), Ordering::Relaxed);
+
+        assert_eq!(
+            jvalue.lookup(&jinterners),
+            value,
+            "Optimized JSON data didn't match original for file: {file_path:?}"
+        );
+
+        jvalues.lock().unwrap().push((file_path.to_owned(), jvalue));
+        file_count.fetch_add(1, Ordering::Relaxed);
+
+        Ok(())
+    })?;
+
+    let file_count = file_count.load(Ordering::Relaxed);
+    let failures = failures.into_sorted();
+    let total_input_bytes = total_input_bytes.load(Ordering::Relaxed);
+    let total_optimized_json_bytes = total_optimized_json_bytes.load(Ordering::Relaxed);
+    let jdatabase = Jdatabase::from_ingested(&jinterners, jvalues.into_inner().unwrap());
+
+    println!(
+        "Parsed {total_input_bytes} bytes from {file_count} files (+ {} failed files)",
+        failures.len(),
+    );
+    print_failures(&failures);
+    write_report(report.failure_report.as_deref(), &failures)?;
+
+    let mut stats = StatsReport {
+        files: Some(FileCounts::new(file_count, 0, 0, &failures)),
+        ..Default::default()
+    };
+    stats.totals.input_bytes = total_input_bytes;
+
+    process_jdatabase(
+        jdatabase,
+        total_optimized_json_bytes,
+        total_input_bytes,
+        output_dir.as_deref(),
+        codec_args,
+        &mut stats,
+    )?;
+
+    match output_dir {
+        Some(output_dir) => stats::write_stats(&output_dir, &stats, stats_args.report),
+        None => Ok(()),
+    }
+}
+
+// Prints statistics about the interned JSON values, then serializes them in all formats, before and
+// after optimizing the interners, unless it's a dry run.
+fn process_jdatabase(
+    jdatabase: Jdatabase,
+    mut total_optimized_json_bytes: usize,
+    total_input_bytes: usize,
+    output_dir: Option<&Path>,
+    codec_args: &CodecArgs,
+    stats: &mut StatsReport,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let jinterners_bytes = jdatabase.jinterners.get_size();
+    total_optimized_json_bytes += jinterners_bytes;
+    println!(
+        "Optimized to {total_optimized_json_bytes} bytes (relative size = {:.02}%)",
+        total_optimized_json_bytes as f64 * 100.0 / total_input_bytes as f64,
+    );
+    println!(
+        "[{:.02}%] Jinterners: {jinterners_bytes} bytes",
+        jinterners_bytes as f64 * 100.0 / total_optimized_json_bytes as f64,
+    );
+    jdatabase
+        .jinterners
+        .print_summary_strings("  ", "String", total_optimized_json_bytes);
+    jdatabase
+        .jinterners
+        .print_summary_arrays("  ", "Array", total_optimized_json_bytes);
+    jdatabase
+        .jinterners
+        .print_summary_objects("  ", "Object", total_optimized_json_bytes);
+    stats.totals.optimized_json_bytes = Some(total_optimized_json_bytes);
+    stats.totals.jinterners_bytes = Some(jinterners_bytes);
+
+    let Some(output_dir) = output_dir else {
+        return Ok(());
+    };
+    jcodec(
+        &jdatabase,
+        output_dir,
+        "json",
+        total_input_bytes,
+        codec_args,
+        stats,
+    )?;
+
+    println!("Optimizing interners...");
+    let opt = joptimize(&jdatabase.jinterners, &jdatabase.jvalues);
+
+    println!("Optimizing interners (manual loop)...");
+    let (jinterners, jvalues) = joptimize_loop(jdatabase.jinterners, jdatabase.jvalues);
+
+    if let Some((jinterners_opt, jvalues_opt)) = opt {
+        assert_eq!(jinterners, jinterners_opt);
+        assert_eq!(jvalues, jvalues_opt);
+    }
+
+    let jdatabase = Jdatabase {
+        jinterners,
+        jvalues,
+    };
+    jcodec(
+        &jdatabase,
+        output_dir,
+        "json_optimized",
+        total_input_bytes,
+        codec_args,
+        stats,
+    )?;
+
+    Ok(())
+}
+
+pub fn stream(
+    inputs: &Inputs,
+    output: &Path,
+    report: &ReportArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let file_count = AtomicUsize::new(0);
+    let failures = Failures::default();
+    let total_input_bytes = AtomicUsize::new(0);
+
+    let arenas = Arenas::default();
+    let datas = Mutex::new(Vec::new());
+
+    info!(?output, "Streaming database");
+    let writer = stream::StreamWriter::create(output)?;
+
+    inputs.visit(&|input| {
+        let file_path = input.path();
+        let bytes = input.read()?;
+        total_input_bytes.fetch_add(bytes.len(), Ordering::Relaxed);
+
+        // Parse directly into the arenas, to avoid allocating the intermediate source data.
+        let optimized = match schema::optimized::seed::from_slice(&arenas, &bytes) {
+            Ok(optimized) => optimized,
+            Err(err) => {
+                failures.record_json_error(file_path, &bytes, &err);
+                return Ok(());
+            }
+        };
+
+        let optimized = match arenas.intern_data(optimized) {
+            Ok(optimized) => optimized,
+            Err(err) => {
+                failures.record(file_path, Stage::Conversion, err);
+                return Ok(());
+            }
+        };
+        writer.write(file_path, optimized, inputs.provenance(input, &bytes)?)?;
+        datas.lock().unwrap().push(optimized);
+        file_count.fetch_add(1, Ordering::Relaxed);
+
+        Ok(())
+    })?;
+
+    let file_count = file_count.load(Ordering::Relaxed);
+    let failures = failures.into_sorted();
+    let total_input_bytes = total_input_bytes.load(Ordering::Relaxed);
+
+    let start = Instant::now();
+    let total_bytes = writer.finish(&arenas)?;
+    let write_time = Instant::now().duration_since(start);
+    info!(?write_time, "Wrote arenas");
+
+    println!(
+        "Parsed {total_input_bytes} bytes from {file_count} files (+ {} failed files)",
+        failures.len(),
+    );
+    print_failures(&failures);
+    write_report(report.failure_report.as_deref(), &failures)?;
+    let datas = datas.into_inner().unwrap();
+    print_duplicates(&datas);
+    println!(
+        "Streamed to {total_bytes} bytes (relative size = {:.02}%)",
+        total_bytes as f64 * 100.0 / total_input_bytes as f64,
+    );
+
+    let arenas_bytes = arenas.get_size();
+    println!("Arenas use {arenas_bytes} bytes in memory");
+    arenas.print_summary(arenas_bytes, &datas);
+
+    Ok(())
+}
+
+// Prints how many snapshots are exact duplicates of an earlier one, e.g. because the upstream API
+// returned the same payload twice in a row. These snapshots share the same handle.
+pub fn print_duplicates(datas: &[Interned<schema::optimized::Data>]) {
+    let distinct_count = datas.iter().collect::<hashbrown::HashSet<_>>().len();
+    println!(
+        "{} snapshots are exact duplicates of another snapshot",
+        datas.len() - distinct_count
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::InputFormat;
+    use crate::schema::generate::{Generator, TEST_CONFIG};
+    use paralight::prelude::*;
+
+    // Ingests the input directory like the build command does, on a pool of the given number of
+    // threads, and returns the serialized databases.
+    fn build_bytes(input_dir: &Path, threads: usize) -> (Vec<u8>, Vec<u8>) {
+        let pool = rayon_core::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        let thread_pool = RayonThreadPool::new(
+            &pool,
+            ThreadCount::try_from(threads).unwrap(),
+            RangeStrategy::WorkStealing,
+        );
+        let input_dirs = [input_dir.to_owned()];
+        let inputs = Inputs::new(&thread_pool, &input_dirs, InputFormat::Auto, true);
+
+        let ingestion = Ingestion::<Disruptions>::default();
+        inputs
+            .visit(&|input| ingestion.ingest(&inputs, input, &|_| true))
+            .unwrap();
+        let (_, mut database, jdatabase) = ingestion.finish();
+        database.compact().unwrap();
+
+        (
+            bincode::serialize(&database).unwrap(),
+            bincode::serialize(&jdatabase).unwrap(),
+        )
+    }
+
+    #[test]
+    fn builds_are_deterministic() {
+        let input_dir: PathBuf = std::env::temp_dir().join(format!(
+            "rust-interning-deterministic-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&input_dir).unwrap();
+        for (i, data) in Generator::new(TEST_CONFIG).take(64).enumerate() {
+            let bytes = serde_json::to_vec(&data).unwrap();
+            std::fs::write(input_dir.join(format!("{i:06}.json")), bytes).unwrap();
+        }
+
+        let sequential = build_bytes(&input_dir, 1);
+        for _ in 0..3 {
+            assert!(build_bytes(&input_dir, 4) == sequential);
+        }
+
+        std::fs::remove_dir_all(&input_dir).unwrap();
+    }
+}
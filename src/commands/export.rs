@@ -0,0 +1,260 @@
+// Export of a database to formats meant for other tools, i.e. plain or resolved JSON, GTFS
+// Realtime and dictionary-compressed Zstandard, as well as generation of synthetic input files.
+
+use crate::cli::{CompressionArgs, DatabaseArgs};
+use crate::compare::EqWith;
+use crate::database::{load_database, ResolvedDatabase};
+use crate::schema;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tracing::{debug, info};
+
+pub fn export(args: &DatabaseArgs, output: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let database = load_database(args)?;
+
+    let start = Instant::now();
+    let mut connection = rusqlite::Connection::open(output)?;
+    schema::optimized::sqlite::export(
+        &mut connection,
+        &database.arenas,
+        &database.datas,
+        &database.paths,
+    )?;
+    let export_time = Instant::now().duration_since(start);
+    info!(?output, ?export_time, "Exported to SQLite file");
+
+    println!(
+        "Exported {} snapshots ({} bytes)",
+        database.datas.len(),
+        output.metadata()?.len(),
+    );
+    Ok(())
+}
+
+pub fn export_json(
+    args: &DatabaseArgs,
+    output_dir: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let database = load_database(args)?;
+
+    let start = Instant::now();
+    for (data, path) in database.datas.iter().zip(database.paths.iter()) {
+        let data = database.arenas.data(*data);
+        let source = data.to_source(&database.arenas);
+        // Check that the conversion is lossless, up to the order of sets.
+        assert!(
+            data.eq_with(&source, &database.arenas),
+            "Regenerated data didn't match snapshot: {path:?}"
+        );
+
+        let output = output_path(output_dir, path)?;
+        std::fs::write(&output, serde_json::to_vec(&source)?)?;
+    }
+    let export_time = Instant::now().duration_since(start);
+    info!(?output_dir, ?export_time, "Exported to JSON files");
+
+    println!(
+        "Exported {} snapshots to {output_dir:?}",
+        database.datas.len()
+    );
+    Ok(())
+}
+
+pub fn export_resolved(
+    args: &DatabaseArgs,
+    output: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let database = load_database(args)?;
+
+    let start = Instant::now();
+    serde_json::to_writer_pretty(
+        BufWriter::new(File::create(output)?),
+        &ResolvedDatabase(&database),
+    )?;
+    let export_time = Instant::now().duration_since(start);
+    info!(?output, ?export_time, "Exported to resolved JSON file");
+
+    println!(
+        "Exported {} snapshots ({} bytes)",
+        database.datas.len(),
+        output.metadata()?.len(),
+    );
+    Ok(())
+}
+
+pub fn export_zstd(
+    args: &DatabaseArgs,
+    output_dir: &Path,
+    dict_size: usize,
+    compression: &CompressionArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let database = load_database(args)?;
+    let arenas = &database.arenas;
+    let level = compression.zstd_level as i32;
+
+    // The strings make up most of the contents of the snapshots, and the arena contains each of
+    // them once, so they are a compact training set.
+    let start = Instant::now();
+    let samples: Vec<&str> = arenas.strings().collect();
+    let dictionary = zstd::dict::from_samples(&samples, dict_size).map_err(|e| {
+        format!(
+            "Failed to train a zstd dictionary from {} strings: {e}",
+            samples.len()
+        )
+    })?;
+    let train_time = Instant::now().duration_since(start);
+    info!(?train_time, "Trained zstd dictionary");
+    std::fs::create_dir_all(output_dir)?;
+    std::fs::write(output_dir.join("dictionary"), &dictionary)?;
+
+    // Snapshots are small and numerous, so they are compressed in-process rather than by spawning
+    // a zstd process for each of them.
+    let start = Instant::now();
+    let mut compressor = zstd::bulk::Compressor::new(level)?;
+    let mut dict_compressor = zstd::bulk::Compressor::with_dictionary(level, &dictionary)?;
+    let mut dict_decompressor = zstd::bulk::Decompressor::with_dictionary(&dictionary)?;
+    let mut json_sizes = Vec::with_capacity(database.datas.len());
+    let mut zstd_sizes = Vec::with_capacity(database.datas.len());
+    let mut dict_sizes = Vec::with_capacity(database.datas.len());
+    for (data, path) in database.datas.iter().zip(database.paths.iter()) {
+        let json = serde_json::to_vec(&arenas.resolver().data(*data))?;
+        let compressed = compressor.compress(&json)?;
+        let dict_compressed = dict_compressor.compress(&json)?;
+        // Check that each snapshot can be decompressed on its own with the dictionary.
+        assert_eq!(
+            dict_decompressor.decompress(&dict_compressed, json.len())?,
+            json,
+            "Decompressed snapshot didn't match: {path:?}"
+        );
+        debug!(
+            ?path,
+            json_bytes = json.len(),
+            zstd_bytes = compressed.len(),
+            dict_bytes = dict_compressed.len(),
+            "Compressed snapshot",
+        );
+
+        let mut output = output_path(output_dir, path)?.into_os_string();
+        output.push(".zst");
+        std::fs::write(&output, &dict_compressed)?;
+
+        json_sizes.push(json.len());
+        zstd_sizes.push(compressed.len());
+        dict_sizes.push(dict_compressed.len());
+    }
+    let export_time = Instant::now().duration_since(start);
+    info!(?output_dir, ?export_time, "Exported to zstd files");
+
+    let json_bytes: usize = json_sizes.iter().sum();
+    println!(
+        "Trained a {}-byte dictionary from {} strings",
+        dictionary.len(),
+        samples.len(),
+    );
+    println!(
+        "Exported {} snapshots to {output_dir:?}, compressed independently of each other",
+        database.datas.len(),
+    );
+    print_snapshot_sizes("JSON", &mut json_sizes, json_bytes);
+    print_snapshot_sizes(&format!("zstd -{level}"), &mut zstd_sizes, json_bytes);
+    print_snapshot_sizes(
+        &format!("zstd -{level} with dictionary"),
+        &mut dict_sizes,
+        json_bytes,
+    );
+    Ok(())
+}
+
+pub fn export_gtfs(
+    args: &DatabaseArgs,
+    output_dir: &Path,
+    merged: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let database = load_database(args)?;
+    let arenas = &database.arenas;
+
+    let start = Instant::now();
+    if merged {
+        let feed = arenas
+            .gtfs_merged_feed(&database.datas)
+            .ok_or("The database doesn't contain any successful snapshot")?;
+        std::fs::create_dir_all(output_dir)?;
+        let output = output_dir.join("alerts.pb");
+        std::fs::write(&output, &feed)?;
+        println!(
+            "Exported a merged feed of {} bytes to {output:?}",
+            feed.len()
+        );
+    } else {
+        let mut feeds = 0;
+        let mut bytes = 0;
+        for (data, path) in database.datas.iter().zip(database.paths.iter()) {
+            // Failed API responses have no disruptions to report.
+            let Some(feed) = arenas.gtfs_feed(*data) else {
+                debug!(?path, "Skipped failed snapshot");
+                continue;
+            };
+            let mut output = output_path(output_dir, path)?.into_os_string();
+            output.push(".pb");
+            std::fs::write(&output, &feed)?;
+            feeds += 1;
+            bytes += feed.len();
+        }
+        println!(
+            "Exported {feeds} feeds of {bytes} bytes in total to {output_dir:?}, skipping {} failed snapshots",
+            database.datas.len() - feeds,
+        );
+    }
+    let export_time = Instant::now().duration_since(start);
+    info!(?output_dir, ?export_time, "Exported to GTFS-Realtime feeds");
+    Ok(())
+}
+
+fn print_snapshot_sizes(title: &str, sizes: &mut [usize], json_bytes: usize) {
+    sizes.sort_unstable();
+    let total: usize = sizes.iter().sum();
+    println!(
+        "  {title}: {total} bytes (relative size = {:.02}%) | per snapshot: min {} | median {} | max {} bytes",
+        total as f64 * 100.0 / json_bytes as f64,
+        sizes.first().unwrap_or(&0),
+        sizes.get(sizes.len() / 2).unwrap_or(&0),
+        sizes.last().unwrap_or(&0),
+    );
+}
+
+// Returns the path where to export the snapshot parsed from the given input file, creating its
+// parent directories.
+fn output_path(output_dir: &Path, path: &Path) -> std::io::Result<PathBuf> {
+    // Strip the root of absolute paths, to write all the files under the output directory.
+    let relative: PathBuf = path
+        .components()
+        .filter(|component| matches!(component, std::path::Component::Normal(_)))
+        .collect();
+    let output = output_dir.join(relative);
+    if let Some(parent) = output.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    Ok(output)
+}
+
+pub fn generate(
+    output_dir: &Path,
+    snapshots: usize,
+    config: schema::generate::Config,
+) -> Result<(), Box<dyn std::error::Error>> {
+    info!(?output_dir, ?config, "Generating synthetic dataset");
+    std::fs::create_dir_all(output_dir)?;
+
+    let mut total_bytes = 0;
+    let generator = schema::generate::Generator::new(config);
+    for (i, data) in generator.take(snapshots).enumerate() {
+        let bytes = serde_json::to_vec(&data)?;
+        total_bytes += bytes.len();
+        std::fs::write(output_dir.join(format!("{i:06}.json")), bytes)?;
+    }
+
+    println!("Generated {snapshots} snapshots ({total_bytes} bytes) in {output_dir:?}");
+    Ok(())
+}
@@ -0,0 +1,452 @@
+// Incremental ingestion of input files into an existing database, either once (`append`) or
+// continuously as files appear in the watched directories or are fetched from a feed. Merging of
+// databases reuses the same path, interning the snapshots of each input into the output arenas.
+
+use crate::cli::{CodecArgs, DatabaseArgs, Format, ReportArgs, StatsArgs, VerifyArgs};
+use crate::commands::build::{check_conversion, convert, print_duplicates};
+use crate::database::{
+    load_database, load_or_create_database, save_database, sorted_by_path, Database,
+};
+use crate::input::{ArchiveFormat, Bytes, InputFile};
+use crate::report::{
+    print_failures, print_unknown_fields, write_report, Failures, Stage, UnknownFields,
+};
+use crate::schema::optimized::Arenas;
+use crate::schema::Disruptions;
+use crate::stats::{FileCounts, StatsReport};
+use crate::walk::Inputs;
+use crate::{input, round_trip, schema, stats};
+use blazinterner::Interned;
+use get_size2::GetSize;
+use notify::Watcher;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+pub fn append(
+    inputs: &Inputs,
+    args: &DatabaseArgs,
+    output_dir: PathBuf,
+    codec_args: &CodecArgs,
+    stats_args: &StatsArgs,
+    report: &ReportArgs,
+    verify: &VerifyArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut database = load_database(args)?;
+    let ingested: HashSet<&Path> = database.paths.iter().map(|path| path.as_path()).collect();
+
+    let should_verify = verify.sampler();
+    let file_count = AtomicUsize::new(0);
+    let verified_count = AtomicUsize::new(0);
+    let file_skipped_count = AtomicUsize::new(0);
+    let failures = Failures::default();
+    let unknown_fields = UnknownFields::default();
+    let total_input_bytes = AtomicUsize::new(0);
+
+    let arenas = &database.arenas;
+    let datas = Mutex::new(Vec::new());
+
+    inputs.visit(&|input| {
+        let file_path = input.path();
+        if ingested.contains(file_path) {
+            // Still account for the file size, to compare the database against all its inputs.
+            total_input_bytes.fetch_add(input.size()? as usize, Ordering::Relaxed);
+            file_skipped_count.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
+
+        let bytes = input.read()?;
+
+        let (data, extras) = match inputs.parse::<Disruptions>(&bytes) {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                failures.record_json_error(file_path, &bytes, &err);
+                return Ok(());
+            }
+        };
+        unknown_fields.record(file_path, &extras);
+        total_input_bytes.fetch_add(bytes.len(), Ordering::Relaxed);
+
+        let Some(optimized) = convert::<Disruptions>(arenas, file_path, &data, &failures) else {
+            return Ok(());
+        };
+        if should_verify(file_path) {
+            verified_count.fetch_add(1, Ordering::Relaxed);
+            if !check_conversion::<Disruptions>(arenas, file_path, &optimized, &data, &failures) {
+                return Ok(());
+            }
+        }
+
+        let optimized = match arenas.intern_data(optimized) {
+            Ok(optimized) => optimized,
+            Err(err) => {
+                failures.record(file_path, Stage::Conversion, err);
+                return Ok(());
+            }
+        };
+        let provenance = inputs.provenance(input, &bytes)?;
+        datas
+            .lock()
+            .unwrap()
+            .push((file_path.to_owned(), (optimized, provenance)));
+        file_count.fetch_add(1, Ordering::Relaxed);
+
+        Ok(())
+    })?;
+
+    let file_count = file_count.load(Ordering::Relaxed);
+    let file_skipped_count = file_skipped_count.load(Ordering::Relaxed);
+    let verified_count = verified_count.load(Ordering::Relaxed);
+    let failures = failures.into_sorted();
+    let total_input_bytes = total_input_bytes.load(Ordering::Relaxed);
+    let (paths, datas) = sorted_by_path(datas.into_inner().unwrap());
+    let unknown_fields = unknown_fields.into_counts();
+
+    println!(
+        "Appended {file_count} new files (+ {file_skipped_count} already ingested files, + {} failed files)",
+        failures.len(),
+    );
+    print_failures(&failures);
+    print_unknown_fields(&unknown_fields);
+    write_report(report.failure_report.as_deref(), &failures)?;
+    println!("Verified {verified_count} of the parsed files");
+    for (path, (data, provenance)) in paths.into_iter().zip(datas) {
+        database.push(path, data, provenance);
+    }
+    print_duplicates(&database.datas);
+    database.compact()?;
+
+    let total_optimized_bytes = database.arenas.get_size() + database.datas.get_size();
+    println!(
+        "Optimized to {total_optimized_bytes} bytes (relative size = {:.02}%)",
+        total_optimized_bytes as f64 * 100.0 / total_input_bytes as f64,
+    );
+    database
+        .arenas
+        .print_summary(total_optimized_bytes, &database.datas);
+
+    let mut stats = StatsReport {
+        files: Some(FileCounts {
+            unknown_fields,
+            ..FileCounts::new(file_count, verified_count, file_skipped_count, &failures)
+        }),
+        interners: database.arenas.interner_stats(),
+        ..Default::default()
+    };
+    stats.totals.input_bytes = total_input_bytes;
+    stats.totals.optimized_bytes = Some(total_optimized_bytes);
+    stats.totals.arenas_bytes = Some(database.arenas.get_size());
+
+    round_trip::codec(
+        database,
+        &output_dir,
+        total_input_bytes,
+        codec_args,
+        &mut stats,
+    )?;
+    stats::write_stats(&output_dir, &stats, stats_args.report)
+}
+
+// Delay without events after which a new file is considered completely written.
+const WATCH_SETTLE_DELAY: Duration = Duration::from_secs(1);
+
+pub fn watch(
+    inputs: &Inputs,
+    args: &DatabaseArgs,
+    save_interval: Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let format = args.format()?;
+    let mut database = load_or_create_database(args)?;
+    let mut ingested: HashSet<PathBuf> = database.paths.iter().cloned().collect();
+
+    // Start watching before the initial scan, so that no file created in between is missed.
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)?;
+    for directory in inputs.dirs {
+        info!(?directory, "Watching directory");
+        watcher.watch(directory, notify::RecursiveMode::Recursive)?;
+    }
+
+    let datas = Mutex::new(Vec::new());
+    inputs.visit(&|input| {
+        if !ingested.contains(input.path()) {
+            if let Some(data) = watch_ingest(&database.arenas, input)? {
+                datas.lock().unwrap().push((input.path().to_owned(), data));
+            }
+        }
+        Ok(())
+    })?;
+
+    let (paths, datas) = sorted_by_path(datas.into_inner().unwrap());
+    let mut dirty = !paths.is_empty();
+    ingested.extend(paths.iter().cloned());
+    for (path, data) in paths.into_iter().zip(datas) {
+        database.push(path, data, None);
+    }
+
+    // Files are ingested once no event was received for them during the settle delay, as they may
+    // be created before their content is written.
+    let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+    let mut last_save: Option<Instant> = None;
+    loop {
+        match receiver.recv_timeout(WATCH_SETTLE_DELAY) {
+            Ok(Ok(event)) => {
+                if matches!(
+                    event.kind,
+                    notify::EventKind::Create(_) | notify::EventKind::Modify(_)
+                ) {
+                    let now = Instant::now();
+                    for path in event.paths {
+                        pending.insert(path, now);
+                    }
+                }
+            }
+            Ok(Err(err)) => warn!(%err, "Error watching input directories"),
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => (),
+            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+
+        let now = Instant::now();
+        let mut settled = Vec::new();
+        pending.retain(|path, last_event| {
+            let is_settled = now.duration_since(*last_event) >= WATCH_SETTLE_DELAY;
+            if is_settled {
+                settled.push(path.clone());
+            }
+            !is_settled
+        });
+        settled.sort_unstable();
+        for path in settled {
+            if !path.is_file() {
+                continue;
+            }
+            if let Some(format) = ArchiveFormat::detect(&path) {
+                // An archive may be partially written, in which case its complete entries are
+                // ingested now and the remaining ones on the next event.
+                let result = input::read_archive(&path, format, |entries| {
+                    for (entry_path, contents) in entries {
+                        let input = InputFile::archived(&entry_path, &contents);
+                        dirty |= watch_ingest_new(&mut database, &mut ingested, &input, inputs)?;
+                    }
+                    Ok(())
+                });
+                if let Err(err) = result {
+                    warn!(?path, %err, "Error reading archive");
+                }
+            } else {
+                let input = InputFile::on_disk(&path);
+                dirty |= watch_ingest_new(&mut database, &mut ingested, &input, inputs)?;
+            }
+        }
+
+        if dirty && last_save.is_none_or(|last_save| now.duration_since(last_save) >= save_interval)
+        {
+            save_database(&database, &args.path, format)?;
+            last_save = Some(now);
+            dirty = false;
+        }
+    }
+
+    Ok(())
+}
+
+// Ingests the snapshots of a new or modified file that aren't in the database yet, returning
+// whether any was added.
+fn watch_ingest_new(
+    database: &mut Database,
+    ingested: &mut HashSet<PathBuf>,
+    input: &InputFile,
+    inputs: &Inputs,
+) -> std::io::Result<bool> {
+    if input.is_ndjson(inputs.format) {
+        // Lines may be appended to an NDJSON file, so it's read again on each event and only its
+        // new records are ingested. A partially written last line fails to parse, and is ingested
+        // on the next event.
+        let Some(bytes) = watch_read(input)? else {
+            return Ok(false);
+        };
+        let mut added = false;
+        for (path, line) in input::ndjson_records(input.path(), &bytes) {
+            let record = InputFile::record(&path, line);
+            added |= watch_ingest_new(database, ingested, &record, inputs)?;
+        }
+        return Ok(added);
+    }
+
+    let path = input.path();
+    if ingested.contains(path) {
+        return Ok(false);
+    }
+    let Some(data) = watch_ingest(&database.arenas, input)? else {
+        return Ok(false);
+    };
+    info!(?path, "Ingested new file");
+    database.push(path.to_owned(), data, None);
+    ingested.insert(path.to_owned());
+    Ok(true)
+}
+
+// Parses a file directly into the arenas, returning `None` if it isn't a valid snapshot.
+fn watch_ingest(
+    arenas: &Arenas,
+    input: &InputFile,
+) -> std::io::Result<Option<Interned<schema::optimized::Data>>> {
+    let Some(bytes) = watch_read(input)? else {
+        return Ok(None);
+    };
+    Ok(ingest_bytes(arenas, input.path(), &bytes))
+}
+
+fn watch_read<'a>(input: &InputFile<'a>) -> std::io::Result<Option<Bytes<'a>>> {
+    // A compressed file may be partially written, in which case it's ingested on the next event.
+    match input.read() {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(err) if err.kind() == std::io::ErrorKind::InvalidData => {
+            warn!(path = ?input.path(), %err, "Error decompressing file");
+            Ok(None)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+fn ingest_bytes(
+    arenas: &Arenas,
+    file_path: &Path,
+    bytes: &[u8],
+) -> Option<Interned<schema::optimized::Data>> {
+    let data = match schema::optimized::seed::from_slice(arenas, bytes) {
+        Ok(data) => data,
+        Err(err) => {
+            warn!(path = ?file_path, %err, "Error parsing JSON");
+            return None;
+        }
+    };
+    match arenas.intern_data(data) {
+        Ok(data) => Some(data),
+        Err(err) => {
+            warn!(path = ?file_path, %err, "Error interning snapshot");
+            None
+        }
+    }
+}
+
+#[cfg(feature = "fetch")]
+pub fn fetch(
+    url: &str,
+    api_key: &str,
+    interval: Duration,
+    output_dir: &Path,
+    database: Option<&DatabaseArgs>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut database = match database {
+        Some(args) => Some((args, args.format()?, load_or_create_database(args)?)),
+        None => None,
+    };
+    std::fs::create_dir_all(output_dir)?;
+
+    loop {
+        let start = Instant::now();
+        info!(url, "Fetching snapshot");
+        let response = ureq::get(url)
+            .header("apikey", api_key)
+            .call()
+            .and_then(|response| response.into_body().read_to_vec());
+        match response {
+            // Request failures are transient, the next poll may succeed.
+            Err(err) => warn!(%err, "Error fetching snapshot"),
+            Ok(bytes) => {
+                let file_name = chrono::Utc::now().format("%Y%m%dT%H%M%SZ.json").to_string();
+                let path = output_dir.join(file_name);
+                // Write to a temporary file first, so that the snapshot never appears half-written
+                // to a concurrent `watch` command.
+                let tmp_path = path.with_extension("tmp");
+                std::fs::write(&tmp_path, &bytes)?;
+                std::fs::rename(&tmp_path, &path)?;
+                info!(?path, bytes = bytes.len(), "Wrote snapshot");
+
+                if let Some((args, format, database)) = &mut database {
+                    if let Some(data) = ingest_bytes(&database.arenas, &path, &bytes) {
+                        database.push(path, data, None);
+                        save_database(database, &args.path, *format)?;
+                    }
+                }
+            }
+        }
+
+        std::thread::sleep(interval.saturating_sub(Instant::now().duration_since(start)));
+    }
+}
+
+pub fn merge(
+    output_dir: PathBuf,
+    format: Option<Format>,
+    databases: Vec<PathBuf>,
+    codec_args: &CodecArgs,
+    stats_args: &StatsArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut merged: Database = Database::new(Arenas::default());
+    let mut total_input_bytes = 0;
+
+    for path in databases {
+        total_input_bytes += path.metadata()?.len() as usize;
+        let database = load_database(&DatabaseArgs { path, format })?;
+
+        let start = Instant::now();
+        let mapping = merged.arenas.merge(&database.arenas)?;
+
+        // The same input file may have been ingested into several databases.
+        let known: HashSet<PathBuf> = merged.paths.iter().cloned().collect();
+        let mut duplicate_count = 0;
+        let snapshot_count = database.datas.len();
+        let snapshots = database.paths.into_iter().zip(database.datas);
+        for ((path, data), provenance) in snapshots.zip(database.provenance) {
+            if known.contains(&path) {
+                duplicate_count += 1;
+                continue;
+            }
+            merged.push(path, mapping.data(data), provenance);
+        }
+        let remap_time = Instant::now().duration_since(start);
+        info!(
+            snapshot_count,
+            duplicate_count,
+            ?remap_time,
+            "Remapped snapshots"
+        );
+    }
+
+    println!(
+        "Merged {total_input_bytes} bytes of databases into {} snapshots",
+        merged.datas.len()
+    );
+    print_duplicates(&merged.datas);
+    merged.compact()?;
+    let total_optimized_bytes = merged.arenas.get_size() + merged.datas.get_size();
+    println!(
+        "Optimized to {total_optimized_bytes} bytes (relative size = {:.02}%)",
+        total_optimized_bytes as f64 * 100.0 / total_input_bytes as f64,
+    );
+    merged
+        .arenas
+        .print_summary(total_optimized_bytes, &merged.datas);
+
+    let mut stats = StatsReport {
+        interners: merged.arenas.interner_stats(),
+        ..Default::default()
+    };
+    stats.totals.input_bytes = total_input_bytes;
+    stats.totals.optimized_bytes = Some(total_optimized_bytes);
+    stats.totals.arenas_bytes = Some(merged.arenas.get_size());
+
+    round_trip::codec(
+        merged,
+        &output_dir,
+        total_input_bytes,
+        codec_args,
+        &mut stats,
+    )?;
+    stats::write_stats(&output_dir, &stats, stats_args.report)
+}
@@ -0,0 +1,395 @@
+// Read-only commands that load a database and print information about its snapshots: summaries,
+// similarity between consecutive snapshots, churn, lifetimes of disruptions, full-text search and
+// queries.
+
+use crate::cli::{DatabaseArgs, Format, Query, TableFormat};
+use crate::commands::build::print_duplicates;
+use crate::database::{load_database, map_database, ArchivedDatabase, Database};
+use crate::provenance::Provenance;
+use crate::schema::optimized::lifetime::Lifetime;
+use crate::schema::optimized::search::SearchIndex;
+use crate::schema::optimized::view::DataView;
+use crate::schema::optimized::ArchivedData;
+use crate::{checksum, schema, version};
+use get_size2::GetSize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+use tracing::info;
+
+pub fn inspect(
+    args: &DatabaseArgs,
+    top: usize,
+    message_templates: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let database = load_database(args)?;
+
+    let success_count = database
+        .datas
+        .iter()
+        .filter(|data| {
+            matches!(
+                database.arenas.data(**data),
+                schema::optimized::Data::Success(_)
+            )
+        })
+        .count();
+    println!(
+        "Loaded {} snapshots ({success_count} successful, {} errors)",
+        database.datas.len(),
+        database.datas.len() - success_count,
+    );
+    print_duplicates(&database.datas);
+
+    let datas_bytes = database.datas.get_size();
+    let arenas_bytes = database.arenas.get_size();
+    let total_bytes = datas_bytes + arenas_bytes;
+    println!("Database uses {total_bytes} bytes in memory");
+    println!(
+        "[{:.02}%] Datas: {datas_bytes} bytes",
+        datas_bytes as f64 * 100.0 / total_bytes as f64,
+    );
+    println!(
+        "[{:.02}%] Arenas: {arenas_bytes} bytes",
+        arenas_bytes as f64 * 100.0 / total_bytes as f64,
+    );
+    database.arenas.print_summary(total_bytes, &database.datas);
+    database.arenas.print_reference_counts(&database.datas, top);
+    database.arenas.print_string_lengths(top);
+    if message_templates {
+        database.arenas.print_message_templates(top);
+    }
+
+    Ok(())
+}
+
+pub fn similarity(
+    args: &DatabaseArgs,
+    threshold: f64,
+    top: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let database = load_database(args)?;
+    database.arenas.print_similarity_clusters(threshold, top);
+    Ok(())
+}
+
+pub fn churn(
+    args: &DatabaseArgs,
+    churn_report: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let database = load_database(args)?;
+    let steps = database.arenas.churn(&database.datas);
+
+    println!("| Snapshot                 | Appeared | Disappeared |  Changed | Unchanged | Path");
+    println!("| ------------------------ | -------- | ----------- | -------- | --------- | ----");
+    for step in &steps {
+        println!(
+            "| {:<24} | {:>8} | {:>11} | {:>8} | {:>9} | {:?}",
+            step.last_updated_date,
+            step.churn.appeared,
+            step.churn.disappeared,
+            step.churn.changed,
+            step.churn.unchanged,
+            database.paths[step.index],
+        );
+    }
+    let total = schema::optimized::churn::ChurnStep::total(&steps);
+    let per_step = |count: usize| count as f64 / steps.len().max(1) as f64;
+    println!(
+        "Over {} consecutive pairs of successful snapshots: {} disruptions appeared ({:.02} per snapshot), {} disappeared ({:.02}), {} changed ({:.02})",
+        steps.len(),
+        total.appeared,
+        per_step(total.appeared),
+        total.disappeared,
+        per_step(total.disappeared),
+        total.changed,
+        per_step(total.changed),
+    );
+
+    if let Some(path) = churn_report {
+        info!(?path, "Writing churn report");
+        serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), &steps)?;
+    }
+    Ok(())
+}
+
+pub fn lifetimes(
+    args: &DatabaseArgs,
+    output: &Path,
+    table_format: Option<TableFormat>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let table_format = match table_format {
+        Some(table_format) => table_format,
+        None => TableFormat::from_path(output)?,
+    };
+    let database = load_database(args)?;
+    let lifetimes = database.arenas.lifetimes(&database.datas);
+
+    let count = |f: fn(&Lifetime) -> bool| lifetimes.iter().filter(|x| f(x)).count();
+    println!(
+        "Tracked {} disruptions: {} with several versions, {} with changed application periods, {} that reappeared after missing from a snapshot",
+        lifetimes.len(),
+        count(|x| x.versions > 1),
+        count(|x| x.period_changes > 0),
+        count(|x| x.reappearances > 0),
+    );
+
+    info!(path = ?output, ?table_format, "Writing lifetimes");
+    let mut writer = BufWriter::new(File::create(output)?);
+    match table_format {
+        TableFormat::Csv => Lifetime::write_csv(&lifetimes, &mut writer)?,
+        TableFormat::Json => serde_json::to_writer_pretty(&mut writer, &lifetimes)?,
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn search(
+    args: &DatabaseArgs,
+    query: &str,
+    top: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let database = load_database(args)?;
+
+    let start = Instant::now();
+    let index = SearchIndex::new(&database.arenas);
+    let index_time = Instant::now().duration_since(start);
+    info!(
+        word_count = index.word_count(),
+        ?index_time,
+        "Indexed titles and messages"
+    );
+
+    database
+        .arenas
+        .print_search(&index, &database.datas, query, top);
+    Ok(())
+}
+
+pub fn run_query(args: &DatabaseArgs, query: Query) -> Result<(), Box<dyn std::error::Error>> {
+    match (args.format()?, &query) {
+        (Format::Indexed, Query::Snapshot { index }) => {
+            let database = Database::open(&args.path)?;
+            let (path, data) = database.get_snapshot(*index)?;
+            return print_snapshot(&path, database.arenas().resolver().view(data));
+        }
+        // Snapshots are regenerated from the deserialized arenas, which are also needed to compare
+        // timestamps.
+        (Format::Rkyv, Query::Snapshot { .. } | Query::ActiveAt { .. }) => (),
+        (Format::Rkyv, _) => return run_query_archived(args, query),
+        _ => (),
+    }
+
+    let database = load_database(args)?;
+    let resolver = database.arenas.resolver();
+
+    match query {
+        Query::Snapshot { index } => {
+            let (Some(path), Some(data)) = (database.paths.get(index), database.datas.get(index))
+            else {
+                return Err(format!(
+                    "Snapshot {index} is out of range, the database contains {} snapshots",
+                    database.datas.len()
+                )
+                .into());
+            };
+            print_snapshot(path, resolver.data(*data))?;
+        }
+        Query::Snapshots => {
+            for (i, data) in database.datas.iter().enumerate() {
+                println!("[{i}] {}", resolver.data(*data));
+            }
+        }
+        Query::Line { id } => {
+            let arenas = &database.arenas;
+            let disruptions = LineDisruptions::collect(
+                database
+                    .datas
+                    .iter()
+                    .filter_map(|data| match arenas.data(*data) {
+                        schema::optimized::Data::Success(data) => Some(data),
+                        schema::optimized::Data::Error(_) => None,
+                    }),
+                |data| (data.last_updated_date(), data.line_disruptions(arenas, &id)),
+            );
+            disruptions.print(&id, |disruption| {
+                let disruption = resolver.disruption(disruption);
+                (
+                    disruption.title(),
+                    disruption.severity(),
+                    disruption
+                        .application_periods()
+                        .map(|period| (period.begin(), period.end()))
+                        .collect(),
+                )
+            });
+        }
+        Query::ActiveAt { timestamp } => {
+            let instant = schema::optimized::active::parse_instant(&timestamp)?;
+            let Some(active) = database.arenas.active_at(&database.datas, instant) else {
+                return Err("The database contains no successful snapshot".into());
+            };
+            println!(
+                "{} of {} disruptions active at {timestamp} in snapshot [{}] {:?}",
+                active.active.len(),
+                active.disruptions,
+                active.snapshot,
+                database.paths[active.snapshot],
+            );
+            for (line, severities) in &active.by_line {
+                match line {
+                    Some((id, name)) => println!("{name} ({id}):"),
+                    None => println!("No impacted line:"),
+                }
+                for (severity, disruptions) in severities {
+                    println!("  {severity}: {}", disruptions.len());
+                    for disruption in disruptions {
+                        let disruption = resolver.disruption(*disruption);
+                        let periods: Vec<String> = disruption
+                            .application_periods()
+                            .map(|period| period.to_string())
+                            .collect();
+                        println!("    - {} ({})", disruption.title(), periods.join(", "));
+                    }
+                }
+            }
+        }
+        Query::Provenance => {
+            let snapshots = database.paths.iter().zip(&database.provenance);
+            for (i, (path, provenance)) in snapshots.enumerate() {
+                print_provenance(i, path, *provenance);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Runs the query directly against the memory-mapped archive, without deserializing the database.
+fn run_query_archived(args: &DatabaseArgs, query: Query) -> Result<(), Box<dyn std::error::Error>> {
+    info!(path = ?args.path, "Accessing rkyv archive");
+    let bytes = map_database(args)?;
+    let database = rkyv::access::<ArchivedDatabase, rkyv::rancor::Error>(version::split_header(
+        checksum::verify(&bytes, "the database")?,
+    )?)?;
+    let arenas = &database.arenas;
+
+    match query {
+        Query::Snapshots => {
+            for (i, data) in database.datas.iter().enumerate() {
+                match arenas.data(data.to_native())? {
+                    ArchivedData::Success(data) => println!(
+                        "[{i}] {} | {} disruptions | {} lines",
+                        data.last_updated_date()?,
+                        data.disruptions(arenas)?.len(),
+                        data.lines(arenas)?.len(),
+                    ),
+                    ArchivedData::Error(data) => println!(
+                        "[{i}] error {} | {}: {}",
+                        data.status_code(),
+                        data.error(arenas)?,
+                        data.message(arenas)?,
+                    ),
+                }
+            }
+        }
+        Query::Line { id } => {
+            let mut snapshots = Vec::new();
+            for data in database.datas.iter() {
+                if let ArchivedData::Success(data) = arenas.data(data.to_native())? {
+                    snapshots.push((
+                        data.last_updated_date()?,
+                        data.line_disruptions(arenas, &id)?,
+                    ));
+                }
+            }
+            let disruptions = LineDisruptions::collect(snapshots.into_iter(), |snapshot| snapshot);
+            // The disruptions are described before printing any of them, so that a missing value is
+            // reported on its own.
+            let mut descriptions = HashMap::new();
+            for &(disruption, _, _) in &disruptions.disruptions {
+                let archived = arenas.disruption(disruption)?;
+                let description = (
+                    archived.title(arenas)?,
+                    archived.severity(arenas)?,
+                    archived.application_periods(arenas)?,
+                );
+                descriptions.insert(disruption, description);
+            }
+            disruptions.print(&id, |disruption| descriptions[&disruption].clone());
+        }
+        Query::Provenance => {
+            let snapshots = database.paths.iter().zip(database.provenance.iter());
+            for (i, (path, provenance)) in snapshots.enumerate() {
+                let provenance = rkyv::deserialize::<_, rkyv::rancor::Error>(provenance)?;
+                print_provenance(i, Path::new(path.as_str()), provenance);
+            }
+        }
+        Query::Snapshot { .. } | Query::ActiveAt { .. } => {
+            unreachable!("deserialized arenas are required")
+        }
+    }
+
+    Ok(())
+}
+
+fn print_provenance(i: usize, path: &Path, provenance: Option<Provenance>) {
+    match provenance {
+        Some(provenance) => println!("[{i}] {path:?} | {provenance}"),
+        None => println!("[{i}] {path:?} | no provenance recorded"),
+    }
+}
+
+fn print_snapshot(path: &Path, data: DataView) -> Result<(), Box<dyn std::error::Error>> {
+    info!(?path, "Regenerating snapshot");
+    let json = serde_json::to_string_pretty(&data)?;
+    println!("{json}");
+    Ok(())
+}
+
+// Disruptions that impacted a line, in order of first appearance, along with the dates of the first
+// and last snapshots that contained them. Interned disruptions are identical across snapshots, so
+// they're deduplicated by their handle.
+struct LineDisruptions<T> {
+    pub disruptions: Vec<(T, String, String)>,
+}
+
+impl<T: Copy + Eq + std::hash::Hash> LineDisruptions<T> {
+    pub fn collect<D>(datas: impl Iterator<Item = D>, f: impl Fn(D) -> (String, Vec<T>)) -> Self {
+        let mut indices: hashbrown::HashMap<T, usize> = hashbrown::HashMap::new();
+        let mut disruptions: Vec<(T, String, String)> = Vec::new();
+        for data in datas {
+            let (date, ids) = f(data);
+            for id in ids {
+                match indices.get(&id) {
+                    Some(&i) => disruptions[i].2.clone_from(&date),
+                    None => {
+                        indices.insert(id, disruptions.len());
+                        disruptions.push((id, date.clone(), date.clone()));
+                    }
+                }
+            }
+        }
+        Self { disruptions }
+    }
+
+    pub fn print<'a>(
+        &self,
+        line_id: &str,
+        describe: impl Fn(T) -> (&'a str, &'a str, Vec<(String, String)>),
+    ) {
+        println!(
+            "{} disruptions impacted line {line_id}",
+            self.disruptions.len()
+        );
+        for (id, first_seen, last_seen) in &self.disruptions {
+            let (title, severity, periods) = describe(*id);
+            println!("- [{severity}] {title} (seen from {first_seen} to {last_seen})");
+            for (begin, end) in periods {
+                println!("    {begin} -> {end}");
+            }
+        }
+    }
+}
@@ -0,0 +1,290 @@
+// Checks of databases and input files: verification of a database against the input files it was
+// built from or against the invariants of its arenas, comparison of two databases, and audit of the
+// information lost by the optimized schema.
+
+use crate::audit::FileAudit;
+use crate::cli::{DatabaseArgs, ReportArgs};
+use crate::compare::EqWith;
+use crate::database::{load_database, read_database};
+use crate::report::{print_failures, write_report, Failures, Stage};
+use crate::schema::optimized::{Arenas, FromSource};
+use crate::schema::Disruptions;
+use crate::walk::Inputs;
+use crate::{audit, diff, schema};
+use blazinterner::Interned;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+use tracing::{debug, info, warn};
+
+pub fn verify(
+    inputs: &Inputs,
+    args: &DatabaseArgs,
+    report: &ReportArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let database = load_database(args)?;
+
+    let snapshots: HashMap<&Path, Interned<schema::optimized::Data>> = database
+        .paths
+        .iter()
+        .map(PathBuf::as_path)
+        .zip(database.datas.iter().copied())
+        .collect();
+
+    // Each file is compared as soon as it's parsed, rather than keeping every parsed file in memory
+    // until all of them are. The arenas are only read, so files are compared in parallel.
+    let failures = Failures::default();
+    let parsed_count = AtomicUsize::new(0);
+    let mismatch_count = AtomicUsize::new(0);
+    let compared = Mutex::new(HashSet::new());
+    let start = Instant::now();
+    inputs.visit(&|input| {
+        let file_path = input.path();
+        let bytes = input.read()?;
+
+        // Files that failed to parse were skipped when building the database.
+        let source = match inputs.parse::<Disruptions>(&bytes) {
+            Ok((source, _)) => source,
+            Err(err) => {
+                failures.record_json_error(file_path, &bytes, &err);
+                return Ok(());
+            }
+        };
+        parsed_count.fetch_add(1, Ordering::Relaxed);
+
+        let Some(data) = snapshots.get(file_path) else {
+            return Ok(());
+        };
+        compared.lock().unwrap().insert(file_path.to_owned());
+        let data = database.arenas.data(*data);
+        if !data.eq_with(&source, &database.arenas) {
+            failures.record(
+                file_path,
+                Stage::Verification,
+                format!(
+                    "database snapshot doesn't match file: {}",
+                    diff::explain(data, &source, &database.arenas)
+                ),
+            );
+            mismatch_count.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    })?;
+    let verify_time = Instant::now().duration_since(start);
+    let snapshots_per_sec = database.datas.len() as f64 / verify_time.as_secs_f64();
+    info!(?verify_time, snapshots_per_sec, "Compared snapshots");
+
+    let parsed_count = parsed_count.into_inner();
+    let mut mismatch_count = mismatch_count.into_inner();
+    let compared = compared.into_inner().unwrap();
+    for path in &database.paths {
+        if !compared.contains(path) {
+            failures.record(
+                path,
+                Stage::Verification,
+                "input file not found for database snapshot",
+            );
+            mismatch_count += 1;
+        }
+    }
+    if parsed_count != database.datas.len() {
+        warn!(
+            snapshot_count = database.datas.len(),
+            parsed_count, "Database and parsed input files don't have the same number of snapshots",
+        );
+    }
+
+    let failures = failures.into_sorted();
+    write_report(report.failure_report.as_deref(), &failures)?;
+
+    if mismatch_count != 0 {
+        return Err(format!("{mismatch_count} snapshots didn't match their input file").into());
+    }
+    println!(
+        "Verified {} snapshots ({snapshots_per_sec:.0} snapshots/s)",
+        database.datas.len()
+    );
+    Ok(())
+}
+
+pub fn verify_db(args: &DatabaseArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let database = read_database(args)?;
+    println!(
+        "Loaded {} snapshots referring to {} interned values",
+        database.datas.len(),
+        database.arenas.value_count(),
+    );
+
+    // The other invariants are checked by looking values up, which requires valid IDs.
+    database.validate()?;
+    println!("All the interned IDs refer to existing values");
+
+    let start = Instant::now();
+    let violations = database.arenas.check_invariants();
+    let check_time = Instant::now().duration_since(start);
+    info!(?check_time, "Checked invariants");
+    if violations.is_empty() {
+        println!("All the sets are sorted, the arenas are consistent with their hash tables and the timestamps are valid");
+        return Ok(());
+    }
+    for violation in &violations {
+        println!("  {violation}");
+    }
+    Err(format!(
+        "invalid database: {} invariant violations",
+        violations.len()
+    )
+    .into())
+}
+
+// Compares two databases, failing if their contents differ. Snapshots are paired by the path of
+// their input file, as databases built from the same files may list them in different orders.
+pub fn diff_databases(
+    left_args: &DatabaseArgs,
+    right_args: &DatabaseArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let left = load_database(left_args)?;
+    let right = load_database(right_args)?;
+
+    let start = Instant::now();
+    let diff = left.arenas.diff(&right.arenas)?;
+    let diff_time = Instant::now().duration_since(start);
+    info!(?diff_time, "Compared arenas");
+
+    let right_snapshots: HashMap<&Path, _> = right
+        .paths
+        .iter()
+        .map(PathBuf::as_path)
+        .zip(&right.datas)
+        .collect();
+    let left_paths: HashSet<&Path> = left.paths.iter().map(PathBuf::as_path).collect();
+    let mut only_left = Vec::new();
+    let mut different = Vec::new();
+    for (path, data) in left.paths.iter().zip(&left.datas) {
+        match right_snapshots.get(path.as_path()) {
+            None => only_left.push(path),
+            Some(right_data) if !diff.same_data(*data, **right_data) => different.push(path),
+            Some(_) => (),
+        }
+    }
+    let only_right: Vec<_> = right
+        .paths
+        .iter()
+        .filter(|path| !left_paths.contains(path.as_path()))
+        .collect();
+
+    println!(
+        "Snapshots: {} vs. {}, {} only in {:?}, {} only in {:?}, {} with different contents",
+        left.datas.len(),
+        right.datas.len(),
+        only_left.len(),
+        left_args.path,
+        only_right.len(),
+        right_args.path,
+        different.len(),
+    );
+    for path in &only_left {
+        println!("  < {path:?}");
+    }
+    for path in &only_right {
+        println!("  > {path:?}");
+    }
+    for path in &different {
+        println!("  ! {path:?}");
+    }
+
+    println!("| Arena                          |     Left |    Right | Only left | Only right |   Delta bytes |");
+    println!("| ------------------------------ | -------- | -------- | --------- | ---------- | ------------- |");
+    for interner in &diff.interners {
+        println!(
+            "| {:<30} | {:>8} | {:>8} | {:>9} | {:>10} | {:>+13} |",
+            interner.left.name,
+            interner.left.objects,
+            interner.right.objects,
+            interner.only_left,
+            interner.only_right,
+            interner.right.bytes as i64 - interner.left.bytes as i64,
+        );
+    }
+
+    let unique_values: usize = diff
+        .interners
+        .iter()
+        .map(|x| x.only_left + x.only_right)
+        .sum();
+    if only_left.is_empty() && only_right.is_empty() && different.is_empty() && unique_values == 0 {
+        println!("The databases have the same contents");
+        return Ok(());
+    }
+    Err(format!(
+        "the databases differ: {} snapshots and {unique_values} interned values aren't in both",
+        only_left.len() + only_right.len() + different.len(),
+    )
+    .into())
+}
+
+pub fn audit(
+    inputs: &Inputs,
+    audit_report: Option<&Path>,
+    report: &ReportArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let file_count = AtomicUsize::new(0);
+    let failures = Failures::default();
+    let audits = Mutex::new(Vec::new());
+    let arenas = Arenas::default();
+
+    inputs.visit(&|input| {
+        let file_path = input.path();
+        let bytes = input.read()?;
+
+        // Parsing as a JSON value canonicalizes the original file.
+        let original: serde_json::Value = match serde_json::from_slice(&bytes) {
+            Ok(original) => original,
+            Err(err) => {
+                failures.record_json_error(file_path, &bytes, &err);
+                return Ok(());
+            }
+        };
+        let data = match schema::source::from_slice(&bytes) {
+            Ok(data) => data,
+            Err(err) => {
+                failures.record_json_error(file_path, &bytes, &err);
+                return Ok(());
+            }
+        };
+        let optimized = match schema::optimized::Data::from_source(&arenas, &data) {
+            Ok(optimized) => optimized,
+            Err(err) => {
+                failures.record(file_path, Stage::Conversion, err);
+                return Ok(());
+            }
+        };
+
+        let regenerated = serde_json::to_value(optimized.to_source(&arenas)).unwrap();
+        let audit = FileAudit::new(file_path, &original, &regenerated);
+        if !audit.is_lossless() {
+            debug!(path = ?file_path, losses = audit.losses.len(), "File isn't reproduced exactly");
+        }
+        audits.lock().unwrap().push(audit);
+        file_count.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    })?;
+
+    let file_count = file_count.load(Ordering::Relaxed);
+    let failures = failures.into_sorted();
+    let mut audits = audits.into_inner().unwrap();
+    audits.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+
+    println!(
+        "Audited {file_count} files (+ {} failed files)",
+        failures.len(),
+    );
+    print_failures(&failures);
+    write_report(report.failure_report.as_deref(), &failures)?;
+    audit::print_audits(&audits, file_count);
+    audit::write_audit_report(audit_report, &audits)?;
+
+    Ok(())
+}
@@ -0,0 +1,440 @@
+// In-memory databases and their loading and saving in the format given on the command line. Besides
+// the optimized `Database`, this contains the variants that only exist to be serialized, e.g. with
+// resolved strings or front-coded arenas, and the `Jdatabase` of generically interned JSON values.
+
+use crate::cli::{DatabaseArgs, Format};
+use crate::provenance::Provenance;
+use crate::schema::archive::AsId;
+use crate::schema::optimized::front_coding::FrontCodedArenas;
+use crate::schema::optimized::view::DataView;
+use crate::schema::optimized::Arenas;
+use crate::schema::{Disruptions, Schema};
+use crate::{alloc, codec, index, schema, stream, version};
+use blazinterner::Interned;
+use get_size2::GetSize;
+use jinterner::{IValue, Jinterners, ValueRef};
+use memmap2::Mmap;
+use rkyv::with::{AsString, Map};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tracing::{debug, info};
+
+pub fn load_database(args: &DatabaseArgs) -> Result<Database, Box<dyn std::error::Error>> {
+    let database = read_database(args)?;
+    database.validate()?;
+    Ok(database)
+}
+
+// Deserializes a database, without validating its IDs.
+pub fn read_database(args: &DatabaseArgs) -> Result<Database, Box<dyn std::error::Error>> {
+    let format = args.format()?;
+    info!(?format, path = ?args.path, "Loading database");
+
+    let bytes = map_database(args)?;
+
+    let phase = alloc::Phase::start("loading", || 0);
+    let database = match codec::database_codec(format) {
+        Some(codec) => codec.decode(&bytes)?,
+        None => {
+            let (arenas, mut records) = stream::read(&bytes)?;
+            // Records are written in the order in which the files were processed.
+            records.sort_unstable_by(|(x, _, _), (y, _, _)| x.cmp(y));
+            Database::from_records(arenas, records)
+        }
+    };
+    phase.finish(|| database.get_size());
+    Ok(database)
+}
+
+pub fn load_or_create_database(
+    args: &DatabaseArgs,
+) -> Result<Database, Box<dyn std::error::Error>> {
+    if args.path.exists() {
+        load_database(args)
+    } else {
+        Ok(Database::new(Arenas::default()))
+    }
+}
+
+// Writes to a temporary file that replaces the database once complete, so that the database is
+// never left half-written.
+pub fn save_database(
+    database: &Database,
+    path: &Path,
+    format: Format,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let start = Instant::now();
+    let tmp_path = path.with_extension("tmp");
+    match codec::database_codec(format) {
+        Some(codec) => std::fs::write(&tmp_path, codec.encode(database)?)?,
+        None => {
+            let writer = stream::StreamWriter::create(&tmp_path)?;
+            let snapshots = database.paths.iter().zip(&database.datas);
+            for ((path, data), provenance) in snapshots.zip(&database.provenance) {
+                writer.write(path, *data, *provenance)?;
+            }
+            writer.finish(&database.arenas)?;
+        }
+    }
+    std::fs::rename(&tmp_path, path)?;
+    let save_time = Instant::now().duration_since(start);
+    info!(
+        ?path,
+        snapshot_count = database.datas.len(),
+        ?save_time,
+        "Saved database"
+    );
+    Ok(())
+}
+
+pub fn map_database(args: &DatabaseArgs) -> Result<Mmap, Box<dyn std::error::Error>> {
+    let file = File::open(&args.path)?;
+    // SAFETY: The database file isn't expected to be modified while we're reading it. The mapping
+    // is page-aligned, which satisfies the alignment requirements of rkyv archives.
+    let mmap = unsafe { Mmap::map(&file)? };
+    Ok(mmap)
+}
+
+pub fn sorted_by_path<T>(mut values: Vec<(PathBuf, T)>) -> (Vec<PathBuf>, Vec<T>) {
+    // Files are processed in parallel, so results arrive in a non-deterministic order. Sort them by
+    // path for reproducibility.
+    values.sort_unstable_by(|(x, _), (y, _)| x.cmp(y));
+    values.into_iter().unzip()
+}
+
+#[derive(
+    Debug,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    GetSize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+#[get_size(ignore(S))]
+pub struct Database<S: Schema = Disruptions> {
+    pub arenas: S::Interners,
+    // Identical snapshots share the same handle.
+    #[rkyv(with = Map<AsId>)]
+    pub datas: Vec<Interned<S::Optimized>>,
+    // Path of the input file that each snapshot in `datas` was parsed from.
+    #[rkyv(with = Map<AsString>)]
+    pub paths: Vec<PathBuf>,
+    // Provenance of each snapshot in `datas`, if it was recorded when ingesting it.
+    pub provenance: Vec<Option<Provenance>>,
+}
+
+impl<S: Schema> Database<S> {
+    pub fn new(arenas: S::Interners) -> Self {
+        Self {
+            arenas,
+            datas: Vec::new(),
+            paths: Vec::new(),
+            provenance: Vec::new(),
+        }
+    }
+
+    // Appends a snapshot.
+    pub fn push(
+        &mut self,
+        path: PathBuf,
+        data: Interned<S::Optimized>,
+        provenance: Option<Provenance>,
+    ) {
+        self.paths.push(path);
+        self.datas.push(data);
+        self.provenance.push(provenance);
+    }
+
+    // Builds a database from records of the stream and indexed formats, in the given order.
+    pub fn from_records(arenas: S::Interners, records: Vec<stream::Record<S>>) -> Self {
+        let mut database = Self::new(arenas);
+        for (path, data, provenance) in records {
+            database.push(path, data, provenance);
+        }
+        database
+    }
+}
+
+impl Database {
+    /// Opens a database in the indexed format, whose snapshots are then decoded individually.
+    pub fn open(path: &Path) -> Result<index::IndexedDatabase, Box<dyn std::error::Error>> {
+        index::IndexedDatabase::open(path)
+    }
+
+    // Checks that all the interned IDs refer to existing values and that the timestamps are in
+    // range, so that a corrupted database is reported when loading it rather than causing a panic
+    // on lookup or when formatting a datetime.
+    pub fn validate(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.paths.len() != self.datas.len() {
+            return Err(format!(
+                "invalid database: {} snapshots but {} paths",
+                self.datas.len(),
+                self.paths.len(),
+            )
+            .into());
+        }
+        if self.provenance.len() != self.datas.len() {
+            return Err(format!(
+                "invalid database: {} snapshots but {} provenance records",
+                self.datas.len(),
+                self.provenance.len(),
+            )
+            .into());
+        }
+        self.arenas
+            .validate()
+            .map_err(|e| format!("invalid database: {e}"))?;
+        self.arenas
+            .check_timestamps()
+            .map_err(|e| format!("invalid database: {e}"))?;
+        for (i, (data, path)) in self.datas.iter().zip(&self.paths).enumerate() {
+            self.arenas
+                .validate_snapshot(*data, i)
+                .map_err(|e| format!("invalid database: {e} (parsed from {path:?})"))?;
+        }
+        Ok(())
+    }
+
+    // Drops the interned values that no snapshot refers to anymore and remaps the snapshots to the
+    // compacted arenas, printing how many bytes were reclaimed.
+    pub fn compact(&mut self) -> Result<(), crate::error::Error> {
+        let phase = alloc::Phase::start("compaction", || self.get_size());
+        let start = Instant::now();
+        let value_count = self.arenas.value_count();
+        let arenas_bytes = self.arenas.get_size();
+
+        let (arenas, mapping) = self.arenas.compact(&self.datas)?;
+        for data in &mut self.datas {
+            *data = mapping.data(*data);
+        }
+        self.arenas = arenas;
+
+        let compact_time = Instant::now().duration_since(start);
+        info!(?compact_time, "Compacted arenas");
+        let compacted_bytes = self.arenas.get_size();
+        println!(
+            "Compaction dropped {} unreferenced values, reclaiming {} bytes ({arenas_bytes} -> {compacted_bytes} bytes of arenas)",
+            value_count - self.arenas.value_count(),
+            arenas_bytes as i64 - compacted_bytes as i64,
+        );
+        phase.finish(|| self.get_size());
+        Ok(())
+    }
+}
+
+// Same as `Database`, but with each snapshot expanded inline rather than referring to the arenas, so
+// that the JSON export can be read without knowing about interning. It can't be deserialized back.
+pub struct ResolvedDatabase<'a>(pub &'a Database);
+
+#[derive(Serialize)]
+struct ResolvedSnapshot<'a> {
+    pub path: &'a Path,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+    pub data: DataView<'a>,
+}
+
+impl Serialize for ResolvedDatabase<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let resolver = self.0.arenas.resolver();
+        let snapshots: Vec<ResolvedSnapshot> = (self.0.paths.iter())
+            .zip(&self.0.datas)
+            .zip(&self.0.provenance)
+            .map(|((path, data), provenance)| ResolvedSnapshot {
+                path,
+                provenance: *provenance,
+                data: resolver.data(*data),
+            })
+            .collect();
+        let mut state = serializer.serialize_struct("Database", 2)?;
+        state.serialize_field("version", &version::CURRENT_VERSION)?;
+        state.serialize_field("snapshots", &snapshots)?;
+        state.end()
+    }
+}
+
+// Same as `Database`, but with the strings front-coded once serialized.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrontCodedDatabase {
+    pub arenas: FrontCodedArenas,
+    pub datas: Vec<Interned<schema::optimized::Data>>,
+    pub paths: Vec<PathBuf>,
+    pub provenance: Vec<Option<Provenance>>,
+}
+
+impl From<Database> for FrontCodedDatabase {
+    fn from(database: Database) -> Self {
+        Self {
+            arenas: FrontCodedArenas(database.arenas),
+            datas: database.datas,
+            paths: database.paths,
+            provenance: database.provenance,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Jdatabase {
+    pub jinterners: Jinterners,
+    pub jvalues: Vec<IValue>,
+}
+
+impl Jdatabase {
+    // Builds a database from values ingested in parallel, sorted by path. They're interned again in
+    // this order, so that the IDs of the jinterners don't depend on the order of ingestion.
+    pub fn from_ingested(jinterners: &Jinterners, jvalues: Vec<(PathBuf, IValue)>) -> Self {
+        let (_, jvalues) = sorted_by_path(jvalues);
+        let canonical = Jinterners::default();
+        let jvalues = (jvalues.iter())
+            .map(|jvalue| canonical.intern_ref(&jvalue.lookup(jinterners)))
+            .collect();
+        Self {
+            jinterners: canonical,
+            jvalues,
+        }
+    }
+}
+
+fn check_eq(
+    jvalue1: &IValue,
+    jinterners1: &Jinterners,
+    jvalue2: &IValue,
+    jinterners2: &Jinterners,
+) -> bool {
+    match (
+        jvalue1.lookup_ref(jinterners1),
+        jvalue2.lookup_ref(jinterners2),
+    ) {
+        (ValueRef::Null, ValueRef::Null) => true,
+        (ValueRef::Bool(x), ValueRef::Bool(y)) => x == y,
+        (ValueRef::U64(x), ValueRef::U64(y)) => x == y,
+        (ValueRef::I64(x), ValueRef::I64(y)) => x == y,
+        (ValueRef::F64(x), ValueRef::F64(y)) => x == y,
+        (ValueRef::String(x), ValueRef::String(y)) => x == y,
+        (ValueRef::Array(x), ValueRef::Array(y)) => {
+            x.len() == y.len()
+                && x.iter()
+                    .zip(y.iter())
+                    .all(|(a, b)| check_eq(a, jinterners1, b, jinterners2))
+        }
+        (ValueRef::Object(x), ValueRef::Object(y)) => {
+            let mut x = x.iter();
+            let y = y.iter();
+            if x.len() != y.len() {
+                false
+            } else {
+                let y: HashMap<_, _> = y.collect();
+                x.all(|(k, a)| {
+                    y.get(k)
+                        .is_some_and(|b| check_eq(a, jinterners1, b, jinterners2))
+                })
+            }
+        }
+        _ => false,
+    }
+}
+
+pub fn joptimize_loop(jinterners: Jinterners, jvalues: Vec<IValue>) -> (Jinterners, Vec<IValue>) {
+    let start = Instant::now();
+    let mut optimized = None;
+    for _ in 0..10 {
+        let (jinterners, jvalues) = match optimized {
+            None => (&jinterners, &jvalues),
+            Some((ref jinterners, ref jvalues)) => (jinterners, jvalues),
+        };
+        match joptimize_once(jinterners, jvalues) {
+            Some(opt) => optimized = Some(opt),
+            None => break,
+        }
+    }
+    let (jinterners_opt, mut jvalues_opt) = optimized.unwrap();
+    let opt_time = Instant::now().duration_since(start);
+    info!(?opt_time, "Optimized interners");
+
+    let start = Instant::now();
+    for (jvalue, jvalue_opt) in jvalues.iter().zip(jvalues_opt.iter()) {
+        assert!(check_eq(jvalue, &jinterners, jvalue_opt, &jinterners_opt));
+    }
+    let check_time = Instant::now().duration_since(start);
+    debug!(?check_time, "Checked equality");
+
+    jvalues_opt.sort_unstable();
+    (jinterners_opt, jvalues_opt)
+}
+
+pub fn joptimize(jinterners: &Jinterners, jvalues: &[IValue]) -> Option<(Jinterners, Vec<IValue>)> {
+    let start = Instant::now();
+    let opt = jinterners.optimize(None);
+    let optimize_time = Instant::now().duration_since(start);
+
+    let (jinterners_opt, mapping) = match opt {
+        None => {
+            info!(?optimize_time, "Calculated identity mapping");
+            return None;
+        }
+        Some(opt) => opt,
+    };
+
+    info!(
+        ?optimize_time,
+        remapped_strings = mapping.count_remapped_strings(),
+        remapped_arrays = mapping.count_remapped_arrays(),
+        remapped_objects = mapping.count_remapped_objects(),
+        "Calculated mapping"
+    );
+
+    let start = Instant::now();
+    let mut jvalues_opt: Vec<_> = jvalues.iter().map(|v| mapping.map(*v)).collect();
+    let map_time = Instant::now().duration_since(start);
+    debug!(?map_time, "Mapped values");
+
+    let start = Instant::now();
+    for (jvalue, jvalue_opt) in jvalues.iter().zip(jvalues_opt.iter()) {
+        assert!(check_eq(jvalue, jinterners, jvalue_opt, &jinterners_opt));
+    }
+    let check_time = Instant::now().duration_since(start);
+    debug!(?check_time, "Checked equality");
+
+    jvalues_opt.sort_unstable();
+    Some((jinterners_opt, jvalues_opt))
+}
+
+fn joptimize_once(
+    jinterners: &Jinterners,
+    jvalues: &[IValue],
+) -> Option<(Jinterners, Vec<IValue>)> {
+    let start = Instant::now();
+    let opt = jinterners.optimize_once();
+    let optimize_time = Instant::now().duration_since(start);
+
+    let (jinterners_opt, mapping) = match opt {
+        None => {
+            info!(?optimize_time, "Calculated identity mapping");
+            return None;
+        }
+        Some(opt) => opt,
+    };
+
+    info!(
+        ?optimize_time,
+        remapped_strings = mapping.count_remapped_strings(),
+        remapped_arrays = mapping.count_remapped_arrays(),
+        remapped_objects = mapping.count_remapped_objects(),
+        "Calculated mapping"
+    );
+
+    let start = Instant::now();
+    let jvalues_opt: Vec<_> = jvalues.iter().map(|v| mapping.map(*v)).collect();
+    let map_time = Instant::now().duration_since(start);
+    debug!(?map_time, "Mapped values");
+
+    Some((jinterners_opt, jvalues_opt))
+}
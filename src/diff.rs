@@ -0,0 +1,165 @@
+// Explanation of verification failures. `EqWith` only tells whether an interned snapshot matches its
+// source, so when it doesn't, the snapshot is converted back into the source schema and both are
+// walked as JSON trees to locate the first mismatch.
+//
+// Most arrays of the schema are sets, so array elements are paired regardless of their order, and
+// reported by their index in the source file. The disruptions, lines and impacted objects keep the
+// order of the source, so these arrays are also compared in order once their elements match.
+
+use crate::schema::optimized::{Arenas, Data};
+use crate::schema::source;
+use serde_json::Value;
+use std::fmt::Write;
+
+// Keys of the arrays whose order is preserved.
+const SEQUENCES: &[&str] = &["disruptions", "lines", "impactedObjects"];
+
+// Maximum length of the values printed in an explanation.
+const MAX_VALUE_LEN: usize = 80;
+
+/// Returns a description of the first difference between the snapshot and its source, e.g.
+/// `disruptions[3].applicationPeriods[0].begin: expected "20240101T100000", found
+/// "20240101T110000"`.
+pub fn explain(data: &Data, source: &source::Data, arenas: &Arenas) -> String {
+    let actual = serde_json::to_value(data.to_source(arenas)).unwrap();
+    let expected = serde_json::to_value(source).unwrap();
+    // Differences in the elements are reported before differences in their order, which is all
+    // that databases upgraded from layouts sorting these arrays can differ by.
+    let mut path = String::new();
+    diff(&mut path, &actual, &expected, false)
+        .or_else(|| diff(&mut path, &actual, &expected, true))
+        .unwrap_or_else(|| "no difference once converted back to the source schema".to_owned())
+}
+
+// Returns the first difference between the values. Sequences are compared in order if `ordered` is
+// set, and as sets otherwise.
+fn diff(path: &mut String, actual: &Value, expected: &Value, ordered: bool) -> Option<String> {
+    match (actual, expected) {
+        (Value::Object(actual), Value::Object(expected)) => {
+            for (key, expected_value) in expected {
+                let len = path.len();
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                path.push_str(key);
+                let mismatch = match actual.get(key) {
+                    Some(actual_value) => diff(path, actual_value, expected_value, ordered),
+                    None => Some(format!("{path}: missing field")),
+                };
+                path.truncate(len);
+                if mismatch.is_some() {
+                    return mismatch;
+                }
+            }
+            actual
+                .keys()
+                .find(|key| !expected.contains_key(*key))
+                .map(|key| format!("{}: unexpected field {key:?}", display_path(path)))
+        }
+        (Value::Array(actual), Value::Array(expected)) => {
+            let key = path.rsplit('.').next().unwrap_or_default();
+            if ordered && SEQUENCES.contains(&key) {
+                diff_seq(path, actual, expected)
+            } else {
+                diff_set(path, actual, expected, ordered)
+            }
+        }
+        _ if actual == expected => None,
+        _ => Some(format!(
+            "{}: expected {}, found {}",
+            display_path(path),
+            truncated(expected),
+            truncated(actual),
+        )),
+    }
+}
+
+fn diff_set(
+    path: &mut String,
+    actual: &[Value],
+    expected: &[Value],
+    ordered: bool,
+) -> Option<String> {
+    let equal = |x, y, ordered| diff(&mut String::new(), x, y, ordered).is_none();
+
+    // Pair the equal elements first.
+    let mut used = vec![false; actual.len()];
+    let mut unmatched = Vec::new();
+    for (i, expected_value) in expected.iter().enumerate() {
+        match (0..actual.len()).find(|&j| !used[j] && equal(&actual[j], expected_value, ordered)) {
+            Some(j) => used[j] = true,
+            None => unmatched.push(i),
+        }
+    }
+
+    // Compare the remaining elements pairwise, to point at the mismatch within them. Elements that
+    // only differ by the order of nested sequences are paired together.
+    for &i in &unmatched {
+        let remaining = || (0..actual.len()).filter(|&j| !used[j]);
+        let Some(j) = (remaining().find(|&j| equal(&actual[j], &expected[i], false)))
+            .or_else(|| remaining().next())
+        else {
+            return Some(format!(
+                "{path}[{i}]: missing element {}",
+                truncated(&expected[i])
+            ));
+        };
+        used[j] = true;
+        let len = path.len();
+        write!(path, "[{i}]").unwrap();
+        let mismatch = diff(path, &actual[j], &expected[i], ordered);
+        path.truncate(len);
+        if mismatch.is_some() {
+            return mismatch;
+        }
+    }
+    (0..actual.len()).find(|&j| !used[j]).map(|j| {
+        format!(
+            "{}: unexpected element {}",
+            display_path(path),
+            truncated(&actual[j])
+        )
+    })
+}
+
+// Compares sequences in order, once they are known to be equal as sets.
+fn diff_seq(path: &mut String, actual: &[Value], expected: &[Value]) -> Option<String> {
+    for (i, (x, y)) in actual.iter().zip(expected).enumerate() {
+        if diff(&mut String::new(), x, y, false).is_some() {
+            return Some(format!(
+                "{path}[{i}]: expected {} at this position, found {}",
+                truncated(y),
+                truncated(x),
+            ));
+        }
+        let len = path.len();
+        write!(path, "[{i}]").unwrap();
+        let mismatch = diff(path, x, y, true);
+        path.truncate(len);
+        if mismatch.is_some() {
+            return mismatch;
+        }
+    }
+    None
+}
+
+fn display_path(path: &str) -> &str {
+    if path.is_empty() {
+        "<root>"
+    } else {
+        path
+    }
+}
+
+fn truncated(value: &Value) -> String {
+    let mut value = value.to_string();
+    if value.len() > MAX_VALUE_LEN {
+        let mut end = MAX_VALUE_LEN;
+        while !value.is_char_boundary(end) {
+            end -= 1;
+        }
+        value.truncate(end);
+        value.push_str("...");
+    }
+    value
+}
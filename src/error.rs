@@ -0,0 +1,49 @@
+use chrono_tz::Tz;
+use std::fmt::{Display, Formatter};
+
+/// Error encountered when converting an input file into the optimized schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// A datetime couldn't be parsed with the expected format.
+    ParseDatetime { value: String, format: &'static str },
+    /// A local datetime doesn't exist in the configured timezone, e.g. because it falls in a
+    /// daylight saving time gap.
+    NonexistentLocalDatetime { value: String, timezone: Tz },
+    /// A local datetime occurs twice in the configured timezone, because it falls in a daylight
+    /// saving time overlap, and ambiguous datetimes are rejected.
+    AmbiguousLocalDatetime { value: String, timezone: Tz },
+    /// A snapshot is neither a complete success nor a complete error.
+    IncompleteData,
+    /// An arena holds too many values (or bytes, or items) to intern another one without
+    /// overflowing its 32-bit IDs.
+    ArenaFull {
+        arena: &'static str,
+        len: usize,
+        unit: &'static str,
+    },
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Error::ParseDatetime { value, format } => {
+                write!(f, "failed to parse datetime ({format} format) from {value:?}")
+            }
+            Error::NonexistentLocalDatetime { value, timezone } => {
+                write!(f, "invalid mapping of {value:?} to the {timezone} timezone")
+            }
+            Error::AmbiguousLocalDatetime { value, timezone } => {
+                write!(f, "ambiguous mapping of {value:?} to the {timezone} timezone")
+            }
+            Error::IncompleteData => f.write_str(
+                "expected either disruptions, lines and lastUpdatedDate, or statusCode, error and message",
+            ),
+            Error::ArenaFull { arena, len, unit } => write!(
+                f,
+                "the {arena} arena holds {len} {unit}, too many to intern another value with 32-bit IDs"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
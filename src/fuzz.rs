@@ -0,0 +1,95 @@
+// Entry points of the fuzz targets in the `fuzz` directory, which feed arbitrary bytes to the
+// deserializers of the databases. The visitors of the interned sets and of the arenas decode
+// untrusted files, so they must reject invalid inputs with an error rather than panicking or
+// allocating memory out of proportion with the input.
+//
+// Each input is decoded with each of the binary serde formats, as they drive the visitors
+// differently: bincode and postcard read the lengths of sequences from the input and pass them as
+// size hints, whereas CBOR also supports sequences of indefinite length. The checksums are skipped,
+// as random inputs would almost never pass them. Values that decode and pass validation must then
+// serialize back to bytes that decode to the same value.
+
+use crate::schema::optimized::{Arenas, InternedSeq, InternedSet};
+use crate::schema::Uuid;
+use crate::version::{Upgraded, Versioned};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::Debug;
+
+#[derive(Debug, Clone, Copy)]
+enum Codec {
+    Bincode,
+    Cbor,
+    Postcard,
+}
+
+impl Codec {
+    const ALL: [Codec; 3] = [Codec::Bincode, Codec::Cbor, Codec::Postcard];
+
+    fn decode<T: DeserializeOwned>(self, bytes: &[u8]) -> Option<T> {
+        match self {
+            Codec::Bincode => bincode::deserialize(bytes).ok(),
+            Codec::Cbor => ciborium::from_reader(bytes).ok(),
+            Codec::Postcard => postcard::from_bytes(bytes).ok(),
+        }
+    }
+
+    fn encode<T: Serialize + ?Sized>(self, value: &T) -> Vec<u8> {
+        match self {
+            Codec::Bincode => bincode::serialize(value).unwrap(),
+            Codec::Cbor => {
+                let mut bytes = Vec::new();
+                ciborium::into_writer(value, &mut bytes).unwrap();
+                bytes
+            }
+            Codec::Postcard => postcard::to_stdvec(value).unwrap(),
+        }
+    }
+
+    // Checks that the value serializes back to bytes that decode to the same value.
+    fn assert_round_trip<T: Serialize + DeserializeOwned + PartialEq + Debug>(self, value: &T) {
+        let bytes = self.encode(value);
+        let decoded: Option<T> = self.decode(&bytes);
+        assert_eq!(decoded.as_ref(), Some(value), "{self:?} round trip");
+    }
+}
+
+/// Decodes a versioned database, upgrading older layouts, and validates it as when loading it.
+pub fn database(bytes: &[u8]) {
+    for codec in Codec::ALL {
+        let Some(Upgraded(database)) = codec.decode(bytes) else {
+            continue;
+        };
+        if database.validate().is_ok() {
+            let decoded: Option<Upgraded> = codec.decode(&codec.encode(&Versioned(&database)));
+            assert_eq!(
+                decoded.map(|x| x.0).as_ref(),
+                Some(&database),
+                "{codec:?} round trip",
+            );
+        }
+    }
+}
+
+/// Decodes the arenas of interned values, and validates them.
+pub fn arenas(bytes: &[u8]) {
+    for codec in Codec::ALL {
+        if let Some(arenas) = codec.decode::<Arenas>(bytes) {
+            if arenas.validate().is_ok() {
+                codec.assert_round_trip(&arenas);
+            }
+        }
+    }
+}
+
+/// Decodes run-length encoded interned sets and sequences.
+pub fn interned_set(bytes: &[u8]) {
+    for codec in Codec::ALL {
+        if let Some(set) = codec.decode::<InternedSet<Uuid>>(bytes) {
+            codec.assert_round_trip(&set);
+        }
+        if let Some(seq) = codec.decode::<InternedSeq<Uuid>>(bytes) {
+            codec.assert_round_trip(&seq);
+        }
+    }
+}
@@ -0,0 +1,178 @@
+// Indexed serialization of the optimized database, so that a single snapshot can be read without
+// deserializing the others. The arenas are stored after the snapshots, and an index of the byte
+// offsets of the snapshots allows decoding any of them on its own.
+//
+// File layout:
+// - the binary header with the version of the database,
+// - the number N of snapshots, as a little-endian u64,
+// - the index: N + 1 offsets, as little-endian u64, of each snapshot relative to the start of the
+//   snapshots section, followed by the end of that section, and a checksum of the number of
+//   snapshots and of the index,
+// - the snapshots, each a bincode-encoded `(path, data, provenance)` record where `data` is the
+//   handle of the snapshot in the arenas, followed by a checksum,
+// - the arenas, encoded with bincode, followed by a checksum until the end of the file.
+
+use crate::checksum::{self, CHECKSUM_LEN};
+use crate::provenance::Provenance;
+use crate::schema::optimized::{Arenas, Data};
+use crate::stream::Record;
+use crate::version;
+use blazinterner::Interned;
+use memmap2::Mmap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+/// Serializes the snapshots in the given order, followed by the arenas.
+pub fn serialize(
+    arenas: &Arenas,
+    paths: &[PathBuf],
+    datas: &[Interned<Data>],
+    provenance: &[Option<Provenance>],
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut snapshots = Vec::new();
+    let mut offsets = Vec::with_capacity(datas.len() + 1);
+    for ((path, data), provenance) in paths.iter().zip(datas).zip(provenance) {
+        let start = snapshots.len();
+        offsets.push(start as u64);
+        bincode::serialize_into(&mut snapshots, &(path, data, provenance))?;
+        checksum::seal(&mut snapshots, start);
+    }
+    offsets.push(snapshots.len() as u64);
+
+    let mut bytes = Vec::with_capacity(
+        version::HEADER_LEN + 8 * (offsets.len() + 1) + CHECKSUM_LEN + snapshots.len(),
+    );
+    bytes.extend_from_slice(&version::header());
+    bytes.extend_from_slice(&(datas.len() as u64).to_le_bytes());
+    for offset in offsets {
+        bytes.extend_from_slice(&offset.to_le_bytes());
+    }
+    checksum::seal(&mut bytes, version::HEADER_LEN);
+    bytes.extend_from_slice(&snapshots);
+    let arenas_start = bytes.len();
+    bincode::serialize_into(&mut bytes, arenas)?;
+    checksum::seal(&mut bytes, arenas_start);
+    Ok(bytes)
+}
+
+/// Reads back all the snapshots of a file written by [`serialize`], in order.
+pub fn read(bytes: &[u8]) -> Result<(Arenas, Vec<Record>), Box<dyn std::error::Error>> {
+    let index = Index::parse(bytes)?;
+    let records = (0..index.len())
+        .map(|i| index.get(i))
+        .collect::<Result<_, _>>()?;
+    Ok((index.arenas()?, records))
+}
+
+// Sections of an indexed database.
+struct Index<'a> {
+    offsets: &'a [u8],
+    snapshots: &'a [u8],
+    arenas: &'a [u8],
+}
+
+impl<'a> Index<'a> {
+    fn parse(bytes: &'a [u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        let payload = version::split_header(bytes)?;
+        let len = payload
+            .first_chunk::<8>()
+            .ok_or("Indexed database is too short to contain the number of snapshots")?;
+        let len = u64::from_le_bytes(*len);
+
+        // The number of snapshots, the offsets and their checksum.
+        let index_len = usize::try_from(len)
+            .ok()
+            .and_then(|len| {
+                len.checked_add(2)?
+                    .checked_mul(8)?
+                    .checked_add(CHECKSUM_LEN)
+            })
+            .filter(|&index_len| index_len <= payload.len())
+            .ok_or_else(|| format!("Invalid number of snapshots in indexed database: {len}"))?;
+        let (index, rest) = payload.split_at(index_len);
+        let offsets = &checksum::verify(index, "the index")?[8..];
+        let end = offset(offsets, offsets.len() / 8 - 1);
+        if end > rest.len() {
+            return Err(format!("Invalid end of snapshots in indexed database: {end}").into());
+        }
+        let (snapshots, arenas) = rest.split_at(end);
+
+        Ok(Self {
+            offsets,
+            snapshots,
+            arenas,
+        })
+    }
+
+    fn len(&self) -> usize {
+        self.offsets.len() / 8 - 1
+    }
+
+    // Decodes the i-th snapshot record.
+    fn get(&self, i: usize) -> Result<Record, Box<dyn std::error::Error>> {
+        if i >= self.len() {
+            return Err(format!(
+                "Snapshot {i} is out of range, the database contains {} snapshots",
+                self.len()
+            )
+            .into());
+        }
+        let (start, end) = (offset(self.offsets, i), offset(self.offsets, i + 1));
+        let record = self
+            .snapshots
+            .get(start..end)
+            .ok_or_else(|| format!("Invalid offsets of snapshot {i} in indexed database"))?;
+        Ok(bincode::deserialize(checksum::verify(
+            record,
+            format_args!("snapshot {i}"),
+        )?)?)
+    }
+
+    fn arenas(&self) -> Result<Arenas, Box<dyn std::error::Error>> {
+        Ok(bincode::deserialize(checksum::verify(
+            self.arenas,
+            "the arenas",
+        )?)?)
+    }
+}
+
+fn offset(offsets: &[u8], i: usize) -> usize {
+    let offset = offsets[8 * i..8 * (i + 1)].try_into().unwrap();
+    u64::from_le_bytes(offset) as usize
+}
+
+/// Database in the indexed layout, whose snapshots are decoded individually on demand. Only the
+/// arenas are deserialized when opening it.
+pub struct IndexedDatabase {
+    bytes: Mmap,
+    arenas: Arenas,
+}
+
+impl IndexedDatabase {
+    pub fn open(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let file = File::open(path)?;
+        // SAFETY: The database file isn't expected to be modified while we're reading it.
+        let bytes = unsafe { Mmap::map(&file)? };
+        let arenas = Index::parse(&bytes)?.arenas()?;
+        arenas
+            .validate()
+            .map_err(|e| format!("invalid database: {e}"))?;
+        arenas
+            .check_timestamps()
+            .map_err(|e| format!("invalid database: {e}"))?;
+        Ok(Self { bytes, arenas })
+    }
+
+    pub fn arenas(&self) -> &Arenas {
+        &self.arenas
+    }
+
+    /// Decodes the i-th snapshot, along with the path of the input file that it was parsed from.
+    pub fn get_snapshot(&self, i: usize) -> Result<(PathBuf, &Data), Box<dyn std::error::Error>> {
+        let (path, data, _) = Index::parse(&self.bytes)?.get(i)?;
+        self.arenas
+            .validate_snapshot(data, i)
+            .map_err(|e| format!("invalid database: {e} (parsed from {path:?})"))?;
+        Ok((path, self.arenas.data(data)))
+    }
+}
@@ -0,0 +1,368 @@
+// Reading of the input files, which may be compressed, or be entries of archives that are traversed
+// as if they were directories. Archive entries are read sequentially, in batches that are then
+// processed in parallel, so that large archives don't need to be extracted or held in memory.
+// NDJSON files are split into one record per line.
+//
+// Each entry of an archive is identified by the path of the archive followed by its path within the
+// archive, e.g. `snapshots/2024-01.tar.zst/2024-01-01/12-00.json`, and each record of an NDJSON file
+// by the path of the file followed by its line number.
+
+use crate::cli::InputFormat;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::Read;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::SystemTime;
+use tracing::{debug, warn};
+
+// Number of archive entries read before processing them.
+const ARCHIVE_BATCH_SIZE: usize = 256;
+// Number of buffers kept on each thread to read the next files. A thread holds more than one buffer
+// at a time when it processes the files of an archive or NDJSON file that it read itself.
+const MAX_BUFFERS_PER_THREAD: usize = 4;
+
+thread_local! {
+    // Buffers of the files that were read and dropped on this thread, which are reused to read the
+    // next files rather than allocating a new buffer for each of them.
+    static BUFFERS: RefCell<Vec<Vec<u8>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Contents of an input file, either borrowed from an archive or NDJSON file, or read into a buffer
+/// that is reused by the next files read on the same thread once this is dropped.
+pub enum Bytes<'a> {
+    Borrowed(&'a [u8]),
+    Buffer(Vec<u8>),
+}
+
+impl Deref for Bytes<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Bytes::Borrowed(bytes) => bytes,
+            Bytes::Buffer(buffer) => buffer,
+        }
+    }
+}
+
+impl Drop for Bytes<'_> {
+    fn drop(&mut self) {
+        if let Bytes::Buffer(buffer) = self {
+            let buffer = std::mem::take(buffer);
+            BUFFERS.with_borrow_mut(|buffers| {
+                if buffers.len() < MAX_BUFFERS_PER_THREAD {
+                    buffers.push(buffer);
+                }
+            });
+        }
+    }
+}
+
+// Reads the file into one of the buffers of this thread, if any.
+fn read_file(path: &Path) -> std::io::Result<Bytes<'static>> {
+    let mut buffer = BUFFERS.with_borrow_mut(Vec::pop).unwrap_or_default();
+    buffer.clear();
+    let mut file = File::open(path)?;
+    buffer.reserve(file.metadata()?.len() as usize);
+    file.read_to_end(&mut buffer)?;
+    Ok(Bytes::Buffer(buffer))
+}
+
+/// A file of the input directories, either on disk or inside an archive, or a record of an NDJSON
+/// file.
+pub struct InputFile<'a> {
+    path: &'a Path,
+    contents: Contents<'a>,
+}
+
+enum Contents<'a> {
+    OnDisk,
+    // Contents of an archive entry, which were read along with the archive.
+    Archived(&'a [u8]),
+    // Line of an NDJSON file, which was already decompressed.
+    Record(&'a [u8]),
+}
+
+impl<'a> InputFile<'a> {
+    pub fn on_disk(path: &'a Path) -> Self {
+        Self {
+            path,
+            contents: Contents::OnDisk,
+        }
+    }
+
+    pub fn archived(path: &'a Path, contents: &'a [u8]) -> Self {
+        Self {
+            path,
+            contents: Contents::Archived(contents),
+        }
+    }
+
+    pub fn record(path: &'a Path, line: &'a [u8]) -> Self {
+        Self {
+            path,
+            contents: Contents::Record(line),
+        }
+    }
+
+    pub fn path(&self) -> &'a Path {
+        self.path
+    }
+
+    /// Reads the file, decompressing it if it's compressed. Decompression failures are reported as
+    /// `InvalidData` errors.
+    pub fn read(&self) -> std::io::Result<Bytes<'a>> {
+        match self.contents {
+            Contents::OnDisk => decompress(self.path, read_file(self.path)?),
+            Contents::Archived(contents) => decompress(self.path, Bytes::Borrowed(contents)),
+            Contents::Record(line) => Ok(Bytes::Borrowed(line)),
+        }
+    }
+
+    /// Returns the size of the file, before decompression.
+    pub fn size(&self) -> std::io::Result<u64> {
+        match self.contents {
+            Contents::OnDisk => Ok(self.path.metadata()?.len()),
+            Contents::Archived(contents) | Contents::Record(contents) => Ok(contents.len() as u64),
+        }
+    }
+
+    /// Returns the last modification time of the file, which is only known for files on disk.
+    pub fn modified(&self) -> std::io::Result<Option<SystemTime>> {
+        match self.contents {
+            Contents::OnDisk => Ok(Some(self.path.metadata()?.modified()?)),
+            Contents::Archived(_) | Contents::Record(_) => Ok(None),
+        }
+    }
+
+    /// Returns whether the file contains one snapshot per line.
+    pub fn is_ndjson(&self, format: InputFormat) -> bool {
+        // Records are already split out of their file.
+        if matches!(self.contents, Contents::Record(_)) {
+            return false;
+        }
+        match format {
+            InputFormat::Json => false,
+            InputFormat::Ndjson => true,
+            InputFormat::Auto => {
+                // Skip the compression extension, if any.
+                let path = match extension(self.path) {
+                    Some("gz" | "xz" | "zst") => Cow::Owned(self.path.with_extension("")),
+                    _ => Cow::Borrowed(self.path),
+                };
+                matches!(extension(&path), Some("ndjson" | "jsonl"))
+            }
+        }
+    }
+}
+
+/// Splits the contents of an NDJSON file into its non-empty lines, each identified by the path of
+/// the file followed by its line number, e.g. `snapshots.ndjson:12`.
+pub fn ndjson_records<'a>(path: &Path, bytes: &'a [u8]) -> Vec<(PathBuf, &'a [u8])> {
+    bytes
+        .split(|&byte| byte == b'\n')
+        .enumerate()
+        .filter(|(_, line)| !line.trim_ascii().is_empty())
+        .map(|(i, line)| {
+            let mut record_path = path.as_os_str().to_owned();
+            record_path.push(format!(":{}", i + 1));
+            (PathBuf::from(record_path), line)
+        })
+        .collect()
+}
+
+// Compression of an input file, detected from its extension, or else from its magic bytes.
+#[derive(Debug, Clone, Copy)]
+pub enum InputCompression {
+    Gzip,
+    Xz,
+    Zstd,
+}
+
+impl InputCompression {
+    fn detect(path: &Path, bytes: &[u8]) -> Option<Self> {
+        match extension(path) {
+            Some("gz") => Some(Self::Gzip),
+            Some("xz") => Some(Self::Xz),
+            Some("zst") => Some(Self::Zstd),
+            _ if bytes.starts_with(&[0x1f, 0x8b]) => Some(Self::Gzip),
+            _ if bytes.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) => Some(Self::Xz),
+            _ if bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+
+    fn program(self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Xz => "xz",
+            Self::Zstd => "zstd",
+        }
+    }
+
+    fn command(self) -> Command {
+        let mut command = Command::new(self.program());
+        command.arg("-c").arg("-d").stderr(Stdio::null());
+        command
+    }
+}
+
+fn decompress<'a>(path: &Path, bytes: Bytes<'a>) -> std::io::Result<Bytes<'a>> {
+    let Some(compression) = InputCompression::detect(path, &bytes) else {
+        return Ok(bytes);
+    };
+
+    let program = compression.program();
+    debug!(?path, program, "Decompressing input file");
+    let child = compression
+        .command()
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    crate::io_command(child, &bytes)
+        .map(Bytes::Buffer)
+        .map_err(|err| {
+            invalid_data(format!(
+                "failed to decompress {path:?} with {program}: {err}"
+            ))
+        })
+}
+
+/// Format of an archive, detected from its extension.
+#[derive(Debug, Clone, Copy)]
+pub enum ArchiveFormat {
+    Tar(Option<InputCompression>),
+    Zip,
+}
+
+impl ArchiveFormat {
+    pub fn detect(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?;
+        if name.ends_with(".tar") {
+            Some(Self::Tar(None))
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::Tar(Some(InputCompression::Gzip)))
+        } else if name.ends_with(".tar.xz") || name.ends_with(".txz") {
+            Some(Self::Tar(Some(InputCompression::Xz)))
+        } else if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+            Some(Self::Tar(Some(InputCompression::Zstd)))
+        } else if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+/// Reads the regular files of an archive, calling the callback on batches of `(path, contents)`
+/// entries. Entries that were successfully read are passed to the callback before any error is
+/// returned, so that a truncated archive yields its complete entries.
+pub fn read_archive(
+    path: &Path,
+    format: ArchiveFormat,
+    mut callback: impl FnMut(Vec<(PathBuf, Vec<u8>)>) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    debug!(?path, ?format, "Reading archive");
+    let mut batch = Vec::with_capacity(ARCHIVE_BATCH_SIZE);
+    let mut push = |entry| -> std::io::Result<()> {
+        batch.push(entry);
+        if batch.len() == ARCHIVE_BATCH_SIZE {
+            callback(std::mem::take(&mut batch))?;
+        }
+        Ok(())
+    };
+
+    let result = match format {
+        ArchiveFormat::Tar(None) => read_tar(path, File::open(path)?, &mut push),
+        ArchiveFormat::Tar(Some(compression)) => {
+            let program = compression.program();
+            let mut child = compression
+                .command()
+                .stdin(File::open(path)?)
+                .stdout(Stdio::piped())
+                .spawn()?;
+            let mut stdout = child.stdout.take().expect("Failed to open stdout");
+            let result = read_tar(path, &mut stdout, &mut push).and_then(|()| {
+                // Drain the padding after the end of the archive, so that the process can exit.
+                std::io::copy(&mut stdout, &mut std::io::sink())?;
+                Ok(())
+            });
+            if result.is_err() {
+                child.kill()?;
+            }
+            let status = child.wait()?;
+            result.and_then(|()| {
+                if status.success() {
+                    Ok(())
+                } else {
+                    Err(invalid_data(format!(
+                        "failed to decompress {path:?} with {program}: {status}"
+                    )))
+                }
+            })
+        }
+        ArchiveFormat::Zip => read_zip(path, &mut push),
+    };
+
+    if !batch.is_empty() {
+        callback(batch)?;
+    }
+    result
+}
+
+fn read_tar(
+    path: &Path,
+    reader: impl Read,
+    push: &mut impl FnMut((PathBuf, Vec<u8>)) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let entry_path = path.join(entry.path()?);
+        let mut contents = Vec::with_capacity(entry.size() as usize);
+        entry.read_to_end(&mut contents)?;
+        push((entry_path, contents))?;
+    }
+    Ok(())
+}
+
+fn read_zip(
+    path: &Path,
+    push: &mut impl FnMut((PathBuf, Vec<u8>)) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        if !entry.is_file() {
+            continue;
+        }
+        // Entries whose name would escape the archive's directory are skipped.
+        let Some(name) = entry.enclosed_name() else {
+            warn!(
+                ?path,
+                name = entry.name(),
+                "Skipping archive entry with an unsafe name"
+            );
+            continue;
+        };
+        let entry_path = path.join(name);
+        let mut contents = Vec::with_capacity(entry.size() as usize);
+        entry.read_to_end(&mut contents)?;
+        push((entry_path, contents))?;
+    }
+    Ok(())
+}
+
+fn extension(path: &Path) -> Option<&str> {
+    path.extension().and_then(|extension| extension.to_str())
+}
+
+fn invalid_data(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
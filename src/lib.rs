@@ -0,0 +1,254 @@
+mod alloc;
+mod audit;
+mod checksum;
+mod cli;
+mod codec;
+mod commands;
+mod compare;
+mod database;
+mod diff;
+mod error;
+pub mod fuzz;
+mod index;
+mod input;
+mod logging;
+mod progress;
+mod provenance;
+mod repl;
+mod report;
+mod round_trip;
+mod schema;
+mod stats;
+mod stream;
+mod timing;
+mod version;
+mod walk;
+
+use crate::walk::Inputs;
+use clap::Parser;
+use cli::{Cli, DatabaseArgs, ExportTarget, SchemaName};
+use commands::build::{build, build_json};
+use commands::export::{export, export_gtfs, export_json, export_resolved, export_zstd, generate};
+use commands::ingest::{append, merge, watch};
+use commands::query::{churn, inspect, lifetimes, run_query, search, similarity};
+use commands::verify::{diff_databases, verify, verify_db};
+use database::{load_database, Database};
+use paralight::prelude::*;
+use repl::Repl;
+use round_trip::{io_command, round_trip};
+use schema::optimized::LocalDatetimes;
+use stats::CodecStats;
+use std::time::Duration;
+
+/// Runs the command given on the command line.
+pub fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+    logging::init(cli.verbose, cli.log_format);
+    schema::optimized::configure_local_datetimes(LocalDatetimes {
+        timezone: cli.timezone,
+        ambiguous: cli.ambiguous_datetimes,
+    });
+    // The commands work with the types of the disruptions API, the only registered schema. Another
+    // schema is added as a variant, dispatched here to the commands instantiated with its types.
+    let SchemaName::Disruptions = cli.schema;
+
+    let thread_pool = RayonThreadPool::new_global(
+        ThreadCount::try_from(rayon_core::current_num_threads())
+            .expect("Paralight cannot operate with 0 threads"),
+        RangeStrategy::WorkStealing,
+    );
+
+    let result = match cli.command {
+        cli::Command::Build {
+            output_dir,
+            input_dirs,
+            codec_args,
+            stats,
+            report,
+            verify,
+            parse,
+            provenance,
+            dry_run,
+        } => build(
+            &Inputs::new(&thread_pool, &input_dirs, cli.input_format, cli.quiet)
+                .parsing(parse)
+                .recording_provenance(provenance),
+            output_dir.filter(|_| !dry_run),
+            &codec_args,
+            &stats,
+            &report,
+            &verify,
+        ),
+        cli::Command::BuildJson {
+            output_dir,
+            input_dirs,
+            codec_args,
+            stats,
+            report,
+            dry_run,
+        } => build_json(
+            &Inputs::new(&thread_pool, &input_dirs, cli.input_format, cli.quiet),
+            output_dir.filter(|_| !dry_run),
+            &codec_args,
+            &stats,
+            &report,
+        ),
+        cli::Command::Stream {
+            output,
+            input_dirs,
+            report,
+        } => commands::build::stream(
+            &Inputs::new(&thread_pool, &input_dirs, cli.input_format, cli.quiet),
+            &output,
+            &report,
+        ),
+        cli::Command::Merge {
+            output_dir,
+            format,
+            databases,
+            codec_args,
+            stats,
+        } => merge(output_dir, format, databases, &codec_args, &stats),
+        cli::Command::Inspect {
+            database,
+            top,
+            message_templates,
+        } => inspect(&database, top, message_templates),
+        cli::Command::Similarity {
+            database,
+            threshold,
+            top,
+        } => similarity(&database, threshold, top),
+        cli::Command::Churn {
+            database,
+            churn_report,
+        } => churn(&database, churn_report.as_deref()),
+        cli::Command::Lifetimes {
+            database,
+            output,
+            table_format,
+        } => lifetimes(&database, &output, table_format),
+        cli::Command::Search {
+            database,
+            terms,
+            top,
+        } => search(&database, &terms.join(" "), top),
+        cli::Command::Repl { database } => {
+            let database = load_database(&database)?;
+            Repl::new(&database.arenas, &database.datas, &database.paths)
+                .run(std::io::stdin().lock())
+        }
+        cli::Command::Query { database, query } => run_query(&database, query),
+        cli::Command::Append {
+            database,
+            output_dir,
+            input_dirs,
+            codec_args,
+            stats,
+            report,
+            verify,
+            parse,
+            provenance,
+        } => append(
+            &Inputs::new(&thread_pool, &input_dirs, cli.input_format, cli.quiet)
+                .parsing(parse)
+                .recording_provenance(provenance),
+            &database,
+            output_dir,
+            &codec_args,
+            &stats,
+            &report,
+            &verify,
+        ),
+        cli::Command::Export { database, target } => match target {
+            ExportTarget::Sqlite { output } => export(&database, &output),
+            ExportTarget::Json { output_dir } => export_json(&database, &output_dir),
+            ExportTarget::Resolved { output } => export_resolved(&database, &output),
+            ExportTarget::Zstd {
+                output_dir,
+                dict_size,
+                compression,
+            } => export_zstd(&database, &output_dir, dict_size, &compression),
+            ExportTarget::Gtfs { output_dir, merged } => {
+                export_gtfs(&database, &output_dir, merged)
+            }
+        },
+        cli::Command::Verify {
+            database,
+            input_dirs,
+            report,
+            parse,
+        } => verify(
+            &Inputs::new(&thread_pool, &input_dirs, cli.input_format, cli.quiet).parsing(parse),
+            &database,
+            &report,
+        ),
+        cli::Command::VerifyDb { database } => verify_db(&database),
+        cli::Command::Diff {
+            format,
+            left,
+            right,
+        } => diff_databases(
+            &DatabaseArgs { path: left, format },
+            &DatabaseArgs {
+                path: right,
+                format,
+            },
+        ),
+        cli::Command::Audit {
+            input_dirs,
+            audit_report,
+            report,
+        } => commands::verify::audit(
+            &Inputs::new(&thread_pool, &input_dirs, cli.input_format, cli.quiet),
+            audit_report.as_deref(),
+            &report,
+        ),
+        cli::Command::Generate {
+            output_dir,
+            snapshots,
+            seed,
+            lines,
+            disruptions,
+            overlap,
+            string_reuse,
+        } => generate(
+            &output_dir,
+            snapshots,
+            schema::generate::Config {
+                seed,
+                lines,
+                disruptions,
+                overlap,
+                string_reuse,
+            },
+        ),
+        #[cfg(feature = "fetch")]
+        cli::Command::Fetch {
+            url,
+            api_key,
+            interval,
+            output_dir,
+            database,
+        } => commands::ingest::fetch(
+            &url,
+            &api_key,
+            Duration::from_secs(interval),
+            &output_dir,
+            database
+                .map(|path| DatabaseArgs { path, format: None })
+                .as_ref(),
+        ),
+        cli::Command::Watch {
+            database,
+            input_dirs,
+            save_interval,
+        } => watch(
+            &Inputs::new(&thread_pool, &input_dirs, cli.input_format, cli.quiet),
+            &database,
+            Duration::from_secs(save_interval),
+        ),
+    };
+    alloc::log_peak();
+    result
+}
@@ -0,0 +1,24 @@
+// Initialization of the `tracing` subscriber that prints diagnostics to stderr. Results are still
+// printed to stdout, so that they can be redirected separately from the logs.
+
+use crate::cli::LogFormat;
+use std::io::IsTerminal;
+use tracing::Level;
+
+/// Installs the global subscriber. Each `-v` flag increases the verbosity from the default `info`
+/// level.
+pub fn init(verbose: u8, format: LogFormat) {
+    let level = match verbose {
+        0 => Level::INFO,
+        1 => Level::DEBUG,
+        _ => Level::TRACE,
+    };
+    let builder = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_ansi(std::io::stderr().is_terminal())
+        .with_writer(std::io::stderr);
+    match format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder.json().init(),
+    }
+}
@@ -1,731 +1,3 @@
-#![feature(exit_status_error)]
-
-mod compare;
-mod schema;
-
-use compare::EqWith;
-use get_size2::GetSize;
-use jinterner::{IValue, Jinterners, ValueRef};
-use paralight::prelude::*;
-use schema::optimized::Arenas;
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::fmt::Debug;
-use std::fs::{read_dir, DirEntry, File};
-use std::io::{Read, Write};
-use std::path::{Path, PathBuf};
-use std::process::{Child, Command, Stdio};
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Mutex;
-use std::thread;
-use std::time::{Duration, Instant};
-
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let file_count = AtomicUsize::new(0);
-    let file_error_count = AtomicUsize::new(0);
-    let total_input_bytes = AtomicUsize::new(0);
-    let total_parsed_bytes = AtomicUsize::new(0);
-    let total_optimized_bytes = AtomicUsize::new(0);
-    let total_optimized_json_bytes = AtomicUsize::new(0);
-
-    let mut args = std::env::args();
-    if args.len() <= 2 {
-        panic!(
-            "Please pass a command line argument with (1) an output directory and (2) one or more directori(es) containing JSON files to parse."
-        );
-    }
-
-    let arenas = Arenas::default();
-    let datas = Mutex::new(Vec::new());
-
-    let jinterners = Jinterners::default();
-    let jvalues = Mutex::new(Vec::new());
-
-    let thread_pool = RayonThreadPool::new_global(
-        ThreadCount::try_from(rayon_core::current_num_threads())
-            .expect("Paralight cannot operate with 0 threads"),
-        RangeStrategy::WorkStealing,
-    );
-
-    args.next(); // Ignoring the program path.
-    let output_dir: PathBuf = args.next().unwrap().into();
-    for directory in args {
-        eprintln!("Visiting directory: {directory:?}");
-        visit_dirs(&thread_pool, &directory, &|file_path| {
-            let mut file = File::open(file_path)?;
-            let mut bytes = Vec::new();
-            file.read_to_end(&mut bytes)?;
-            total_input_bytes.fetch_add(bytes.len(), Ordering::Relaxed);
-
-            let data: Result<schema::source::Data, _> = serde_json::from_slice(&bytes);
-            let data = match data {
-                Ok(data) => data,
-                Err(err) => {
-                    eprintln!("Error parsing JSON in file: {file_path:?}\n\t{err:?}");
-                    file_error_count.fetch_add(1, Ordering::Relaxed);
-                    return Ok(());
-                }
-            };
-            total_parsed_bytes.fetch_add(data.get_size(), Ordering::Relaxed);
-
-            let optimized = schema::optimized::Data::from(&arenas, data.clone());
-            total_optimized_bytes.fetch_add(optimized.get_size(), Ordering::Relaxed);
-
-            assert!(
-                optimized.eq_with(&data, &arenas),
-                "Optimized data didn't match original for file: {file_path:?}"
-            );
-
-            datas.lock().unwrap().push(optimized);
-            file_count.fetch_add(1, Ordering::Relaxed);
-
-            let value: Result<serde_json::Value, _> = serde_json::from_slice(&bytes);
-            let value = match value {
-                Ok(value) => value,
-                Err(err) => {
-                    eprintln!("Error parsing JSON in file: {file_path:?}\n\t{err:?}");
-                    return Ok(());
-                }
-            };
-
-            let jvalue = jinterners.intern_ref(&value);
-            total_optimized_json_bytes.fetch_add(jvalue.get_size(), Ordering::Relaxed);
-
-            assert_eq!(
-                jvalue.lookup(&jinterners),
-                value,
-                "Optimized JSON data didn't match original for file: {file_path:?}"
-            );
-
-            jvalues.lock().unwrap().push(jvalue);
-
-            Ok(())
-        })?;
-    }
-
-    let file_count = file_count.load(Ordering::Relaxed);
-    let file_error_count = file_error_count.load(Ordering::Relaxed);
-    let total_input_bytes = total_input_bytes.load(Ordering::Relaxed);
-    let total_parsed_bytes = total_parsed_bytes.load(Ordering::Relaxed);
-    let mut total_optimized_bytes = total_optimized_bytes.load(Ordering::Relaxed);
-    let mut total_optimized_json_bytes = total_optimized_json_bytes.load(Ordering::Relaxed);
-    let datas = datas.into_inner().unwrap();
-    let jvalues = jvalues.into_inner().unwrap();
-
-    println!("Parsed {total_input_bytes} bytes from {file_count} files (+ {file_error_count} failed files)");
-    println!(
-        "Expanded to {total_parsed_bytes} bytes in memory (relative size = {:.02}%)",
-        total_parsed_bytes as f64 * 100.0 / total_input_bytes as f64,
-    );
-
-    let arenas_bytes = arenas.get_size();
-    total_optimized_bytes += arenas_bytes;
-    println!(
-        "Optimized to {total_optimized_bytes} bytes (relative size = {:.02}%)",
-        total_optimized_bytes as f64 * 100.0 / total_input_bytes as f64,
-    );
-    println!(
-        "[{:.02}%] Arenas: {arenas_bytes} bytes",
-        arenas_bytes as f64 * 100.0 / total_optimized_bytes as f64,
-    );
-    arenas.print_summary(total_optimized_bytes);
-
-    let database = Database { arenas, datas };
-    codec(&database, output_dir.clone(), total_input_bytes)?;
-
-    let jinterners_bytes = jinterners.get_size();
-    total_optimized_json_bytes += jinterners_bytes;
-    println!(
-        "Optimized to {total_optimized_json_bytes} bytes (relative size = {:.02}%)",
-        total_optimized_json_bytes as f64 * 100.0 / total_input_bytes as f64,
-    );
-    println!(
-        "[{:.02}%] Jinterners: {jinterners_bytes} bytes",
-        jinterners_bytes as f64 * 100.0 / total_optimized_json_bytes as f64,
-    );
-    jinterners.print_summary_strings("  ", "String", total_optimized_json_bytes);
-    jinterners.print_summary_arrays("  ", "Array", total_optimized_json_bytes);
-    jinterners.print_summary_objects("  ", "Object", total_optimized_json_bytes);
-
-    let jdatabase = Jdatabase {
-        jinterners,
-        jvalues,
-    };
-    jcodec(&jdatabase, output_dir.clone(), total_input_bytes)?;
-
-    println!("Optimizing interners...");
-    let opt = joptimize(&jdatabase.jinterners, &jdatabase.jvalues);
-
-    println!("Optimizing interners (manual loop)...");
-    let (jinterners, jvalues) = joptimize_loop(jdatabase.jinterners, jdatabase.jvalues);
-
-    if let Some((jinterners_opt, jvalues_opt)) = opt {
-        assert_eq!(jinterners, jinterners_opt);
-        assert_eq!(jvalues, jvalues_opt);
-    }
-
-    let jdatabase = Jdatabase {
-        jinterners,
-        jvalues,
-    };
-    jcodec(&jdatabase, output_dir, total_input_bytes)?;
-
-    Ok(())
-}
-
-fn check_eq(
-    jvalue1: &IValue,
-    jinterners1: &Jinterners,
-    jvalue2: &IValue,
-    jinterners2: &Jinterners,
-) -> bool {
-    match (
-        jvalue1.lookup_ref(jinterners1),
-        jvalue2.lookup_ref(jinterners2),
-    ) {
-        (ValueRef::Null, ValueRef::Null) => true,
-        (ValueRef::Bool(x), ValueRef::Bool(y)) => x == y,
-        (ValueRef::U64(x), ValueRef::U64(y)) => x == y,
-        (ValueRef::I64(x), ValueRef::I64(y)) => x == y,
-        (ValueRef::F64(x), ValueRef::F64(y)) => x == y,
-        (ValueRef::String(x), ValueRef::String(y)) => x == y,
-        (ValueRef::Array(x), ValueRef::Array(y)) => {
-            x.len() == y.len()
-                && x.iter()
-                    .zip(y.iter())
-                    .all(|(a, b)| check_eq(a, jinterners1, b, jinterners2))
-        }
-        (ValueRef::Object(x), ValueRef::Object(y)) => {
-            let mut x = x.iter();
-            let y = y.iter();
-            if x.len() != y.len() {
-                false
-            } else {
-                let y: HashMap<_, _> = y.collect();
-                x.all(|(k, a)| {
-                    y.get(k)
-                        .is_some_and(|b| check_eq(a, jinterners1, b, jinterners2))
-                })
-            }
-        }
-        _ => false,
-    }
-}
-
-fn joptimize_loop(jinterners: Jinterners, jvalues: Vec<IValue>) -> (Jinterners, Vec<IValue>) {
-    let start = Instant::now();
-    let mut optimized = None;
-    for _ in 0..10 {
-        let (jinterners, jvalues) = match optimized {
-            None => (&jinterners, &jvalues),
-            Some((ref jinterners, ref jvalues)) => (jinterners, jvalues),
-        };
-        match joptimize_once(jinterners, jvalues) {
-            Some(opt) => optimized = Some(opt),
-            None => break,
-        }
-    }
-    let (jinterners_opt, mut jvalues_opt) = optimized.unwrap();
-    let opt_time = Instant::now().duration_since(start);
-    eprintln!("Optimization time: {:?}", opt_time);
-
-    eprint!("Checking equality...");
-    let start = Instant::now();
-    for (jvalue, jvalue_opt) in jvalues.iter().zip(jvalues_opt.iter()) {
-        assert!(check_eq(jvalue, &jinterners, jvalue_opt, &jinterners_opt));
-    }
-    let check_time = Instant::now().duration_since(start);
-    eprintln!(" {:?}", check_time);
-
-    jvalues_opt.sort_unstable();
-    (jinterners_opt, jvalues_opt)
-}
-
-fn joptimize(jinterners: &Jinterners, jvalues: &[IValue]) -> Option<(Jinterners, Vec<IValue>)> {
-    eprint!("Calculating mapping...");
-    let start = Instant::now();
-    let opt = jinterners.optimize(None);
-    let optimize_time = Instant::now().duration_since(start);
-
-    let (jinterners_opt, mapping) = match opt {
-        None => {
-            eprintln!(" {:?} | identity", optimize_time,);
-            return None;
-        }
-        Some(opt) => opt,
-    };
-
-    eprintln!(
-        " {:?} | remapping {} strings, {} arrays, {} objects",
-        optimize_time,
-        mapping.count_remapped_strings(),
-        mapping.count_remapped_arrays(),
-        mapping.count_remapped_objects()
-    );
-
-    eprint!("Mapping values...");
-    let start = Instant::now();
-    let mut jvalues_opt: Vec<_> = jvalues.iter().map(|v| mapping.map(*v)).collect();
-    let map_time = Instant::now().duration_since(start);
-    eprintln!(" {:?}", map_time);
-
-    eprint!("Checking equality...");
-    let start = Instant::now();
-    for (jvalue, jvalue_opt) in jvalues.iter().zip(jvalues_opt.iter()) {
-        assert!(check_eq(jvalue, jinterners, jvalue_opt, &jinterners_opt));
-    }
-    let check_time = Instant::now().duration_since(start);
-    eprintln!(" {:?}", check_time);
-
-    jvalues_opt.sort_unstable();
-    Some((jinterners_opt, jvalues_opt))
-}
-
-fn joptimize_once(
-    jinterners: &Jinterners,
-    jvalues: &[IValue],
-) -> Option<(Jinterners, Vec<IValue>)> {
-    eprint!("Calculating mapping...");
-    let start = Instant::now();
-    let opt = jinterners.optimize_once();
-    let optimize_time = Instant::now().duration_since(start);
-
-    let (jinterners_opt, mapping) = match opt {
-        None => {
-            eprintln!(" {:?} | identity", optimize_time,);
-            return None;
-        }
-        Some(opt) => opt,
-    };
-
-    eprintln!(
-        " {:?} | remapping {} strings, {} arrays, {} objects",
-        optimize_time,
-        mapping.count_remapped_strings(),
-        mapping.count_remapped_arrays(),
-        mapping.count_remapped_objects()
-    );
-
-    eprint!("Mapping values...");
-    let start = Instant::now();
-    let jvalues_opt: Vec<_> = jvalues.iter().map(|v| mapping.map(*v)).collect();
-    let map_time = Instant::now().duration_since(start);
-    eprintln!(" {:?}", map_time);
-
-    Some((jinterners_opt, jvalues_opt))
-}
-
-#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
-struct Database {
-    arenas: Arenas,
-    datas: Vec<schema::optimized::Data>,
-}
-
-fn codec(
-    database: &Database,
-    output_dir: PathBuf,
-    total_input_bytes: usize,
-) -> Result<(), Box<dyn std::error::Error>> {
-    eprintln!("Serializing database into directory: {output_dir:?}");
-
-    let bincode_bytes = serde_round_trip(
-        database,
-        output_dir.join("bincode.db"),
-        |value| Ok(bincode::serialize(value)?),
-        |bytes| Ok(bincode::deserialize(bytes)?),
-    )?;
-
-    let cbor_bytes = serde_round_trip(
-        database,
-        output_dir.join("cbor.db"),
-        |value| {
-            let mut output = Vec::new();
-            ciborium::into_writer(value, &mut output)?;
-            Ok(output)
-        },
-        |bytes| Ok(ciborium::from_reader(bytes)?),
-    )?;
-
-    let json_bytes = serde_round_trip(
-        database,
-        output_dir.join("json.db"),
-        |value| Ok(serde_json::to_vec(value)?),
-        |bytes| Ok(serde_json::from_slice(bytes)?),
-    )?;
-
-    let json_pretty_bytes = serde_round_trip(
-        database,
-        output_dir.join("json_pretty.db"),
-        |value| Ok(serde_json::to_vec_pretty(value)?),
-        |bytes| Ok(serde_json::from_slice(bytes)?),
-    )?;
-
-    let postcard_bytes = serde_round_trip(
-        database,
-        output_dir.join("postcard.db"),
-        |value| Ok(postcard::to_stdvec(value)?),
-        |bytes| Ok(postcard::from_bytes(bytes)?),
-    )?;
-
-    println!("+---------------+-------------------+-------------------+-------------------+-------------------+-------------------+");
-    println!("|    Format     |       Bytes       |      gzip -6      |       xz -6       |     brotli -6     |     zstd -12      |");
-    println!("+---------------+-----------+-------+-----------+-------+-----------+-------+-----------+-------+-----------+-------+");
-    bincode_bytes.print_sizes("Bincode", total_input_bytes);
-    cbor_bytes.print_sizes("CBOR", total_input_bytes);
-    json_bytes.print_sizes("JSON", total_input_bytes);
-    json_pretty_bytes.print_sizes("JSON (pretty)", total_input_bytes);
-    postcard_bytes.print_sizes("Postcard", total_input_bytes);
-    println!("+---------------+---------+-+-------+---------+-+-------+---------+-+-------+---------+-+-------+---------+-+-------+");
-    println!("|               |   enc   |   dec   |   enc   |   dec   |   enc   |   dec   |   enc   |   dec   |   enc   |   dec   |");
-    println!("+---------------+---------+---------+---------+---------+---------+---------+---------+---------+---------+---------+");
-    bincode_bytes.print_times("Bincode");
-    cbor_bytes.print_times("CBOR");
-    json_bytes.print_times("JSON");
-    json_pretty_bytes.print_times("JSON (pretty)");
-    postcard_bytes.print_times("Postcard");
-    println!("+---------------+---------+---------+---------+---------+---------+---------+---------+---------+---------+---------+");
-
-    Ok(())
-}
-
-#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
-struct Jdatabase {
-    jinterners: Jinterners,
-    jvalues: Vec<IValue>,
-}
-
-fn jcodec(
-    database: &Jdatabase,
-    output_dir: PathBuf,
-    total_input_bytes: usize,
-) -> Result<(), Box<dyn std::error::Error>> {
-    eprintln!("Serializing database into directory: {output_dir:?}");
-
-    let bincode_bytes = serde_round_trip(
-        database,
-        output_dir.join("bincode.jdb"),
-        |value| Ok(bincode::serialize(value)?),
-        |bytes| Ok(bincode::deserialize(bytes)?),
-    )?;
-
-    let cbor_bytes = serde_round_trip(
-        database,
-        output_dir.join("cbor.jdb"),
-        |value| {
-            let mut output = Vec::new();
-            ciborium::into_writer(value, &mut output)?;
-            Ok(output)
-        },
-        |bytes| Ok(ciborium::from_reader(bytes)?),
-    )?;
-
-    let json_bytes = serde_round_trip(
-        database,
-        output_dir.join("json.jdb"),
-        |value| Ok(serde_json::to_vec(value)?),
-        |bytes| Ok(serde_json::from_slice(bytes)?),
-    )?;
-
-    let postcard_bytes = serde_round_trip(
-        database,
-        output_dir.join("postcard.jdb"),
-        |value| Ok(postcard::to_stdvec(value)?),
-        |bytes| Ok(postcard::from_bytes(bytes)?),
-    )?;
-
-    println!("+---------------+-------------------+-------------------+-------------------+-------------------+-------------------+");
-    println!("|    Format     |       Bytes       |      gzip -6      |       xz -6       |     brotli -6     |     zstd -12      |");
-    println!("+---------------+-----------+-------+-----------+-------+-----------+-------+-----------+-------+-----------+-------+");
-    bincode_bytes.print_sizes("Bincode", total_input_bytes);
-    cbor_bytes.print_sizes("CBOR", total_input_bytes);
-    json_bytes.print_sizes("JSON", total_input_bytes);
-    postcard_bytes.print_sizes("Postcard", total_input_bytes);
-    println!("+---------------+---------+-+-------+---------+-+-------+---------+-+-------+---------+-+-------+---------+-+-------+");
-    println!("|               |   enc   |   dec   |   enc   |   dec   |   enc   |   dec   |   enc   |   dec   |   enc   |   dec   |");
-    println!("+---------------+---------+---------+---------+---------+---------+---------+---------+---------+---------+---------+");
-    bincode_bytes.print_times("Bincode");
-    cbor_bytes.print_times("CBOR");
-    json_bytes.print_times("JSON");
-    postcard_bytes.print_times("Postcard");
-    println!("+---------------+---------+---------+---------+---------+---------+---------+---------+---------+---------+---------+");
-
-    Ok(())
-}
-
-fn visit_dirs(
-    thread_pool: &RayonThreadPool,
-    dir: impl AsRef<Path> + Debug,
-    callback: &(impl Fn(&Path) -> std::io::Result<()> + Sync),
-) -> std::io::Result<()> {
-    eprintln!("Reading directory: {dir:?}");
-
-    // Sort entries by path for reproducibility.
-    let mut entries: Vec<DirEntry> = read_dir(dir)?.collect::<Result<_, _>>()?;
-    entries.sort_unstable_by_key(|x| x.path());
-    entries
-        .par_iter()
-        .with_thread_pool(thread_pool)
-        .try_for_each(|entry| -> std::io::Result<()> {
-            let mut path = entry.path();
-            let mut file_type = entry.file_type()?;
-
-            // Resolve symbolic links.
-            if file_type.is_symlink() {
-                eprint!("Resolving symlink: {path:?}");
-                path = std::fs::canonicalize(path)?;
-                eprintln!(" -> {path:?}");
-                file_type = path.metadata()?.file_type();
-            }
-
-            if file_type.is_dir() {
-                visit_dirs(thread_pool, path, callback)?;
-            } else if file_type.is_file() {
-                callback(&path)?;
-            } else {
-                eprintln!("Skipping path of unknown file type {file_type:?}: {path:?}");
-            }
-
-            Ok(())
-        })?;
-
-    Ok(())
-}
-
-struct Stats {
-    serialized: CodecStats,
-    gzip: CodecStats,
-    xz: CodecStats,
-    brotli: CodecStats,
-    zstd: CodecStats,
-}
-
-struct CodecStats {
-    encoded_size: usize,
-    encode_time: Duration,
-    decode_time: Duration,
-}
-
-impl Stats {
-    fn print_sizes(&self, title: &str, total_bytes: usize) {
-        println!(
-            "| {title:<13} | {:>9} |{:>5.02}% | {:>9} |{:>5.02}% | {:>9} |{:>5.02}% | {:>9} |{:>5.02}% | {:>9} |{:>5.02}% |",
-            self.serialized.encoded_size,
-            self.serialized.encoded_size as f64 * 100.0 / total_bytes as f64,
-            self.gzip.encoded_size,
-            self.gzip.encoded_size as f64 * 100.0 / total_bytes as f64,
-            self.xz.encoded_size,
-            self.xz.encoded_size as f64 * 100.0 / total_bytes as f64,
-            self.brotli.encoded_size,
-            self.brotli.encoded_size as f64 * 100.0 / total_bytes as f64,
-            self.zstd.encoded_size,
-            self.zstd.encoded_size as f64 * 100.0 / total_bytes as f64,
-        );
-    }
-
-    fn print_times(&self, title: &str) {
-        println!(
-            "| {title:<13} |{:>5} ms |{:>5} ms |{:>5} ms |{:>5} ms |{:>5} ms |{:>5} ms |{:>5} ms |{:>5} ms |{:>5} ms |{:>5} ms |",
-            self.serialized.encode_time.as_millis(),
-            self.serialized.decode_time.as_millis(),
-            self.gzip.encode_time.as_millis(),
-            self.gzip.decode_time.as_millis(),
-            self.xz.encode_time.as_millis(),
-            self.xz.decode_time.as_millis(),
-            self.brotli.encode_time.as_millis(),
-            self.brotli.decode_time.as_millis(),
-            self.zstd.encode_time.as_millis(),
-            self.zstd.decode_time.as_millis(),
-        );
-    }
-}
-
-fn serde_round_trip<T: PartialEq + Debug>(
-    t: &T,
-    path: impl AsRef<Path> + Debug,
-    serialize: impl FnOnce(&T) -> Result<Vec<u8>, Box<dyn std::error::Error>>,
-    deserialize: impl FnOnce(&[u8]) -> Result<T, Box<dyn std::error::Error>>,
-) -> Result<Stats, Box<dyn std::error::Error>> {
-    eprintln!("- Serializing to: {path:?}");
-
-    eprint!("Serializing...");
-    let start = Instant::now();
-    let serialized = serialize(t)?;
-    let encode_time = Instant::now().duration_since(start);
-    eprintln!(
-        " {:?} | {:.02} MB/s",
-        encode_time,
-        serialized.len() as f64 / (1_000_000.0 * encode_time.as_secs_f64()),
-    );
-
-    eprint!("Deserializing...");
-    let start = Instant::now();
-    let deserialized = deserialize(&serialized)?;
-    let decode_time = Instant::now().duration_since(start);
-    eprintln!(
-        " {:?} | {:.02} MB/s",
-        decode_time,
-        serialized.len() as f64 / (1_000_000.0 * decode_time.as_secs_f64()),
-    );
-
-    assert_eq!(&deserialized, t);
-
-    let mut f = File::create(path)?;
-    f.write_all(&serialized)?;
-    drop(f);
-
-    Ok(Stats {
-        serialized: CodecStats {
-            encoded_size: serialized.len(),
-            encode_time,
-            decode_time,
-        },
-        gzip: gzip_round_trip(&serialized)?,
-        xz: xz_round_trip(&serialized)?,
-        brotli: brotli_round_trip(&serialized)?,
-        zstd: zstd_round_trip(&serialized)?,
-    })
-}
-
-fn gzip_round_trip(bytes: &[u8]) -> Result<CodecStats, Box<dyn std::error::Error>> {
-    codec_round_trip(
-        "gzip",
-        bytes,
-        || {
-            let mut command = Command::new("gzip");
-            command.arg("-c").arg("-6");
-            command
-        },
-        || {
-            let mut command = Command::new("gzip");
-            command.arg("-c").arg("-d");
-            command
-        },
-    )
-}
-
-fn xz_round_trip(bytes: &[u8]) -> Result<CodecStats, Box<dyn std::error::Error>> {
-    codec_round_trip(
-        "xz",
-        bytes,
-        || {
-            let mut command = Command::new("xz");
-            command.arg("-c").arg("-6");
-            command
-        },
-        || {
-            let mut command = Command::new("xz");
-            command.arg("-c").arg("-d");
-            command
-        },
-    )
-}
-
-fn brotli_round_trip(bytes: &[u8]) -> Result<CodecStats, Box<dyn std::error::Error>> {
-    codec_round_trip(
-        "brotli",
-        bytes,
-        || {
-            let mut command = Command::new("brotli");
-            command.arg("-c").arg("-6");
-            command
-        },
-        || {
-            let mut command = Command::new("brotli");
-            command.arg("-c").arg("-d");
-            command
-        },
-    )
-}
-
-fn zstd_round_trip(bytes: &[u8]) -> Result<CodecStats, Box<dyn std::error::Error>> {
-    codec_round_trip(
-        "zstd",
-        bytes,
-        || {
-            let mut command = Command::new("zstd");
-            command.arg("-c").arg("-12");
-            command
-        },
-        || {
-            let mut command = Command::new("zstd");
-            command.arg("-c").arg("-d");
-            command
-        },
-    )
-}
-
-fn codec_round_trip(
-    title: &str,
-    bytes: &[u8],
-    compress: impl FnOnce() -> Command,
-    decompress: impl FnOnce() -> Command,
-) -> Result<CodecStats, Box<dyn std::error::Error>> {
-    eprint!("[{title}] Compressing {} bytes...", bytes.len());
-    let start = Instant::now();
-    let compressed: Vec<u8> = io_command(
-        compress()
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::null())
-            .spawn()?,
-        bytes,
-    )?;
-    let encode_time = Instant::now().duration_since(start);
-    eprintln!(
-        " {:?} | {:.02} MB/s",
-        encode_time,
-        compressed.len() as f64 / (1_000_000.0 * encode_time.as_secs_f64()),
-    );
-
-    // Decompress to validate that compression worked properly.
-    eprint!("[{title}] Decompressing {} bytes...", compressed.len());
-    let start = Instant::now();
-    let decompressed: Vec<u8> = io_command(
-        decompress()
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::null())
-            .spawn()?,
-        &compressed,
-    )?;
-    let decode_time = Instant::now().duration_since(start);
-    eprintln!(
-        " {:?} | {:.02} MB/s",
-        decode_time,
-        compressed.len() as f64 / (1_000_000.0 * decode_time.as_secs_f64()),
-    );
-
-    assert_eq!(decompressed, bytes);
-    Ok(CodecStats {
-        encoded_size: compressed.len(),
-        encode_time,
-        decode_time,
-    })
-}
-
-fn io_command(mut child: Child, input: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    thread::scope(|s| -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-        let mut stdin = child.stdin.take().expect("Failed to open stdin");
-        let mut stdout = child.stdout.take().expect("Failed to open stdout");
-
-        let input_thread = s.spawn(move || -> std::io::Result<()> {
-            stdin.write_all(input)?;
-            drop(stdin);
-            Ok(())
-        });
-
-        let output_thread = s.spawn(move || -> std::io::Result<Vec<u8>> {
-            let mut output = Vec::new();
-            stdout.read_to_end(&mut output)?;
-            Ok(output)
-        });
-
-        child.wait()?.exit_ok()?;
-
-        input_thread.join().expect("Failed to join input thread")?;
-        let output = output_thread
-            .join()
-            .expect("Failed to join output thread")?;
-        Ok(output)
-    })
+    rust_interning::run()
 }
@@ -0,0 +1,77 @@
+// Progress reporting while ingesting input files. The input directories are scanned upfront, so
+// that the progress bar can display an ETA based on the number of bytes to process.
+
+use indicatif::{ProgressBar, ProgressStyle};
+use std::fs::read_dir;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+pub struct Progress {
+    bar: ProgressBar,
+    file_count: u64,
+    files_done: AtomicU64,
+}
+
+impl Progress {
+    /// Counts the files and bytes in the given directories, and creates a progress bar for them.
+    /// The progress bar is hidden if `quiet` is set or if stderr isn't a terminal.
+    pub fn scan(input_dirs: &[PathBuf], quiet: bool) -> std::io::Result<Self> {
+        let mut file_count = 0;
+        let mut total_bytes = 0;
+        for directory in input_dirs {
+            scan_dir(directory, &mut file_count, &mut total_bytes)?;
+        }
+        tracing::info!(file_count, total_bytes, "Scanned input directories");
+
+        let bar = if quiet {
+            ProgressBar::hidden()
+        } else {
+            ProgressBar::new(total_bytes)
+        };
+        bar.set_style(
+            ProgressStyle::with_template(
+                "[{elapsed_precise}] {wide_bar} {bytes}/{total_bytes} ({bytes_per_sec}, ETA {eta}) {msg}",
+            )
+            .unwrap(),
+        );
+        let progress = Self {
+            bar,
+            file_count,
+            files_done: AtomicU64::new(0),
+        };
+        progress.set_message(0);
+        Ok(progress)
+    }
+
+    /// Records that a file of the given size was processed. This can be called concurrently from
+    /// multiple threads.
+    pub fn inc(&self, bytes: u64) {
+        let files_done = self.files_done.fetch_add(1, Ordering::Relaxed) + 1;
+        self.set_message(files_done);
+        self.bar.inc(bytes);
+    }
+
+    pub fn finish(&self) {
+        self.bar.finish_and_clear();
+    }
+
+    fn set_message(&self, files_done: u64) {
+        self.bar
+            .set_message(format!("{files_done}/{} files", self.file_count));
+    }
+}
+
+// Follows symbolic links, consistently with the traversal of the input directories.
+fn scan_dir(dir: &Path, file_count: &mut u64, total_bytes: &mut u64) -> std::io::Result<()> {
+    for entry in read_dir(dir)? {
+        let path = entry?.path();
+        let metadata = path.metadata()?;
+        if metadata.is_dir() {
+            scan_dir(&path, file_count, total_bytes)?;
+        } else if metadata.is_file() {
+            *file_count += 1;
+            *total_bytes += metadata.len();
+        }
+    }
+    Ok(())
+}
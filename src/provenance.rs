@@ -0,0 +1,101 @@
+// Provenance of the snapshots of a database, recorded on request when ingesting input files: which
+// contents were ingested, how old the file was and when it was ingested. Together with the path that
+// each snapshot keeps, this tells whether an input file changed since it was ingested, or which run
+// ingested a snapshot once databases are merged or archived.
+//
+// Records are a few integers, which are distinct for nearly every snapshot, so they are stored next
+// to the paths rather than interned.
+
+use crate::input::InputFile;
+use chrono::{DateTime, SecondsFormat};
+use get_size2::GetSize;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+use std::time::SystemTime;
+
+/// Provenance of a snapshot.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    GetSize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct Provenance {
+    /// CRC32 of the contents of the input file, after decompression.
+    pub checksum: u32,
+    /// Last modification of the input file, in milliseconds since the Unix epoch. Unknown for
+    /// archive entries and NDJSON records.
+    pub modified: Option<i64>,
+    /// Ingestion of the snapshot, in milliseconds since the Unix epoch.
+    pub ingested: i64,
+}
+
+impl Provenance {
+    /// Records the provenance of a snapshot parsed from the given contents of an input file.
+    pub fn new(input: &InputFile, bytes: &[u8]) -> std::io::Result<Self> {
+        Ok(Self {
+            checksum: crc32fast::hash(bytes),
+            modified: input.modified()?.map(millis),
+            ingested: millis(SystemTime::now()),
+        })
+    }
+}
+
+fn millis(time: SystemTime) -> i64 {
+    DateTime::<chrono::Utc>::from(time).timestamp_millis()
+}
+
+fn rfc3339(millis: i64) -> String {
+    match DateTime::from_timestamp_millis(millis) {
+        Some(datetime) => datetime.to_rfc3339_opts(SecondsFormat::Millis, true),
+        None => format!("{millis} ms"),
+    }
+}
+
+impl Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "checksum {:08x} | modified ", self.checksum)?;
+        match self.modified {
+            Some(modified) => f.write_str(&rfc3339(modified))?,
+            None => f.write_str("unknown")?,
+        }
+        write!(f, " | ingested {}", rfc3339(self.ingested))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::generate::test_database;
+    use crate::version::{Upgraded, Versioned};
+
+    #[test]
+    fn provenance_survives_serialization_and_upgrades() {
+        let mut database = test_database(2);
+        database.provenance[0] = Some(Provenance {
+            checksum: 0x1234_5678,
+            modified: None,
+            ingested: 1_700_000_000_000,
+        });
+        let bytes = postcard::to_stdvec(&Versioned(&database)).unwrap();
+        let Upgraded(decoded) = postcard::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded, database);
+
+        // Version 6 didn't have the provenance records.
+        let v6 = serde_json::json!([6, {
+            "arenas": database.arenas,
+            "datas": database.datas,
+            "paths": database.paths,
+        }]);
+        let Upgraded(upgraded) = serde_json::from_value(v6).unwrap();
+        assert_eq!(upgraded.datas, database.datas);
+        assert_eq!(upgraded.provenance, [None, None]);
+    }
+}
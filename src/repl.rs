@@ -0,0 +1,181 @@
+// Interactive exploration of a database: commands are read line by line from the standard input and
+// run against the deserialized arenas, printing resolved values rather than interned IDs. A command
+// that fails only prints its error, so that a typo doesn't end the session.
+
+use crate::schema::optimized::search::SearchIndex;
+use crate::schema::optimized::{Arenas, Data};
+use crate::schema::Uuid;
+use blazinterner::Interned;
+use get_size2::GetSize;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+// Maximum number of disruptions listed by the `search` command.
+const SEARCH_TOP: usize = 20;
+
+const HELP: &str = "Commands:
+  lines              List the distinct lines, with their ID, name and mode.
+  disruption <uuid>  Print each version of a disruption and the snapshots containing it.
+  snapshot <n>       Print a snapshot as JSON, in the format of the input files.
+  stats              Print the number of snapshots and the size of each arena.
+  search <text>      List the disruptions whose title or message contains all the words.
+  help               Print this help.
+  quit               Exit (or end the input).";
+
+/// Database loaded for exploration.
+pub struct Repl<'a> {
+    arenas: &'a Arenas,
+    datas: &'a [Interned<Data>],
+    paths: &'a [PathBuf],
+    // Built on the first search.
+    index: Option<SearchIndex>,
+}
+
+impl<'a> Repl<'a> {
+    pub fn new(arenas: &'a Arenas, datas: &'a [Interned<Data>], paths: &'a [PathBuf]) -> Self {
+        Self {
+            arenas,
+            datas,
+            paths,
+            index: None,
+        }
+    }
+
+    /// Runs the commands read from the input until it ends or a `quit` command.
+    pub fn run(&mut self, input: impl BufRead) -> Result<(), Box<dyn std::error::Error>> {
+        println!(
+            "Loaded {} snapshots, type `help` for the list of commands",
+            self.datas.len()
+        );
+        let mut lines = input.lines();
+        loop {
+            print!("> ");
+            std::io::stdout().flush()?;
+            let Some(line) = lines.next() else {
+                println!();
+                return Ok(());
+            };
+            let line = line?;
+            let (command, argument) = match line.trim().split_once(char::is_whitespace) {
+                Some((command, argument)) => (command, argument.trim()),
+                None => (line.trim(), ""),
+            };
+            let result = match command {
+                "" => Ok(()),
+                "quit" | "exit" => return Ok(()),
+                "help" => {
+                    println!("{HELP}");
+                    Ok(())
+                }
+                "lines" => {
+                    self.lines();
+                    Ok(())
+                }
+                "disruption" => self.disruption(argument),
+                "snapshot" => self.snapshot(argument),
+                "stats" => {
+                    self.stats();
+                    Ok(())
+                }
+                "search" => {
+                    self.search(argument);
+                    Ok(())
+                }
+                _ => Err(format!(
+                    "Unknown command {command:?}, type `help` for the list of commands"
+                )
+                .into()),
+            };
+            if let Err(e) = result {
+                println!("Error: {e}");
+            }
+        }
+    }
+
+    fn lines(&self) {
+        let headers = self.arenas.line_headers();
+        for (id, name, mode) in &headers {
+            println!("{id} | {name} | {mode}");
+        }
+        println!("{} lines", headers.len());
+    }
+
+    fn disruption(&self, argument: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let id: Uuid = argument
+            .parse()
+            .map_err(|e| format!("Invalid UUID {argument:?}: {e}"))?;
+        let versions = self.arenas.disruption_versions(&id);
+        if versions.is_empty() {
+            return Err(format!("No disruption with UUID {argument}").into());
+        }
+        let resolver = self.arenas.resolver();
+        for (i, version) in versions.iter().enumerate() {
+            let snapshots: Vec<usize> = self
+                .datas
+                .iter()
+                .enumerate()
+                .filter(|(_, data)| match self.arenas.data(**data) {
+                    Data::Success(data) => data.disruptions(self.arenas).contains(version),
+                    Data::Error(_) => false,
+                })
+                .map(|(i, _)| i)
+                .collect();
+            println!(
+                "Version {} of {}, in {} snapshots{}",
+                i + 1,
+                versions.len(),
+                snapshots.len(),
+                match (snapshots.first(), snapshots.last()) {
+                    (Some(first), Some(last)) => format!(" (from [{first}] to [{last}])"),
+                    _ => String::new(),
+                },
+            );
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&resolver.disruption(*version))?
+            );
+        }
+        Ok(())
+    }
+
+    fn snapshot(&self, argument: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let index: usize = argument
+            .parse()
+            .map_err(|e| format!("Invalid snapshot position {argument:?}: {e}"))?;
+        let (Some(path), Some(data)) = (self.paths.get(index), self.datas.get(index)) else {
+            return Err(format!(
+                "Snapshot {index} is out of range, the database contains {} snapshots",
+                self.datas.len()
+            )
+            .into());
+        };
+        println!("{path:?}");
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&self.arenas.resolver().data(*data))?
+        );
+        Ok(())
+    }
+
+    fn stats(&self) {
+        println!(
+            "{} snapshots, arenas use {} bytes in memory",
+            self.datas.len(),
+            self.arenas.get_size(),
+        );
+        for interner in self.arenas.interner_stats() {
+            println!(
+                "  {}: {} values, {} bytes",
+                interner.name, interner.objects, interner.bytes
+            );
+        }
+    }
+
+    fn search(&mut self, query: &str) {
+        let index = self
+            .index
+            .get_or_insert_with(|| SearchIndex::new(self.arenas));
+        self.arenas
+            .print_search(index, self.datas, query, SEARCH_TOP);
+    }
+}
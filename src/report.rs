@@ -0,0 +1,147 @@
+// Tracking of the input files that couldn't be ingested, so that they can be triaged after a run.
+
+use crate::schema::{field_name, Extras};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Stage of the pipeline at which an input file failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// The file isn't valid JSON or doesn't match the source schema.
+    Parse,
+    /// The parsed file couldn't be converted to the optimized schema.
+    Conversion,
+    /// The optimized data doesn't match the parsed file.
+    Verification,
+}
+
+impl Stage {
+    fn description(self) -> &'static str {
+        match self {
+            Stage::Parse => "Error parsing JSON",
+            Stage::Conversion => "Invalid data",
+            Stage::Verification => "Verification failed",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Failure {
+    pub path: PathBuf,
+    pub stage: Stage,
+    pub error: String,
+    /// Position of the error in the input file, if known.
+    pub byte_offset: Option<usize>,
+}
+
+#[derive(Default)]
+pub struct Failures(Mutex<Vec<Failure>>);
+
+impl Failures {
+    pub fn record(&self, path: &Path, stage: Stage, error: impl Display) {
+        self.push(path, stage, error.to_string(), None);
+    }
+
+    pub fn record_json_error(&self, path: &Path, bytes: &[u8], error: &serde_json::Error) {
+        self.push(
+            path,
+            Stage::Parse,
+            error.to_string(),
+            byte_offset(bytes, error),
+        );
+    }
+
+    fn push(&self, path: &Path, stage: Stage, error: String, byte_offset: Option<usize>) {
+        tracing::warn!(?path, ?stage, %error, "{}", stage.description());
+        self.0.lock().unwrap().push(Failure {
+            path: path.to_owned(),
+            stage,
+            error,
+            byte_offset,
+        });
+    }
+
+    /// Returns the failures sorted by path, for reproducibility.
+    pub fn into_sorted(self) -> Vec<Failure> {
+        let mut failures = self.0.into_inner().unwrap();
+        failures.sort_unstable();
+        failures
+    }
+}
+
+pub fn print_failures(failures: &[Failure]) {
+    for failure in failures {
+        println!(
+            "- Failed file {:?}: {}: {}",
+            failure.path,
+            failure.stage.description(),
+            failure.error,
+        );
+    }
+}
+
+/// Number of occurrences of each field that the schema doesn't know, across the input files parsed
+/// in tolerant mode.
+#[derive(Default)]
+pub struct UnknownFields(Mutex<BTreeMap<String, usize>>);
+
+impl UnknownFields {
+    pub fn record(&self, path: &Path, extras: &Extras) {
+        if extras.is_empty() {
+            return;
+        }
+        tracing::debug!(?path, count = extras.len(), "Dropped unknown fields");
+        let mut counts = self.0.lock().unwrap();
+        for field in extras.keys() {
+            *counts.entry(field_name(field)).or_insert(0) += 1;
+        }
+    }
+
+    pub fn into_counts(self) -> BTreeMap<String, usize> {
+        self.0.into_inner().unwrap()
+    }
+}
+
+pub fn print_unknown_fields(counts: &BTreeMap<String, usize>) {
+    for (field, count) in counts {
+        println!("- Dropped unknown field {field:?} ({count} occurrences)");
+    }
+}
+
+/// Writes the failures as a JSON array to the given path, if any.
+pub fn write_report(
+    path: Option<&Path>,
+    failures: &[Failure],
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(path) = path {
+        tracing::info!(?path, "Writing failure report");
+        serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), failures)?;
+    }
+    Ok(())
+}
+
+// Converts the line and column reported by serde_json (both 1-based, the column counting bytes)
+// into an offset in the input.
+fn byte_offset(bytes: &[u8], error: &serde_json::Error) -> Option<usize> {
+    let line_start = match error.line() {
+        // Errors that aren't tied to a position, such as I/O errors.
+        0 => return None,
+        1 => 0,
+        line => {
+            bytes
+                .iter()
+                .enumerate()
+                .filter(|(_, b)| **b == b'\n')
+                .nth(line - 2)?
+                .0
+                + 1
+        }
+    };
+    Some(line_start + error.column().saturating_sub(1))
+}
@@ -0,0 +1,370 @@
+// Round trips of databases through each serialization format and compression algorithm, measuring
+// the encoded sizes and the encoding and decoding times that are printed and recorded in the stats.
+
+use crate::cli::{CodecArgs, CompressionArgs};
+use crate::codec;
+use crate::codec::Codec;
+use crate::database::{Database, FrontCodedDatabase, Jdatabase};
+use crate::schema::optimized::protobuf;
+use crate::stats::{CodecStats, FormatStats, Samples, Stats, StatsReport};
+use std::fmt::Debug;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use tracing::{debug, info, info_span};
+
+pub fn codec(
+    database: Database,
+    output_dir: &Path,
+    total_input_bytes: usize,
+    codec_args: &CodecArgs,
+    stats: &mut StatsReport,
+) -> Result<(), Box<dyn std::error::Error>> {
+    info!(?output_dir, "Serializing database");
+    let optimized = round_trips(
+        &database,
+        &codec::database_codecs(),
+        output_dir,
+        ".db",
+        "optimized",
+        codec_args,
+    )?;
+    // Consumers of the protobuf database generate their code from its schema.
+    if codec_args.selects(codec::Protobuf.stem()) {
+        std::fs::write(
+            output_dir.join(protobuf::SCHEMA_FILE_NAME),
+            protobuf::SCHEMA,
+        )?;
+    }
+    let compression = &codec_args.compression;
+    if !optimized.is_empty() {
+        print_stats(&optimized, total_input_bytes, codec_args);
+    }
+
+    // Strings are looked up in place in rkyv archives, which requires storing each of them in full,
+    // so only the serde formats are front-coded.
+    info!("Serializing database with front-coded strings");
+    let database = FrontCodedDatabase::from(database);
+    let front_coded = round_trips(
+        &database,
+        &codec::plain_codecs(),
+        output_dir,
+        "_fc.db",
+        "front_coded",
+        codec_args,
+    )?;
+
+    if !front_coded.is_empty() {
+        println!("Front-coded strings (size delta relative to the same format above):");
+        print_size_header(compression);
+        for format in &front_coded {
+            let baseline = optimized
+                .iter()
+                .find(|baseline| baseline.format == format.format)
+                .expect("Front-coded formats are a subset of the optimized formats");
+            format
+                .stats
+                .print_size_deltas(format.format, &baseline.stats);
+        }
+        println!("+---------------+-----------+-------+-----------+-------+-----------+-------+-----------+-------+-----------+-------+");
+    }
+
+    stats.zstd_level = Some(compression.zstd_level);
+    stats.formats.extend(optimized);
+    stats.formats.extend(front_coded);
+
+    Ok(())
+}
+
+pub fn jcodec(
+    database: &Jdatabase,
+    output_dir: &Path,
+    variant: &'static str,
+    total_input_bytes: usize,
+    codec_args: &CodecArgs,
+    stats: &mut StatsReport,
+) -> Result<(), Box<dyn std::error::Error>> {
+    info!(?output_dir, "Serializing database");
+    let formats = round_trips(
+        database,
+        &codec::plain_codecs(),
+        output_dir,
+        ".jdb",
+        variant,
+        codec_args,
+    )?;
+    if !formats.is_empty() {
+        print_stats(&formats, total_input_bytes, codec_args);
+    }
+
+    stats.zstd_level = Some(codec_args.compression.zstd_level);
+    stats.formats.extend(formats);
+
+    Ok(())
+}
+
+// Round-trips the value through each selected codec, writing it to a file named after the codec
+// followed by the given suffix.
+fn round_trips<T: PartialEq + Debug>(
+    value: &T,
+    codecs: &[Box<dyn Codec<T>>],
+    output_dir: &Path,
+    suffix: &str,
+    variant: &'static str,
+    codec_args: &CodecArgs,
+) -> Result<Vec<FormatStats>, Box<dyn std::error::Error>> {
+    codecs
+        .iter()
+        .filter(|codec| codec_args.selects(codec.stem()))
+        .map(|codec| {
+            let file = format!("{}{suffix}", codec.stem());
+            let stats = round_trip(value, output_dir.join(&file), codec_args, codec.as_ref())?;
+            Ok(FormatStats {
+                file,
+                variant,
+                format: codec.name(),
+                stats,
+            })
+        })
+        .collect()
+}
+
+fn print_size_header(compression: &CompressionArgs) {
+    println!("+---------------+-------------------+-------------------+-------------------+-------------------+-------------------+");
+    println!(
+        "|    Format     |       Bytes       |      gzip -6      |       xz -6       |     brotli -6     |{:^19}|",
+        format!("zstd -{}", compression.zstd_level),
+    );
+    println!("+---------------+-----------+-------+-----------+-------+-----------+-------+-----------+-------+-----------+-------+");
+}
+
+// Prints the table of sizes followed by the table of times. With several iterations, the median
+// times of each format are followed by the fastest and slowest runs and the median throughputs.
+fn print_stats(formats: &[FormatStats], total_input_bytes: usize, codec_args: &CodecArgs) {
+    print_size_header(&codec_args.compression);
+    for format in formats {
+        format.stats.print_sizes(format.format, total_input_bytes);
+    }
+    println!("+---------------+---------+-+-------+---------+-+-------+---------+-+-------+---------+-+-------+---------+-+-------+");
+    println!("|               |   enc   |   dec   |   enc   |   dec   |   enc   |   dec   |   enc   |   dec   |   enc   |   dec   |");
+    println!("+---------------+---------+---------+---------+---------+---------+---------+---------+---------+---------+---------+");
+    for format in formats {
+        format
+            .stats
+            .print_times(format.format, |samples| samples.median);
+        if codec_args.bench_iterations > 1 {
+            format.stats.print_times("  min", |samples| samples.min);
+            format.stats.print_times("  max", |samples| samples.max);
+            format.stats.print_throughputs("  MB/s");
+        }
+    }
+    println!("+---------------+---------+---------+---------+---------+---------+---------+---------+---------+---------+---------+");
+}
+
+pub fn round_trip<T: PartialEq + Debug>(
+    t: &T,
+    path: impl AsRef<Path> + Debug,
+    codec_args: &CodecArgs,
+    codec: &dyn Codec<T>,
+) -> Result<Stats, Box<dyn std::error::Error>> {
+    let _span = info_span!("serialize", ?path).entered();
+    let iterations = codec_args.bench_iterations;
+
+    let (serialized, encode) = Samples::measure(iterations, || codec.encode(t))?;
+    debug!(
+        encode_time = ?encode.median,
+        mb_per_sec = encode.mb_per_sec(serialized.len()),
+        "Serialized",
+    );
+
+    let (deserialized, decode) = Samples::measure(iterations, || codec.decode(&serialized))?;
+    debug!(
+        decode_time = ?decode.median,
+        mb_per_sec = decode.mb_per_sec(serialized.len()),
+        "Deserialized",
+    );
+
+    assert_eq!(&deserialized, t);
+
+    let mut f = File::create(path)?;
+    f.write_all(&serialized)?;
+    drop(f);
+
+    Ok(Stats {
+        serialized: CodecStats {
+            encoded_size: serialized.len(),
+            encode,
+            decode,
+        },
+        gzip: gzip_round_trip(&serialized, iterations)?,
+        xz: xz_round_trip(&serialized, iterations)?,
+        brotli: brotli_round_trip(&serialized, iterations)?,
+        zstd: zstd_round_trip(&serialized, codec_args.compression.zstd_level, iterations)?,
+    })
+}
+
+fn gzip_round_trip(
+    bytes: &[u8],
+    iterations: u32,
+) -> Result<CodecStats, Box<dyn std::error::Error>> {
+    codec_round_trip(
+        "gzip",
+        bytes,
+        iterations,
+        || {
+            let mut command = Command::new("gzip");
+            command.arg("-c").arg("-6");
+            command
+        },
+        || {
+            let mut command = Command::new("gzip");
+            command.arg("-c").arg("-d");
+            command
+        },
+    )
+}
+
+fn xz_round_trip(bytes: &[u8], iterations: u32) -> Result<CodecStats, Box<dyn std::error::Error>> {
+    codec_round_trip(
+        "xz",
+        bytes,
+        iterations,
+        || {
+            let mut command = Command::new("xz");
+            command.arg("-c").arg("-6");
+            command
+        },
+        || {
+            let mut command = Command::new("xz");
+            command.arg("-c").arg("-d");
+            command
+        },
+    )
+}
+
+fn brotli_round_trip(
+    bytes: &[u8],
+    iterations: u32,
+) -> Result<CodecStats, Box<dyn std::error::Error>> {
+    codec_round_trip(
+        "brotli",
+        bytes,
+        iterations,
+        || {
+            let mut command = Command::new("brotli");
+            command.arg("-c").arg("-6");
+            command
+        },
+        || {
+            let mut command = Command::new("brotli");
+            command.arg("-c").arg("-d");
+            command
+        },
+    )
+}
+
+fn zstd_round_trip(
+    bytes: &[u8],
+    level: u32,
+    iterations: u32,
+) -> Result<CodecStats, Box<dyn std::error::Error>> {
+    codec_round_trip(
+        "zstd",
+        bytes,
+        iterations,
+        || {
+            let mut command = Command::new("zstd");
+            command.arg("-c").arg(format!("-{level}"));
+            command
+        },
+        || {
+            let mut command = Command::new("zstd");
+            command.arg("-c").arg("-d");
+            command
+        },
+    )
+}
+
+fn codec_round_trip(
+    title: &str,
+    bytes: &[u8],
+    iterations: u32,
+    compress: impl Fn() -> Command,
+    decompress: impl Fn() -> Command,
+) -> Result<CodecStats, Box<dyn std::error::Error>> {
+    let _span = info_span!("compress", codec = title).entered();
+
+    let (compressed, encode) = Samples::measure(iterations, || {
+        io_command(
+            compress()
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()?,
+            bytes,
+        )
+    })?;
+    debug!(
+        input_bytes = bytes.len(),
+        encode_time = ?encode.median,
+        mb_per_sec = encode.mb_per_sec(compressed.len()),
+        "Compressed",
+    );
+
+    // Decompress to validate that compression worked properly.
+    let (decompressed, decode) = Samples::measure(iterations, || {
+        io_command(
+            decompress()
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()?,
+            &compressed,
+        )
+    })?;
+    debug!(
+        compressed_bytes = compressed.len(),
+        decode_time = ?decode.median,
+        mb_per_sec = decode.mb_per_sec(compressed.len()),
+        "Decompressed",
+    );
+
+    assert_eq!(decompressed, bytes);
+    Ok(CodecStats {
+        encoded_size: compressed.len(),
+        encode,
+        decode,
+    })
+}
+
+pub fn io_command(mut child: Child, input: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    thread::scope(|s| -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let mut stdin = child.stdin.take().expect("Failed to open stdin");
+        let mut stdout = child.stdout.take().expect("Failed to open stdout");
+
+        let input_thread = s.spawn(move || -> std::io::Result<()> {
+            stdin.write_all(input)?;
+            drop(stdin);
+            Ok(())
+        });
+
+        let output_thread = s.spawn(move || -> std::io::Result<Vec<u8>> {
+            let mut output = Vec::new();
+            stdout.read_to_end(&mut output)?;
+            Ok(output)
+        });
+
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(format!("process exited unsuccessfully: {status}").into());
+        }
+
+        input_thread.join().expect("Failed to join input thread")?;
+        let output = output_thread
+            .join()
+            .expect("Failed to join output thread")?;
+        Ok(output)
+    })
+}
@@ -0,0 +1,68 @@
+// Pseudonymization of the parsed snapshots, so that databases can be shared without the identifiers
+// of the upstream API. Each UUID is replaced by a name-based UUID (version 5) derived from it and
+// from a key: the same UUID always maps to the same pseudonym, so disruptions keep referring to each
+// other and the interners deduplicate exactly the same values as without anonymization.
+//
+// Snapshots are anonymized right after parsing, before they are converted and before they are
+// compared to their conversion, so verifying a database requires the same options as building it.
+
+use super::source::{Data, Disruption, Str};
+use super::Uuid;
+
+/// Replaces the UUIDs, and optionally the messages, of parsed snapshots with pseudonyms.
+pub struct Anonymizer {
+    // Namespace of the pseudonyms, derived from the key.
+    namespace: uuid::Uuid,
+    redact_messages: bool,
+}
+
+impl Anonymizer {
+    /// Creates an anonymizer keyed with the given secret. Without a secret, anyone can tell whether
+    /// a database contains a known upstream UUID by computing its pseudonym.
+    pub fn new(key: &str, redact_messages: bool) -> Self {
+        Self {
+            namespace: uuid::Uuid::new_v5(&uuid::Uuid::NAMESPACE_OID, key.as_bytes()),
+            redact_messages,
+        }
+    }
+
+    /// Returns the pseudonym of a UUID.
+    pub fn uuid(&self, id: &Uuid) -> Uuid {
+        Uuid(uuid::Uuid::new_v5(&self.namespace, id.0.as_bytes()))
+    }
+
+    // Replaces a message with a placeholder. Distinct messages get distinct placeholders, so that
+    // the strings are still shared between the same disruptions.
+    fn redact(&self, message: &mut Option<Str>) {
+        if !self.redact_messages {
+            return;
+        }
+        if let Some(text) = message {
+            let hash = uuid::Uuid::new_v5(&self.namespace, text.as_bytes());
+            *text = format!("[redacted {}]", hash.simple()).into();
+        }
+    }
+
+    /// Anonymizes a parsed snapshot in place.
+    pub fn anonymize(&self, data: &mut Data) {
+        for disruption in data.disruptions.iter_mut().flatten() {
+            self.anonymize_disruption(disruption);
+        }
+        for line in data.lines.iter_mut().flatten() {
+            for object in &mut line.impacted_objects {
+                for id in &mut object.disruption_ids {
+                    *id = self.uuid(id);
+                }
+            }
+        }
+    }
+
+    fn anonymize_disruption(&self, disruption: &mut Disruption) {
+        disruption.id = self.uuid(&disruption.id);
+        if let Some(id) = &mut disruption.disruption_id {
+            *id = self.uuid(id);
+        }
+        self.redact(&mut disruption.message);
+        self.redact(&mut disruption.short_message);
+    }
+}
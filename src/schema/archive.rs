@@ -0,0 +1,306 @@
+// Wrappers to archive the interning types with rkyv, which these types don't support natively.
+// Interned handles are archived as their raw IDs, and arenas as vectors of their values in ID order,
+// so that the IDs remain valid when reading the archive in place.
+
+use super::introspect::Introspect;
+use blazinterner::{Arena, ArenaSlice, ArenaStr, Interned, InternedSlice, InternedStr};
+use rkyv::rancor::{Fallible, Source};
+use rkyv::ser::{Allocator, Writer};
+use rkyv::string::{ArchivedString, StringResolver};
+use rkyv::vec::{ArchivedVec, VecResolver};
+use rkyv::with::{ArchiveWith, DeserializeWith, SerializeWith};
+use rkyv::{Archive, Archived, Deserialize, Place, Serialize, SerializeUnsized};
+use std::borrow::Borrow;
+use std::hash::Hash;
+
+// Archives an interned handle as its ID.
+pub struct AsId;
+
+impl<T: ?Sized, Storage> ArchiveWith<Interned<T, Storage>> for AsId {
+    type Archived = Archived<u32>;
+    type Resolver = ();
+
+    fn resolve_with(field: &Interned<T, Storage>, resolver: (), out: Place<Self::Archived>) {
+        field.id().resolve(resolver, out);
+    }
+}
+
+impl<T: ?Sized, Storage, S: Fallible + ?Sized> SerializeWith<Interned<T, Storage>, S> for AsId {
+    fn serialize_with(_field: &Interned<T, Storage>, _serializer: &mut S) -> Result<(), S::Error> {
+        Ok(())
+    }
+}
+
+impl<T: ?Sized, Storage, D: Fallible + ?Sized>
+    DeserializeWith<Archived<u32>, Interned<T, Storage>, D> for AsId
+{
+    fn deserialize_with(
+        field: &Archived<u32>,
+        _deserializer: &mut D,
+    ) -> Result<Interned<T, Storage>, D::Error> {
+        Ok(Interned::from_id(field.to_native()))
+    }
+}
+
+impl ArchiveWith<InternedStr> for AsId {
+    type Archived = Archived<u32>;
+    type Resolver = ();
+
+    fn resolve_with(field: &InternedStr, resolver: (), out: Place<Self::Archived>) {
+        field.id().resolve(resolver, out);
+    }
+}
+
+impl<S: Fallible + ?Sized> SerializeWith<InternedStr, S> for AsId {
+    fn serialize_with(_field: &InternedStr, _serializer: &mut S) -> Result<(), S::Error> {
+        Ok(())
+    }
+}
+
+impl<D: Fallible + ?Sized> DeserializeWith<Archived<u32>, InternedStr, D> for AsId {
+    fn deserialize_with(
+        field: &Archived<u32>,
+        _deserializer: &mut D,
+    ) -> Result<InternedStr, D::Error> {
+        Ok(InternedStr::from_id(field.to_native()))
+    }
+}
+
+impl<T> ArchiveWith<InternedSlice<T>> for AsId {
+    type Archived = Archived<u32>;
+    type Resolver = ();
+
+    fn resolve_with(field: &InternedSlice<T>, resolver: (), out: Place<Self::Archived>) {
+        field.id().resolve(resolver, out);
+    }
+}
+
+impl<T, S: Fallible + ?Sized> SerializeWith<InternedSlice<T>, S> for AsId {
+    fn serialize_with(_field: &InternedSlice<T>, _serializer: &mut S) -> Result<(), S::Error> {
+        Ok(())
+    }
+}
+
+impl<T, D: Fallible + ?Sized> DeserializeWith<Archived<u32>, InternedSlice<T>, D> for AsId {
+    fn deserialize_with(
+        field: &Archived<u32>,
+        _deserializer: &mut D,
+    ) -> Result<InternedSlice<T>, D::Error> {
+        Ok(InternedSlice::from_id(field.to_native()))
+    }
+}
+
+// Archives a UUID as its raw bytes.
+pub struct AsBytes;
+
+impl ArchiveWith<uuid::Uuid> for AsBytes {
+    type Archived = [u8; 16];
+    type Resolver = [(); 16];
+
+    fn resolve_with(field: &uuid::Uuid, resolver: [(); 16], out: Place<Self::Archived>) {
+        field.as_bytes().resolve(resolver, out);
+    }
+}
+
+impl<S: Fallible + ?Sized> SerializeWith<uuid::Uuid, S> for AsBytes {
+    fn serialize_with(_field: &uuid::Uuid, _serializer: &mut S) -> Result<[(); 16], S::Error> {
+        Ok([(); 16])
+    }
+}
+
+impl<D: Fallible + ?Sized> DeserializeWith<[u8; 16], uuid::Uuid, D> for AsBytes {
+    fn deserialize_with(field: &[u8; 16], _deserializer: &mut D) -> Result<uuid::Uuid, D::Error> {
+        Ok(uuid::Uuid::from_bytes(*field))
+    }
+}
+
+// Archives an arena as the vector of its values, ordered by ID.
+pub struct AsArena;
+
+impl<T: Archive, Storage> ArchiveWith<Arena<T, Storage>> for AsArena {
+    type Archived = ArchivedVec<T::Archived>;
+    type Resolver = VecResolver;
+
+    fn resolve_with(field: &Arena<T, Storage>, resolver: VecResolver, out: Place<Self::Archived>) {
+        ArchivedVec::resolve_from_len(field.len(), resolver, out);
+    }
+}
+
+impl<T, Storage, S> SerializeWith<Arena<T, Storage>, S> for AsArena
+where
+    T: Serialize<S> + Eq + Hash,
+    Storage: Borrow<T>,
+    S: Fallible + Allocator + Writer + ?Sized,
+{
+    fn serialize_with(
+        field: &Arena<T, Storage>,
+        serializer: &mut S,
+    ) -> Result<VecResolver, S::Error> {
+        let values = field.values();
+        ArchivedVec::<T::Archived>::serialize_from_iter::<T, _, _>(values, serializer)
+    }
+}
+
+impl<T, Storage, D> DeserializeWith<ArchivedVec<T::Archived>, Arena<T, Storage>, D> for AsArena
+where
+    T: Archive + Eq + Hash,
+    T::Archived: Deserialize<T, D>,
+    Storage: Borrow<T> + From<T>,
+    D: Fallible + ?Sized,
+{
+    fn deserialize_with(
+        field: &ArchivedVec<T::Archived>,
+        deserializer: &mut D,
+    ) -> Result<Arena<T, Storage>, D::Error> {
+        let mut arena = Arena::default();
+        for value in field.iter() {
+            arena.push_mut(value.deserialize(deserializer)?.into());
+        }
+        Ok(arena)
+    }
+}
+
+impl ArchiveWith<ArenaStr> for AsArena {
+    type Archived = ArchivedVec<ArchivedString>;
+    type Resolver = VecResolver;
+
+    fn resolve_with(field: &ArenaStr, resolver: VecResolver, out: Place<Self::Archived>) {
+        ArchivedVec::resolve_from_len(field.strings(), resolver, out);
+    }
+}
+
+impl<S> SerializeWith<ArenaStr, S> for AsArena
+where
+    S: Fallible + Allocator + Writer + ?Sized,
+    S::Error: Source,
+    str: SerializeUnsized<S>,
+{
+    fn serialize_with(field: &ArenaStr, serializer: &mut S) -> Result<VecResolver, S::Error> {
+        let values = field.values().map(StrRef);
+        ArchivedVec::<ArchivedString>::serialize_from_iter::<StrRef, _, _>(values, serializer)
+    }
+}
+
+impl<D: Fallible + ?Sized> DeserializeWith<ArchivedVec<ArchivedString>, ArenaStr, D> for AsArena {
+    fn deserialize_with(
+        field: &ArchivedVec<ArchivedString>,
+        _deserializer: &mut D,
+    ) -> Result<ArenaStr, D::Error> {
+        let mut arena = ArenaStr::default();
+        for value in field.iter() {
+            arena.push_mut(value.as_str());
+        }
+        Ok(arena)
+    }
+}
+
+impl<H> ArchiveWith<ArenaSlice<H>> for AsArena {
+    type Archived = ArchivedVec<ArchivedVec<Archived<u32>>>;
+    type Resolver = VecResolver;
+
+    fn resolve_with(field: &ArenaSlice<H>, resolver: VecResolver, out: Place<Self::Archived>) {
+        ArchivedVec::resolve_from_len(field.slices(), resolver, out);
+    }
+}
+
+impl<H: Handle, S> SerializeWith<ArenaSlice<H>, S> for AsArena
+where
+    S: Fallible + Allocator + Writer + ?Sized,
+{
+    fn serialize_with(field: &ArenaSlice<H>, serializer: &mut S) -> Result<VecResolver, S::Error> {
+        let values = field.values().map(IdsRef);
+        ArchivedVec::<ArchivedVec<Archived<u32>>>::serialize_from_iter::<IdsRef<H>, _, _>(
+            values, serializer,
+        )
+    }
+}
+
+impl<H: Handle, D: Fallible + ?Sized>
+    DeserializeWith<ArchivedVec<ArchivedVec<Archived<u32>>>, ArenaSlice<H>, D> for AsArena
+{
+    fn deserialize_with(
+        field: &ArchivedVec<ArchivedVec<Archived<u32>>>,
+        _deserializer: &mut D,
+    ) -> Result<ArenaSlice<H>, D::Error> {
+        let mut arena = ArenaSlice::default();
+        for value in field.iter() {
+            let slice: Box<[_]> = value.iter().map(|id| H::from_id(id.to_native())).collect();
+            arena.push_copy_mut(&slice);
+        }
+        Ok(arena)
+    }
+}
+
+/// Handle that can be stored in the interned slices of an `ArenaSlice`, which are archived as the
+/// IDs of their handles.
+pub trait Handle: Copy + Default + Eq + Hash {
+    fn id(self) -> u32;
+    fn from_id(id: u32) -> Self;
+}
+
+impl<T: ?Sized, Storage> Handle for Interned<T, Storage> {
+    fn id(self) -> u32 {
+        Interned::id(&self)
+    }
+
+    fn from_id(id: u32) -> Self {
+        Interned::from_id(id)
+    }
+}
+
+impl Handle for InternedStr {
+    fn id(self) -> u32 {
+        InternedStr::id(&self)
+    }
+
+    fn from_id(id: u32) -> Self {
+        InternedStr::from_id(id)
+    }
+}
+
+// Helper to archive an interned string without allocating a `String`.
+struct StrRef<'a>(&'a str);
+
+impl Archive for StrRef<'_> {
+    type Archived = ArchivedString;
+    type Resolver = StringResolver;
+
+    fn resolve(&self, resolver: StringResolver, out: Place<Self::Archived>) {
+        ArchivedString::resolve_from_str(self.0, resolver, out);
+    }
+}
+
+impl<S> Serialize<S> for StrRef<'_>
+where
+    S: Fallible + ?Sized,
+    S::Error: Source,
+    str: SerializeUnsized<S>,
+{
+    fn serialize(&self, serializer: &mut S) -> Result<StringResolver, S::Error> {
+        ArchivedString::serialize_from_str(self.0, serializer)
+    }
+}
+
+// Helper to archive an interned slice of handles as the vector of their IDs.
+struct IdsRef<'a, H>(&'a [H]);
+
+impl<H> Archive for IdsRef<'_, H> {
+    type Archived = ArchivedVec<Archived<u32>>;
+    type Resolver = VecResolver;
+
+    fn resolve(&self, resolver: VecResolver, out: Place<Self::Archived>) {
+        ArchivedVec::resolve_from_len(self.0.len(), resolver, out);
+    }
+}
+
+impl<H: Handle, S> Serialize<S> for IdsRef<'_, H>
+where
+    S: Fallible + Allocator + Writer + ?Sized,
+{
+    fn serialize(&self, serializer: &mut S) -> Result<VecResolver, S::Error> {
+        ArchivedVec::<Archived<u32>>::serialize_from_iter::<u32, _, _>(
+            self.0.iter().map(|x| x.id()),
+            serializer,
+        )
+    }
+}
@@ -0,0 +1,346 @@
+// Generator of synthetic snapshots of the disruptions API, so that the pipeline can be run without
+// access to the real-world corpus. The output is fully determined by the seed.
+//
+// The network is a fixed set of lines, each serving some stop areas shared with other lines. Each
+// snapshot is taken two minutes after the previous one, keeps some of the previous disruptions as is
+// and replaces the others by new ones, each impacting a few lines and stop areas. Free-text fields
+// are either drawn from the texts generated so far or newly generated, to control how many strings
+// the interning can deduplicate.
+
+use super::optimized::timezone;
+use super::source::{ApplicationPeriod, Data, Disruption, ImpactedObject, Line, Str};
+use super::Uuid;
+use chrono::{DateTime, Duration, SecondsFormat, TimeZone, Utc};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::collections::BTreeMap;
+
+const MODES: &[(&str, &str)] = &[
+    ("Metro", "Métro"),
+    ("RapidTransit", "RER"),
+    ("Tramway", "Tramway"),
+    ("Bus", "Bus"),
+];
+const CAUSES: &[&str] = &["PERTURBATION", "TRAVAUX"];
+const SEVERITIES: &[&str] = &["BLOQUANTE", "PERTURBEE", "INFORMATION"];
+const TAGS: &[&str] = &["Actualité", "Travaux", "Ascenseur", "Accessibilité"];
+// Vocabulary of the free-text fields.
+const WORDS: &str =
+    "trafic interrompu perturbé entre et en raison de travaux d'un incident voyageur \
+    technique station fermée bus remplacement mis place reprise prévue vers ascenseur indisponible \
+    manifestation sur la voie ligne arrêt non desservi déviation colis suspect signalisation";
+const STOP_NAMES: &[&str] = &[
+    "Gare", "Porte", "Place", "Pont", "Mairie", "Église", "Parc", "Château", "Marché", "Lycée",
+];
+
+// Interval between two snapshots, i.e. the default polling interval of the API.
+const SNAPSHOT_INTERVAL: Duration = Duration::minutes(2);
+
+/// Parameters of a synthetic dataset.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Seed of the random number generator.
+    pub seed: u64,
+    /// Number of lines in the network.
+    pub lines: usize,
+    /// Number of disruptions in each snapshot.
+    pub disruptions: usize,
+    /// Probability that a disruption of a snapshot is kept as is in the next one.
+    pub overlap: f64,
+    /// Probability that a free-text field reuses a text generated before.
+    pub string_reuse: f64,
+}
+
+/// Infinite iterator over the snapshots of a synthetic dataset.
+pub struct Generator {
+    config: Config,
+    rng: StdRng,
+    words: Vec<&'static str>,
+    network: Vec<NetworkLine>,
+    stop_areas: Vec<(Str<'static>, Str<'static>)>,
+    active: Vec<ActiveDisruption>,
+    texts: BTreeMap<TextKind, Vec<String>>,
+    time: DateTime<Utc>,
+}
+
+// A line along with the indices of the stop areas it serves.
+struct NetworkLine {
+    line: Line<'static>,
+    stop_areas: Vec<usize>,
+}
+
+// A disruption along with the lines it impacts, optionally at a specific stop area.
+struct ActiveDisruption {
+    disruption: Disruption<'static>,
+    impacted: Vec<(usize, Option<usize>)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum TextKind {
+    Title,
+    Message,
+    ShortMessage,
+}
+
+impl Generator {
+    pub fn new(config: Config) -> Self {
+        let mut rng = StdRng::seed_from_u64(config.seed);
+        let words: Vec<&str> = WORDS.split_whitespace().collect();
+
+        let stop_area_count = config.lines * 8;
+        let stop_areas = (0..stop_area_count)
+            .map(|i| {
+                let name = format!(
+                    "{} {}",
+                    STOP_NAMES.choose(&mut rng).unwrap(),
+                    capitalized(words.choose(&mut rng).unwrap()),
+                );
+                (format!("stop_area:IDFM:{}", 70000 + i).into(), name.into())
+            })
+            .collect();
+
+        let network = (0..config.lines)
+            .map(|i| {
+                let (mode, mode_name) = *MODES.choose(&mut rng).unwrap();
+                let short_name = (i + 1).to_string();
+                let stop_count = rng.gen_range(5..=20).min(stop_area_count);
+                NetworkLine {
+                    line: Line {
+                        id: format!("line:IDFM:C{:05}", 1000 + i).into(),
+                        name: format!("{mode_name} {short_name}").into(),
+                        short_name: short_name.into(),
+                        mode: mode.into(),
+                        network_id: format!("network:IDFM:{}", rng.gen_range(1..=10)).into(),
+                        impacted_objects: Vec::new(),
+                    },
+                    stop_areas: rand::seq::index::sample(&mut rng, stop_area_count, stop_count)
+                        .into_vec(),
+                }
+            })
+            .collect();
+
+        Self {
+            config,
+            rng,
+            words,
+            network,
+            stop_areas,
+            active: Vec::new(),
+            texts: BTreeMap::new(),
+            time: Utc.with_ymd_and_hms(2024, 1, 1, 6, 0, 0).unwrap(),
+        }
+    }
+
+    fn new_disruption(&mut self) -> ActiveDisruption {
+        let id = self.uuid();
+        let period_count = self.rng.gen_range(1..=3);
+        let application_periods = (0..period_count)
+            .map(|_| {
+                let begin = self.time - Duration::hours(self.rng.gen_range(0..72));
+                let end = begin + Duration::hours(self.rng.gen_range(1..48));
+                ApplicationPeriod {
+                    begin: local(begin).into(),
+                    end: local(end).into(),
+                }
+            })
+            .collect();
+        let last_update = local(self.time - Duration::seconds(self.rng.gen_range(0..3600)));
+
+        let tags = self.rng.gen_bool(0.5).then(|| {
+            let count = self.rng.gen_range(1..=2);
+            TAGS.choose_multiple(&mut self.rng, count)
+                .map(|&tag| tag.into())
+                .collect()
+        });
+        let title = self.text(TextKind::Title, 3..8);
+        let message = format!("<p>{}</p>", self.text(TextKind::Message, 15..40));
+        let short_message = self
+            .rng
+            .gen_bool(0.3)
+            .then(|| self.text(TextKind::ShortMessage, 5..12));
+        // Some disruptions refer to another one, e.g. the cause of a cascading disruption.
+        let disruption_id = match self.active.choose(&mut self.rng) {
+            Some(other) if self.rng.gen_bool(0.1) => Some(other.disruption.id.clone()),
+            _ => None,
+        };
+
+        let line_count = self.rng.gen_range(1..=3).min(self.network.len());
+        let impacted = rand::seq::index::sample(&mut self.rng, self.network.len(), line_count)
+            .into_iter()
+            .map(|line| {
+                let stop_area = if self.rng.gen_bool(0.5) {
+                    self.network[line].stop_areas.choose(&mut self.rng).copied()
+                } else {
+                    None
+                };
+                (line, stop_area)
+            })
+            .collect();
+
+        ActiveDisruption {
+            disruption: Disruption {
+                id,
+                application_periods,
+                last_update: last_update.into(),
+                cause: (*CAUSES.choose(&mut self.rng).unwrap()).into(),
+                severity: (*SEVERITIES.choose(&mut self.rng).unwrap()).into(),
+                tags,
+                title: title.into(),
+                message: Some(message.into()),
+                short_message: short_message.map(Into::into),
+                disruption_id,
+            },
+            impacted,
+        }
+    }
+
+    fn uuid(&mut self) -> Uuid {
+        Uuid(uuid::Builder::from_random_bytes(self.rng.gen()).into_uuid())
+    }
+
+    // Returns a previous text of the same kind with the configured probability, or else a new text
+    // with a number of words in the given range.
+    fn text(&mut self, kind: TextKind, words: std::ops::Range<usize>) -> String {
+        let texts = self.texts.entry(kind).or_default();
+        if !texts.is_empty() && self.rng.gen_bool(self.config.string_reuse) {
+            return texts.choose(&mut self.rng).unwrap().clone();
+        }
+        let count = self.rng.gen_range(words);
+        let text = capitalized(
+            &(0..count)
+                .map(|_| *self.words.choose(&mut self.rng).unwrap())
+                .collect::<Vec<_>>()
+                .join(" "),
+        );
+        texts.push(text.clone());
+        text
+    }
+
+    // Lists the lines impacted by the active disruptions, along with the objects they impact.
+    fn impacted_lines(&self) -> Vec<Line<'static>> {
+        let mut impacted: BTreeMap<usize, BTreeMap<Option<usize>, Vec<Uuid>>> = BTreeMap::new();
+        for active in &self.active {
+            for &(line, stop_area) in &active.impacted {
+                impacted
+                    .entry(line)
+                    .or_default()
+                    .entry(stop_area)
+                    .or_default()
+                    .push(active.disruption.id.clone());
+            }
+        }
+
+        impacted
+            .into_iter()
+            .map(|(line, objects)| {
+                let line = &self.network[line].line;
+                Line {
+                    impacted_objects: objects
+                        .into_iter()
+                        .map(|(stop_area, disruption_ids)| {
+                            let (typ, id, name) = match stop_area {
+                                None => ("line", &line.id, &line.name),
+                                Some(i) => {
+                                    ("stop_area", &self.stop_areas[i].0, &self.stop_areas[i].1)
+                                }
+                            };
+                            ImpactedObject {
+                                typ: typ.into(),
+                                id: id.clone(),
+                                name: name.clone(),
+                                disruption_ids,
+                            }
+                        })
+                        .collect(),
+                    ..line.clone()
+                }
+            })
+            .collect()
+    }
+}
+
+impl Iterator for Generator {
+    type Item = Data<'static>;
+
+    fn next(&mut self) -> Option<Data<'static>> {
+        let overlap = self.config.overlap;
+        let rng = &mut self.rng;
+        self.active.retain(|_| rng.gen_bool(overlap));
+        while self.active.len() < self.config.disruptions {
+            let disruption = self.new_disruption();
+            self.active.push(disruption);
+        }
+
+        let data = Data {
+            disruptions: Some(self.active.iter().map(|x| x.disruption.clone()).collect()),
+            lines: Some(self.impacted_lines()),
+            last_updated_date: Some(
+                self.time
+                    .to_rfc3339_opts(SecondsFormat::Millis, true)
+                    .into(),
+            ),
+            status_code: None,
+            error: None,
+            message: None,
+        };
+        self.time += SNAPSHOT_INTERVAL;
+        Some(data)
+    }
+}
+
+// Formats a time as in the API, i.e. in the local timezone. Converting from UTC ensures that the
+// local time exists, even across daylight saving time changes.
+fn local(time: DateTime<Utc>) -> String {
+    time.with_timezone(&timezone())
+        .format("%Y%m%dT%H%M%S")
+        .to_string()
+}
+
+fn capitalized(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Small dataset shared by the tests.
+#[cfg(test)]
+pub const TEST_CONFIG: Config = Config {
+    seed: 0,
+    lines: 10,
+    disruptions: 5,
+    overlap: 0.5,
+    string_reuse: 0.5,
+};
+
+/// Converts and interns the first `count` snapshots of the test dataset.
+#[cfg(test)]
+pub fn intern_test_snapshots(
+    arenas: &super::optimized::Arenas,
+    count: usize,
+) -> Vec<blazinterner::Interned<super::optimized::Data>> {
+    use super::optimized::FromSource;
+
+    Generator::new(TEST_CONFIG)
+        .take(count)
+        .map(|source| {
+            let data = FromSource::from_source(arenas, &source).unwrap();
+            arenas.intern_data(data).unwrap()
+        })
+        .collect()
+}
+
+/// Database of the first `count` snapshots of the test dataset, parsed from `000000.json`,
+/// `000001.json`, etc. and without provenance.
+#[cfg(test)]
+pub fn test_database(count: usize) -> crate::database::Database {
+    let arenas = super::optimized::Arenas::default();
+    let datas = intern_test_snapshots(&arenas, count);
+    let mut database = crate::database::Database::new(arenas);
+    for (i, data) in datas.into_iter().enumerate() {
+        database.push(format!("{i:06}.json").into(), data, None);
+    }
+    database
+}
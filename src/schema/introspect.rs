@@ -0,0 +1,105 @@
+// Enumeration of the contents of arenas. blazinterner only gives access to interned values through
+// their handles, but handles are assigned sequentially from 0, so the contents of an arena are the
+// values of the IDs below its length.
+
+use blazinterner::{Arena, ArenaSlice, ArenaStr, Interned, InternedSlice, InternedStr};
+use std::borrow::Borrow;
+use std::hash::Hash;
+
+/// Read-only access to all the values interned in an arena.
+pub trait Introspect {
+    /// Handle to a value of this arena.
+    type Handle: Copy;
+    /// Type of the values interned in this arena.
+    type Value: ?Sized;
+
+    /// Returns the number of values interned in this arena.
+    fn len(&self) -> usize;
+
+    /// Returns the handle with the given ID, without checking that it's in this arena.
+    fn handle(id: u32) -> Self::Handle;
+
+    /// Returns the value of a handle of this arena.
+    fn value(&self, handle: Self::Handle) -> &Self::Value;
+
+    /// Returns the handle of the given value if it's interned, without interning it otherwise.
+    fn position(&self, value: &Self::Value) -> Option<Self::Handle>;
+
+    /// Returns the handles of all the values, in the order in which they were interned.
+    fn ids(&self) -> impl ExactSizeIterator<Item = Self::Handle> + Clone {
+        (0..self.len() as u32).map(Self::handle)
+    }
+
+    /// Returns all the values along with their handles, in the order in which they were interned.
+    fn iter(&self) -> impl ExactSizeIterator<Item = (Self::Handle, &Self::Value)> + Clone {
+        self.ids().map(|handle| (handle, self.value(handle)))
+    }
+
+    /// Returns all the values, in the order in which they were interned.
+    fn values(&self) -> impl ExactSizeIterator<Item = &Self::Value> + Clone {
+        self.ids().map(|handle| self.value(handle))
+    }
+}
+
+impl<T: ?Sized + Eq + Hash, Storage: Borrow<T>> Introspect for Arena<T, Storage> {
+    type Handle = Interned<T, Storage>;
+    type Value = T;
+
+    fn len(&self) -> usize {
+        Arena::len(self)
+    }
+
+    fn handle(id: u32) -> Self::Handle {
+        Interned::from_id(id)
+    }
+
+    fn value(&self, handle: Self::Handle) -> &T {
+        self.lookup_ref(handle)
+    }
+
+    fn position(&self, value: &T) -> Option<Self::Handle> {
+        self.find(value)
+    }
+}
+
+impl Introspect for ArenaStr {
+    type Handle = InternedStr;
+    type Value = str;
+
+    fn len(&self) -> usize {
+        self.strings()
+    }
+
+    fn handle(id: u32) -> InternedStr {
+        InternedStr::from_id(id)
+    }
+
+    fn value(&self, handle: InternedStr) -> &str {
+        self.lookup(handle)
+    }
+
+    fn position(&self, value: &str) -> Option<InternedStr> {
+        self.find(value)
+    }
+}
+
+impl<T: Eq + Hash> Introspect for ArenaSlice<T> {
+    type Handle = InternedSlice<T>;
+    type Value = [T];
+
+    fn len(&self) -> usize {
+        self.slices()
+    }
+
+    fn handle(id: u32) -> InternedSlice<T> {
+        InternedSlice::from_id(id)
+    }
+
+    fn value(&self, handle: InternedSlice<T>) -> &[T] {
+        self.lookup(handle)
+    }
+
+    fn position(&self, value: &[T]) -> Option<InternedSlice<T>> {
+        self.find(value)
+    }
+}
@@ -1,12 +1,178 @@
+pub mod anonymize;
+pub mod archive;
+pub mod generate;
+pub mod introspect;
 pub mod optimized;
 pub mod source;
+pub mod tagged;
 
+use crate::compare::EqWith;
+use anonymize::Anonymizer;
+use blazinterner::Interned;
 use get_size2::GetSize;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
 
-#[derive(Default, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
-pub struct Uuid(uuid::Uuid);
+/// Shape of the snapshots of a feed, from the JSON files returned by its API to their interned
+/// representation. The ingestion of input files and the `Database` holding the interned snapshots
+/// are generic over it, and the schema is selected with `--schema`. The compaction, statistics,
+/// serialization formats and queries of a database are specific to each schema.
+pub trait Schema {
+    /// Snapshot as parsed from an input file, which may borrow from it. It's serialized back to
+    /// JSON once anonymized.
+    type Source<'a>: Deserialize<'a> + Serialize + GetSize;
+    /// Snapshot whose values are interned in the interners.
+    type Optimized: for<'a> EqWith<Self::Source<'a>, Self::Interners> + GetSize;
+    /// Arenas holding the values interned by all the snapshots.
+    type Interners: Default + GetSize + Sync;
+    /// Error encountered when converting a parsed snapshot.
+    type Error: Display;
+
+    /// Parses an input file.
+    fn from_slice(bytes: &[u8]) -> serde_json::Result<Self::Source<'_>> {
+        serde_json::from_slice(bytes)
+    }
+
+    /// Parses an input file, moving the fields that the schema doesn't know into the returned
+    /// extras instead of failing. Schemas that don't support it parse strictly.
+    fn from_slice_tolerant(bytes: &[u8]) -> serde_json::Result<(Self::Source<'_>, Extras)> {
+        Self::from_slice(bytes).map(|source| (source, Extras::new()))
+    }
+
+    /// Copies whatever a parsed snapshot borrows from its input, for parsers that parse a copy of
+    /// the input file.
+    fn into_owned<'a>(source: Self::Source<'_>) -> Self::Source<'a>;
+
+    /// Replaces the identifiers of a parsed snapshot with pseudonyms, before converting it.
+    fn anonymize(source: &mut Self::Source<'_>, anonymizer: &Anonymizer);
+
+    /// Parses an input file directly into the interners, without going through the source
+    /// snapshot. Schemas without such a parser return `None`, so that the file is parsed and
+    /// converted instead.
+    fn from_slice_interned(_interners: &Self::Interners, _bytes: &[u8]) -> Option<Self::Optimized> {
+        None
+    }
+
+    /// Converts a parsed snapshot, interning its values.
+    fn from_source(
+        interners: &Self::Interners,
+        source: &Self::Source<'_>,
+    ) -> Result<Self::Optimized, Self::Error>;
+
+    /// Interns a converted snapshot, so that identical snapshots share the same handle.
+    fn intern(
+        interners: &Self::Interners,
+        optimized: Self::Optimized,
+    ) -> Result<Interned<Self::Optimized>, Self::Error>;
+
+    /// Describes how a converted snapshot differs from its source, once `eq_with` found that they
+    /// don't match.
+    fn explain(
+        optimized: &Self::Optimized,
+        source: &Self::Source<'_>,
+        interners: &Self::Interners,
+    ) -> String;
+}
+
+/// Snapshots of the traffic disruptions API.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Disruptions {}
+
+impl Schema for Disruptions {
+    type Source<'a> = source::Data<'a>;
+    type Optimized = optimized::Data;
+    type Interners = optimized::Arenas;
+    type Error = crate::error::Error;
+
+    fn from_slice(bytes: &[u8]) -> serde_json::Result<source::Data<'_>> {
+        source::from_slice(bytes)
+    }
+
+    fn from_slice_tolerant(bytes: &[u8]) -> serde_json::Result<(source::Data<'_>, Extras)> {
+        source::from_slice_tolerant(bytes)
+    }
+
+    fn into_owned<'a>(data: source::Data<'_>) -> source::Data<'a> {
+        data.into_owned()
+    }
+
+    fn anonymize(data: &mut source::Data<'_>, anonymizer: &Anonymizer) {
+        anonymizer.anonymize(data);
+    }
+
+    fn from_slice_interned(arenas: &optimized::Arenas, bytes: &[u8]) -> Option<optimized::Data> {
+        optimized::seed::from_slice(arenas, bytes).ok()
+    }
+
+    fn from_source(
+        arenas: &optimized::Arenas,
+        data: &source::Data<'_>,
+    ) -> Result<optimized::Data, crate::error::Error> {
+        optimized::FromSource::from_source(arenas, data)
+    }
+
+    fn intern(
+        arenas: &optimized::Arenas,
+        data: optimized::Data,
+    ) -> Result<Interned<optimized::Data>, crate::error::Error> {
+        arenas.intern_data(data)
+    }
+
+    fn explain(
+        data: &optimized::Data,
+        source: &source::Data<'_>,
+        arenas: &optimized::Arenas,
+    ) -> String {
+        crate::diff::explain(data, source, arenas)
+    }
+}
+
+/// Fields of a JSON file that the schema doesn't know, by path, e.g. `disruptions[3].newField`.
+pub type Extras = HashMap<String, Value>;
+
+/// Returns the path of an extra field without the positions in arrays, e.g.
+/// `disruptions[].newField`, to count the occurrences of each field across objects.
+pub fn field_name(path: &str) -> String {
+    let mut name = String::with_capacity(path.len());
+    let mut in_brackets = false;
+    for c in path.chars() {
+        match c {
+            '[' => in_brackets = true,
+            ']' => in_brackets = false,
+            _ if in_brackets => continue,
+            _ => (),
+        }
+        name.push(c);
+    }
+    name
+}
+
+#[derive(
+    Default,
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct Uuid(#[rkyv(with = archive::AsBytes)] uuid::Uuid);
 
 impl GetSize for Uuid {
     // There is nothing on the heap, so the default implementation works out of the box.
 }
+
+impl FromStr for Uuid {
+    type Err = uuid::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        uuid::Uuid::parse_str(s).map(Uuid)
+    }
+}
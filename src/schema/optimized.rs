@@ -1,56 +1,547 @@
+pub mod active;
+pub mod avro;
+pub mod bitmap;
+pub mod churn;
+mod compact;
+pub mod diff;
+mod domains;
+pub mod front_coding;
+pub mod gtfs;
+pub mod invariants;
+pub mod known;
+pub mod lifetime;
+pub mod protobuf;
+mod refcount;
+pub mod reverse;
+pub mod search;
+pub mod seed;
+mod set_codecs;
+mod similarity;
+pub mod sqlite;
+mod templates;
+#[cfg(test)]
+mod tests;
+pub mod v1;
+pub mod v2;
+pub mod v3;
+pub mod v4;
+pub mod v5;
+pub mod validate;
+pub mod view;
+
+use super::archive::{AsArena, AsId, Handle};
+use super::introspect::Introspect;
 use super::source;
+use super::tagged::Tagged;
 use super::Uuid;
+use crate::cli::AmbiguousDatetimes;
 use crate::compare::EqWith;
+use crate::error::Error;
+use crate::stats::InternerStats;
 use blazinterner::{Arena, ArenaSlice, ArenaStr, Interned, InternedSlice, InternedStr};
 use chrono::format::SecondsFormat;
 use chrono::offset::LocalResult;
-use chrono::{DateTime, NaiveDateTime};
-use chrono_tz::Europe::Paris;
+use chrono::{DateTime, NaiveDateTime, Offset, Utc};
+use chrono_tz::Tz;
 use get_size2::{GetSize, GetSizeTracker};
+use known::{Known, Mode, Severity};
+use validate::Id;
+// Sets of handles are hashed with foldhash like the arenas, rather than with the SipHash of the
+// standard library, as they don't hold untrusted keys.
+use hashbrown::HashSet;
+use rkyv::rancor::Fallible;
+use rkyv::ser::{Allocator, Writer};
+use rkyv::vec::{ArchivedVec, VecResolver};
+use rkyv::with::Map;
+use rkyv::{Archived, Place};
+use rust_interning_derive::FromSource;
 use serde::de::{SeqAccess, Visitor};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_tuple::{Deserialize_tuple, Serialize_tuple};
+use std::borrow::Borrow;
 use std::hash::Hash;
 use std::marker::PhantomData;
-
-#[derive(Default, Debug, PartialEq, Eq, Serialize_tuple, Deserialize_tuple, GetSize)]
+use std::mem::size_of;
+use std::rc::Rc;
+use std::sync::OnceLock;
+
+// The interned UUIDs are the IDs of disruptions. Their storage is tagged so that these handles can't
+// be looked up in an arena of other UUIDs.
+type UuidStorage = Tagged<Uuid, Disruption>;
+pub type DisruptionUuid = Interned<Uuid, UuidStorage>;
+
+#[derive(
+    Default,
+    Debug,
+    PartialEq,
+    Eq,
+    Serialize_tuple,
+    Deserialize_tuple,
+    GetSize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
 pub struct Arenas {
+    #[rkyv(with = AsArena)]
     string: ArenaStr,
-    uuid: Arena<Uuid>,
-    disruption_set: ArenaSet<Disruption>,
+    #[rkyv(with = AsArena)]
+    uuid: Arena<Uuid, UuidStorage>,
+    disruption_set: ArenaSeq<Interned<Disruption>>,
+    #[rkyv(with = AsArena)]
     disruption: Arena<Disruption>,
+    application_period_set: ArenaSet<Interned<ApplicationPeriod>>,
+    #[rkyv(with = AsArena)]
     application_period: Arena<ApplicationPeriod>,
-    line_set: ArenaSet<Line>,
+    string_set: ArenaSet<InternedStr>,
+    #[rkyv(with = AsArena)]
+    timestamp: Arena<LocalTimestampSeconds>,
+    line_set: ArenaSeq<Interned<Line>>,
+    #[rkyv(with = AsArena)]
     line: Arena<Line>,
+    #[rkyv(with = AsArena)]
     line_header: Arena<LineHeader>,
+    #[rkyv(with = AsArena)]
     impacted_object: Arena<ImpactedObject>,
+    #[rkyv(with = AsArena)]
     object: Arena<Object>,
-    uuid_set: ArenaSet<Uuid>,
+    uuid_set: ArenaSet<DisruptionUuid>,
+    #[rkyv(with = AsArena)]
+    data: Arena<Data>,
 }
 
 impl Arenas {
-    pub fn print_summary(&self, total_bytes: usize) {
+    /// Interns a snapshot, so that identical snapshots share the same handle.
+    pub fn intern_data(&self, data: Data) -> Result<Interned<Data>, Error> {
+        self.data.try_intern(data)
+    }
+
+    pub fn data(&self, data: Interned<Data>) -> &Data {
+        self.data.lookup_ref(data)
+    }
+
+    pub fn disruption(&self, disruption: Interned<Disruption>) -> &Disruption {
+        self.disruption.lookup_ref(disruption)
+    }
+
+    /// Returns all the interned strings, in the order of their IDs.
+    pub fn strings(&self) -> impl Iterator<Item = &str> + '_ {
+        self.string.values()
+    }
+
+    /// Returns the distinct versions of the disruption with the given UUID, in the order in which
+    /// they were interned.
+    pub fn disruption_versions(&self, id: &Uuid) -> Vec<Interned<Disruption>> {
+        let Some(id) = self.uuid.find(id) else {
+            return Vec::new();
+        };
+        self.disruption
+            .iter()
+            .filter(|(_, disruption)| disruption.id == id)
+            .map(|(handle, _)| handle)
+            .collect()
+    }
+
+    /// Returns the ID, name and mode of each distinct line header, sorted by ID.
+    pub fn line_headers(&self) -> Vec<(&str, &str, &str)> {
+        let mut headers: Vec<_> = self
+            .line_header
+            .values()
+            .map(|header| {
+                (
+                    self.string.lookup(header.id),
+                    self.string.lookup(header.name),
+                    header.mode.as_str(&self.string),
+                )
+            })
+            .collect();
+        headers.sort_unstable();
+        headers
+    }
+
+    pub fn print_summary(&self, total_bytes: usize, datas: &[Interned<Data>]) {
         self.string.print_summary("", "String", total_bytes);
         self.uuid.print_summary("", "Uuid", total_bytes);
+        self.data.print_summary("", "Data", total_bytes);
         self.disruption_set
-            .print_summary("", "InternedSet<Disruption>", total_bytes);
+            .print_summary("  ", "InternedSeq<Disruption>", total_bytes);
         self.disruption
-            .print_summary("  ", "Disruption", total_bytes);
+            .print_summary("    ", "Disruption", total_bytes);
+        self.application_period_set.print_summary(
+            "      ",
+            "InternedSet<ApplicationPeriod>",
+            total_bytes,
+        );
         self.application_period
-            .print_summary("    ", "ApplicationPeriod", total_bytes);
+            .print_summary("        ", "ApplicationPeriod", total_bytes);
+        self.string_set
+            .print_summary("      ", "InternedSet<String>", total_bytes);
+        self.timestamp
+            .print_summary("      ", "LocalTimestampSeconds", total_bytes);
         self.line_set
-            .print_summary("", "InternedSet<Line>", total_bytes);
-        self.line.print_summary("  ", "Line", total_bytes);
+            .print_summary("  ", "InternedSeq<Line>", total_bytes);
+        self.line.print_summary("    ", "Line", total_bytes);
         self.line_header
-            .print_summary("    ", "LineHeader", total_bytes);
+            .print_summary("      ", "LineHeader", total_bytes);
         self.impacted_object
-            .print_summary("    ", "ImpactedObject", total_bytes);
-        self.object.print_summary("      ", "Object", total_bytes);
+            .print_summary("      ", "ImpactedObject", total_bytes);
+        self.object.print_summary("        ", "Object", total_bytes);
         self.uuid_set
-            .print_summary("      ", "InternedSet<Uuid>", total_bytes);
+            .print_summary("        ", "InternedSet<Uuid>", total_bytes);
+        self.print_deduplication(datas);
+        self.print_set_encodings();
+        self.print_set_codecs();
+        self.print_known_values();
+        self.print_string_domains();
+        self.print_string_storage();
+    }
+
+    /// Returns the size of each arena, with the same names and order as in the printed summary.
+    pub fn interner_stats(&self) -> Vec<InternerStats> {
+        vec![
+            InternerStats {
+                name: "String",
+                objects: self.string.strings(),
+                items: None,
+                bytes: self.string.get_size(),
+            },
+            arena_stats("Uuid", &self.uuid),
+            arena_stats("Data", &self.data),
+            self.disruption_set.stats("InternedSeq<Disruption>"),
+            arena_stats("Disruption", &self.disruption),
+            self.application_period_set
+                .stats("InternedSet<ApplicationPeriod>"),
+            arena_stats("ApplicationPeriod", &self.application_period),
+            self.string_set.stats("InternedSet<String>"),
+            arena_stats("LocalTimestampSeconds", &self.timestamp),
+            self.line_set.stats("InternedSeq<Line>"),
+            arena_stats("Line", &self.line),
+            arena_stats("LineHeader", &self.line_header),
+            arena_stats("ImpactedObject", &self.impacted_object),
+            arena_stats("Object", &self.object),
+            self.uuid_set.stats("InternedSet<Uuid>"),
+        ]
+    }
+
+    // The string arena stores all the bytes in a single buffer, along with the range of each
+    // string. Compare it with an interner holding each string in a separate `Rc<String>`, referenced
+    // from both its vector of values and its hash table, where each string costs two pointers, a heap
+    // allocation with the reference counts and the `String`, and a buffer.
+    fn print_string_storage(&self) {
+        let strings = self.string.strings();
+        let bytes = self.string.bytes();
+        let arena_bytes = self.string.get_size();
+        let separate_bytes = strings
+            * (2 * size_of::<Rc<String>>() + 2 * size_of::<usize>() + size_of::<String>())
+            + bytes;
+        println!(
+            "String arena: {strings} strings ({bytes} bytes) use {arena_bytes} bytes, vs. {separate_bytes} bytes with one Rc<String> per string (relative size = {:.02}%)",
+            arena_bytes as f64 * 100.0 / separate_bytes as f64,
+        );
+    }
+}
+
+fn arena_stats<T: ?Sized, Storage>(name: &'static str, arena: &Arena<T, Storage>) -> InternerStats
+where
+    Arena<T, Storage>: GetSize,
+{
+    InternerStats {
+        name,
+        objects: arena.len(),
+        items: None,
+        bytes: arena.get_size(),
+    }
+}
+
+// Mapping from the IDs of another `Arenas` to the IDs of the same values in this one.
+pub struct ArenasMapping {
+    string: Box<[InternedStr]>,
+    uuid: Box<[DisruptionUuid]>,
+    disruption_set: Box<[InternedSlice<Interned<Disruption>>]>,
+    disruption: Box<[Interned<Disruption>]>,
+    application_period_set: Box<[InternedSlice<Interned<ApplicationPeriod>>]>,
+    application_period: Box<[Interned<ApplicationPeriod>]>,
+    string_set: Box<[InternedSlice<InternedStr>]>,
+    timestamp: Box<[Interned<LocalTimestampSeconds>]>,
+    line_set: Box<[InternedSlice<Interned<Line>>]>,
+    line: Box<[Interned<Line>]>,
+    line_header: Box<[Interned<LineHeader>]>,
+    impacted_object: Box<[Interned<ImpactedObject>]>,
+    object: Box<[Interned<Object>]>,
+    uuid_set: Box<[InternedSlice<DisruptionUuid>]>,
+    data: Box<[Interned<Data>]>,
+}
+
+impl Arenas {
+    // Interns all the values of the other arenas into these ones, returning the resulting mapping
+    // of IDs. Arenas are processed so that the values they refer to are always mapped first.
+    pub fn merge(&self, other: &Arenas) -> Result<ArenasMapping, Error> {
+        let string = other
+            .string
+            .values()
+            .map(|x| self.string.try_intern(x))
+            .collect::<Result<_, _>>()?;
+        let uuid = map_arena(&other.uuid, |x| self.uuid.try_intern(x.clone()))?;
+        let application_period = map_arena(&other.application_period, |x| {
+            self.application_period.try_intern(x.clone())
+        })?;
+        let timestamp = map_arena(&other.timestamp, |x| self.timestamp.try_intern(x.clone()))?;
+
+        let mut mapping = ArenasMapping {
+            string,
+            uuid,
+            disruption_set: Box::default(),
+            disruption: Box::default(),
+            application_period_set: Box::default(),
+            application_period,
+            string_set: Box::default(),
+            timestamp,
+            line_set: Box::default(),
+            line: Box::default(),
+            line_header: Box::default(),
+            impacted_object: Box::default(),
+            object: Box::default(),
+            uuid_set: Box::default(),
+            data: Box::default(),
+        };
+
+        let application_period_set = map_arena_set(&other.application_period_set, |x| {
+            self.application_period_set
+                .try_intern(x.iter().map(|x| mapping.application_period(*x)))
+        })?;
+        mapping.application_period_set = application_period_set;
+        let string_set = map_arena_set(&other.string_set, |x| {
+            self.string_set
+                .try_intern(x.iter().map(|x| mapping.string(*x)))
+        })?;
+        mapping.string_set = string_set;
+        let object = map_arena(&other.object, |x| self.object.try_intern(x.map(&mapping)))?;
+        mapping.object = object;
+        let uuid_set = map_arena_set(&other.uuid_set, |x| {
+            self.uuid_set.try_intern(x.iter().map(|x| mapping.uuid(*x)))
+        })?;
+        mapping.uuid_set = uuid_set;
+        let impacted_object = map_arena(&other.impacted_object, |x| {
+            self.impacted_object.try_intern(x.map(&mapping))
+        })?;
+        mapping.impacted_object = impacted_object;
+        let line_header = map_arena(&other.line_header, |x| {
+            self.line_header.try_intern(x.map(&mapping))
+        })?;
+        mapping.line_header = line_header;
+        let line = map_arena(&other.line, |x| self.line.try_intern(x.map(&mapping)))?;
+        mapping.line = line;
+        let line_set = map_arena_set(&other.line_set, |x| {
+            self.line_set.try_intern(x.iter().map(|x| mapping.line(*x)))
+        })?;
+        mapping.line_set = line_set;
+        let disruption = map_arena(&other.disruption, |x| {
+            self.disruption.try_intern(x.map(&mapping))
+        })?;
+        mapping.disruption = disruption;
+        let disruption_set = map_arena_set(&other.disruption_set, |x| {
+            self.disruption_set
+                .try_intern(x.iter().map(|x| mapping.disruption(*x)))
+        })?;
+        mapping.disruption_set = disruption_set;
+        let data = map_arena(&other.data, |x| self.data.try_intern(x.map(&mapping)))?;
+        mapping.data = data;
+
+        Ok(mapping)
+    }
+}
+
+impl ArenasMapping {
+    fn string(&self, x: InternedStr) -> InternedStr {
+        self.string[x.id() as usize]
+    }
+
+    fn uuid(&self, x: DisruptionUuid) -> DisruptionUuid {
+        self.uuid[x.id() as usize]
+    }
+
+    fn disruption_set(
+        &self,
+        x: InternedSlice<Interned<Disruption>>,
+    ) -> InternedSlice<Interned<Disruption>> {
+        self.disruption_set[x.id() as usize]
+    }
+
+    fn disruption(&self, x: Interned<Disruption>) -> Interned<Disruption> {
+        self.disruption[x.id() as usize]
+    }
+
+    fn application_period_set(
+        &self,
+        x: InternedSlice<Interned<ApplicationPeriod>>,
+    ) -> InternedSlice<Interned<ApplicationPeriod>> {
+        self.application_period_set[x.id() as usize]
+    }
+
+    fn application_period(&self, x: Interned<ApplicationPeriod>) -> Interned<ApplicationPeriod> {
+        self.application_period[x.id() as usize]
+    }
+
+    fn string_set(&self, x: InternedSlice<InternedStr>) -> InternedSlice<InternedStr> {
+        self.string_set[x.id() as usize]
+    }
+
+    fn timestamp(&self, x: Interned<LocalTimestampSeconds>) -> Interned<LocalTimestampSeconds> {
+        self.timestamp[x.id() as usize]
+    }
+
+    fn line_set(&self, x: InternedSlice<Interned<Line>>) -> InternedSlice<Interned<Line>> {
+        self.line_set[x.id() as usize]
+    }
+
+    fn line(&self, x: Interned<Line>) -> Interned<Line> {
+        self.line[x.id() as usize]
+    }
+
+    fn line_header(&self, x: Interned<LineHeader>) -> Interned<LineHeader> {
+        self.line_header[x.id() as usize]
+    }
+
+    fn impacted_object(&self, x: Interned<ImpactedObject>) -> Interned<ImpactedObject> {
+        self.impacted_object[x.id() as usize]
+    }
+
+    fn object(&self, x: Interned<Object>) -> Interned<Object> {
+        self.object[x.id() as usize]
+    }
+
+    fn uuid_set(&self, x: InternedSlice<DisruptionUuid>) -> InternedSlice<DisruptionUuid> {
+        self.uuid_set[x.id() as usize]
+    }
+
+    pub fn data(&self, x: Interned<Data>) -> Interned<Data> {
+        self.data[x.id() as usize]
+    }
+}
+
+fn map_arena<T, Storage, U>(
+    arena: &Arena<T, Storage>,
+    f: impl FnMut(&T) -> Result<U, Error>,
+) -> Result<Box<[U]>, Error>
+where
+    T: Eq + Hash,
+    Storage: Borrow<T>,
+{
+    arena.values().map(f).collect()
+}
+
+fn map_arena_set<H: Handle, U, const SORTED: bool>(
+    arena: &ArenaSet<H, SORTED>,
+    f: impl FnMut(&[H]) -> Result<U, Error>,
+) -> Result<Box<[U]>, Error> {
+    arena.0.values().map(f).collect()
+}
+
+// Conversion of a value of the source schema, interning its contents in the arenas. This is
+// derived with `#[derive(FromSource)]` for most types, along with the `EqWith` implementation that
+// checks the conversion.
+pub trait FromSource<S>: Sized {
+    fn from_source(arenas: &Arenas, source: &S) -> Result<Self, Error>;
+}
+
+impl FromSource<Uuid> for Uuid {
+    fn from_source(_arenas: &Arenas, source: &Uuid) -> Result<Self, Error> {
+        Ok(source.clone())
+    }
+}
+
+impl EqWith<Uuid, Arenas> for Uuid {
+    fn eq_with(&self, other: &Uuid, _arenas: &Arenas) -> bool {
+        self == other
     }
 }
 
+// Room kept in each arena for the values that other threads intern concurrently, as checking the
+// room left and interning a value aren't a single atomic step.
+const CONCURRENT_ROOM: usize = 1 << 24;
+
+// Checks that `added` more values (or items, or bytes) fit in an arena holding `len` of them,
+// without overflowing its 32-bit IDs and offsets.
+fn check_room(
+    arena: &'static str,
+    len: usize,
+    unit: &'static str,
+    added: usize,
+) -> Result<(), Error> {
+    let needed = len.saturating_add(added).saturating_add(CONCURRENT_ROOM);
+    if needed > u32::MAX as usize {
+        return Err(Error::ArenaFull { arena, len, unit });
+    }
+    Ok(())
+}
+
+// Interning that reports a full arena as an error, rather than panicking once its IDs overflow.
+// Values that are already interned are still found in a full arena.
+trait TryIntern<'a> {
+    type Value;
+    type Handle;
+
+    fn try_intern(&self, value: Self::Value) -> Result<Self::Handle, Error>;
+}
+
+impl<'a> TryIntern<'a> for ArenaStr {
+    type Value = &'a str;
+    type Handle = InternedStr;
+
+    fn try_intern(&self, value: &'a str) -> Result<InternedStr, Error> {
+        let arena = InternedStr::ARENA;
+        let room = check_room(arena, self.strings(), "strings", 1)
+            .and_then(|()| check_room(arena, self.bytes(), "bytes", value.len()));
+        match room {
+            Ok(()) => Ok(self.intern(value)),
+            Err(err) => self.find(value).ok_or(err),
+        }
+    }
+}
+
+impl<T, Storage> TryIntern<'_> for Arena<T, Storage>
+where
+    T: Eq + Hash,
+    Storage: Borrow<T> + From<T>,
+    Interned<T, Storage>: Id,
+{
+    type Value = T;
+    type Handle = Interned<T, Storage>;
+
+    fn try_intern(&self, value: T) -> Result<Interned<T, Storage>, Error> {
+        match check_room(<Interned<T, Storage>>::ARENA, self.len(), "values", 1) {
+            Ok(()) => Ok(self.intern(value)),
+            Err(err) => self.find(&value).ok_or(err),
+        }
+    }
+}
+
+fn intern_from<T, Storage, S>(
+    arena: &Arena<T, Storage>,
+    arenas: &Arenas,
+    source: &S,
+) -> Result<Interned<T, Storage>, Error>
+where
+    T: FromSource<S> + Eq + Hash,
+    Storage: Borrow<T> + From<T>,
+    Interned<T, Storage>: Id,
+{
+    arena.try_intern(T::from_source(arenas, source)?)
+}
+
+// Converts the source value and looks it up in the arena, without interning it.
+fn find_from<T, Storage, S>(
+    arena: &Arena<T, Storage>,
+    arenas: &Arenas,
+    source: &S,
+) -> Option<Interned<T, Storage>>
+where
+    T: FromSource<S> + Eq + Hash,
+    Storage: Borrow<T>,
+{
+    arena.find(&T::from_source(arenas, source).ok()?)
+}
+
 fn option_eq_by<T, U>(lhs: &Option<T>, rhs: &Option<U>, pred: impl Fn(&T, &U) -> bool) -> bool {
     match (lhs, rhs) {
         (None, None) => true,
@@ -59,62 +550,123 @@ fn option_eq_by<T, U>(lhs: &Option<T>, rhs: &Option<U>, pred: impl Fn(&T, &U) ->
     }
 }
 
-fn set_eq_by<T, U>(lhs: &[T], rhs: &[U], pred: impl Fn(&T, &U) -> bool) -> bool {
-    let len = lhs.len();
-    if len != rhs.len() {
-        return false;
+// Compares a set of handles with source items, by looking up the handle of each source item with
+// `key` and comparing both sides as sorted multisets. A source item without handle isn't interned,
+// so it can't be in the set.
+fn set_eq_by_key<H: Copy + Ord, U>(lhs: &[H], rhs: &[U], key: impl Fn(&U) -> Option<H>) -> bool {
+    match (lhs, rhs) {
+        _ if lhs.len() != rhs.len() => return false,
+        ([x], [y]) => return key(y) == Some(*x),
+        _ => (),
     }
-
-    let mut used = vec![false; len];
-    'outer: for x in lhs.iter() {
-        for (i, y) in rhs.iter().enumerate() {
-            if used[i] {
-                continue;
-            }
-            if pred(x, y) {
-                used[i] = true;
-                continue 'outer;
-            }
-        }
+    let Some(mut rhs) = rhs.iter().map(key).collect::<Option<Vec<_>>>() else {
         return false;
+    };
+    rhs.sort_unstable();
+
+    if lhs.is_sorted() {
+        lhs == rhs
+    } else {
+        let mut lhs = lhs.to_vec();
+        lhs.sort_unstable();
+        lhs == rhs
     }
-
-    true
 }
 
-#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, GetSize)]
-struct ArenaSet<T: ?Sized, Storage = T>(ArenaSlice<Interned<T, Storage>>);
+fn seq_eq_by<T, U>(lhs: &[T], rhs: &[U], pred: impl Fn(&T, &U) -> bool) -> bool {
+    lhs.len() == rhs.len() && lhs.iter().zip(rhs).all(|(x, y)| pred(x, y))
+}
 
-impl<T: ?Sized, Storage> Default for ArenaSet<T, Storage> {
+// Arena of sets of handles, sorted when interned so that sets with the same items in a different
+// order are interned once. Arenas of sequences keep the order of the items instead.
+#[derive(
+    Debug,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    GetSize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+struct ArenaSet<H: Handle, const SORTED: bool = true>(#[rkyv(with = AsArena)] ArenaSlice<H>);
+
+type ArenaSeq<H> = ArenaSet<H, false>;
+
+impl<H: Handle, const SORTED: bool> Default for ArenaSet<H, SORTED> {
     fn default() -> Self {
         Self(ArenaSlice::default())
     }
 }
 
-impl<T: ?Sized, Storage> ArenaSet<T, Storage> {
-    fn print_summary(&self, prefix: &str, title: &str, total_bytes: usize) {
+impl<H: Handle, const SORTED: bool> ArenaSet<H, SORTED> {
+    fn print_summary(&self, prefix: &str, title: &str, total_bytes: usize)
+    where
+        H: GetSize,
+    {
         self.0.print_summary(prefix, title, total_bytes);
     }
 
-    fn lookup(&self, interned: InternedSlice<Interned<T, Storage>>) -> SortedSet<'_, T, Storage> {
-        SortedSet(self.0.lookup(interned))
+    fn stats(&self, name: &'static str) -> InternerStats
+    where
+        Self: GetSize,
+    {
+        InternerStats {
+            name,
+            objects: self.0.slices(),
+            items: Some(self.0.items()),
+            bytes: self.get_size(),
+        }
     }
 
-    fn intern(
-        &self,
-        set: impl IntoIterator<Item = Interned<T, Storage>>,
-    ) -> InternedSlice<Interned<T, Storage>> {
-        let mut set: Box<[_]> = set.into_iter().collect();
-        set.sort_unstable();
-        self.0.intern_copy(&set)
+    fn lookup(&self, interned: InternedSlice<H>) -> Items<'_, H> {
+        Items(self.0.lookup(interned))
+    }
+
+    fn intern(&self, items: impl IntoIterator<Item = H>) -> InternedSlice<H>
+    where
+        H: Ord,
+    {
+        let mut items: Box<[_]> = items.into_iter().collect();
+        if SORTED {
+            items.sort_unstable();
+        }
+        self.0.intern_copy(&items)
+    }
+
+    fn try_intern(&self, items: impl IntoIterator<Item = H>) -> Result<InternedSlice<H>, Error>
+    where
+        H: Ord,
+        InternedSlice<H>: Id,
+    {
+        let mut items: Box<[_]> = items.into_iter().collect();
+        if SORTED {
+            items.sort_unstable();
+        }
+        let arena = <InternedSlice<H>>::ARENA;
+        let room = check_room(arena, self.0.slices(), "sets", 1)
+            .and_then(|()| check_room(arena, self.0.items(), "items", items.len()));
+        match room {
+            Ok(()) => Ok(self.0.intern_copy(&items)),
+            Err(err) => self.0.find(&items).ok_or(err),
+        }
     }
 }
 
-struct SortedSet<'a, T: ?Sized, Storage = T>(&'a [Interned<T, Storage>]);
+// Items of an interned set or sequence.
+struct Items<'a, H>(&'a [H]);
+
+impl<H> Items<'_, H> {
+    fn set_eq_by_key<U>(&self, rhs: &[U], key: impl Fn(&U) -> Option<H>) -> bool
+    where
+        H: Copy + Ord,
+    {
+        set_eq_by_key(self.0, rhs, key)
+    }
 
-impl<'a, T: ?Sized, Storage> SortedSet<'a, T, Storage> {
-    fn set_eq_by<U>(&self, rhs: &[U], pred: impl Fn(&Interned<T, Storage>, &U) -> bool) -> bool {
-        set_eq_by(self.0, rhs, pred)
+    fn seq_eq_by<U>(&self, rhs: &[U], pred: impl Fn(&H, &U) -> bool) -> bool {
+        seq_eq_by(self.0, rhs, pred)
     }
 }
 
@@ -130,14 +682,25 @@ impl<T: ?Sized, Storage> GetSize for InternedSet<T, Storage> {
 }
 
 impl<T: ?Sized, Storage> InternedSet<T, Storage> {
+    // Sorts the handles and drops duplicates, so that the IDs are strictly increasing as the serde
+    // encoding requires.
     fn new(set: impl IntoIterator<Item = Interned<T, Storage>>) -> Self {
-        let mut set: Box<[_]> = set.into_iter().collect();
+        let mut set: Vec<_> = set.into_iter().collect();
         set.sort_unstable();
-        Self { set }
+        set.dedup();
+        Self {
+            set: set.into_boxed_slice(),
+        }
     }
 
-    fn set_eq_by<U>(&self, rhs: &[U], pred: impl Fn(&Interned<T, Storage>, &U) -> bool) -> bool {
-        set_eq_by(&self.set, rhs, pred)
+    fn iter(&self) -> impl Iterator<Item = Interned<T, Storage>> + Clone + '_ {
+        self.set.iter().copied()
+    }
+}
+
+impl<T: ?Sized, Storage> FromIterator<Interned<T, Storage>> for InternedSet<T, Storage> {
+    fn from_iter<I: IntoIterator<Item = Interned<T, Storage>>>(iter: I) -> Self {
+        Self::new(iter)
     }
 }
 
@@ -146,30 +709,59 @@ impl<T: ?Sized, Storage> Serialize for InternedSet<T, Storage> {
     where
         S: Serializer,
     {
-        let mut rle_encoded = Vec::with_capacity(self.set.len());
-        let mut prev: Option<u32> = None;
-        let mut streak: i32 = 0;
+        serializer.collect_seq(rle_encode_set(self.set.iter().map(|x| x.id())))
+    }
+}
 
-        for x in &self.set {
-            let id = x.id();
-            let diff = id - prev.unwrap_or(0);
-            if prev.is_some() && diff == 1 {
-                streak += 1;
-            } else {
-                if streak != 0 {
-                    rle_encoded.push(-streak);
-                    streak = 0;
-                }
-                rle_encoded.push(diff as i32);
+// Encodes strictly increasing IDs as the differences between consecutive IDs, where negative
+// numbers encode streaks of consecutive IDs.
+fn rle_encode_set(ids: impl ExactSizeIterator<Item = u32>) -> Vec<i32> {
+    let mut rle_encoded = Vec::with_capacity(ids.len());
+    let mut prev: Option<u32> = None;
+    let mut streak: i32 = 0;
+
+    for id in ids {
+        let diff = id - prev.unwrap_or(0);
+        if prev.is_some() && diff == 1 {
+            streak += 1;
+        } else {
+            if streak != 0 {
+                rle_encoded.push(-streak);
+                streak = 0;
             }
-            prev = Some(id);
-        }
-        if streak != 0 {
-            rle_encoded.push(-streak);
+            rle_encoded.push(diff as i32);
         }
+        prev = Some(id);
+    }
+    if streak != 0 {
+        rle_encoded.push(-streak);
+    }
+    rle_encoded
+}
+
+// Capacity to reserve for the elements of a deserialized sequence. The size hint comes from the
+// input, so it's capped to avoid reserving gigabytes for a few bytes of a corrupted file.
+fn cautious_capacity<T>(size_hint: Option<usize>) -> usize {
+    const MAX_PREALLOC_BYTES: usize = 1 << 20;
+    size_hint
+        .unwrap_or(0)
+        .min(MAX_PREALLOC_BYTES / size_of::<T>().max(1))
+}
 
-        serializer.collect_seq(rle_encoded)
+// Maximum number of IDs decoded from a set or sequence. A streak of a few bytes can claim up to 2^31
+// consecutive IDs, whereas the sets and sequences of the arenas hold the items of a single snapshot,
+// i.e. thousands of IDs at most. This bounds the memory that a corrupted file can make us allocate
+// to 64 MiB per set.
+const MAX_DECODED_IDS: usize = 1 << 24;
+
+// Checks that a streak of the given length can be appended to the IDs decoded so far.
+fn check_streak<E: serde::de::Error>(decoded: usize, streak: u32) -> Result<(), E> {
+    if decoded.saturating_add(streak as usize) > MAX_DECODED_IDS {
+        return Err(E::custom(format_args!(
+            "streak of {streak} consecutive IDs after {decoded} IDs exceeds the maximum of {MAX_DECODED_IDS} IDs"
+        )));
     }
+    Ok(())
 }
 
 impl<'de, T: ?Sized, Storage> Deserialize<'de> for InternedSet<T, Storage> {
@@ -204,21 +796,39 @@ impl<'de, T: ?Sized, Storage> Visitor<'de> for InternedSetVisitor<T, Storage> {
     where
         A: SeqAccess<'de>,
     {
-        let mut set = match seq.size_hint() {
-            None => Vec::new(),
-            Some(size_hint) => Vec::with_capacity(size_hint),
-        };
+        let mut set = Vec::with_capacity(cautious_capacity::<u32>(seq.size_hint()));
 
-        let mut prev = 0;
+        // IDs are strictly increasing, so each difference after the first ID must be positive and
+        // can't overflow.
+        let mut prev: Option<u32> = None;
         while let Some(x) = seq.next_element::<i32>()? {
             if x < 0 {
-                for _ in 0..-x {
-                    prev += 1;
-                    set.push(Interned::from_id(prev));
-                }
+                let last = prev.ok_or_else(|| {
+                    serde::de::Error::custom("streak of consecutive IDs before the first ID")
+                })?;
+                check_streak(set.len(), x.unsigned_abs())?;
+                let end = last.checked_add(x.unsigned_abs()).ok_or_else(|| {
+                    serde::de::Error::custom(format_args!(
+                        "streak of {} consecutive IDs after ID {last} overflows",
+                        x.unsigned_abs(),
+                    ))
+                })?;
+                set.extend((last + 1..=end).map(Interned::from_id));
+                prev = Some(end);
             } else {
-                prev += x as u32;
-                set.push(Interned::from_id(prev));
+                if let (0, Some(last)) = (x, prev) {
+                    return Err(serde::de::Error::custom(format_args!(
+                        "difference of 0 after ID {last} duplicates it"
+                    )));
+                }
+                let last = prev.unwrap_or(0);
+                let id = last.checked_add(x as u32).ok_or_else(|| {
+                    serde::de::Error::custom(format_args!(
+                        "difference of {x} after ID {last} overflows"
+                    ))
+                })?;
+                set.push(Interned::from_id(id));
+                prev = Some(id);
             }
         }
 
@@ -228,73 +838,133 @@ impl<'de, T: ?Sized, Storage> Visitor<'de> for InternedSetVisitor<T, Storage> {
     }
 }
 
+// Sets are archived as the plain vector of their IDs, as the delta encoding used with serde would
+// prevent reading them in place.
+impl<T: ?Sized, Storage> rkyv::Archive for InternedSet<T, Storage> {
+    type Archived = ArchivedVec<Archived<u32>>;
+    type Resolver = VecResolver;
+
+    fn resolve(&self, resolver: VecResolver, out: Place<Self::Archived>) {
+        ArchivedVec::resolve_from_len(self.set.len(), resolver, out);
+    }
+}
+
+impl<T: ?Sized, Storage, S> rkyv::Serialize<S> for InternedSet<T, Storage>
+where
+    S: Fallible + Allocator + Writer + ?Sized,
+{
+    fn serialize(&self, serializer: &mut S) -> Result<VecResolver, S::Error> {
+        ArchivedVec::<Archived<u32>>::serialize_from_iter::<u32, _, _>(
+            self.set.iter().map(|x| x.id()),
+            serializer,
+        )
+    }
+}
+
+impl<T: ?Sized, Storage, D: Fallible + ?Sized> rkyv::Deserialize<InternedSet<T, Storage>, D>
+    for ArchivedVec<Archived<u32>>
+{
+    fn deserialize(&self, _deserializer: &mut D) -> Result<InternedSet<T, Storage>, D::Error> {
+        Ok(InternedSet {
+            set: self
+                .iter()
+                .map(|id| Interned::from_id(id.to_native()))
+                .collect(),
+        })
+    }
+}
+
+/// Sequence of interned values, which keeps the order in which they were collected.
 #[derive(Debug, Hash, PartialEq, Eq)]
-pub struct InternedStrSet {
-    set: Box<[InternedStr]>,
+pub struct InternedSeq<T: ?Sized, Storage = T> {
+    seq: Box<[Interned<T, Storage>]>,
 }
 
-impl GetSize for InternedStrSet {
+impl<T: ?Sized, Storage> GetSize for InternedSeq<T, Storage> {
     fn get_heap_size_with_tracker<Tr: GetSizeTracker>(&self, tracker: Tr) -> (usize, Tr) {
-        self.set.get_heap_size_with_tracker(tracker)
+        self.seq.get_heap_size_with_tracker(tracker)
     }
 }
 
-impl InternedStrSet {
-    fn new(set: impl IntoIterator<Item = InternedStr>) -> Self {
-        let mut set: Box<[_]> = set.into_iter().collect();
-        set.sort_unstable();
-        Self { set }
+impl<T: ?Sized, Storage> InternedSeq<T, Storage> {
+    fn seq_eq_by<U>(&self, rhs: &[U], pred: impl Fn(&Interned<T, Storage>, &U) -> bool) -> bool {
+        seq_eq_by(&self.seq, rhs, pred)
+    }
+
+    fn map(&self, f: impl Fn(Interned<T, Storage>) -> Interned<T, Storage>) -> Self {
+        self.iter().map(f).collect()
     }
 
-    fn set_eq_by<U>(&self, rhs: &[U], pred: impl Fn(&InternedStr, &U) -> bool) -> bool {
-        set_eq_by(&self.set, rhs, pred)
+    fn iter(&self) -> impl Iterator<Item = Interned<T, Storage>> + Clone + '_ {
+        self.seq.iter().copied()
     }
 }
 
-impl Serialize for InternedStrSet {
+impl<T: ?Sized, Storage> FromIterator<Interned<T, Storage>> for InternedSeq<T, Storage> {
+    fn from_iter<I: IntoIterator<Item = Interned<T, Storage>>>(iter: I) -> Self {
+        Self {
+            seq: iter.into_iter().collect(),
+        }
+    }
+}
+
+// Sequences are serialized like sets, as the differences between consecutive IDs where negative
+// numbers encode streaks of consecutive IDs. As the IDs aren't sorted, the other differences can be
+// zero or negative: these are escaped with a zero followed by the difference. Differences wrap
+// around, so that any ID can follow any other.
+impl<T: ?Sized, Storage> Serialize for InternedSeq<T, Storage> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        let mut rle_encoded = Vec::with_capacity(self.set.len());
-        let mut prev: Option<u32> = None;
-        let mut streak: i32 = 0;
+        serializer.collect_seq(rle_encode_seq(self.seq.iter().map(|x| x.id())))
+    }
+}
 
-        for x in &self.set {
-            let id = x.id();
-            let diff = id - prev.unwrap_or(0);
-            if prev.is_some() && diff == 1 {
-                streak += 1;
-            } else {
-                if streak != 0 {
-                    rle_encoded.push(-streak);
-                    streak = 0;
-                }
-                rle_encoded.push(diff as i32);
+fn rle_encode_seq(ids: impl ExactSizeIterator<Item = u32>) -> Vec<i32> {
+    let mut encoded = Vec::with_capacity(ids.len());
+    let mut prev: Option<u32> = None;
+    let mut streak: i32 = 0;
+
+    for id in ids {
+        let diff = id.wrapping_sub(prev.unwrap_or(0)) as i32;
+        if prev.is_some() && diff == 1 {
+            streak += 1;
+        } else {
+            if streak != 0 {
+                encoded.push(-streak);
+                streak = 0;
             }
-            prev = Some(id);
-        }
-        if streak != 0 {
-            rle_encoded.push(-streak);
+            if diff <= 0 {
+                encoded.push(0);
+            }
+            encoded.push(diff);
         }
-
-        serializer.collect_seq(rle_encoded)
+        prev = Some(id);
     }
+    if streak != 0 {
+        encoded.push(-streak);
+    }
+    encoded
 }
 
-impl<'de> Deserialize<'de> for InternedStrSet {
+impl<'de, T: ?Sized, Storage> Deserialize<'de> for InternedSeq<T, Storage> {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: Deserializer<'de>,
     {
-        deserializer.deserialize_seq(InternedStrSetVisitor)
+        deserializer.deserialize_seq(InternedSeqVisitor {
+            _phantom: PhantomData,
+        })
     }
 }
 
-struct InternedStrSetVisitor;
+struct InternedSeqVisitor<T: ?Sized, Storage> {
+    _phantom: PhantomData<fn() -> InternedSeq<T, Storage>>,
+}
 
-impl<'de> Visitor<'de> for InternedStrSetVisitor {
-    type Value = InternedStrSet;
+impl<'de, T: ?Sized, Storage> Visitor<'de> for InternedSeqVisitor<T, Storage> {
+    type Value = InternedSeq<T, Storage>;
 
     fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
         formatter.write_str("a sequence of values")
@@ -304,86 +974,305 @@ impl<'de> Visitor<'de> for InternedStrSetVisitor {
     where
         A: SeqAccess<'de>,
     {
-        let mut set = match seq.size_hint() {
-            None => Vec::new(),
-            Some(size_hint) => Vec::with_capacity(size_hint),
-        };
+        let mut ids = Vec::with_capacity(cautious_capacity::<u32>(seq.size_hint()));
 
-        let mut prev = 0;
+        // Differences wrap around, so only the structure of the encoding needs to be checked.
+        let mut prev: Option<u32> = None;
         while let Some(x) = seq.next_element::<i32>()? {
             if x < 0 {
-                for _ in 0..-x {
-                    prev += 1;
-                    set.push(InternedStr::from_id(prev));
+                let mut last = prev.ok_or_else(|| {
+                    serde::de::Error::custom("streak of consecutive IDs before the first ID")
+                })?;
+                check_streak(ids.len(), x.unsigned_abs())?;
+                for _ in 0..x.unsigned_abs() {
+                    last = last.wrapping_add(1);
+                    ids.push(Interned::from_id(last));
                 }
+                prev = Some(last);
             } else {
-                prev += x as u32;
-                set.push(InternedStr::from_id(prev));
+                let diff = if x == 0 {
+                    let diff = seq.next_element::<i32>()?.ok_or_else(|| {
+                        serde::de::Error::custom("escaped difference missing at the end")
+                    })?;
+                    if diff > 0 {
+                        return Err(serde::de::Error::custom(format_args!(
+                            "escaped difference {diff} is positive"
+                        )));
+                    }
+                    diff
+                } else {
+                    x
+                };
+                let id = prev.unwrap_or(0).wrapping_add(diff as u32);
+                ids.push(Interned::from_id(id));
+                prev = Some(id);
             }
         }
 
+        Ok(InternedSeq {
+            seq: ids.into_boxed_slice(),
+        })
+    }
+}
+
+// Sequences are archived like sets, as the plain vector of their IDs.
+impl<T: ?Sized, Storage> rkyv::Archive for InternedSeq<T, Storage> {
+    type Archived = ArchivedVec<Archived<u32>>;
+    type Resolver = VecResolver;
+
+    fn resolve(&self, resolver: VecResolver, out: Place<Self::Archived>) {
+        ArchivedVec::resolve_from_len(self.seq.len(), resolver, out);
+    }
+}
+
+impl<T: ?Sized, Storage, S> rkyv::Serialize<S> for InternedSeq<T, Storage>
+where
+    S: Fallible + Allocator + Writer + ?Sized,
+{
+    fn serialize(&self, serializer: &mut S) -> Result<VecResolver, S::Error> {
+        ArchivedVec::<Archived<u32>>::serialize_from_iter::<u32, _, _>(
+            self.seq.iter().map(|x| x.id()),
+            serializer,
+        )
+    }
+}
+
+impl<T: ?Sized, Storage, D: Fallible + ?Sized> rkyv::Deserialize<InternedSeq<T, Storage>, D>
+    for ArchivedVec<Archived<u32>>
+{
+    fn deserialize(&self, _deserializer: &mut D) -> Result<InternedSeq<T, Storage>, D::Error> {
+        Ok(self
+            .iter()
+            .map(|id| Interned::from_id(id.to_native()))
+            .collect())
+    }
+}
+
+// Set of strings stored inline in the disruptions of older layouts, which now intern these sets in
+// the `string_set` arena. It's only deserialized, to upgrade these layouts.
+#[derive(Debug, Hash, PartialEq, Eq)]
+pub struct InternedStrSet {
+    set: Box<[InternedStr]>,
+}
+
+impl<'de> Deserialize<'de> for InternedStrSet {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // The encoding is the same as for other interned sets, whose decoding is validated.
+        let set = InternedSet::<String>::deserialize(deserializer)?;
         Ok(InternedStrSet {
-            set: set.into_boxed_slice(),
+            set: set.iter().map(|x| InternedStr::from_id(x.id())).collect(),
         })
     }
 }
 
-#[derive(Debug, Hash, PartialEq, Eq, Serialize, Deserialize, GetSize)]
-pub struct TimestampSecondsParis(i64);
+#[derive(
+    Debug,
+    Clone,
+    Hash,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    GetSize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct LocalTimestampSeconds(i64);
+
+// Format used to display local datetimes in query results.
+const DISPLAY_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Timezone of the local datetimes when none is configured, i.e. that of the Paris network.
+pub const DEFAULT_TIMEZONE: Tz = Tz::Europe__Paris;
+
+/// Interpretation of the local datetimes in the input files.
+#[derive(Debug, Clone, Copy)]
+pub struct LocalDatetimes {
+    pub timezone: Tz,
+    /// Instant chosen for the local datetimes that occur twice, when clocks are turned back.
+    pub ambiguous: AmbiguousDatetimes,
+}
+
+impl Default for LocalDatetimes {
+    fn default() -> Self {
+        Self {
+            timezone: DEFAULT_TIMEZONE,
+            ambiguous: AmbiguousDatetimes::Earliest,
+        }
+    }
+}
+
+// Interpretation of the local datetimes in the input files. It's a process-wide setting rather than
+// a parameter of each conversion, as local datetimes are parsed and formatted deep inside the
+// derived conversions, the deserialization seeds and the archived accessors.
+static LOCAL_DATETIMES: OnceLock<LocalDatetimes> = OnceLock::new();
+
+/// Sets the interpretation of the local datetimes, before any of them is parsed or formatted.
+pub fn configure_local_datetimes(config: LocalDatetimes) {
+    LOCAL_DATETIMES
+        .set(config)
+        .expect("Local datetimes must be configured before converting any of them");
+}
+
+fn local_datetimes() -> LocalDatetimes {
+    *LOCAL_DATETIMES.get_or_init(LocalDatetimes::default)
+}
+
+/// Returns the timezone of the local datetimes.
+pub fn timezone() -> Tz {
+    local_datetimes().timezone
+}
 
-impl TimestampSecondsParis {
-    fn from_formatted(x: &str, format: &str) -> Self {
-        let naive_datetime = NaiveDateTime::parse_from_str(x, format).unwrap_or_else(|_| {
-            panic!("Failed to parse datetime (custom format {format:?}) from {x}")
-        });
-        let datetime = match naive_datetime.and_local_timezone(Paris) {
+impl LocalTimestampSeconds {
+    fn from_formatted(x: &str, format: &'static str) -> Result<Self, Error> {
+        Self::from_formatted_in(x, format, local_datetimes())
+    }
+
+    // Both instants of an ambiguous local datetime are formatted back to the same local datetime,
+    // so the policy only affects the stored instant, not the regenerated snapshots.
+    fn from_formatted_in(
+        x: &str,
+        format: &'static str,
+        config: LocalDatetimes,
+    ) -> Result<Self, Error> {
+        let naive_datetime =
+            NaiveDateTime::parse_from_str(x, format).map_err(|_| Error::ParseDatetime {
+                value: x.to_owned(),
+                format,
+            })?;
+        let LocalDatetimes {
+            timezone,
+            ambiguous,
+        } = config;
+        let datetime = match naive_datetime.and_local_timezone(timezone) {
             LocalResult::Single(x) => x,
             LocalResult::Ambiguous(earliest, latest) => {
-                eprintln!("Ambiguous mapping of {naive_datetime:?} to the Paris timezone: {earliest:?} or {latest:?}");
-                earliest
+                tracing::debug!(
+                    ?naive_datetime,
+                    ?earliest,
+                    ?latest,
+                    %timezone,
+                    ?ambiguous,
+                    "Ambiguous mapping to the local timezone"
+                );
+                match ambiguous {
+                    AmbiguousDatetimes::Earliest => earliest,
+                    AmbiguousDatetimes::Latest => latest,
+                    AmbiguousDatetimes::Reject => {
+                        return Err(Error::AmbiguousLocalDatetime {
+                            value: x.to_owned(),
+                            timezone,
+                        })
+                    }
+                }
             }
             LocalResult::None => {
-                panic!("Invalid mapping of {naive_datetime:?} to the Paris timezone")
+                return Err(Error::NonexistentLocalDatetime {
+                    value: x.to_owned(),
+                    timezone,
+                })
             }
         };
-        TimestampSecondsParis(datetime.timestamp())
+        Ok(LocalTimestampSeconds(datetime.timestamp()))
+    }
+
+    // Local datetime of this timestamp, if both the instant and its local time are within the
+    // range of datetimes.
+    fn to_local(&self) -> Option<NaiveDateTime> {
+        let datetime = DateTime::from_timestamp(self.0, 0)?.with_timezone(&timezone());
+        datetime
+            .naive_utc()
+            .checked_add_offset(datetime.offset().fix())
     }
 
+    // Databases are rejected on load if any of their timestamps is out of range. Archives aren't
+    // validated, so their accessors check the range of each timestamp instead.
     fn to_formatted(&self, format: &str) -> String {
-        DateTime::from_timestamp(self.0, 0)
-            .unwrap()
-            .with_timezone(&Paris)
-            .naive_local()
+        self.to_local()
+            .expect("timestamps are in the range of datetimes")
             .format(format)
             .to_string()
     }
 }
 
-#[derive(Debug, Hash, PartialEq, Eq, Serialize, Deserialize, GetSize)]
+impl FromSource<source::Str<'_>> for LocalTimestampSeconds {
+    fn from_source(_arenas: &Arenas, source: &source::Str<'_>) -> Result<Self, Error> {
+        Self::from_formatted(source, "%Y%m%dT%H%M%S")
+    }
+}
+
+impl EqWith<source::Str<'_>, Arenas> for LocalTimestampSeconds {
+    fn eq_with(&self, other: &source::Str<'_>, _arenas: &Arenas) -> bool {
+        self.to_formatted("%Y%m%dT%H%M%S") == **other
+    }
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Hash,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    GetSize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
 pub struct TimestampMillis(i64);
 
 impl TimestampMillis {
-    fn from_rfc3339(x: &str) -> Self {
-        let datetime = DateTime::parse_from_rfc3339(x)
-            .unwrap_or_else(|_| panic!("Failed to parse datetime (RFC 3339 format) from {x}"));
-        TimestampMillis(datetime.timestamp_millis())
+    fn from_rfc3339(x: &str) -> Result<Self, Error> {
+        let datetime = DateTime::parse_from_rfc3339(x).map_err(|_| Error::ParseDatetime {
+            value: x.to_owned(),
+            format: "RFC 3339",
+        })?;
+        Ok(TimestampMillis(datetime.timestamp_millis()))
     }
 
-    fn to_rfc3339(&self) -> String {
+    fn to_datetime(&self) -> Option<DateTime<Utc>> {
         DateTime::from_timestamp_millis(self.0)
-            .unwrap()
+    }
+
+    // Databases are rejected on load if any of their timestamps is out of range, unlike archives
+    // queried in place (see `ArchiveError`).
+    fn to_rfc3339(&self) -> String {
+        self.to_datetime()
+            .expect("timestamps are in the range of datetimes")
             .to_rfc3339_opts(SecondsFormat::Millis, true)
     }
 }
 
-#[derive(Debug, Hash, PartialEq, Eq, Serialize, Deserialize, GetSize)]
+impl FromSource<source::Str<'_>> for TimestampMillis {
+    fn from_source(_arenas: &Arenas, source: &source::Str<'_>) -> Result<Self, Error> {
+        Self::from_rfc3339(source)
+    }
+}
+
+#[derive(
+    Debug,
+    Hash,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    GetSize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
 pub enum Data {
     Success(DataSuccess),
     Error(DataError),
 }
 
-impl EqWith<source::Data, Arenas> for Data {
-    fn eq_with(&self, other: &source::Data, arenas: &Arenas) -> bool {
+impl EqWith<source::Data<'_>, Arenas> for Data {
+    fn eq_with(&self, other: &source::Data<'_>, arenas: &Arenas) -> bool {
         match self {
             Data::Success(data) => data.eq_with(other, arenas),
             Data::Error(data) => data.eq_with(other, arenas),
@@ -391,9 +1280,9 @@ impl EqWith<source::Data, Arenas> for Data {
     }
 }
 
-impl Data {
-    pub fn from(arenas: &Arenas, source: source::Data) -> Self {
-        match source {
+impl FromSource<source::Data<'_>> for Data {
+    fn from_source(arenas: &Arenas, source: &source::Data<'_>) -> Result<Self, Error> {
+        let data = match source {
             source::Data {
                 disruptions: Some(disruptions),
                 lines: Some(lines),
@@ -402,18 +1291,18 @@ impl Data {
                 error: None,
                 message: None,
             } => {
-                let disruptions = disruptions.into_iter().map(|x| {
-                    let disruption = Disruption::from(arenas, x);
-                    arenas.disruption.intern(disruption)
-                });
-                let lines = lines.into_iter().map(|x| {
-                    let line = Line::from(arenas, x);
-                    arenas.line.intern(line)
-                });
+                let disruptions = disruptions
+                    .iter()
+                    .map(|x| intern_from(&arenas.disruption, arenas, x))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let lines = lines
+                    .iter()
+                    .map(|x| intern_from(&arenas.line, arenas, x))
+                    .collect::<Result<Vec<_>, _>>()?;
                 Data::Success(DataSuccess {
-                    disruptions: arenas.disruption_set.intern(disruptions),
-                    lines: arenas.line_set.intern(lines),
-                    last_updated_date: TimestampMillis::from_rfc3339(&last_updated_date),
+                    disruptions: arenas.disruption_set.try_intern(disruptions)?,
+                    lines: arenas.line_set.try_intern(lines)?,
+                    last_updated_date: TimestampMillis::from_source(arenas, last_updated_date)?,
                 })
             }
             source::Data {
@@ -424,55 +1313,295 @@ impl Data {
                 error: Some(error),
                 message: Some(message),
             } => Data::Error(DataError {
-                status_code,
-                error: arenas.string.intern(&error),
-                message: arenas.string.intern(&message),
+                status_code: *status_code,
+                error: arenas.string.try_intern(error)?,
+                message: arenas.string.try_intern(message)?,
+            }),
+            _ => return Err(Error::IncompleteData),
+        };
+        Ok(data)
+    }
+}
+
+impl Data {
+    fn map(&self, mapping: &ArenasMapping) -> Self {
+        match self {
+            Data::Success(data) => Data::Success(DataSuccess {
+                disruptions: mapping.disruption_set(data.disruptions),
+                lines: mapping.line_set(data.lines),
+                last_updated_date: data.last_updated_date.clone(),
+            }),
+            Data::Error(data) => Data::Error(DataError {
+                status_code: data.status_code,
+                error: mapping.string(data.error),
+                message: mapping.string(data.message),
             }),
-            _ => panic!("Invalid data: {source:?}"),
         }
     }
 }
 
-#[derive(Debug, Hash, PartialEq, Eq, Serialize_tuple, Deserialize_tuple, GetSize)]
+#[derive(
+    Debug,
+    Hash,
+    PartialEq,
+    Eq,
+    Serialize_tuple,
+    Deserialize_tuple,
+    GetSize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
 pub struct DataSuccess {
+    #[rkyv(with = AsId)]
     disruptions: InternedSlice<Interned<Disruption>>,
+    #[rkyv(with = AsId)]
     lines: InternedSlice<Interned<Line>>,
     last_updated_date: TimestampMillis,
 }
 
-impl EqWith<source::Data, Arenas> for DataSuccess {
-    fn eq_with(&self, other: &source::Data, arenas: &Arenas) -> bool {
+impl DataSuccess {
+    pub fn last_updated_date(&self) -> String {
+        self.last_updated_date.to_rfc3339()
+    }
+
+    pub fn disruptions<'a>(&self, arenas: &'a Arenas) -> &'a [Interned<Disruption>] {
+        arenas.disruption_set.lookup(self.disruptions).0
+    }
+
+    pub fn lines<'a>(&self, arenas: &'a Arenas) -> &'a [Interned<Line>] {
+        arenas.line_set.lookup(self.lines).0
+    }
+
+    /// Returns the disruptions of this snapshot that impact the line with the given ID.
+    pub fn line_disruptions(&self, arenas: &Arenas, line_id: &str) -> Vec<Interned<Disruption>> {
+        // Compare handles rather than strings. A line ID that was never interned can't match.
+        let Some(line_id) = arenas.string.position(line_id) else {
+            return Vec::new();
+        };
+        let disruption_ids: HashSet<DisruptionUuid> = self
+            .lines(arenas)
+            .iter()
+            .map(|line| arenas.line.lookup_ref(*line))
+            .filter(|line| arenas.line_header.lookup_ref(line.header).id == line_id)
+            .flat_map(|line| line.impacted_objects.iter())
+            .flat_map(|object| {
+                let object = arenas.impacted_object.lookup_ref(object);
+                arenas.uuid_set.lookup(object.disruption_ids).0
+            })
+            .copied()
+            .collect();
+        self.disruptions(arenas)
+            .iter()
+            .copied()
+            .filter(|disruption| {
+                disruption_ids.contains(&arenas.disruption.lookup_ref(*disruption).id)
+            })
+            .collect()
+    }
+}
+
+/// Value of an archived database that can't be read. Archives are queried in place, without
+/// validating their IDs and timestamps upfront like deserialized databases.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArchiveError {
+    /// An interned ID is out of the bounds of its arena.
+    MissingValue {
+        arena: &'static str,
+        id: u32,
+        arena_len: usize,
+    },
+    /// A timestamp is out of the range of datetimes.
+    InvalidTimestamp { timestamp: i64 },
+}
+
+impl std::fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ArchiveError::MissingValue {
+                arena,
+                id,
+                arena_len,
+            } => write!(
+                f,
+                "invalid database: {arena} #{id} is missing, the {arena} arena only contains {arena_len} values"
+            ),
+            ArchiveError::InvalidTimestamp { timestamp } => {
+                write!(f, "invalid database: timestamp {timestamp} is out of range")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {}
+
+// Looks the value with the given ID up in an archived arena.
+fn archived_lookup<'a, T>(
+    arena: &'static str,
+    values: &'a ArchivedVec<T>,
+    id: u32,
+) -> Result<&'a T, ArchiveError> {
+    values.get(id as usize).ok_or(ArchiveError::MissingValue {
+        arena,
+        id,
+        arena_len: values.len(),
+    })
+}
+
+impl ArchivedArenas {
+    pub fn data(&self, data: u32) -> Result<&ArchivedData, ArchiveError> {
+        archived_lookup(<Interned<Data>>::ARENA, &self.data, data)
+    }
+
+    pub fn disruption(&self, disruption: u32) -> Result<&ArchivedDisruption, ArchiveError> {
+        archived_lookup(<Interned<Disruption>>::ARENA, &self.disruption, disruption)
+    }
+
+    pub fn string(&self, string: u32) -> Result<&str, ArchiveError> {
+        archived_lookup(InternedStr::ARENA, &self.string, string).map(|x| x.as_str())
+    }
+}
+
+// Accessors to query an archived snapshot in place, without deserializing the database.
+impl ArchivedDataSuccess {
+    pub fn last_updated_date(&self) -> Result<String, ArchiveError> {
+        let timestamp = self.last_updated_date.0.to_native();
+        let datetime = TimestampMillis(timestamp)
+            .to_datetime()
+            .ok_or(ArchiveError::InvalidTimestamp { timestamp })?;
+        Ok(datetime.to_rfc3339_opts(SecondsFormat::Millis, true))
+    }
+
+    pub fn disruptions<'a>(
+        &self,
+        arenas: &'a ArchivedArenas,
+    ) -> Result<&'a [Archived<u32>], ArchiveError> {
+        let arena = <InternedSlice<Interned<Disruption>>>::ARENA;
+        let id = self.disruptions.to_native();
+        Ok(archived_lookup(arena, &arenas.disruption_set.0, id)?.as_slice())
+    }
+
+    pub fn lines<'a>(
+        &self,
+        arenas: &'a ArchivedArenas,
+    ) -> Result<&'a [Archived<u32>], ArchiveError> {
+        let arena = <InternedSlice<Interned<Line>>>::ARENA;
+        let id = self.lines.to_native();
+        Ok(archived_lookup(arena, &arenas.line_set.0, id)?.as_slice())
+    }
+
+    /// Returns the IDs of the disruptions of this snapshot that impact the line with the given ID.
+    pub fn line_disruptions(
+        &self,
+        arenas: &ArchivedArenas,
+        line_id: &str,
+    ) -> Result<Vec<u32>, ArchiveError> {
+        let mut disruption_ids: HashSet<u32> = HashSet::new();
+        for line in self.lines(arenas)? {
+            let line = archived_lookup(<Interned<Line>>::ARENA, &arenas.line, line.to_native())?;
+            let header_id = line.header.to_native();
+            let header = archived_lookup(
+                <Interned<LineHeader>>::ARENA,
+                &arenas.line_header,
+                header_id,
+            )?;
+            if arenas.string(header.id.to_native())? != line_id {
+                continue;
+            }
+            for object in line.impacted_objects.iter() {
+                let arena = <Interned<ImpactedObject>>::ARENA;
+                let object = archived_lookup(arena, &arenas.impacted_object, object.to_native())?;
+                let arena = <InternedSlice<DisruptionUuid>>::ARENA;
+                let ids =
+                    archived_lookup(arena, &arenas.uuid_set.0, object.disruption_ids.to_native())?;
+                disruption_ids.extend(ids.iter().map(|id| id.to_native()));
+            }
+        }
+        let mut disruptions = Vec::new();
+        for disruption in self.disruptions(arenas)? {
+            let disruption = disruption.to_native();
+            if disruption_ids.contains(&arenas.disruption(disruption)?.id.to_native()) {
+                disruptions.push(disruption);
+            }
+        }
+        Ok(disruptions)
+    }
+}
+
+impl EqWith<source::Data<'_>, Arenas> for DataSuccess {
+    fn eq_with(&self, other: &source::Data<'_>, arenas: &Arenas) -> bool {
         other.disruptions.as_ref().is_some_and(|other| {
             arenas
                 .disruption_set
                 .lookup(self.disruptions)
-                .set_eq_by(other, |x, y| {
+                .seq_eq_by(other, |x, y| {
                     arenas.disruption.lookup_ref(*x).eq_with(y, arenas)
                 })
         }) && other.lines.as_ref().is_some_and(|other| {
             arenas
                 .line_set
                 .lookup(self.lines)
-                .set_eq_by(other, |x, y| arenas.line.lookup_ref(*x).eq_with(y, arenas))
+                .seq_eq_by(other, |x, y| arenas.line.lookup_ref(*x).eq_with(y, arenas))
         }) && other
             .last_updated_date
             .as_ref()
-            .is_some_and(|other| self.last_updated_date.to_rfc3339() == *other)
+            .is_some_and(|other| self.last_updated_date.to_rfc3339() == **other)
             && other.status_code.is_none()
             && other.error.is_none()
             && other.message.is_none()
     }
 }
 
-#[derive(Debug, Hash, PartialEq, Eq, Serialize_tuple, Deserialize_tuple, GetSize)]
+#[derive(
+    Debug,
+    Hash,
+    PartialEq,
+    Eq,
+    Serialize_tuple,
+    Deserialize_tuple,
+    GetSize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
 pub struct DataError {
     status_code: i32,
+    #[rkyv(with = AsId)]
     error: InternedStr,
+    #[rkyv(with = AsId)]
     message: InternedStr,
 }
 
-impl EqWith<source::Data, Arenas> for DataError {
-    fn eq_with(&self, other: &source::Data, arenas: &Arenas) -> bool {
+impl DataError {
+    pub fn status_code(&self) -> i32 {
+        self.status_code
+    }
+
+    pub fn error<'a>(&self, arenas: &'a Arenas) -> &'a str {
+        arenas.string.lookup(self.error)
+    }
+
+    pub fn message<'a>(&self, arenas: &'a Arenas) -> &'a str {
+        arenas.string.lookup(self.message)
+    }
+}
+
+impl ArchivedDataError {
+    pub fn status_code(&self) -> i32 {
+        self.status_code.to_native()
+    }
+
+    pub fn error<'a>(&self, arenas: &'a ArchivedArenas) -> Result<&'a str, ArchiveError> {
+        arenas.string(self.error.to_native())
+    }
+
+    pub fn message<'a>(&self, arenas: &'a ArchivedArenas) -> Result<&'a str, ArchiveError> {
+        arenas.string(self.message.to_native())
+    }
+}
+
+impl EqWith<source::Data<'_>, Arenas> for DataError {
+    fn eq_with(&self, other: &source::Data<'_>, arenas: &Arenas) -> bool {
         other
             .status_code
             .as_ref()
@@ -491,197 +1620,272 @@ impl EqWith<source::Data, Arenas> for DataError {
     }
 }
 
-#[derive(Debug, Hash, PartialEq, Eq, Serialize_tuple, Deserialize_tuple, GetSize)]
+#[derive(
+    Debug,
+    Hash,
+    PartialEq,
+    Eq,
+    Serialize_tuple,
+    Deserialize_tuple,
+    GetSize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+    FromSource,
+)]
+#[intern(source = source::Disruption<'_>)]
 pub struct Disruption {
-    pub id: Interned<Uuid>,
-    pub application_periods: InternedSet<ApplicationPeriod>,
-    pub last_update: TimestampSecondsParis,
+    #[rkyv(with = AsId)]
+    #[intern(uuid)]
+    pub id: DisruptionUuid,
+    #[rkyv(with = AsId)]
+    #[intern(set(application_period, application_period_set))]
+    pub application_periods: InternedSlice<Interned<ApplicationPeriod>>,
+    #[rkyv(with = AsId)]
+    #[intern(timestamp)]
+    pub last_update: Interned<LocalTimestampSeconds>,
+    #[rkyv(with = AsId)]
+    #[intern(string)]
     pub cause: InternedStr,
-    pub severity: InternedStr,
-    pub tags: Option<InternedStrSet>,
+    pub severity: Known<Severity>,
+    #[rkyv(with = Map<AsId>)]
+    #[intern(option(set(string, string_set)))]
+    pub tags: Option<InternedSlice<InternedStr>>,
+    #[rkyv(with = AsId)]
+    #[intern(string)]
     pub title: InternedStr,
+    #[rkyv(with = Map<AsId>)]
+    #[intern(option(string))]
     pub message: Option<InternedStr>,
+    #[rkyv(with = Map<AsId>)]
+    #[intern(option(string))]
     pub short_message: Option<InternedStr>,
-    pub disruption_id: Option<Interned<Uuid>>,
+    #[rkyv(with = Map<AsId>)]
+    #[intern(option(uuid))]
+    pub disruption_id: Option<DisruptionUuid>,
 }
 
-impl EqWith<source::Disruption, Arenas> for Disruption {
-    fn eq_with(&self, other: &source::Disruption, arenas: &Arenas) -> bool {
-        self.id.eq_with(&other.id, &arenas.uuid)
-            && self
-                .application_periods
-                .set_eq_by(&other.application_periods, |x, y| {
-                    arenas.application_period.lookup_ref(*x).eq_with(y, arenas)
-                })
-            && self.last_update.to_formatted("%Y%m%dT%H%M%S") == other.last_update
-            && self.cause.eq_with(&other.cause, &arenas.string)
-            && self.severity.eq_with(&other.severity, &arenas.string)
-            && option_eq_by(&self.tags, &other.tags, |x, y| {
-                x.set_eq_by(y, |x, y| x.eq_with(y, &arenas.string))
-            })
-            && self.title.eq_with(&other.title, &arenas.string)
-            && option_eq_by(&self.message, &other.message, |x, y| {
-                x.eq_with(y, &arenas.string)
-            })
-            && option_eq_by(&self.short_message, &other.short_message, |x, y| {
-                x.eq_with(y, &arenas.string)
-            })
-            && option_eq_by(&self.disruption_id, &other.disruption_id, |x, y| {
-                x.eq_with(y, &arenas.uuid)
-            })
+impl Disruption {
+    pub fn title<'a>(&self, arenas: &'a Arenas) -> &'a str {
+        arenas.string.lookup(self.title)
     }
-}
 
-impl Disruption {
-    pub fn from(arenas: &Arenas, source: source::Disruption) -> Self {
+    fn map(&self, mapping: &ArenasMapping) -> Self {
         Self {
-            id: arenas.uuid.intern(source.id),
-            application_periods: InternedSet::new(source.application_periods.into_iter().map(
-                |x| {
-                    let application_period = ApplicationPeriod::from(arenas, x);
-                    arenas.application_period.intern(application_period)
-                },
-            )),
-            last_update: TimestampSecondsParis::from_formatted(
-                &source.last_update,
-                "%Y%m%dT%H%M%S",
-            ),
-            cause: arenas.string.intern(&source.cause),
-            severity: arenas.string.intern(&source.severity),
-            tags: source
-                .tags
-                .map(|x| InternedStrSet::new(x.into_iter().map(|x| arenas.string.intern(&x)))),
-            title: arenas.string.intern(&source.title),
-            message: source.message.map(|x| arenas.string.intern(&x)),
-            short_message: source.short_message.map(|x| arenas.string.intern(&x)),
-            disruption_id: source.disruption_id.map(|x| arenas.uuid.intern(x)),
+            id: mapping.uuid(self.id),
+            application_periods: mapping.application_period_set(self.application_periods),
+            last_update: mapping.timestamp(self.last_update),
+            cause: mapping.string(self.cause),
+            severity: self.severity.map(|x| mapping.string(x)),
+            tags: self.tags.map(|x| mapping.string_set(x)),
+            title: mapping.string(self.title),
+            message: self.message.map(|x| mapping.string(x)),
+            short_message: self.short_message.map(|x| mapping.string(x)),
+            disruption_id: self.disruption_id.map(|x| mapping.uuid(x)),
         }
     }
 }
 
-#[derive(Debug, Hash, PartialEq, Eq, Serialize_tuple, Deserialize_tuple, GetSize)]
-pub struct ApplicationPeriod {
-    pub begin: TimestampSecondsParis,
-    pub end: TimestampSecondsParis,
-}
+impl ArchivedDisruption {
+    pub fn title<'a>(&self, arenas: &'a ArchivedArenas) -> Result<&'a str, ArchiveError> {
+        arenas.string(self.title.to_native())
+    }
 
-impl EqWith<source::ApplicationPeriod, Arenas> for ApplicationPeriod {
-    fn eq_with(&self, other: &source::ApplicationPeriod, _arenas: &Arenas) -> bool {
-        self.begin.to_formatted("%Y%m%dT%H%M%S") == other.begin
-            && self.end.to_formatted("%Y%m%dT%H%M%S") == other.end
+    pub fn severity<'a>(&self, arenas: &'a ArchivedArenas) -> Result<&'a str, ArchiveError> {
+        self.severity.as_str(arenas)
     }
-}
 
-impl ApplicationPeriod {
-    pub fn from(_arenas: &Arenas, source: source::ApplicationPeriod) -> Self {
-        Self {
-            begin: TimestampSecondsParis::from_formatted(&source.begin, "%Y%m%dT%H%M%S"),
-            end: TimestampSecondsParis::from_formatted(&source.end, "%Y%m%dT%H%M%S"),
-        }
+    /// Returns the beginning and end of each application period, in local time.
+    pub fn application_periods(
+        &self,
+        arenas: &ArchivedArenas,
+    ) -> Result<Vec<(String, String)>, ArchiveError> {
+        let arena = <InternedSlice<Interned<ApplicationPeriod>>>::ARENA;
+        let id = self.application_periods.to_native();
+        let formatted = |timestamp: &ArchivedLocalTimestampSeconds| {
+            let timestamp = timestamp.0.to_native();
+            let datetime = LocalTimestampSeconds(timestamp)
+                .to_local()
+                .ok_or(ArchiveError::InvalidTimestamp { timestamp })?;
+            Ok(datetime.format(DISPLAY_FORMAT).to_string())
+        };
+        archived_lookup(arena, &arenas.application_period_set.0, id)?
+            .iter()
+            .map(|period| {
+                let arena = <Interned<ApplicationPeriod>>::ARENA;
+                let period =
+                    archived_lookup(arena, &arenas.application_period, period.to_native())?;
+                Ok((formatted(&period.begin)?, formatted(&period.end)?))
+            })
+            .collect()
     }
 }
 
-#[derive(Debug, Hash, PartialEq, Eq, Serialize_tuple, Deserialize_tuple, GetSize)]
-pub struct Line {
-    pub header: Interned<LineHeader>,
-    pub impacted_objects: InternedSet<ImpactedObject>,
+#[derive(
+    Debug,
+    Clone,
+    Hash,
+    PartialEq,
+    Eq,
+    Serialize_tuple,
+    Deserialize_tuple,
+    GetSize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+    FromSource,
+)]
+#[intern(source = source::ApplicationPeriod<'_>)]
+pub struct ApplicationPeriod {
+    pub begin: LocalTimestampSeconds,
+    pub end: LocalTimestampSeconds,
 }
 
-impl EqWith<source::Line, Arenas> for Line {
-    fn eq_with(&self, other: &source::Line, arenas: &Arenas) -> bool {
-        arenas
-            .line_header
-            .lookup_ref(self.header)
-            .eq_with(other, arenas)
-            && self
-                .impacted_objects
-                .set_eq_by(&other.impacted_objects, |x, y| {
-                    arenas.impacted_object.lookup_ref(*x).eq_with(y, arenas)
-                })
+// Allows interning an application period by reference, which only clones it if it isn't already
+// interned.
+impl From<&ApplicationPeriod> for ApplicationPeriod {
+    fn from(value: &ApplicationPeriod) -> Self {
+        value.clone()
     }
 }
 
+#[derive(
+    Debug,
+    Hash,
+    PartialEq,
+    Eq,
+    Serialize_tuple,
+    Deserialize_tuple,
+    GetSize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+    FromSource,
+)]
+#[intern(source = source::Line<'_>)]
+pub struct Line {
+    #[rkyv(with = AsId)]
+    #[intern(flatten(line_header))]
+    pub header: Interned<LineHeader>,
+    #[intern(seq(impacted_object))]
+    pub impacted_objects: InternedSeq<ImpactedObject>,
+}
+
 impl Line {
-    pub fn from(arenas: &Arenas, source: source::Line) -> Self {
+    fn map(&self, mapping: &ArenasMapping) -> Self {
         Self {
-            header: arenas.line_header.intern(LineHeader {
-                id: arenas.string.intern(&source.id),
-                name: arenas.string.intern(&source.name),
-                short_name: arenas.string.intern(&source.short_name),
-                mode: arenas.string.intern(&source.mode),
-                network_id: arenas.string.intern(&source.network_id),
-            }),
-            impacted_objects: InternedSet::new(source.impacted_objects.into_iter().map(|x| {
-                let impacted_object = ImpactedObject::from(arenas, x);
-                arenas.impacted_object.intern(impacted_object)
-            })),
+            header: mapping.line_header(self.header),
+            impacted_objects: self.impacted_objects.map(|x| mapping.impacted_object(x)),
         }
     }
 }
 
-#[derive(Debug, Hash, PartialEq, Eq, Serialize_tuple, Deserialize_tuple, GetSize)]
+#[derive(
+    Debug,
+    Hash,
+    PartialEq,
+    Eq,
+    Serialize_tuple,
+    Deserialize_tuple,
+    GetSize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+    FromSource,
+)]
+#[intern(source = source::Line<'_>)]
 pub struct LineHeader {
+    #[rkyv(with = AsId)]
+    #[intern(string)]
     pub id: InternedStr,
+    #[rkyv(with = AsId)]
+    #[intern(string)]
     pub name: InternedStr,
+    #[rkyv(with = AsId)]
+    #[intern(string)]
     pub short_name: InternedStr,
-    pub mode: InternedStr,
+    pub mode: Known<Mode>,
+    #[rkyv(with = AsId)]
+    #[intern(string)]
     pub network_id: InternedStr,
 }
 
-impl EqWith<source::Line, Arenas> for LineHeader {
-    fn eq_with(&self, other: &source::Line, arenas: &Arenas) -> bool {
-        self.id.eq_with(&other.id, &arenas.string)
-            && self.name.eq_with(&other.name, &arenas.string)
-            && self.short_name.eq_with(&other.short_name, &arenas.string)
-            && self.mode.eq_with(&other.mode, &arenas.string)
-            && self.network_id.eq_with(&other.network_id, &arenas.string)
+impl LineHeader {
+    fn map(&self, mapping: &ArenasMapping) -> Self {
+        Self {
+            id: mapping.string(self.id),
+            name: mapping.string(self.name),
+            short_name: mapping.string(self.short_name),
+            mode: self.mode.map(|x| mapping.string(x)),
+            network_id: mapping.string(self.network_id),
+        }
     }
 }
 
-#[derive(Debug, Hash, PartialEq, Eq, Serialize_tuple, Deserialize_tuple, GetSize)]
+#[derive(
+    Debug,
+    Hash,
+    PartialEq,
+    Eq,
+    Serialize_tuple,
+    Deserialize_tuple,
+    GetSize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+    FromSource,
+)]
+#[intern(source = source::ImpactedObject<'_>)]
 pub struct ImpactedObject {
+    #[rkyv(with = AsId)]
+    #[intern(flatten(object))]
     pub object: Interned<Object>,
-    pub disruption_ids: InternedSlice<Interned<Uuid>>,
-}
-
-impl EqWith<source::ImpactedObject, Arenas> for ImpactedObject {
-    fn eq_with(&self, other: &source::ImpactedObject, arenas: &Arenas) -> bool {
-        arenas.object.lookup_ref(self.object).eq_with(other, arenas)
-            && arenas
-                .uuid_set
-                .lookup(self.disruption_ids)
-                .set_eq_by(&other.disruption_ids, |x, y| x.eq_with(y, &arenas.uuid))
-    }
+    #[rkyv(with = AsId)]
+    #[intern(set(uuid, uuid_set))]
+    pub disruption_ids: InternedSlice<DisruptionUuid>,
 }
 
 impl ImpactedObject {
-    pub fn from(arenas: &Arenas, source: source::ImpactedObject) -> Self {
-        let disruption_ids = source
-            .disruption_ids
-            .into_iter()
-            .map(|x| arenas.uuid.intern(x));
+    fn map(&self, mapping: &ArenasMapping) -> Self {
         Self {
-            object: arenas.object.intern(Object {
-                typ: arenas.string.intern(&source.typ),
-                id: arenas.string.intern(&source.id),
-                name: arenas.string.intern(&source.name),
-            }),
-            disruption_ids: arenas.uuid_set.intern(disruption_ids),
+            object: mapping.object(self.object),
+            disruption_ids: mapping.uuid_set(self.disruption_ids),
         }
     }
 }
 
-#[derive(Debug, Hash, PartialEq, Eq, Serialize_tuple, Deserialize_tuple, GetSize)]
+#[derive(
+    Debug,
+    Hash,
+    PartialEq,
+    Eq,
+    Serialize_tuple,
+    Deserialize_tuple,
+    GetSize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+    FromSource,
+)]
+#[intern(source = source::ImpactedObject<'_>)]
 pub struct Object {
+    #[rkyv(with = AsId)]
+    #[intern(string)]
     pub typ: InternedStr,
+    #[rkyv(with = AsId)]
+    #[intern(string)]
     pub id: InternedStr,
+    #[rkyv(with = AsId)]
+    #[intern(string)]
     pub name: InternedStr,
 }
 
-impl EqWith<source::ImpactedObject, Arenas> for Object {
-    fn eq_with(&self, other: &source::ImpactedObject, arenas: &Arenas) -> bool {
-        self.typ.eq_with(&other.typ, &arenas.string)
-            && self.id.eq_with(&other.id, &arenas.string)
-            && self.name.eq_with(&other.name, &arenas.string)
+impl Object {
+    fn map(&self, mapping: &ArenasMapping) -> Self {
+        Self {
+            typ: mapping.string(self.typ),
+            id: mapping.string(self.id),
+            name: mapping.string(self.name),
+        }
     }
 }
@@ -0,0 +1,140 @@
+// Disruptions that are active at a given instant, i.e. whose application periods cover it, according
+// to the snapshot updated the closest to that instant. A disruption is listed under each line whose
+// impacted objects refer to its UUID.
+
+use super::{Arenas, Data, Disruption, DisruptionUuid, Line, LineHeader, LocalTimestampSeconds};
+use crate::error::Error;
+use blazinterner::Interned;
+use chrono::DateTime;
+use std::collections::{BTreeMap, HashMap};
+
+// Formats of the local datetimes accepted in addition to RFC 3339: the one displayed by the
+// queries, with a `T` separator, and the one of the source files.
+const LOCAL_FORMATS: [&str; 2] = ["%Y-%m-%dT%H:%M:%S", "%Y%m%dT%H%M%S"];
+
+/// Line impacted by disruptions, identified by its ID and name.
+pub type LineKey<'a> = (&'a str, &'a str);
+
+/// Disruptions of a snapshot that are active at an instant.
+pub struct ActiveDisruptions<'a> {
+    /// Position of the snapshot in the database.
+    pub snapshot: usize,
+    /// Number of disruptions in the snapshot.
+    pub disruptions: usize,
+    /// Active disruptions, in the order of the snapshot.
+    pub active: Vec<Interned<Disruption>>,
+    /// Active disruptions by impacted line and by severity. Disruptions that impact no line are
+    /// listed under `None`.
+    pub by_line: BTreeMap<Option<LineKey<'a>>, BTreeMap<&'a str, Vec<Interned<Disruption>>>>,
+}
+
+/// Parses an instant, either in RFC 3339 or as a local datetime in the configured timezone, and
+/// returns its number of seconds since the Unix epoch.
+pub fn parse_instant(x: &str) -> Result<i64, Error> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(x) {
+        return Ok(datetime.timestamp());
+    }
+    for format in LOCAL_FORMATS {
+        match LocalTimestampSeconds::from_formatted(x, format) {
+            Ok(LocalTimestampSeconds(timestamp)) => return Ok(timestamp),
+            Err(Error::ParseDatetime { .. }) => (),
+            // The local datetime is well-formed, but isn't a single instant in the timezone.
+            Err(e) => return Err(e),
+        }
+    }
+    Err(Error::ParseDatetime {
+        value: x.to_owned(),
+        format: "RFC 3339 or local",
+    })
+}
+
+impl Arenas {
+    /// Returns the disruptions that are active at the given instant, in seconds since the Unix
+    /// epoch, according to the successful snapshot whose update date is the closest to it, or
+    /// `None` if there is no successful snapshot.
+    pub fn active_at(
+        &self,
+        datas: &[Interned<Data>],
+        instant: i64,
+    ) -> Option<ActiveDisruptions<'_>> {
+        let (snapshot, data) = datas
+            .iter()
+            .enumerate()
+            .filter_map(|(i, data)| match self.data(*data) {
+                Data::Success(data) => Some((i, data)),
+                Data::Error(_) => None,
+            })
+            .min_by_key(|(_, data)| (data.last_updated_date.0 - instant * 1000).abs())?;
+
+        let disruptions = data.disruptions(self);
+        let active: Vec<Interned<Disruption>> = disruptions
+            .iter()
+            .copied()
+            .filter(|disruption| {
+                let periods = self.disruption(*disruption).application_periods;
+                self.application_period_set
+                    .lookup(periods)
+                    .0
+                    .iter()
+                    .map(|period| self.application_period.lookup_ref(*period))
+                    .any(|period| (period.begin.0..=period.end.0).contains(&instant))
+            })
+            .collect();
+
+        let lines = self.impacted_lines(data.lines(self));
+        let mut by_line: BTreeMap<_, BTreeMap<_, Vec<_>>> = BTreeMap::new();
+        for &handle in &active {
+            let disruption = self.disruption(handle);
+            let severity = disruption.severity.as_str(&self.string);
+            let keys = match lines.get(&disruption.id) {
+                Some(headers) => headers
+                    .iter()
+                    .map(|header| {
+                        let header = self.line_header.lookup_ref(*header);
+                        Some((
+                            self.string.lookup(header.id),
+                            self.string.lookup(header.name),
+                        ))
+                    })
+                    .collect(),
+                None => vec![None],
+            };
+            for key in keys {
+                by_line
+                    .entry(key)
+                    .or_default()
+                    .entry(severity)
+                    .or_default()
+                    .push(handle);
+            }
+        }
+
+        Some(ActiveDisruptions {
+            snapshot,
+            disruptions: disruptions.len(),
+            active,
+            by_line,
+        })
+    }
+    /// Returns the headers of the lines whose impacted objects refer to each disruption, among the
+    /// given lines of a snapshot.
+    pub(super) fn impacted_lines(
+        &self,
+        lines: &[Interned<Line>],
+    ) -> HashMap<DisruptionUuid, Vec<Interned<LineHeader>>> {
+        let mut impacted: HashMap<DisruptionUuid, Vec<Interned<LineHeader>>> = HashMap::new();
+        for line in lines {
+            let line = self.line.lookup_ref(*line);
+            for object in line.impacted_objects.iter() {
+                let object = self.impacted_object.lookup_ref(object);
+                for id in self.uuid_set.lookup(object.disruption_ids).0 {
+                    let headers = impacted.entry(*id).or_default();
+                    if !headers.contains(&line.header) {
+                        headers.push(line.header);
+                    }
+                }
+            }
+        }
+        impacted
+    }
+}
@@ -0,0 +1,495 @@
+// Avro serialization of the optimized database. Unlike the other formats, an Avro object container
+// starts with the schema of its records and can compress its blocks of records by itself, which
+// makes it a point of comparison for the schemaless formats and for the external compressors.
+//
+// The database is a single record with the same layout as the protobuf message, so the arenas are
+// converted through the protobuf messages. Its schema is `interning.avsc`, and readers of the
+// container only rely on the schema embedded in it.
+
+use super::protobuf::{
+    self, ApplicationPeriod, ArenasMessage, Data, DataError, DataResult, DataSuccess, Database,
+    Disruption, IdList, ImpactedObject, Line, LineHeader, Object, ProvenanceMessage, Snapshot,
+};
+use super::Arenas;
+use crate::provenance::Provenance;
+use crate::stream::Record;
+use apache_avro::schema::SchemaKind;
+use apache_avro::types::Value;
+use apache_avro::{Codec, DeflateSettings, Reader, Schema, Writer};
+use blazinterner::Interned;
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// Compression of the blocks of an Avro container.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Null,
+    Deflate,
+    Snappy,
+}
+
+impl Compression {
+    fn codec(self) -> Codec {
+        match self {
+            Self::Null => Codec::Null,
+            Self::Deflate => Codec::Deflate(DeflateSettings::default()),
+            Self::Snappy => Codec::Snappy,
+        }
+    }
+}
+
+fn schema() -> &'static Schema {
+    static SCHEMA: OnceLock<Schema> = OnceLock::new();
+    SCHEMA.get_or_init(|| {
+        Schema::parse_str(include_str!("interning.avsc")).expect("The Avro schema is valid")
+    })
+}
+
+/// Serializes the snapshots in the given order, along with the arenas, into an Avro container
+/// whose blocks are compressed as given.
+pub fn serialize(
+    arenas: &Arenas,
+    paths: &[PathBuf],
+    datas: &[Interned<super::Data>],
+    provenance: &[Option<Provenance>],
+    compression: Compression,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let database = protobuf::to_message(arenas, paths, datas, provenance)?;
+    let mut writer = Writer::with_codec(schema(), Vec::new(), compression.codec())?;
+    writer.append_value(encode_database(database))?;
+    Ok(writer.into_inner()?)
+}
+
+/// Reads back all the snapshots of a container written by [`serialize`], in order. The
+/// compression is read from the container.
+pub fn read(bytes: &[u8]) -> Result<(Arenas, Vec<Record>), Box<dyn Error>> {
+    let mut reader = Reader::new(bytes)?;
+    let database = reader.next().ok_or("The Avro container is empty")??;
+    if reader.next().is_some() {
+        return Err("The Avro container contains more than one database".into());
+    }
+    protobuf::from_message(decode_database(database)?)
+}
+
+fn record<const N: usize>(fields: [(&str, Value); N]) -> Value {
+    Value::Record(
+        fields
+            .into_iter()
+            .map(|(name, value)| (name.to_owned(), value))
+            .collect(),
+    )
+}
+
+fn id(id: u32) -> Value {
+    Value::Long(id.into())
+}
+
+fn ids(ids: Vec<u32>) -> Value {
+    array(ids, id)
+}
+
+fn array<T>(values: Vec<T>, f: impl Fn(T) -> Value) -> Value {
+    Value::Array(values.into_iter().map(f).collect())
+}
+
+// Value of a union with null, whose null branch comes first.
+fn optional(value: Option<Value>) -> Value {
+    match value {
+        None => Value::Union(0, Box::new(Value::Null)),
+        Some(value) => Value::Union(1, Box::new(value)),
+    }
+}
+
+fn encode_database(database: Database) -> Value {
+    record([
+        ("version", Value::Int(database.version as i32)),
+        ("arenas", encode_arenas(database.arenas.unwrap_or_default())),
+        ("snapshots", array(database.snapshots, encode_snapshot)),
+        ("timezone", Value::String(database.timezone)),
+    ])
+}
+
+fn encode_snapshot(snapshot: Snapshot) -> Value {
+    let provenance = snapshot.provenance.map(|provenance| {
+        record([
+            ("checksum", Value::Long(provenance.checksum.into())),
+            ("modified", optional(provenance.modified.map(Value::Long))),
+            ("ingested", Value::Long(provenance.ingested)),
+        ])
+    });
+    record([
+        ("data", id(snapshot.data)),
+        ("path", Value::String(snapshot.path)),
+        ("provenance", optional(provenance)),
+    ])
+}
+
+fn encode_arenas(arenas: ArenasMessage) -> Value {
+    record([
+        ("strings", array(arenas.strings, Value::String)),
+        ("uuids", array(arenas.uuids, |uuid| Value::Fixed(16, uuid))),
+        ("disruption_sets", array(arenas.disruption_sets, encode_ids)),
+        ("disruptions", array(arenas.disruptions, encode_disruption)),
+        (
+            "application_period_sets",
+            array(arenas.application_period_sets, encode_ids),
+        ),
+        (
+            "application_periods",
+            array(arenas.application_periods, |period| {
+                record([
+                    ("begin", Value::Long(period.begin)),
+                    ("end", Value::Long(period.end)),
+                ])
+            }),
+        ),
+        ("string_sets", array(arenas.string_sets, encode_ids)),
+        ("timestamps", array(arenas.timestamps, Value::Long)),
+        ("line_sets", array(arenas.line_sets, encode_ids)),
+        (
+            "lines",
+            array(arenas.lines, |line| {
+                record([
+                    ("header", id(line.header)),
+                    ("impacted_objects", ids(line.impacted_objects)),
+                ])
+            }),
+        ),
+        (
+            "line_headers",
+            array(arenas.line_headers, |header| {
+                record([
+                    ("id", id(header.id)),
+                    ("name", id(header.name)),
+                    ("short_name", id(header.short_name)),
+                    ("mode", id(header.mode)),
+                    ("network_id", id(header.network_id)),
+                ])
+            }),
+        ),
+        (
+            "impacted_objects",
+            array(arenas.impacted_objects, |object| {
+                record([
+                    ("object", id(object.object)),
+                    ("disruption_ids", id(object.disruption_ids)),
+                ])
+            }),
+        ),
+        (
+            "objects",
+            array(arenas.objects, |object| {
+                record([
+                    ("type", id(object.typ)),
+                    ("id", id(object.id)),
+                    ("name", id(object.name)),
+                ])
+            }),
+        ),
+        ("uuid_sets", array(arenas.uuid_sets, encode_ids)),
+        ("data", array(arenas.data, encode_data)),
+    ])
+}
+
+fn encode_ids(list: IdList) -> Value {
+    record([("ids", ids(list.ids))])
+}
+
+fn encode_disruption(disruption: Disruption) -> Value {
+    record([
+        ("id", id(disruption.id)),
+        ("application_periods", id(disruption.application_periods)),
+        ("last_update", id(disruption.last_update)),
+        ("cause", id(disruption.cause)),
+        ("severity", id(disruption.severity)),
+        ("tags", optional(disruption.tags.map(id))),
+        ("title", id(disruption.title)),
+        ("message", optional(disruption.message.map(id))),
+        ("short_message", optional(disruption.short_message.map(id))),
+        ("disruption_id", optional(disruption.disruption_id.map(id))),
+    ])
+}
+
+// Data are a union of the success and error records, as snapshots always have one of them.
+fn encode_data(data: Data) -> Value {
+    let (index, value) = match data.result {
+        Some(DataResult::Success(success)) => (
+            0,
+            record([
+                ("disruptions", id(success.disruptions)),
+                ("lines", id(success.lines)),
+                ("last_updated_date", Value::Long(success.last_updated_date)),
+            ]),
+        ),
+        Some(DataResult::Error(error)) => (
+            1,
+            record([
+                ("status_code", Value::Int(error.status_code)),
+                ("error", id(error.error)),
+                ("message", id(error.message)),
+            ]),
+        ),
+        None => unreachable!("Data converted from the arenas always have a result"),
+    };
+    Value::Union(index, Box::new(value))
+}
+
+fn unexpected(expected: &str, value: &Value) -> Box<dyn Error> {
+    format!(
+        "Expected {expected} in the Avro container, found a value of type {:?}",
+        SchemaKind::from(value)
+    )
+    .into()
+}
+
+// Fields of a record read from the container, which are looked up by name so that they don't
+// depend on the order of the fields in the embedded schema.
+struct Fields(Vec<(String, Value)>);
+
+impl Fields {
+    fn new(value: Value) -> Result<Self, Box<dyn Error>> {
+        match value {
+            Value::Record(fields) => Ok(Self(fields)),
+            value => Err(unexpected("a record", &value)),
+        }
+    }
+
+    fn take(&mut self, name: &str) -> Result<Value, Box<dyn Error>> {
+        let (_, value) = self
+            .0
+            .iter_mut()
+            .find(|(field, _)| field == name)
+            .ok_or_else(|| format!("Missing field {name:?} in the Avro container"))?;
+        Ok(std::mem::replace(value, Value::Null))
+    }
+
+    fn id(&mut self, name: &str) -> Result<u32, Box<dyn Error>> {
+        decode_id(self.take(name)?)
+    }
+
+    fn long(&mut self, name: &str) -> Result<i64, Box<dyn Error>> {
+        decode_long(self.take(name)?)
+    }
+
+    fn int(&mut self, name: &str) -> Result<i32, Box<dyn Error>> {
+        match self.take(name)? {
+            Value::Int(x) => Ok(x),
+            value => Err(unexpected("an int", &value)),
+        }
+    }
+
+    fn optional<T>(
+        &mut self,
+        name: &str,
+        f: impl FnOnce(Value) -> Result<T, Box<dyn Error>>,
+    ) -> Result<Option<T>, Box<dyn Error>> {
+        match self.take(name)? {
+            Value::Union(_, value) => match *value {
+                Value::Null => Ok(None),
+                value => f(value).map(Some),
+            },
+            value => Err(unexpected("a union", &value)),
+        }
+    }
+
+    fn array<T>(
+        &mut self,
+        name: &str,
+        f: impl Fn(Value) -> Result<T, Box<dyn Error>>,
+    ) -> Result<Vec<T>, Box<dyn Error>> {
+        match self.take(name)? {
+            Value::Array(values) => values.into_iter().map(f).collect(),
+            value => Err(unexpected("an array", &value)),
+        }
+    }
+}
+
+fn decode_id(value: Value) -> Result<u32, Box<dyn Error>> {
+    Ok(decode_long(value)?.try_into()?)
+}
+
+fn decode_long(value: Value) -> Result<i64, Box<dyn Error>> {
+    match value {
+        Value::Long(x) => Ok(x),
+        value => Err(unexpected("a long", &value)),
+    }
+}
+
+fn decode_database(value: Value) -> Result<Database, Box<dyn Error>> {
+    let mut fields = Fields::new(value)?;
+    Ok(Database {
+        version: fields.int("version")?.try_into()?,
+        arenas: Some(decode_arenas(fields.take("arenas")?)?),
+        snapshots: fields.array("snapshots", decode_snapshot)?,
+        timezone: match fields.take("timezone")? {
+            Value::String(timezone) => timezone,
+            value => return Err(unexpected("a string", &value)),
+        },
+    })
+}
+
+fn decode_snapshot(value: Value) -> Result<Snapshot, Box<dyn Error>> {
+    let mut fields = Fields::new(value)?;
+    Ok(Snapshot {
+        data: fields.id("data")?,
+        path: match fields.take("path")? {
+            Value::String(path) => path,
+            value => return Err(unexpected("a string", &value)),
+        },
+        provenance: fields.optional("provenance", |value| {
+            let mut fields = Fields::new(value)?;
+            Ok(ProvenanceMessage {
+                checksum: fields.long("checksum")?.try_into()?,
+                modified: fields.optional("modified", decode_long)?,
+                ingested: fields.long("ingested")?,
+            })
+        })?,
+    })
+}
+
+fn decode_arenas(value: Value) -> Result<ArenasMessage, Box<dyn Error>> {
+    let mut fields = Fields::new(value)?;
+    Ok(ArenasMessage {
+        strings: fields.array("strings", |value| match value {
+            Value::String(x) => Ok(x),
+            value => Err(unexpected("a string", &value)),
+        })?,
+        uuids: fields.array("uuids", |value| match value {
+            Value::Fixed(16, uuid) => Ok(uuid),
+            value => Err(unexpected("a UUID", &value)),
+        })?,
+        disruption_sets: fields.array("disruption_sets", decode_ids)?,
+        disruptions: fields.array("disruptions", decode_disruption)?,
+        application_period_sets: fields.array("application_period_sets", decode_ids)?,
+        application_periods: fields.array("application_periods", |value| {
+            let mut fields = Fields::new(value)?;
+            Ok(ApplicationPeriod {
+                begin: fields.long("begin")?,
+                end: fields.long("end")?,
+            })
+        })?,
+        string_sets: fields.array("string_sets", decode_ids)?,
+        timestamps: fields.array("timestamps", decode_long)?,
+        line_sets: fields.array("line_sets", decode_ids)?,
+        lines: fields.array("lines", |value| {
+            let mut fields = Fields::new(value)?;
+            Ok(Line {
+                header: fields.id("header")?,
+                impacted_objects: fields.array("impacted_objects", decode_id)?,
+            })
+        })?,
+        line_headers: fields.array("line_headers", |value| {
+            let mut fields = Fields::new(value)?;
+            Ok(LineHeader {
+                id: fields.id("id")?,
+                name: fields.id("name")?,
+                short_name: fields.id("short_name")?,
+                mode: fields.id("mode")?,
+                network_id: fields.id("network_id")?,
+            })
+        })?,
+        impacted_objects: fields.array("impacted_objects", |value| {
+            let mut fields = Fields::new(value)?;
+            Ok(ImpactedObject {
+                object: fields.id("object")?,
+                disruption_ids: fields.id("disruption_ids")?,
+            })
+        })?,
+        objects: fields.array("objects", |value| {
+            let mut fields = Fields::new(value)?;
+            Ok(Object {
+                typ: fields.id("type")?,
+                id: fields.id("id")?,
+                name: fields.id("name")?,
+            })
+        })?,
+        uuid_sets: fields.array("uuid_sets", decode_ids)?,
+        data: fields.array("data", decode_data)?,
+    })
+}
+
+fn decode_ids(value: Value) -> Result<IdList, Box<dyn Error>> {
+    Ok(IdList {
+        ids: Fields::new(value)?.array("ids", decode_id)?,
+    })
+}
+
+fn decode_disruption(value: Value) -> Result<Disruption, Box<dyn Error>> {
+    let mut fields = Fields::new(value)?;
+    Ok(Disruption {
+        id: fields.id("id")?,
+        application_periods: fields.id("application_periods")?,
+        last_update: fields.id("last_update")?,
+        cause: fields.id("cause")?,
+        severity: fields.id("severity")?,
+        tags: fields.optional("tags", decode_id)?,
+        title: fields.id("title")?,
+        message: fields.optional("message", decode_id)?,
+        short_message: fields.optional("short_message", decode_id)?,
+        disruption_id: fields.optional("disruption_id", decode_id)?,
+    })
+}
+
+fn decode_data(value: Value) -> Result<Data, Box<dyn Error>> {
+    let result = match value {
+        Value::Union(0, success) => {
+            let mut fields = Fields::new(*success)?;
+            DataResult::Success(DataSuccess {
+                disruptions: fields.id("disruptions")?,
+                lines: fields.id("lines")?,
+                last_updated_date: fields.long("last_updated_date")?,
+            })
+        }
+        Value::Union(1, error) => {
+            let mut fields = Fields::new(*error)?;
+            DataResult::Error(DataError {
+                status_code: fields.int("status_code")?,
+                error: fields.id("error")?,
+                message: fields.id("message")?,
+            })
+        }
+        value => return Err(unexpected("a success or an error", &value)),
+    };
+    Ok(Data {
+        result: Some(result),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provenance::Provenance;
+    use crate::schema::generate::test_database;
+
+    #[test]
+    fn avro_containers_round_trip_with_each_compression() {
+        let mut database = test_database(2);
+        database.provenance[1] = Some(Provenance {
+            checksum: 0xdead_beef,
+            modified: None,
+            ingested: 1_700_000_000_000,
+        });
+        let crate::Database {
+            arenas,
+            datas,
+            paths,
+            provenance,
+        } = database;
+
+        for compression in [Compression::Null, Compression::Deflate, Compression::Snappy] {
+            let bytes = serialize(&arenas, &paths, &datas, &provenance, compression).unwrap();
+            let (decoded, records) = read(&bytes).unwrap();
+            assert_eq!(decoded, arenas, "{compression:?}");
+            let expected: Vec<_> = paths
+                .iter()
+                .cloned()
+                .zip(datas.iter().copied())
+                .zip(provenance.iter().copied())
+                .map(|((path, data), provenance)| (path, data, provenance))
+                .collect();
+            assert_eq!(records, expected, "{compression:?}");
+            assert!(read(&bytes[..bytes.len() / 2]).is_err(), "{compression:?}");
+        }
+    }
+}
@@ -0,0 +1,183 @@
+// Alternative representation of interned sets as roaring bitmaps, which compress dense ranges of
+// IDs better than a boxed slice, both in memory and once serialized. The stats compare it with the
+// `InternedSet` representation on all the sets of the arenas.
+
+use super::{Arenas, InternedSet};
+use crate::schema::introspect::Introspect;
+use blazinterner::Interned;
+use get_size2::{GetSize, GetSizeTracker};
+use roaring::RoaringBitmap;
+use serde::de::{SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::marker::PhantomData;
+use std::mem::size_of;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct RoaringSet<T: ?Sized, Storage = T> {
+    bitmap: RoaringBitmap,
+    _phantom: PhantomData<fn() -> Interned<T, Storage>>,
+}
+
+impl<T: ?Sized, Storage> RoaringSet<T, Storage> {
+    pub fn iter(&self) -> impl Iterator<Item = Interned<T, Storage>> + '_ {
+        self.bitmap.iter().map(Interned::from_id)
+    }
+}
+
+impl<T: ?Sized, Storage> FromIterator<Interned<T, Storage>> for RoaringSet<T, Storage> {
+    fn from_iter<I: IntoIterator<Item = Interned<T, Storage>>>(iter: I) -> Self {
+        Self {
+            bitmap: iter.into_iter().map(|x| x.id()).collect(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+// The roaring crate doesn't expose the size of its containers' headers, so each container is
+// counted as a key and a vector.
+impl<T: ?Sized, Storage> GetSize for RoaringSet<T, Storage> {
+    fn get_heap_size_with_tracker<Tr: GetSizeTracker>(&self, tracker: Tr) -> (usize, Tr) {
+        let stats = self.bitmap.statistics();
+        let containers_bytes = stats.n_containers as usize * size_of::<(u16, Vec<u16>)>();
+        let values_bytes = stats.n_bytes_array_containers
+            + stats.n_bytes_run_containers
+            + stats.n_bytes_bitset_containers;
+        (containers_bytes + values_bytes as usize, tracker)
+    }
+}
+
+// Sets are serialized in the portable roaring format.
+impl<T: ?Sized, Storage> Serialize for RoaringSet<T, Storage> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut bytes = Vec::with_capacity(self.bitmap.serialized_size());
+        self.bitmap
+            .serialize_into(&mut bytes)
+            .map_err(serde::ser::Error::custom)?;
+        serializer.serialize_bytes(&bytes)
+    }
+}
+
+impl<'de, T: ?Sized, Storage> Deserialize<'de> for RoaringSet<T, Storage> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_bytes(RoaringSetVisitor(PhantomData))
+    }
+}
+
+struct RoaringSetVisitor<T: ?Sized, Storage>(PhantomData<fn() -> RoaringSet<T, Storage>>);
+
+impl<'de, T: ?Sized, Storage> Visitor<'de> for RoaringSetVisitor<T, Storage> {
+    type Value = RoaringSet<T, Storage>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a serialized roaring bitmap")
+    }
+
+    fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(RoaringSet {
+            bitmap: RoaringBitmap::deserialize_from(v).map_err(E::custom)?,
+            _phantom: PhantomData,
+        })
+    }
+
+    // Some formats such as JSON encode bytes as a sequence.
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut bytes = Vec::new();
+        while let Some(x) = seq.next_element()? {
+            bytes.push(x);
+        }
+        self.visit_bytes(&bytes)
+    }
+}
+
+// Sizes in bytes of a group of sets in both representations.
+#[derive(Default)]
+struct Comparison {
+    set_count: usize,
+    item_count: usize,
+    distinct_count: usize,
+    slice_memory: usize,
+    slice_serialized: u64,
+    roaring_memory: usize,
+    roaring_serialized: u64,
+}
+
+impl Comparison {
+    fn add<T: ?Sized, Storage>(&mut self, set: &InternedSet<T, Storage>) {
+        let roaring: RoaringSet<T, Storage> = set.iter().collect();
+        // Bitmaps can't represent duplicate IDs, which a set contains if its source did.
+        let mut distinct = set.set.to_vec();
+        distinct.dedup();
+        debug_assert!(roaring.iter().eq(distinct.iter().copied()));
+
+        self.set_count += 1;
+        self.item_count += set.set.len();
+        self.distinct_count += distinct.len();
+        self.slice_memory += set.get_size();
+        self.slice_serialized += bincode::serialized_size(set).unwrap();
+        self.roaring_memory += roaring.get_size();
+        self.roaring_serialized += bincode::serialized_size(&roaring).unwrap();
+    }
+
+    fn print(&self, title: &str) {
+        println!(
+            "  {title}: {} sets, {} items ({} distinct) | slice: {} bytes in memory, {} bytes serialized | roaring: {} bytes in memory, {} bytes serialized",
+            self.set_count,
+            self.item_count,
+            self.distinct_count,
+            self.slice_memory,
+            self.slice_serialized,
+            self.roaring_memory,
+            self.roaring_serialized,
+        );
+    }
+}
+
+impl Arenas {
+    /// Prints the sizes of all the sets of the arenas, represented as boxed slices (RLE-encoded once
+    /// serialized) and as roaring bitmaps.
+    pub fn print_set_encodings(&self) {
+        println!("Set encodings (boxed slice vs. roaring bitmap):");
+
+        // Bitmaps can't keep the order of sequences, so these are compared as if they were sets.
+        let mut comparison = Comparison::default();
+        for line in self.line.values() {
+            comparison.add(&InternedSet::new(line.impacted_objects.iter()));
+        }
+        comparison.print("InternedSeq<ImpactedObject>");
+
+        // Sets interned in arenas are stored as slices, which are compared as if they were
+        // serialized individually.
+        let mut comparison = Comparison::default();
+        for set in self.disruption_set.0.values() {
+            comparison.add(&InternedSet::new(set.iter().copied()));
+        }
+        comparison.print("InternedSeq<Disruption>");
+
+        let mut comparison = Comparison::default();
+        for set in self.application_period_set.0.values() {
+            comparison.add(&InternedSet::new(set.iter().copied()));
+        }
+        comparison.print("InternedSet<ApplicationPeriod>");
+
+        let mut comparison = Comparison::default();
+        for set in self.line_set.0.values() {
+            comparison.add(&InternedSet::new(set.iter().copied()));
+        }
+        comparison.print("InternedSeq<Line>");
+
+        let mut comparison = Comparison::default();
+        for set in self.uuid_set.0.values() {
+            comparison.add(&InternedSet::new(set.iter().copied()));
+        }
+        comparison.print("InternedSet<Uuid>");
+    }
+}
@@ -0,0 +1,121 @@
+// Churn of the disruptions over time: which disruptions appear, disappear or change from one snapshot
+// to the next. Disruptions are identified by their UUID, and the contents of a disruption with the
+// same UUID are compared by their interned handle, so no value needs to be looked up beyond the
+// disruptions themselves. Consecutive snapshots that share their set of disruptions are detected by
+// its handle alone.
+//
+// Snapshots of failed API responses don't tell which disruptions were ongoing, so they're skipped and
+// the next successful snapshot is compared with the last successful one.
+
+use super::{Arenas, Data, Disruption, DisruptionUuid};
+use blazinterner::{Interned, InternedSlice};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Changes of the disruptions between two snapshots.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Churn {
+    /// Disruptions whose UUID isn't in the previous snapshot.
+    pub appeared: usize,
+    /// Disruptions of the previous snapshot whose UUID isn't in this one.
+    pub disappeared: usize,
+    /// Disruptions whose UUID is in both snapshots, with different contents.
+    pub changed: usize,
+    /// Disruptions whose UUID is in both snapshots, with the same contents.
+    pub unchanged: usize,
+}
+
+impl Churn {
+    fn add(&mut self, other: &Churn) {
+        self.appeared += other.appeared;
+        self.disappeared += other.disappeared;
+        self.changed += other.changed;
+        self.unchanged += other.unchanged;
+    }
+}
+
+/// Churn of a successful snapshot relative to the previous successful one.
+#[derive(Debug, Serialize)]
+pub struct ChurnStep {
+    /// Position of the snapshot in the database.
+    pub index: usize,
+    /// Position of the snapshot that it's compared with.
+    pub previous: usize,
+    pub last_updated_date: String,
+    #[serde(flatten)]
+    pub churn: Churn,
+}
+
+impl ChurnStep {
+    /// Returns the sum of the churn of all the steps.
+    pub fn total(steps: &[ChurnStep]) -> Churn {
+        let mut total = Churn::default();
+        for step in steps {
+            total.add(&step.churn);
+        }
+        total
+    }
+}
+
+type Disruptions = HashMap<DisruptionUuid, Interned<Disruption>>;
+
+impl Arenas {
+    /// Returns the churn of each successful snapshot relative to the previous one, in the order of
+    /// the given snapshots.
+    pub fn churn(&self, datas: &[Interned<Data>]) -> Vec<ChurnStep> {
+        let mut steps = Vec::new();
+        let mut previous: Option<(usize, InternedSlice<Interned<Disruption>>, Disruptions)> = None;
+        for (index, data) in datas.iter().enumerate() {
+            let Data::Success(data) = self.data(*data) else {
+                continue;
+            };
+            let last_updated_date = data.last_updated_date();
+            match &mut previous {
+                Some((previous_index, previous_set, previous_disruptions))
+                    if *previous_set == data.disruptions =>
+                {
+                    steps.push(ChurnStep {
+                        index,
+                        previous: *previous_index,
+                        last_updated_date,
+                        churn: Churn {
+                            unchanged: previous_disruptions.len(),
+                            ..Churn::default()
+                        },
+                    });
+                    *previous_index = index;
+                }
+                _ => {
+                    let disruptions: Disruptions = data
+                        .disruptions(self)
+                        .iter()
+                        .map(|x| (self.disruption(*x).id, *x))
+                        .collect();
+                    if let Some((previous_index, _, previous_disruptions)) = &previous {
+                        steps.push(ChurnStep {
+                            index,
+                            previous: *previous_index,
+                            last_updated_date,
+                            churn: compare(previous_disruptions, &disruptions),
+                        });
+                    }
+                    previous = Some((index, data.disruptions, disruptions));
+                }
+            }
+        }
+        steps
+    }
+}
+
+fn compare(previous: &Disruptions, current: &Disruptions) -> Churn {
+    let mut churn = Churn::default();
+    for (id, disruption) in current {
+        match previous.get(id) {
+            None => churn.appeared += 1,
+            Some(previous) if previous != disruption => churn.changed += 1,
+            Some(_) => churn.unchanged += 1,
+        }
+    }
+    churn.disappeared = previous.len() - churn.changed - churn.unchanged;
+    churn
+}
@@ -0,0 +1,260 @@
+// Compaction of the arenas, dropping the values that no snapshot refers to anymore, e.g. the values
+// of snapshots skipped while merging databases, or those interned before a file failed to parse.
+//
+// Compaction also renumbers the surviving values in the order in which they're first reached from
+// the snapshots, so that their IDs only depend on the snapshots and not on the order in which the
+// values were interned, which follows the scheduling of threads when ingesting files in parallel.
+// Arenas are walked so that all the values referring to a given arena are numbered before visiting
+// it, and the values of each arena are visited in the order of their new IDs. Sorted sets are the
+// exception: their items are ordered by ID, so the items reached for the first time are numbered in
+// the order of their values instead. The new IDs are then known upfront, and the values are
+// interned into new arenas in the order of these IDs.
+
+use super::validate::{Id, PerArena, References, Visitor};
+use super::{ArenaSet, Arenas, ArenasMapping, Data, TryIntern};
+use crate::error::Error;
+use crate::schema::archive::Handle;
+use blazinterner::{Arena, Interned, InternedSlice, InternedStr};
+use hashbrown::HashMap;
+use std::borrow::Borrow;
+use std::convert::Infallible;
+
+// Marker of the values that aren't reachable from the snapshots.
+const UNREACHED: u32 = u32::MAX;
+
+// New ID of each value of each arena, or `UNREACHED`, along with the number of values reached so
+// far in each arena.
+struct Ranks {
+    new_ids: PerArena<u32>,
+    counts: HashMap<&'static str, u32>,
+}
+
+impl Visitor for Ranks {
+    type Error = Infallible;
+
+    fn visit<I: Id>(&mut self, id: I) -> Result<(), Infallible> {
+        let new_id = &mut I::slots(&mut self.new_ids)[id.raw_id() as usize];
+        if *new_id == UNREACHED {
+            let count = self.counts.entry(I::ARENA).or_default();
+            *new_id = *count;
+            *count += 1;
+        }
+        Ok(())
+    }
+}
+
+impl Arenas {
+    /// Returns new arenas containing only the values that the given snapshots refer to, directly
+    /// or indirectly, along with the mapping from the IDs of these arenas to the new ones. The new
+    /// IDs only depend on the given snapshots, in this order.
+    pub fn compact(&self, datas: &[Interned<Data>]) -> Result<(Arenas, ArenasMapping), Error> {
+        let ranks = self.rank(datas);
+        let new_ids = &ranks.new_ids;
+
+        let mapping = ArenasMapping {
+            string: mapped_ids(&new_ids.string, InternedStr::from_id),
+            uuid: mapped_ids(&new_ids.uuid, Interned::from_id),
+            disruption_set: mapped_ids(&new_ids.disruption_set, InternedSlice::from_id),
+            disruption: mapped_ids(&new_ids.disruption, Interned::from_id),
+            application_period_set: mapped_ids(
+                &new_ids.application_period_set,
+                InternedSlice::from_id,
+            ),
+            application_period: mapped_ids(&new_ids.application_period, Interned::from_id),
+            string_set: mapped_ids(&new_ids.string_set, InternedSlice::from_id),
+            timestamp: mapped_ids(&new_ids.timestamp, Interned::from_id),
+            line_set: mapped_ids(&new_ids.line_set, InternedSlice::from_id),
+            line: mapped_ids(&new_ids.line, Interned::from_id),
+            line_header: mapped_ids(&new_ids.line_header, Interned::from_id),
+            impacted_object: mapped_ids(&new_ids.impacted_object, Interned::from_id),
+            object: mapped_ids(&new_ids.object, Interned::from_id),
+            uuid_set: mapped_ids(&new_ids.uuid_set, InternedSlice::from_id),
+            data: mapped_ids(&new_ids.data, Interned::from_id),
+        };
+
+        let compacted = Arenas::default();
+        for i in reached(&new_ids.string) {
+            let id = compacted
+                .string
+                .try_intern(self.string.lookup(InternedStr::from_id(i)))?;
+            debug_assert_eq!(id, mapping.string(InternedStr::from_id(i)));
+        }
+        intern_reached(&self.uuid, &new_ids.uuid, |x| {
+            compacted.uuid.try_intern(x.clone())
+        })?;
+        intern_reached_set(&self.string_set, &new_ids.string_set, |x| {
+            compacted
+                .string_set
+                .try_intern(x.iter().map(|x| mapping.string(*x)))
+        })?;
+        intern_reached(&self.application_period, &new_ids.application_period, |x| {
+            compacted.application_period.try_intern(x.clone())
+        })?;
+        intern_reached(&self.timestamp, &new_ids.timestamp, |x| {
+            compacted.timestamp.try_intern(x.clone())
+        })?;
+        intern_reached_set(
+            &self.application_period_set,
+            &new_ids.application_period_set,
+            |x| {
+                compacted
+                    .application_period_set
+                    .try_intern(x.iter().map(|x| mapping.application_period(*x)))
+            },
+        )?;
+        intern_reached(&self.object, &new_ids.object, |x| {
+            compacted.object.try_intern(x.map(&mapping))
+        })?;
+        intern_reached_set(&self.uuid_set, &new_ids.uuid_set, |x| {
+            compacted
+                .uuid_set
+                .try_intern(x.iter().map(|x| mapping.uuid(*x)))
+        })?;
+        intern_reached(&self.impacted_object, &new_ids.impacted_object, |x| {
+            compacted.impacted_object.try_intern(x.map(&mapping))
+        })?;
+        intern_reached(&self.line_header, &new_ids.line_header, |x| {
+            compacted.line_header.try_intern(x.map(&mapping))
+        })?;
+        intern_reached(&self.line, &new_ids.line, |x| {
+            compacted.line.try_intern(x.map(&mapping))
+        })?;
+        intern_reached_set(&self.line_set, &new_ids.line_set, |x| {
+            compacted
+                .line_set
+                .try_intern(x.iter().map(|x| mapping.line(*x)))
+        })?;
+        intern_reached(&self.disruption, &new_ids.disruption, |x| {
+            compacted.disruption.try_intern(x.map(&mapping))
+        })?;
+        intern_reached_set(&self.disruption_set, &new_ids.disruption_set, |x| {
+            compacted
+                .disruption_set
+                .try_intern(x.iter().map(|x| mapping.disruption(*x)))
+        })?;
+        intern_reached(&self.data, &new_ids.data, |x| {
+            compacted.data.try_intern(x.map(&mapping))
+        })?;
+
+        Ok((compacted, mapping))
+    }
+
+    /// Returns the total number of values in the arenas.
+    pub fn value_count(&self) -> usize {
+        self.string.strings()
+            + self.uuid.len()
+            + self.disruption_set.len()
+            + self.disruption.len()
+            + self.application_period_set.len()
+            + self.application_period.len()
+            + self.string_set.len()
+            + self.timestamp.len()
+            + self.line_set.len()
+            + self.line.len()
+            + self.line_header.len()
+            + self.impacted_object.len()
+            + self.object.len()
+            + self.uuid_set.len()
+            + self.data.len()
+    }
+
+    // Numbers the values reachable from the snapshots. Arenas are processed so that all the values
+    // referring to a given arena are numbered before visiting it.
+    fn rank(&self, datas: &[Interned<Data>]) -> Ranks {
+        let mut ranks = Ranks {
+            new_ids: PerArena::new(self, UNREACHED),
+            counts: HashMap::new(),
+        };
+        let Ok(()) = ranks.visit_all(datas.iter().copied());
+        rank_arena(&self.data, &mut ranks);
+        rank_arena_set(&self.disruption_set, &mut ranks, |_| ());
+        rank_arena(&self.disruption, &mut ranks);
+        rank_arena_set(&self.application_period_set, &mut ranks, |items| {
+            items.sort_unstable_by_key(|x| {
+                let period = self.application_period.lookup_ref(*x);
+                (period.begin.0, period.end.0)
+            })
+        });
+        rank_arena_set(&self.string_set, &mut ranks, |items| {
+            items.sort_unstable_by_key(|x| self.string.lookup(*x))
+        });
+        rank_arena_set(&self.line_set, &mut ranks, |_| ());
+        rank_arena(&self.line, &mut ranks);
+        rank_arena(&self.line_header, &mut ranks);
+        rank_arena(&self.impacted_object, &mut ranks);
+        rank_arena_set(&self.uuid_set, &mut ranks, |items| {
+            items.sort_unstable_by_key(|x| self.uuid.lookup_ref(*x).0)
+        });
+        rank_arena(&self.object, &mut ranks);
+        ranks
+    }
+}
+
+// Numbers the values referred to by the reached values of the arena.
+fn rank_arena<T: References>(arena: &Arena<T>, ranks: &mut Ranks)
+where
+    Interned<T>: Id,
+{
+    for i in reached(<Interned<T>>::slots(&mut ranks.new_ids)) {
+        let Ok(()) = arena.lookup_ref(Interned::from_id(i)).visit_ids(ranks);
+    }
+}
+
+// Numbers the items of the reached sets of the arena, in the order given by `sort` for each set.
+fn rank_arena_set<H: Handle + Id, const SORTED: bool>(
+    arena: &ArenaSet<H, SORTED>,
+    ranks: &mut Ranks,
+    sort: impl Fn(&mut [H]),
+) where
+    InternedSlice<H>: Id,
+{
+    let mut items = Vec::new();
+    for i in reached(<InternedSlice<H>>::slots(&mut ranks.new_ids)) {
+        items.extend_from_slice(arena.lookup(InternedSlice::from_id(i)).0);
+        sort(&mut items);
+        let Ok(()) = ranks.visit_all(items.drain(..));
+    }
+}
+
+// Old IDs of the reached values, in the order of their new IDs.
+fn reached(new_ids: &[u32]) -> Vec<u32> {
+    let mut old_ids = vec![0; new_ids.iter().filter(|&&id| id != UNREACHED).count()];
+    for (i, &new_id) in new_ids.iter().enumerate() {
+        if new_id != UNREACHED {
+            old_ids[new_id as usize] = i as u32;
+        }
+    }
+    old_ids
+}
+
+// Values that aren't reached are mapped to an ID out of bounds, as nothing refers to them.
+fn mapped_ids<I>(new_ids: &[u32], from_id: impl Fn(u32) -> I) -> Box<[I]> {
+    new_ids.iter().map(|&id| from_id(id)).collect()
+}
+
+fn intern_reached<T, Storage>(
+    arena: &Arena<T, Storage>,
+    new_ids: &[u32],
+    mut f: impl FnMut(&T) -> Result<Interned<T, Storage>, Error>,
+) -> Result<(), Error>
+where
+    Storage: Borrow<T>,
+{
+    for (new_id, i) in reached(new_ids).into_iter().enumerate() {
+        let id = f(arena.lookup_ref(Interned::from_id(i)))?;
+        debug_assert_eq!(id.id(), new_id as u32);
+    }
+    Ok(())
+}
+
+fn intern_reached_set<H: Handle, const SORTED: bool>(
+    arena: &ArenaSet<H, SORTED>,
+    new_ids: &[u32],
+    mut f: impl FnMut(&[H]) -> Result<InternedSlice<H>, Error>,
+) -> Result<(), Error> {
+    for (new_id, i) in reached(new_ids).into_iter().enumerate() {
+        let id = f(arena.lookup(InternedSlice::from_id(i)).0)?;
+        debug_assert_eq!(id.id(), new_id as u32);
+    }
+    Ok(())
+}
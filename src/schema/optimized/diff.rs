@@ -0,0 +1,98 @@
+// Comparison of the contents of two sets of arenas, e.g. of a database appended to incrementally
+// and of the same files built from scratch. The IDs of equal values generally differ between the
+// two, as they depend on the order in which values were interned, so both sides are merged into
+// fresh arenas where equal values share an ID.
+
+use super::validate::Id;
+use super::{Arenas, ArenasMapping, Data};
+use crate::error::Error;
+use crate::stats::InternerStats;
+use blazinterner::Interned;
+
+/// Sizes of an arena on each side, along with the number of values found on one side only.
+pub struct InternerDiff {
+    pub left: InternerStats,
+    pub right: InternerStats,
+    pub only_left: usize,
+    pub only_right: usize,
+}
+
+/// Differences between two sets of arenas.
+pub struct ArenasDiff {
+    /// One entry per arena, with the same names and order as `Arenas::interner_stats`.
+    pub interners: Vec<InternerDiff>,
+    left: ArenasMapping,
+    right: ArenasMapping,
+}
+
+impl ArenasDiff {
+    /// Returns whether a snapshot of the left arenas is identical to one of the right arenas.
+    pub fn same_data(&self, left: Interned<Data>, right: Interned<Data>) -> bool {
+        self.left.data(left) == self.right.data(right)
+    }
+}
+
+impl Arenas {
+    /// Compares the values of these arenas with those of the other ones.
+    pub fn diff(&self, other: &Arenas) -> Result<ArenasDiff, Error> {
+        let merged = Arenas::default();
+        let left = merged.merge(self)?;
+        let right = merged.merge(other)?;
+
+        let unique = [
+            count_unique(&left.string, &right.string, &merged),
+            count_unique(&left.uuid, &right.uuid, &merged),
+            count_unique(&left.data, &right.data, &merged),
+            count_unique(&left.disruption_set, &right.disruption_set, &merged),
+            count_unique(&left.disruption, &right.disruption, &merged),
+            count_unique(
+                &left.application_period_set,
+                &right.application_period_set,
+                &merged,
+            ),
+            count_unique(&left.application_period, &right.application_period, &merged),
+            count_unique(&left.string_set, &right.string_set, &merged),
+            count_unique(&left.timestamp, &right.timestamp, &merged),
+            count_unique(&left.line_set, &right.line_set, &merged),
+            count_unique(&left.line, &right.line, &merged),
+            count_unique(&left.line_header, &right.line_header, &merged),
+            count_unique(&left.impacted_object, &right.impacted_object, &merged),
+            count_unique(&left.object, &right.object, &merged),
+            count_unique(&left.uuid_set, &right.uuid_set, &merged),
+        ];
+        let interners = self
+            .interner_stats()
+            .into_iter()
+            .zip(other.interner_stats())
+            .zip(unique)
+            .map(|((left, right), (only_left, only_right))| InternerDiff {
+                left,
+                right,
+                only_left,
+                only_right,
+            })
+            .collect();
+
+        Ok(ArenasDiff {
+            interners,
+            left,
+            right,
+        })
+    }
+}
+
+// Counts the values of each side that the other side has no equal value for, given the IDs that
+// the values of each side were mapped to in the merged arenas.
+fn count_unique<H: Id>(left: &[H], right: &[H], merged: &Arenas) -> (usize, usize) {
+    let reached = |ids: &[H]| {
+        let mut reached = vec![false; H::arena_len(merged)];
+        for id in ids {
+            reached[id.raw_id() as usize] = true;
+        }
+        reached
+    };
+    let unique =
+        |ids: &[H], other: &[bool]| ids.iter().filter(|id| !other[id.raw_id() as usize]).count();
+    let (left_reached, right_reached) = (reached(left), reached(right));
+    (unique(left, &right_reached), unique(right, &left_reached))
+}
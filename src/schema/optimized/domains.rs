@@ -0,0 +1,359 @@
+// Fields that refer to the string arena, and how much content they share. All the fields intern
+// their strings into a single arena, which stores a string used by several fields only once. Tagging
+// each string with the fields that refer to it tells whether this sharing actually happens, or
+// whether one arena per field would cost about the same.
+//
+// Like reference counts, domains are structural: they are collected from the values of the arenas,
+// regardless of how many snapshots refer to these values.
+//
+// The lengths of the strings tell where the bytes of the arena go, e.g. whether a few long messages
+// outweigh the many short identifiers, to guide which fields deserve a more compact encoding.
+
+use super::refcount::truncated;
+use super::{Arenas, Data};
+use crate::schema::introspect::Introspect;
+use blazinterner::InternedStr;
+use get_size2::GetSize;
+
+/// Field of the optimized schema that refers to the string arena.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Domain {
+    DataErrorError,
+    DataErrorMessage,
+    DisruptionCause,
+    DisruptionSeverity,
+    DisruptionTags,
+    DisruptionTitle,
+    DisruptionMessage,
+    DisruptionShortMessage,
+    LineHeaderId,
+    LineHeaderName,
+    LineHeaderShortName,
+    LineHeaderMode,
+    LineHeaderNetworkId,
+    ObjectTyp,
+    ObjectId,
+    ObjectName,
+}
+
+/// Set of domains, with one bit per domain.
+pub(super) type Domains = u32;
+
+impl Domain {
+    const ALL: [Domain; 16] = [
+        Domain::DataErrorError,
+        Domain::DataErrorMessage,
+        Domain::DisruptionCause,
+        Domain::DisruptionSeverity,
+        Domain::DisruptionTags,
+        Domain::DisruptionTitle,
+        Domain::DisruptionMessage,
+        Domain::DisruptionShortMessage,
+        Domain::LineHeaderId,
+        Domain::LineHeaderName,
+        Domain::LineHeaderShortName,
+        Domain::LineHeaderMode,
+        Domain::LineHeaderNetworkId,
+        Domain::ObjectTyp,
+        Domain::ObjectId,
+        Domain::ObjectName,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Domain::DataErrorError => "DataError.error",
+            Domain::DataErrorMessage => "DataError.message",
+            Domain::DisruptionCause => "Disruption.cause",
+            Domain::DisruptionSeverity => "Disruption.severity",
+            Domain::DisruptionTags => "Disruption.tags",
+            Domain::DisruptionTitle => "Disruption.title",
+            Domain::DisruptionMessage => "Disruption.message",
+            Domain::DisruptionShortMessage => "Disruption.short_message",
+            Domain::LineHeaderId => "LineHeader.id",
+            Domain::LineHeaderName => "LineHeader.name",
+            Domain::LineHeaderShortName => "LineHeader.short_name",
+            Domain::LineHeaderMode => "LineHeader.mode",
+            Domain::LineHeaderNetworkId => "LineHeader.network_id",
+            Domain::ObjectTyp => "Object.typ",
+            Domain::ObjectId => "Object.id",
+            Domain::ObjectName => "Object.name",
+        }
+    }
+
+    pub(super) fn bit(self) -> Domains {
+        1 << self as u32
+    }
+
+    fn category(self) -> Category {
+        match self {
+            Domain::DataErrorError
+            | Domain::DataErrorMessage
+            | Domain::DisruptionTitle
+            | Domain::DisruptionMessage
+            | Domain::DisruptionShortMessage => Category::Text,
+            Domain::LineHeaderId | Domain::LineHeaderNetworkId | Domain::ObjectId => {
+                Category::Identifier
+            }
+            Domain::LineHeaderName | Domain::LineHeaderShortName | Domain::ObjectName => {
+                Category::Name
+            }
+            Domain::DisruptionCause
+            | Domain::DisruptionSeverity
+            | Domain::DisruptionTags
+            | Domain::LineHeaderMode
+            | Domain::ObjectTyp => Category::Code,
+        }
+    }
+}
+
+fn names(domains: Domains) -> String {
+    if domains == 0 {
+        return "unreferenced".to_owned();
+    }
+    (Domain::ALL.iter())
+        .filter(|x| domains & x.bit() != 0)
+        .map(|x| x.name())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Kind of content of the fields that refer to the string arena.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Category {
+    /// Titles and messages written for humans.
+    Text,
+    /// Identifiers of lines, networks and objects.
+    Identifier,
+    /// Names of lines and objects.
+    Name,
+    /// Values from a small set, like causes and tags.
+    Code,
+}
+
+impl Category {
+    const ALL: [Category; 4] = [
+        Category::Text,
+        Category::Identifier,
+        Category::Name,
+        Category::Code,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Category::Text => "text",
+            Category::Identifier => "identifiers",
+            Category::Name => "names",
+            Category::Code => "codes",
+        }
+    }
+
+    fn domains(self) -> Domains {
+        (Domain::ALL.iter())
+            .filter(|x| x.category() == self)
+            .fold(0, |domains, x| domains | x.bit())
+    }
+}
+
+/// Number of strings and bytes of the strings whose length is in [min, max).
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(super) struct LengthBucket {
+    pub(super) min: usize,
+    pub(super) max: usize,
+    pub(super) strings: usize,
+    pub(super) bytes: usize,
+}
+
+/// Groups the given lengths into buckets of powers of two: [0, 1), [1, 2), [2, 4), [4, 8), etc.,
+/// up to the bucket of the longest string.
+pub(super) fn length_histogram(lengths: impl Iterator<Item = usize>) -> Vec<LengthBucket> {
+    let mut buckets: Vec<LengthBucket> = Vec::new();
+    for len in lengths {
+        let i = match len {
+            0 => 0,
+            _ => len.ilog2() as usize + 1,
+        };
+        while buckets.len() <= i {
+            let min = match buckets.len() {
+                0 => 0,
+                j => 1 << (j - 1),
+            };
+            buckets.push(LengthBucket {
+                min,
+                max: 1 << buckets.len(),
+                ..Default::default()
+            });
+        }
+        buckets[i].strings += 1;
+        buckets[i].bytes += len;
+    }
+    buckets
+}
+
+impl Arenas {
+    /// Returns the domains that refer to each string, indexed by the ID of the string. Strings
+    /// that no value refers to have no domain.
+    pub(super) fn string_domains(&self) -> Vec<Domains> {
+        let mut domains = vec![0; self.string.strings()];
+        let mut tag = |string: InternedStr, domain: Domain| {
+            domains[string.id() as usize] |= domain.bit();
+        };
+
+        for data in self.data.values() {
+            if let Data::Error(error) = data {
+                tag(error.error, Domain::DataErrorError);
+                tag(error.message, Domain::DataErrorMessage);
+            }
+        }
+        for disruption in self.disruption.values() {
+            tag(disruption.cause, Domain::DisruptionCause);
+            if let Some(severity) = disruption.severity.other() {
+                tag(severity, Domain::DisruptionSeverity);
+            }
+            if let Some(tags) = disruption.tags {
+                for &x in self.string_set.lookup(tags).0 {
+                    tag(x, Domain::DisruptionTags);
+                }
+            }
+            tag(disruption.title, Domain::DisruptionTitle);
+            if let Some(message) = disruption.message {
+                tag(message, Domain::DisruptionMessage);
+            }
+            if let Some(short_message) = disruption.short_message {
+                tag(short_message, Domain::DisruptionShortMessage);
+            }
+        }
+        for header in self.line_header.values() {
+            tag(header.id, Domain::LineHeaderId);
+            tag(header.name, Domain::LineHeaderName);
+            tag(header.short_name, Domain::LineHeaderShortName);
+            if let Some(mode) = header.mode.other() {
+                tag(mode, Domain::LineHeaderMode);
+            }
+            tag(header.network_id, Domain::LineHeaderNetworkId);
+        }
+        for object in self.object.values() {
+            tag(object.typ, Domain::ObjectTyp);
+            tag(object.id, Domain::ObjectId);
+            tag(object.name, Domain::ObjectName);
+        }
+        domains
+    }
+
+    // Prints the strings of each domain, the pairs of domains that share strings, and compares the
+    // string arena with one arena per domain, in which a string shared by several domains is stored
+    // once per domain.
+    pub(super) fn print_string_domains(&self) {
+        let domains = self.string_domains();
+        let len = |i: usize| self.string.lookup(InternedStr::from_id(i as u32)).len();
+        let sum = |filter: &dyn Fn(Domains) -> bool| {
+            (domains.iter().enumerate())
+                .filter(|(_, &x)| filter(x))
+                .fold((0, 0), |(count, bytes), (i, _)| (count + 1, bytes + len(i)))
+        };
+
+        println!("String domains (fields referring to each string, and strings shared with other fields):");
+        for domain in Domain::ALL {
+            let bit = domain.bit();
+            let (count, bytes) = sum(&|x| x & bit != 0);
+            if count == 0 {
+                continue;
+            }
+            let (shared, shared_bytes) = sum(&|x| x & bit != 0 && x != bit);
+            println!(
+                "  {}: {count} strings ({bytes} bytes) | {shared} shared with other fields ({shared_bytes} bytes, {:.02}%)",
+                domain.name(),
+                shared_bytes as f64 * 100.0 / bytes as f64,
+            );
+        }
+
+        let mut pairs = Vec::new();
+        for (i, a) in Domain::ALL.iter().enumerate() {
+            for b in &Domain::ALL[i + 1..] {
+                let both = a.bit() | b.bit();
+                let (count, bytes) = sum(&|x| x & both == both);
+                if count != 0 {
+                    pairs.push((a.name(), b.name(), count, bytes));
+                }
+            }
+        }
+        pairs.sort_unstable_by_key(|&(_, _, _, bytes)| std::cmp::Reverse(bytes));
+        println!(
+            "Cross-domain sharing ({} pairs of fields share strings):",
+            pairs.len()
+        );
+        for (a, b, count, bytes) in pairs {
+            println!("  {a} & {b}: {count} strings ({bytes} bytes)");
+        }
+
+        // Each string costs the same overhead in any string arena, for its range in the buffer and
+        // its hash table entry.
+        let strings = self.string.strings();
+        let arena_bytes = self.string.get_size();
+        let overhead = (arena_bytes - self.string.bytes()) as f64 / strings as f64;
+        let (copies, copy_bytes) = (domains.iter().enumerate())
+            .map(|(i, x)| (x.count_ones() as usize, x.count_ones() as usize * len(i)))
+            .fold((0, 0), |(a, b), (c, d)| (a + c, b + d));
+        let (unreferenced, _) = sum(&|x| x == 0);
+        let per_domain_bytes = copy_bytes as f64 + copies as f64 * overhead;
+        println!(
+            "One string arena per field would store {copies} strings ({copy_bytes} bytes) in about {per_domain_bytes:.0} bytes, vs. {strings} strings ({} bytes, {unreferenced} unreferenced) in {arena_bytes} bytes for the shared arena (relative size = {:.02}%)",
+            self.string.bytes(),
+            per_domain_bytes * 100.0 / arena_bytes as f64,
+        );
+    }
+
+    /// Prints a histogram of the lengths of the interned strings, the bytes of each category of
+    /// fields, and the `top` longest strings.
+    pub fn print_string_lengths(&self, top: usize) {
+        let domains = self.string_domains();
+        let strings: Vec<&str> = self.string.values().collect();
+        let total_bytes = self.string.bytes();
+
+        println!("String lengths (in bytes):");
+        let histogram = length_histogram(strings.iter().map(|x| x.len()));
+        let max_strings = histogram.iter().map(|x| x.strings).max().unwrap_or(0);
+        for bucket in &histogram {
+            // Bars are scaled to the most populated bucket.
+            let bar = "#".repeat((bucket.strings * 40).div_ceil(max_strings.max(1)));
+            println!(
+                "  [{:>5}, {:>5}): {:>7} strings ({:>6.02}%) | {:>9} bytes ({:>6.02}%) {bar}",
+                bucket.min,
+                bucket.max,
+                bucket.strings,
+                bucket.strings as f64 * 100.0 / strings.len() as f64,
+                bucket.bytes,
+                bucket.bytes as f64 * 100.0 / total_bytes as f64,
+            );
+        }
+
+        // A string referred to by several categories counts in each of them.
+        println!("String bytes per category of fields:");
+        for category in Category::ALL {
+            let mask = category.domains();
+            let (count, bytes) = (strings.iter().zip(&domains))
+                .filter(|(_, &x)| x & mask != 0)
+                .fold((0, 0), |(count, bytes), (x, _)| {
+                    (count + 1, bytes + x.len())
+                });
+            println!(
+                "  {}: {count} strings | {bytes} bytes ({:.02}%) | mean {:.02} bytes per string",
+                category.name(),
+                bytes as f64 * 100.0 / total_bytes as f64,
+                bytes as f64 / count as f64,
+            );
+        }
+
+        // Ties are listed by ID, i.e. in the order in which strings were first interned.
+        let mut longest: Vec<usize> = (0..strings.len()).collect();
+        longest.sort_by_key(|&i| std::cmp::Reverse(strings[i].len()));
+        println!("Longest strings:");
+        for &i in longest.iter().take(top) {
+            println!(
+                "  {:>7} bytes | {} | {}",
+                strings[i].len(),
+                names(domains[i]),
+                truncated(format!("{:?}", strings[i])),
+            );
+        }
+    }
+}
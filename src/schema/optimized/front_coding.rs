@@ -0,0 +1,189 @@
+// Front-coded serialization of the string arena. Many interned strings share long prefixes, such as
+// the `line:IDFM:` of line IDs, so the strings are sorted and each one is stored as the length of
+// the prefix it shares with the previous one, followed by the rest of it. The IDs of the strings in
+// sorted order are stored alongside, to restore the original IDs when deserializing.
+//
+// Only the string arena differs from the default serialization of the arenas.
+
+use super::{
+    ApplicationPeriod, ArenaSeq, ArenaSet, Arenas, Data, Disruption, DisruptionUuid,
+    ImpactedObject, Line, LineHeader, LocalTimestampSeconds, Object, UuidStorage,
+};
+use crate::schema::Uuid;
+use blazinterner::{Arena, ArenaStr, Interned, InternedStr};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+// Fields of the arenas, in the order in which they are serialized.
+type Fields = (
+    FrontCodedStr<ArenaStr>,
+    Arena<Uuid, UuidStorage>,
+    ArenaSeq<Interned<Disruption>>,
+    Arena<Disruption>,
+    ArenaSet<Interned<ApplicationPeriod>>,
+    Arena<ApplicationPeriod>,
+    ArenaSet<InternedStr>,
+    Arena<LocalTimestampSeconds>,
+    ArenaSeq<Interned<Line>>,
+    Arena<Line>,
+    Arena<LineHeader>,
+    Arena<ImpactedObject>,
+    Arena<Object>,
+    ArenaSet<DisruptionUuid>,
+    Arena<Data>,
+);
+
+/// Arenas serialized with a front-coded string arena.
+#[derive(Debug, PartialEq, Eq)]
+pub struct FrontCodedArenas(pub Arenas);
+
+impl Serialize for FrontCodedArenas {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let arenas = &self.0;
+        (
+            FrontCodedStr(&arenas.string),
+            &arenas.uuid,
+            &arenas.disruption_set,
+            &arenas.disruption,
+            &arenas.application_period_set,
+            &arenas.application_period,
+            &arenas.string_set,
+            &arenas.timestamp,
+            &arenas.line_set,
+            &arenas.line,
+            &arenas.line_header,
+            &arenas.impacted_object,
+            &arenas.object,
+            &arenas.uuid_set,
+            &arenas.data,
+        )
+            .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for FrontCodedArenas {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (
+            FrontCodedStr(string),
+            uuid,
+            disruption_set,
+            disruption,
+            application_period_set,
+            application_period,
+            string_set,
+            timestamp,
+            line_set,
+            line,
+            line_header,
+            impacted_object,
+            object,
+            uuid_set,
+            data,
+        ): Fields = Deserialize::deserialize(deserializer)?;
+        Ok(FrontCodedArenas(Arenas {
+            string,
+            uuid,
+            disruption_set,
+            disruption,
+            application_period_set,
+            application_period,
+            string_set,
+            timestamp,
+            line_set,
+            line,
+            line_header,
+            impacted_object,
+            object,
+            uuid_set,
+            data,
+        }))
+    }
+}
+
+struct FrontCodedStr<T>(T);
+
+impl Serialize for FrontCodedStr<&ArenaStr> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let arena = self.0;
+        let mut order: Vec<u32> = (0..arena.strings() as u32).collect();
+        order.sort_unstable_by_key(|&i| arena.lookup(InternedStr::from_id(i)));
+
+        let mut previous = "";
+        let entries: Vec<(u32, &str)> = order
+            .iter()
+            .map(|&i| {
+                let s = arena.lookup(InternedStr::from_id(i));
+                let prefix_len = common_prefix_len(previous, s);
+                previous = s;
+                (prefix_len as u32, &s[prefix_len..])
+            })
+            .collect();
+
+        (order, entries).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for FrontCodedStr<ArenaStr> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (order, entries): (Vec<u32>, Vec<(u32, String)>) =
+            Deserialize::deserialize(deserializer)?;
+        if order.len() != entries.len() {
+            return Err(D::Error::custom(format!(
+                "front-coded strings: {} IDs for {} strings",
+                order.len(),
+                entries.len(),
+            )));
+        }
+
+        let mut strings: Vec<Option<String>> = vec![None; order.len()];
+        let mut previous = String::new();
+        for (&id, (prefix_len, suffix)) in order.iter().zip(entries) {
+            let prefix_len = prefix_len as usize;
+            if !previous.is_char_boundary(prefix_len) {
+                return Err(D::Error::custom(format!(
+                    "front-coded strings: invalid prefix length {prefix_len} after {previous:?}",
+                )));
+            }
+            previous.truncate(prefix_len);
+            previous.push_str(&suffix);
+
+            match strings.get_mut(id as usize) {
+                Some(slot @ None) => *slot = Some(previous.clone()),
+                _ => {
+                    return Err(D::Error::custom(format!(
+                        "front-coded strings: invalid or duplicate ID {id}",
+                    )))
+                }
+            }
+        }
+
+        let bytes = strings.iter().flatten().map(String::len).sum();
+        let mut arena = ArenaStr::with_capacity(strings.len(), bytes);
+        // All the IDs are distinct and in bounds, so each string has been set.
+        for s in strings.iter().flatten() {
+            arena.push_mut(s);
+        }
+        Ok(FrontCodedStr(arena))
+    }
+}
+
+// Returns the length in bytes of the longest common prefix of both strings, on a character
+// boundary so that the suffixes remain valid strings.
+fn common_prefix_len(a: &str, b: &str) -> usize {
+    a.char_indices()
+        .zip(b.chars())
+        .find(|&((_, x), y)| x != y)
+        .map_or(a.len().min(b.len()), |((i, _), _)| i)
+}
@@ -0,0 +1,296 @@
+// Export of the disruptions as GTFS-Realtime feeds of service alerts, so that archived snapshots can
+// be replayed into standard transit tooling. The messages below are the subset of
+// `gtfs-realtime.proto` that service alerts need, with the same field numbers, so the feeds decode
+// with any GTFS-Realtime parser.
+//
+// Each impacted object of a line becomes an informed entity: the route of the line for impacted
+// lines, and the stop on this route for other objects (stop areas and stop points). The IDs are the
+// ones of the source, e.g. "line:IDFM:C01371", which match the IDs of the static GTFS of the network.
+
+use super::known::{KnownOr, Mode, Severity};
+use super::{Arenas, Data, DataSuccess, Disruption, DisruptionUuid};
+use blazinterner::Interned;
+use prost::Message;
+use std::collections::HashMap;
+
+// Language of the titles and messages.
+const LANGUAGE: &str = "fr";
+
+#[derive(Clone, PartialEq, Message)]
+pub(super) struct FeedMessage {
+    #[prost(message, required, tag = "1")]
+    pub(super) header: FeedHeader,
+    #[prost(message, repeated, tag = "2")]
+    pub(super) entity: Vec<FeedEntity>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(super) struct FeedHeader {
+    #[prost(string, required, tag = "1")]
+    pub(super) gtfs_realtime_version: String,
+    #[prost(enumeration = "Incrementality", optional, tag = "2")]
+    pub(super) incrementality: Option<i32>,
+    /// Seconds since the Unix epoch.
+    #[prost(uint64, optional, tag = "3")]
+    pub(super) timestamp: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, prost::Enumeration)]
+#[repr(i32)]
+pub(super) enum Incrementality {
+    FullDataset = 0,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(super) struct FeedEntity {
+    #[prost(string, required, tag = "1")]
+    pub(super) id: String,
+    #[prost(message, optional, tag = "5")]
+    pub(super) alert: Option<Alert>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(super) struct Alert {
+    #[prost(message, repeated, tag = "1")]
+    pub(super) active_period: Vec<TimeRange>,
+    #[prost(message, repeated, tag = "5")]
+    pub(super) informed_entity: Vec<EntitySelector>,
+    #[prost(enumeration = "Cause", optional, tag = "6")]
+    pub(super) cause: Option<i32>,
+    #[prost(enumeration = "Effect", optional, tag = "7")]
+    pub(super) effect: Option<i32>,
+    #[prost(message, optional, tag = "10")]
+    pub(super) header_text: Option<TranslatedString>,
+    #[prost(message, optional, tag = "11")]
+    pub(super) description_text: Option<TranslatedString>,
+    #[prost(enumeration = "SeverityLevel", optional, tag = "14")]
+    pub(super) severity_level: Option<i32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(super) struct TimeRange {
+    #[prost(uint64, optional, tag = "1")]
+    pub(super) start: Option<u64>,
+    #[prost(uint64, optional, tag = "2")]
+    pub(super) end: Option<u64>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(super) struct EntitySelector {
+    #[prost(string, optional, tag = "2")]
+    pub(super) route_id: Option<String>,
+    #[prost(int32, optional, tag = "3")]
+    pub(super) route_type: Option<i32>,
+    #[prost(string, optional, tag = "5")]
+    pub(super) stop_id: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(super) struct TranslatedString {
+    #[prost(message, repeated, tag = "1")]
+    pub(super) translation: Vec<Translation>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(super) struct Translation {
+    #[prost(string, required, tag = "1")]
+    pub(super) text: String,
+    #[prost(string, optional, tag = "2")]
+    pub(super) language: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, prost::Enumeration)]
+#[repr(i32)]
+pub(super) enum Cause {
+    Unknown = 1,
+    Other = 2,
+    Maintenance = 9,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, prost::Enumeration)]
+#[repr(i32)]
+pub(super) enum Effect {
+    NoService = 1,
+    ReducedService = 2,
+    Other = 7,
+    Unknown = 8,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, prost::Enumeration)]
+#[repr(i32)]
+pub(super) enum SeverityLevel {
+    Unknown = 1,
+    Info = 2,
+    Warning = 3,
+    Severe = 4,
+}
+
+// Informed entities of each disruption of a snapshot.
+type InformedEntities = HashMap<DisruptionUuid, Vec<EntitySelector>>;
+
+impl Arenas {
+    /// Returns the encoded GTFS-Realtime feed of the disruptions of a snapshot, or `None` for a
+    /// failed API response.
+    pub fn gtfs_feed(&self, data: Interned<Data>) -> Option<Vec<u8>> {
+        let Data::Success(data) = self.data(data) else {
+            return None;
+        };
+        let entities = self.informed_entities(data);
+        let mut alerts: Vec<(Interned<Disruption>, &[EntitySelector])> = Vec::new();
+        let mut seen = Vec::new();
+        for &disruption in data.disruptions(self) {
+            let id = self.disruption(disruption).id;
+            // A feed lists each disruption once.
+            if !seen.contains(&id) {
+                seen.push(id);
+                alerts.push((disruption, entities.get(&id).map_or(&[], Vec::as_slice)));
+            }
+        }
+        Some(self.feed(data, alerts).encode_to_vec())
+    }
+
+    /// Returns the encoded GTFS-Realtime feed of all the disruptions of the given snapshots, each
+    /// with its last version and the entities that it informed in the last snapshot containing it,
+    /// or `None` if there is no successful snapshot. The feed is timestamped with the last
+    /// successful snapshot.
+    pub fn gtfs_merged_feed(&self, datas: &[Interned<Data>]) -> Option<Vec<u8>> {
+        let mut indices: HashMap<DisruptionUuid, usize> = HashMap::new();
+        let mut alerts: Vec<(Interned<Disruption>, Vec<EntitySelector>)> = Vec::new();
+        let mut last = None;
+        for data in datas {
+            let Data::Success(data) = self.data(*data) else {
+                continue;
+            };
+            let mut entities = self.informed_entities(data);
+            for &disruption in data.disruptions(self) {
+                let id = self.disruption(disruption).id;
+                let alert = (disruption, entities.remove(&id).unwrap_or_default());
+                match indices.get(&id) {
+                    Some(&i) => alerts[i] = alert,
+                    None => {
+                        indices.insert(id, alerts.len());
+                        alerts.push(alert);
+                    }
+                }
+            }
+            last = Some(data);
+        }
+        let alerts = alerts.iter().map(|(x, entities)| (*x, entities.as_slice()));
+        Some(self.feed(last?, alerts).encode_to_vec())
+    }
+
+    fn feed<'a>(
+        &self,
+        data: &DataSuccess,
+        alerts: impl IntoIterator<Item = (Interned<Disruption>, &'a [EntitySelector])>,
+    ) -> FeedMessage {
+        FeedMessage {
+            header: FeedHeader {
+                gtfs_realtime_version: "2.0".to_owned(),
+                incrementality: Some(Incrementality::FullDataset.into()),
+                timestamp: u64::try_from(data.last_updated_date.0 / 1000).ok(),
+            },
+            entity: alerts
+                .into_iter()
+                .map(|(disruption, entities)| self.feed_entity(disruption, entities))
+                .collect(),
+        }
+    }
+
+    fn feed_entity(
+        &self,
+        disruption: Interned<Disruption>,
+        entities: &[EntitySelector],
+    ) -> FeedEntity {
+        let disruption = self.disruption(disruption);
+        let text = |text: &str| TranslatedString {
+            translation: vec![Translation {
+                text: text.to_owned(),
+                language: Some(LANGUAGE.to_owned()),
+            }],
+        };
+        // Planned works are the only cause that GTFS-Realtime has an equivalent for.
+        let cause = match self.string.lookup(disruption.cause) {
+            "TRAVAUX" => Cause::Maintenance,
+            "PERTURBATION" => Cause::Unknown,
+            _ => Cause::Other,
+        };
+        let (effect, severity) = match disruption.severity.get() {
+            KnownOr::Known(Severity::Blocking) => (Effect::NoService, SeverityLevel::Severe),
+            KnownOr::Known(Severity::Disrupted) => (Effect::ReducedService, SeverityLevel::Warning),
+            KnownOr::Known(Severity::Information) => (Effect::Other, SeverityLevel::Info),
+            KnownOr::Other(_) => (Effect::Unknown, SeverityLevel::Unknown),
+        };
+        let active_period = self
+            .application_period_set
+            .lookup(disruption.application_periods)
+            .0
+            .iter()
+            .map(|period| {
+                let period = self.application_period.lookup_ref(*period);
+                TimeRange {
+                    start: u64::try_from(period.begin.0).ok(),
+                    end: u64::try_from(period.end.0).ok(),
+                }
+            })
+            .collect();
+
+        FeedEntity {
+            id: self.uuid.lookup_ref(disruption.id).0.to_string(),
+            alert: Some(Alert {
+                active_period,
+                informed_entity: entities.to_vec(),
+                cause: Some(cause.into()),
+                effect: Some(effect.into()),
+                header_text: Some(text(self.string.lookup(disruption.title))),
+                // Messages are in HTML, which the feeds carry as is.
+                description_text: disruption.message.map(|x| text(self.string.lookup(x))),
+                severity_level: Some(severity.into()),
+            }),
+        }
+    }
+
+    fn informed_entities(&self, data: &DataSuccess) -> InformedEntities {
+        let mut entities = InformedEntities::new();
+        for line in data.lines(self) {
+            let line = self.line.lookup_ref(*line);
+            let header = self.line_header.lookup_ref(line.header);
+            let route_id = self.string.lookup(header.id);
+            let route_type = match header.mode.get() {
+                KnownOr::Known(mode) => Some(route_type(mode)),
+                KnownOr::Other(_) => None,
+            };
+            for object in line.impacted_objects.iter() {
+                let object = self.impacted_object.lookup_ref(object);
+                let target = self.object.lookup_ref(object.object);
+                let stop_id = match self.string.lookup(target.typ) {
+                    "line" => None,
+                    _ => Some(self.string.lookup(target.id).to_owned()),
+                };
+                let selector = EntitySelector {
+                    route_id: Some(route_id.to_owned()),
+                    route_type,
+                    stop_id,
+                };
+                for id in self.uuid_set.lookup(object.disruption_ids).0 {
+                    let selectors = entities.entry(*id).or_default();
+                    if !selectors.contains(&selector) {
+                        selectors.push(selector.clone());
+                    }
+                }
+            }
+        }
+        entities
+    }
+}
+
+// Returns the route type of the static GTFS for a transport mode.
+fn route_type(mode: Mode) -> i32 {
+    match mode {
+        Mode::Tramway => 0,
+        Mode::Metro => 1,
+        Mode::RapidTransit | Mode::LocalTrain => 2,
+        Mode::Bus => 3,
+        Mode::Funicular => 7,
+    }
+}
@@ -0,0 +1,173 @@
+// Structural invariants of the arenas, beyond the IDs being in bounds, which the `verify-db`
+// command checks on databases written by any version of the tool:
+// - the sets interned in arenas of sets are sorted, so that equal sets share a handle,
+// - looking up each value of an arena returns its own handle, i.e. the hash table of the arena is
+//   consistent with its values and no value is interned twice,
+// - the timestamps can be converted back to datetimes, which displaying them relies on. Loading a
+//   database checks this one as well, so that querying it doesn't panic.
+//
+// Checking them looks values up, so the IDs must have been validated first.
+
+use super::validate::Id;
+use super::{ArenaSet, Arenas, Data};
+use crate::schema::archive::Handle;
+use crate::schema::introspect::Introspect;
+use blazinterner::InternedSlice;
+use std::fmt::{Display, Formatter};
+
+/// Value of an arena that breaks a structural invariant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub(super) arena: &'static str,
+    pub(super) id: u32,
+    pub(super) problem: Problem,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum Problem {
+    /// The items of a sorted set aren't in increasing order, starting at the given index.
+    Unsorted { index: usize },
+    /// Looking the value up returns another handle, or none.
+    Misplaced { found: Option<u32> },
+    /// A timestamp is out of the range of datetimes.
+    InvalidTimestamp { timestamp: i64 },
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{} #{}: ", self.arena, self.id)?;
+        match self.problem {
+            Problem::Unsorted { index } => {
+                write!(f, "items {index} and {} aren't sorted", index + 1)
+            }
+            Problem::Misplaced { found: Some(found) } => {
+                write!(f, "looking up the value returns #{found}")
+            }
+            Problem::Misplaced { found: None } => {
+                f.write_str("looking up the value doesn't find it")
+            }
+            Problem::InvalidTimestamp { timestamp } => {
+                write!(f, "timestamp {timestamp} is out of range")
+            }
+        }
+    }
+}
+
+impl Arenas {
+    /// Checks the structural invariants of all the arenas, returning the values that break them.
+    /// The IDs must have been validated with `validate`.
+    pub fn check_invariants(&self) -> Vec<Violation> {
+        let mut violations = Vec::new();
+
+        check_positions(&self.string, &mut violations);
+        check_positions(&self.uuid, &mut violations);
+        check_positions(&self.disruption_set.0, &mut violations);
+        check_positions(&self.disruption, &mut violations);
+        check_positions(&self.application_period_set.0, &mut violations);
+        check_positions(&self.application_period, &mut violations);
+        check_positions(&self.string_set.0, &mut violations);
+        check_positions(&self.timestamp, &mut violations);
+        check_positions(&self.line_set.0, &mut violations);
+        check_positions(&self.line, &mut violations);
+        check_positions(&self.line_header, &mut violations);
+        check_positions(&self.impacted_object, &mut violations);
+        check_positions(&self.object, &mut violations);
+        check_positions(&self.uuid_set.0, &mut violations);
+        check_positions(&self.data, &mut violations);
+
+        // Sequences keep the order of their items, so only sets are sorted.
+        check_sorted(&self.application_period_set, &mut violations);
+        check_sorted(&self.string_set, &mut violations);
+        check_sorted(&self.uuid_set, &mut violations);
+
+        self.visit_invalid_timestamps(|violation| violations.push(violation));
+
+        violations
+    }
+
+    /// Checks that all the timestamps can be converted back to datetimes, returning the first one
+    /// that can't. The IDs must have been validated with `validate`.
+    pub fn check_timestamps(&self) -> Result<(), Violation> {
+        let mut first = None;
+        self.visit_invalid_timestamps(|violation| {
+            first.get_or_insert(violation);
+        });
+        first.map_or(Ok(()), Err)
+    }
+
+    fn visit_invalid_timestamps(&self, mut f: impl FnMut(Violation)) {
+        let mut check_timestamp = |arena, id, timestamp: i64, valid: bool| {
+            if !valid {
+                f(Violation {
+                    arena,
+                    id,
+                    problem: Problem::InvalidTimestamp { timestamp },
+                });
+            }
+        };
+        for (id, timestamp) in self.timestamp.iter() {
+            check_timestamp(
+                "timestamp",
+                id.id(),
+                timestamp.0,
+                timestamp.to_local().is_some(),
+            );
+        }
+        for (id, period) in self.application_period.iter() {
+            for timestamp in [&period.begin, &period.end] {
+                check_timestamp(
+                    "application_period",
+                    id.id(),
+                    timestamp.0,
+                    timestamp.to_local().is_some(),
+                );
+            }
+        }
+        for (id, data) in self.data.iter() {
+            if let Data::Success(data) = data {
+                let timestamp = &data.last_updated_date;
+                check_timestamp(
+                    "data",
+                    id.id(),
+                    timestamp.0,
+                    timestamp.to_datetime().is_some(),
+                );
+            }
+        }
+    }
+}
+
+// Checks that looking up each value of an arena returns its own handle.
+pub(super) fn check_positions<A>(arena: &A, violations: &mut Vec<Violation>)
+where
+    A: Introspect,
+    A::Handle: Id + PartialEq,
+{
+    for (handle, value) in arena.iter() {
+        let found = arena.position(value);
+        if found != Some(handle) {
+            violations.push(Violation {
+                arena: <A::Handle as Id>::ARENA,
+                id: handle.raw_id(),
+                problem: Problem::Misplaced {
+                    found: found.map(Id::raw_id),
+                },
+            });
+        }
+    }
+}
+
+fn check_sorted<H: Handle + Ord>(arena: &ArenaSet<H>, violations: &mut Vec<Violation>)
+where
+    InternedSlice<H>: Id,
+{
+    for (handle, set) in arena.0.iter() {
+        if let Some(index) = set.windows(2).position(|x| x[0] > x[1]) {
+            violations.push(Violation {
+                arena: <InternedSlice<H> as Id>::ARENA,
+                id: handle.id(),
+                problem: Problem::Unsorted { index },
+            });
+        }
+    }
+}
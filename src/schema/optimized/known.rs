@@ -0,0 +1,264 @@
+// Encoding of the string fields that take a handful of well-known values, such as the mode of lines
+// or the severity of disruptions. Known values are stored as a variant of a closed enum rather than
+// as an interned string, and any other value falls back to the string arena, so that unexpected
+// values from the API are still represented exactly.
+//
+// Both cases are packed into a single u32, so that these fields stay as small as a string handle:
+// IDs below the number of variants are known values, and the others are string IDs offset by that
+// number. A field switches to this encoding by declaring its values with `known_values!` and by
+// changing its type to `Known<_>`.
+
+use super::{ArchiveError, ArchivedArenas, Arenas, Error, FromSource, TryIntern};
+use crate::compare::EqWith;
+use crate::schema::introspect::Introspect;
+use crate::schema::source::Str;
+use blazinterner::{ArenaStr, InternedStr};
+use get_size2::GetSize;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Debug, Formatter};
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+/// Closed set of values of a string field.
+pub trait KnownValues: Copy + Eq + 'static {
+    /// All the variants, in the order of their IDs.
+    const VARIANTS: &'static [Self];
+
+    /// Returns the string that this variant stands for.
+    fn as_str(self) -> &'static str;
+
+    fn from_str(value: &str) -> Option<Self> {
+        Self::VARIANTS.iter().copied().find(|x| x.as_str() == value)
+    }
+}
+
+/// Declares an enum of known values, each with the string that it stands for.
+macro_rules! known_values {
+    (
+        $(#[$attr:meta])*
+        pub enum $name:ident {
+            $($variant:ident => $value:literal,)*
+        }
+    ) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum $name {
+            $($variant,)*
+        }
+
+        impl KnownValues for $name {
+            const VARIANTS: &'static [Self] = &[$(Self::$variant,)*];
+
+            fn as_str(self) -> &'static str {
+                match self {
+                    $(Self::$variant => $value,)*
+                }
+            }
+        }
+    };
+}
+
+known_values! {
+    /// Modes of the lines of the network.
+    pub enum Mode {
+        Metro => "Metro",
+        RapidTransit => "RapidTransit",
+        LocalTrain => "LocalTrain",
+        Tramway => "Tramway",
+        Bus => "Bus",
+        Funicular => "Funicular",
+    }
+}
+
+known_values! {
+    /// Severities of disruptions.
+    pub enum Severity {
+        Blocking => "BLOQUANTE",
+        Disrupted => "PERTURBEE",
+        Information => "INFORMATION",
+    }
+}
+
+/// Value of a field with known values, either one of them or any other string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KnownOr<K> {
+    Known(K),
+    Other(InternedStr),
+}
+
+/// Value of a field with known values, packed into a u32.
+#[derive(Serialize, Deserialize, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+#[serde(transparent, bound = "")]
+pub struct Known<K> {
+    id: u32,
+    #[serde(skip)]
+    _phantom: PhantomData<K>,
+}
+
+impl<K: KnownValues> Known<K> {
+    /// Encodes the given string, interning it if it isn't one of the known values.
+    pub fn new(strings: &ArenaStr, value: &str) -> Result<Self, Error> {
+        Ok(match K::from_str(value) {
+            Some(known) => KnownOr::Known(known).into(),
+            None => KnownOr::Other(strings.try_intern(value)?).into(),
+        })
+    }
+
+    /// Encodes a string that is already interned, without interning it again if it isn't one of
+    /// the known values.
+    pub fn from_interned(strings: &ArenaStr, value: InternedStr) -> Self {
+        match K::from_str(strings.lookup(value)) {
+            Some(known) => KnownOr::Known(known).into(),
+            None => KnownOr::Other(value).into(),
+        }
+    }
+
+    pub fn get(self) -> KnownOr<K> {
+        match K::VARIANTS.get(self.id as usize) {
+            Some(known) => KnownOr::Known(*known),
+            None => KnownOr::Other(InternedStr::from_id(self.id - K::VARIANTS.len() as u32)),
+        }
+    }
+
+    /// Returns the interned string of this value, if it isn't one of the known values.
+    pub fn other(self) -> Option<InternedStr> {
+        match self.get() {
+            KnownOr::Known(_) => None,
+            KnownOr::Other(string) => Some(string),
+        }
+    }
+
+    pub fn as_str(self, strings: &ArenaStr) -> &str {
+        match self.get() {
+            KnownOr::Known(known) => known.as_str(),
+            KnownOr::Other(string) => strings.lookup(string),
+        }
+    }
+
+    pub(super) fn map(self, f: impl FnOnce(InternedStr) -> InternedStr) -> Self {
+        match self.get() {
+            KnownOr::Known(_) => self,
+            KnownOr::Other(string) => KnownOr::Other(f(string)).into(),
+        }
+    }
+
+    /// Returns the packed representation of this value.
+    pub(super) fn id(self) -> u32 {
+        self.id
+    }
+
+    pub(super) fn from_id(id: u32) -> Self {
+        Self {
+            id,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<K: KnownValues> From<KnownOr<K>> for Known<K> {
+    fn from(value: KnownOr<K>) -> Self {
+        let id = match value {
+            KnownOr::Known(known) => K::VARIANTS.iter().position(|x| *x == known).unwrap() as u32,
+            KnownOr::Other(string) => string.id() + K::VARIANTS.len() as u32,
+        };
+        Self {
+            id,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<K: KnownValues> ArchivedKnown<K> {
+    pub fn as_str<'a>(&self, arenas: &'a ArchivedArenas) -> Result<&'a str, ArchiveError> {
+        let id = self.id.to_native();
+        match K::VARIANTS.get(id as usize) {
+            Some(known) => Ok(known.as_str()),
+            None => arenas.string(id - K::VARIANTS.len() as u32),
+        }
+    }
+}
+
+// The traits are implemented by hand, as deriving them would require them from the enum of known
+// values, which is only a marker here.
+
+impl<K> Clone for Known<K> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<K> Copy for Known<K> {}
+
+impl<K> PartialEq for Known<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl<K> Eq for Known<K> {}
+
+impl<K> Hash for Known<K> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl<K: KnownValues + Debug> Debug for Known<K> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.get().fmt(f)
+    }
+}
+
+impl<K> GetSize for Known<K> {}
+
+impl<K: KnownValues> FromSource<Str<'_>> for Known<K> {
+    fn from_source(arenas: &Arenas, source: &Str<'_>) -> Result<Self, Error> {
+        Known::new(&arenas.string, source)
+    }
+}
+
+impl<K: KnownValues> EqWith<Str<'_>, Arenas> for Known<K> {
+    fn eq_with(&self, other: &Str<'_>, arenas: &Arenas) -> bool {
+        self.as_str(&arenas.string) == &**other
+    }
+}
+
+impl Arenas {
+    pub(super) fn print_known_values(&self) {
+        println!("Known values (enum variants vs. interned strings):");
+        print_known("LineHeader.mode", self.line_header.values().map(|x| x.mode));
+        print_known(
+            "Disruption.severity",
+            self.disruption.values().map(|x| x.severity),
+        );
+    }
+}
+
+// Prints how many values of a field are known. The known values that are used would otherwise each
+// be stored once in the string arena.
+fn print_known<K: KnownValues>(name: &str, values: impl Iterator<Item = Known<K>>) {
+    let mut used = vec![false; K::VARIANTS.len()];
+    let (mut known, mut other) = (0, 0);
+    for value in values {
+        match value.get() {
+            KnownOr::Known(_) => {
+                known += 1;
+                used[value.id as usize] = true;
+            }
+            KnownOr::Other(_) => other += 1,
+        }
+    }
+
+    let used: Vec<_> = (K::VARIANTS.iter())
+        .zip(used)
+        .filter_map(|(x, used)| used.then_some(x.as_str()))
+        .collect();
+    let total = known + other;
+    println!(
+        "  {name}: {} variants | {total} values, {known} known ({:.02}%), {other} other | {} known strings ({} bytes) not interned",
+        K::VARIANTS.len(),
+        known as f64 * 100.0 / total as f64,
+        used.len(),
+        used.iter().map(|x| x.len()).sum::<usize>(),
+    );
+}
@@ -0,0 +1,184 @@
+// Lifetimes of the disruptions: when each disruption first and last appeared in the snapshots, and
+// how it evolved in between. Disruptions are tracked by their interned UUID, and their versions and
+// application periods by their interned handles, so that the values only need to be looked up to
+// format the resulting table.
+//
+// As for the churn, snapshots of failed API responses are skipped: a disruption missing from them
+// isn't considered to have disappeared.
+
+use super::{ApplicationPeriod, Arenas, Data, Disruption, DisruptionUuid, TimestampMillis};
+use blazinterner::{Interned, InternedSlice};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+
+// Format of the bounds of the application periods, which are local datetimes.
+const PERIOD_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+/// Lifetime of a disruption across the snapshots of a database.
+#[derive(Debug, Serialize)]
+pub struct Lifetime {
+    pub id: String,
+    /// Position of the first snapshot containing the disruption.
+    pub first_snapshot: usize,
+    /// Position of the last snapshot containing the disruption.
+    pub last_snapshot: usize,
+    /// Update dates of the first and last snapshots containing the disruption.
+    pub first_seen: String,
+    pub last_seen: String,
+    /// Seconds elapsed between the first and last snapshots containing the disruption.
+    pub duration_seconds: i64,
+    /// Number of snapshots containing the disruption.
+    pub snapshots: usize,
+    /// Number of times that the disruption appeared again after missing from a snapshot.
+    pub reappearances: usize,
+    /// Number of distinct contents of the disruption.
+    pub versions: usize,
+    /// Number of times that the application periods changed from one appearance to the next.
+    pub period_changes: usize,
+    /// Application periods when the disruption first and last appeared, as space-separated
+    /// `begin/end` intervals.
+    pub first_periods: String,
+    pub last_periods: String,
+}
+
+impl Lifetime {
+    const CSV_HEADER: &str = "id,first_snapshot,last_snapshot,first_seen,last_seen,duration_seconds,snapshots,reappearances,versions,period_changes,first_periods,last_periods";
+
+    /// Writes the lifetimes as CSV, with a header line. None of the fields contains commas or
+    /// quotes, so they don't need to be quoted.
+    pub fn write_csv(lifetimes: &[Lifetime], mut writer: impl Write) -> std::io::Result<()> {
+        writeln!(writer, "{}", Self::CSV_HEADER)?;
+        for x in lifetimes {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{},{},{},{},{},{}",
+                x.id,
+                x.first_snapshot,
+                x.last_snapshot,
+                x.first_seen,
+                x.last_seen,
+                x.duration_seconds,
+                x.snapshots,
+                x.reappearances,
+                x.versions,
+                x.period_changes,
+                x.first_periods,
+                x.last_periods,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// State of a disruption while walking the snapshots.
+struct Tracker {
+    id: DisruptionUuid,
+    first: (usize, TimestampMillis),
+    last: (usize, TimestampMillis),
+    // Number of successful snapshots before the last one containing the disruption.
+    last_step: usize,
+    snapshots: usize,
+    reappearances: usize,
+    versions: usize,
+    period_changes: usize,
+    first_periods: InternedSlice<Interned<ApplicationPeriod>>,
+    last_periods: InternedSlice<Interned<ApplicationPeriod>>,
+}
+
+impl Arenas {
+    /// Returns the lifetime of each disruption of the given snapshots, in order of first
+    /// appearance.
+    pub fn lifetimes(&self, datas: &[Interned<Data>]) -> Vec<Lifetime> {
+        let mut indices: HashMap<DisruptionUuid, usize> = HashMap::new();
+        let mut trackers: Vec<Tracker> = Vec::new();
+        // Each version belongs to a single UUID, so it's counted when first seen.
+        let mut versions: HashSet<Interned<Disruption>> = HashSet::new();
+
+        let successful = datas
+            .iter()
+            .enumerate()
+            .filter_map(|(i, data)| match self.data(*data) {
+                Data::Success(data) => Some((i, data)),
+                Data::Error(_) => None,
+            });
+        for (step, (index, data)) in successful.enumerate() {
+            let seen = (index, data.last_updated_date.clone());
+            for &handle in data.disruptions(self) {
+                let disruption = self.disruption(handle);
+                let periods = disruption.application_periods;
+                let new_version = versions.insert(handle);
+                let Some(&i) = indices.get(&disruption.id) else {
+                    indices.insert(disruption.id, trackers.len());
+                    trackers.push(Tracker {
+                        id: disruption.id,
+                        first: seen.clone(),
+                        last: seen.clone(),
+                        last_step: step,
+                        snapshots: 1,
+                        reappearances: 0,
+                        versions: 1,
+                        period_changes: 0,
+                        first_periods: periods,
+                        last_periods: periods,
+                    });
+                    continue;
+                };
+                let tracker = &mut trackers[i];
+                // The same disruption may be listed twice in a snapshot.
+                if tracker.last_step == step {
+                    continue;
+                }
+                if tracker.last_step + 1 != step {
+                    tracker.reappearances += 1;
+                }
+                if new_version {
+                    tracker.versions += 1;
+                }
+                if tracker.last_periods != periods {
+                    tracker.period_changes += 1;
+                }
+                tracker.last = seen.clone();
+                tracker.last_step = step;
+                tracker.snapshots += 1;
+                tracker.last_periods = periods;
+            }
+        }
+
+        trackers
+            .into_iter()
+            .map(|tracker| Lifetime {
+                id: self.uuid.lookup_ref(tracker.id).0.to_string(),
+                first_snapshot: tracker.first.0,
+                last_snapshot: tracker.last.0,
+                first_seen: tracker.first.1.to_rfc3339(),
+                last_seen: tracker.last.1.to_rfc3339(),
+                duration_seconds: (tracker.last.1 .0 - tracker.first.1 .0) / 1000,
+                snapshots: tracker.snapshots,
+                reappearances: tracker.reappearances,
+                versions: tracker.versions,
+                period_changes: tracker.period_changes,
+                first_periods: self.format_periods(tracker.first_periods),
+                last_periods: self.format_periods(tracker.last_periods),
+            })
+            .collect()
+    }
+
+    fn format_periods(&self, periods: InternedSlice<Interned<ApplicationPeriod>>) -> String {
+        let periods: Vec<String> = self
+            .application_period_set
+            .lookup(periods)
+            .0
+            .iter()
+            .map(|period| {
+                let period = self.application_period.lookup_ref(*period);
+                format!(
+                    "{}/{}",
+                    period.begin.to_formatted(PERIOD_FORMAT),
+                    period.end.to_formatted(PERIOD_FORMAT),
+                )
+            })
+            .collect();
+        periods.join(" ")
+    }
+}
@@ -0,0 +1,547 @@
+// Protobuf serialization of the optimized database, so that services written in other languages can
+// consume it with the code generated from `interning.proto`. The messages mirror the arenas one to
+// one: each arena is a repeated field indexed by the IDs of its values, and handles are stored as
+// these IDs, so that the interning is preserved rather than expanded.
+//
+// The messages below are written by hand with the same field numbers as the schema, like the
+// GTFS-Realtime messages. The file is a bare `Database` message, without the header and checksum of
+// the other binary formats, so that any protobuf parser reads it as is. Its version and timezone are
+// fields of the message instead, and older versions aren't upgraded.
+
+use super::known::Known;
+use super::{ArenaSet, Arenas, InternedSeq, LocalTimestampSeconds, TimestampMillis};
+use crate::provenance::Provenance;
+use crate::schema::archive::Handle;
+use crate::schema::introspect::Introspect;
+use crate::schema::{optimized, Uuid};
+use crate::stream::Record;
+use crate::version::{self, CURRENT_VERSION};
+use blazinterner::{Arena, ArenaSlice, ArenaStr, Interned, InternedSlice, InternedStr};
+use prost::Message;
+use std::borrow::Borrow;
+use std::error::Error;
+use std::hash::Hash;
+use std::path::PathBuf;
+
+/// File name of the schema, which is written next to the serialized databases.
+pub const SCHEMA_FILE_NAME: &str = "interning.proto";
+
+/// Schema of the serialized databases.
+pub const SCHEMA: &str = include_str!("interning.proto");
+
+#[derive(Clone, PartialEq, Message)]
+pub(super) struct Database {
+    #[prost(uint32, tag = "1")]
+    pub(super) version: u32,
+    #[prost(message, optional, tag = "2")]
+    pub(super) arenas: Option<ArenasMessage>,
+    #[prost(message, repeated, tag = "3")]
+    pub(super) snapshots: Vec<Snapshot>,
+    #[prost(string, tag = "4")]
+    pub(super) timezone: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(super) struct Snapshot {
+    #[prost(uint32, tag = "1")]
+    pub(super) data: u32,
+    #[prost(string, tag = "2")]
+    pub(super) path: String,
+    #[prost(message, optional, tag = "3")]
+    pub(super) provenance: Option<ProvenanceMessage>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(super) struct ProvenanceMessage {
+    #[prost(fixed32, tag = "1")]
+    pub(super) checksum: u32,
+    #[prost(int64, optional, tag = "2")]
+    pub(super) modified: Option<i64>,
+    #[prost(int64, tag = "3")]
+    pub(super) ingested: i64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(super) struct ArenasMessage {
+    #[prost(string, repeated, tag = "1")]
+    pub(super) strings: Vec<String>,
+    #[prost(bytes = "vec", repeated, tag = "2")]
+    pub(super) uuids: Vec<Vec<u8>>,
+    #[prost(message, repeated, tag = "3")]
+    pub(super) disruption_sets: Vec<IdList>,
+    #[prost(message, repeated, tag = "4")]
+    pub(super) disruptions: Vec<Disruption>,
+    #[prost(message, repeated, tag = "5")]
+    pub(super) application_period_sets: Vec<IdList>,
+    #[prost(message, repeated, tag = "6")]
+    pub(super) application_periods: Vec<ApplicationPeriod>,
+    #[prost(message, repeated, tag = "7")]
+    pub(super) string_sets: Vec<IdList>,
+    #[prost(sint64, repeated, tag = "8")]
+    pub(super) timestamps: Vec<i64>,
+    #[prost(message, repeated, tag = "9")]
+    pub(super) line_sets: Vec<IdList>,
+    #[prost(message, repeated, tag = "10")]
+    pub(super) lines: Vec<Line>,
+    #[prost(message, repeated, tag = "11")]
+    pub(super) line_headers: Vec<LineHeader>,
+    #[prost(message, repeated, tag = "12")]
+    pub(super) impacted_objects: Vec<ImpactedObject>,
+    #[prost(message, repeated, tag = "13")]
+    pub(super) objects: Vec<Object>,
+    #[prost(message, repeated, tag = "14")]
+    pub(super) uuid_sets: Vec<IdList>,
+    #[prost(message, repeated, tag = "15")]
+    pub(super) data: Vec<Data>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(super) struct IdList {
+    #[prost(uint32, repeated, tag = "1")]
+    pub(super) ids: Vec<u32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(super) struct Disruption {
+    #[prost(uint32, tag = "1")]
+    pub(super) id: u32,
+    #[prost(uint32, tag = "2")]
+    pub(super) application_periods: u32,
+    #[prost(uint32, tag = "3")]
+    pub(super) last_update: u32,
+    #[prost(uint32, tag = "4")]
+    pub(super) cause: u32,
+    #[prost(uint32, tag = "5")]
+    pub(super) severity: u32,
+    #[prost(uint32, optional, tag = "6")]
+    pub(super) tags: Option<u32>,
+    #[prost(uint32, tag = "7")]
+    pub(super) title: u32,
+    #[prost(uint32, optional, tag = "8")]
+    pub(super) message: Option<u32>,
+    #[prost(uint32, optional, tag = "9")]
+    pub(super) short_message: Option<u32>,
+    #[prost(uint32, optional, tag = "10")]
+    pub(super) disruption_id: Option<u32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(super) struct ApplicationPeriod {
+    #[prost(sint64, tag = "1")]
+    pub(super) begin: i64,
+    #[prost(sint64, tag = "2")]
+    pub(super) end: i64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(super) struct Line {
+    #[prost(uint32, tag = "1")]
+    pub(super) header: u32,
+    #[prost(uint32, repeated, tag = "2")]
+    pub(super) impacted_objects: Vec<u32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(super) struct LineHeader {
+    #[prost(uint32, tag = "1")]
+    pub(super) id: u32,
+    #[prost(uint32, tag = "2")]
+    pub(super) name: u32,
+    #[prost(uint32, tag = "3")]
+    pub(super) short_name: u32,
+    #[prost(uint32, tag = "4")]
+    pub(super) mode: u32,
+    #[prost(uint32, tag = "5")]
+    pub(super) network_id: u32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(super) struct ImpactedObject {
+    #[prost(uint32, tag = "1")]
+    pub(super) object: u32,
+    #[prost(uint32, tag = "2")]
+    pub(super) disruption_ids: u32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(super) struct Object {
+    #[prost(uint32, tag = "1")]
+    pub(super) typ: u32,
+    #[prost(uint32, tag = "2")]
+    pub(super) id: u32,
+    #[prost(uint32, tag = "3")]
+    pub(super) name: u32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(super) struct Data {
+    #[prost(oneof = "DataResult", tags = "1, 2")]
+    pub(super) result: Option<DataResult>,
+}
+
+#[derive(Clone, PartialEq, prost::Oneof)]
+pub(super) enum DataResult {
+    #[prost(message, tag = "1")]
+    Success(DataSuccess),
+    #[prost(message, tag = "2")]
+    Error(DataError),
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(super) struct DataSuccess {
+    #[prost(uint32, tag = "1")]
+    pub(super) disruptions: u32,
+    #[prost(uint32, tag = "2")]
+    pub(super) lines: u32,
+    #[prost(int64, tag = "3")]
+    pub(super) last_updated_date: i64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(super) struct DataError {
+    #[prost(int32, tag = "1")]
+    pub(super) status_code: i32,
+    #[prost(uint32, tag = "2")]
+    pub(super) error: u32,
+    #[prost(uint32, tag = "3")]
+    pub(super) message: u32,
+}
+
+/// Serializes the snapshots in the given order, along with the arenas.
+pub fn serialize(
+    arenas: &Arenas,
+    paths: &[PathBuf],
+    datas: &[Interned<optimized::Data>],
+    provenance: &[Option<Provenance>],
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    Ok(to_message(arenas, paths, datas, provenance)?.encode_to_vec())
+}
+
+/// Reads back all the snapshots of a file written by [`serialize`], in order.
+pub fn read(bytes: &[u8]) -> Result<(Arenas, Vec<Record>), Box<dyn Error>> {
+    from_message(Database::decode(bytes)?)
+}
+
+// The messages are also the layout of the Avro records, which are converted from and to them.
+pub(super) fn to_message(
+    arenas: &Arenas,
+    paths: &[PathBuf],
+    datas: &[Interned<optimized::Data>],
+    provenance: &[Option<Provenance>],
+) -> Result<Database, Box<dyn Error>> {
+    let snapshots = paths
+        .iter()
+        .zip(datas)
+        .zip(provenance)
+        .map(|((path, data), provenance)| {
+            Ok(Snapshot {
+                data: data.id(),
+                path: path
+                    .to_str()
+                    .ok_or_else(|| format!("Path {path:?} isn't valid UTF-8"))?
+                    .to_owned(),
+                provenance: provenance.map(|provenance| ProvenanceMessage {
+                    checksum: provenance.checksum,
+                    modified: provenance.modified,
+                    ingested: provenance.ingested,
+                }),
+            })
+        })
+        .collect::<Result<_, Box<dyn Error>>>()?;
+    Ok(Database {
+        version: CURRENT_VERSION,
+        arenas: Some(encode_arenas(arenas)),
+        snapshots,
+        timezone: optimized::timezone().name().to_owned(),
+    })
+}
+
+pub(super) fn from_message(database: Database) -> Result<(Arenas, Vec<Record>), Box<dyn Error>> {
+    if database.version != CURRENT_VERSION {
+        return Err(format!(
+            "Unsupported version {} of the database, only version {CURRENT_VERSION} can be read",
+            database.version
+        )
+        .into());
+    }
+    version::check_timezone(&database.timezone).map_err(|e| e.to_string())?;
+    let arenas = decode_arenas(database.arenas.unwrap_or_default())?;
+    let records = database
+        .snapshots
+        .into_iter()
+        .map(|snapshot| {
+            (
+                PathBuf::from(snapshot.path),
+                Interned::from_id(snapshot.data),
+                snapshot.provenance.map(|provenance| Provenance {
+                    checksum: provenance.checksum,
+                    modified: provenance.modified,
+                    ingested: provenance.ingested,
+                }),
+            )
+        })
+        .collect();
+    Ok((arenas, records))
+}
+
+fn encode_arenas(arenas: &Arenas) -> ArenasMessage {
+    ArenasMessage {
+        strings: arenas.string.values().map(str::to_owned).collect(),
+        uuids: arenas
+            .uuid
+            .values()
+            .map(|uuid| uuid.0.as_bytes().to_vec())
+            .collect(),
+        disruption_sets: encode_sets(&arenas.disruption_set),
+        disruptions: arenas.disruption.values().map(encode_disruption).collect(),
+        application_period_sets: encode_sets(&arenas.application_period_set),
+        application_periods: arenas
+            .application_period
+            .values()
+            .map(|period| ApplicationPeriod {
+                begin: period.begin.0,
+                end: period.end.0,
+            })
+            .collect(),
+        string_sets: encode_sets(&arenas.string_set),
+        timestamps: arenas.timestamp.values().map(|x| x.0).collect(),
+        line_sets: encode_sets(&arenas.line_set),
+        lines: arenas
+            .line
+            .values()
+            .map(|line| Line {
+                header: line.header.id(),
+                impacted_objects: line.impacted_objects.iter().map(|x| x.id()).collect(),
+            })
+            .collect(),
+        line_headers: arenas
+            .line_header
+            .values()
+            .map(|header| LineHeader {
+                id: header.id.id(),
+                name: header.name.id(),
+                short_name: header.short_name.id(),
+                mode: header.mode.id(),
+                network_id: header.network_id.id(),
+            })
+            .collect(),
+        impacted_objects: arenas
+            .impacted_object
+            .values()
+            .map(|object| ImpactedObject {
+                object: object.object.id(),
+                disruption_ids: object.disruption_ids.id(),
+            })
+            .collect(),
+        objects: arenas
+            .object
+            .values()
+            .map(|object| Object {
+                typ: object.typ.id(),
+                id: object.id.id(),
+                name: object.name.id(),
+            })
+            .collect(),
+        uuid_sets: encode_sets(&arenas.uuid_set),
+        data: arenas.data.values().map(encode_data).collect(),
+    }
+}
+
+fn encode_sets<H: Handle, const SORTED: bool>(arena: &ArenaSet<H, SORTED>) -> Vec<IdList> {
+    arena
+        .0
+        .values()
+        .map(|set| IdList {
+            ids: set.iter().map(|x| x.id()).collect(),
+        })
+        .collect()
+}
+
+fn encode_disruption(disruption: &optimized::Disruption) -> Disruption {
+    Disruption {
+        id: disruption.id.id(),
+        application_periods: disruption.application_periods.id(),
+        last_update: disruption.last_update.id(),
+        cause: disruption.cause.id(),
+        severity: disruption.severity.id(),
+        tags: disruption.tags.map(|x| x.id()),
+        title: disruption.title.id(),
+        message: disruption.message.map(|x| x.id()),
+        short_message: disruption.short_message.map(|x| x.id()),
+        disruption_id: disruption.disruption_id.map(|x| x.id()),
+    }
+}
+
+fn encode_data(data: &optimized::Data) -> Data {
+    let result = match data {
+        optimized::Data::Success(success) => DataResult::Success(DataSuccess {
+            disruptions: success.disruptions.id(),
+            lines: success.lines.id(),
+            last_updated_date: success.last_updated_date.0,
+        }),
+        optimized::Data::Error(error) => DataResult::Error(DataError {
+            status_code: error.status_code,
+            error: error.error.id(),
+            message: error.message.id(),
+        }),
+    };
+    Data {
+        result: Some(result),
+    }
+}
+
+// The values are pushed in the order of their IDs without deduplicating them, like when
+// deserializing the other formats. Their IDs are validated once the whole database is loaded.
+fn decode_arenas(arenas: ArenasMessage) -> Result<Arenas, Box<dyn Error>> {
+    let bytes = arenas.strings.iter().map(String::len).sum();
+    let mut string = ArenaStr::with_capacity(arenas.strings.len(), bytes);
+    for x in &arenas.strings {
+        string.push_mut(x);
+    }
+    let uuids = arenas
+        .uuids
+        .into_iter()
+        .map(|bytes| Ok(Uuid(uuid::Uuid::from_slice(&bytes)?).into()))
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+    let data = arenas
+        .data
+        .into_iter()
+        .enumerate()
+        .map(|(id, data)| decode_data(data).ok_or_else(|| format!("Data #{id} has no result")))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Arenas {
+        string,
+        uuid: decode_arena(uuids, |x| x),
+        disruption_set: decode_sets(arenas.disruption_sets),
+        disruption: decode_arena(arenas.disruptions, decode_disruption),
+        application_period_set: decode_sets(arenas.application_period_sets),
+        application_period: decode_arena(arenas.application_periods, |period| {
+            optimized::ApplicationPeriod {
+                begin: LocalTimestampSeconds(period.begin),
+                end: LocalTimestampSeconds(period.end),
+            }
+        }),
+        string_set: decode_sets(arenas.string_sets),
+        timestamp: decode_arena(arenas.timestamps, LocalTimestampSeconds),
+        line_set: decode_sets(arenas.line_sets),
+        line: decode_arena(arenas.lines, |line| optimized::Line {
+            header: Interned::from_id(line.header),
+            impacted_objects: line
+                .impacted_objects
+                .into_iter()
+                .map(Interned::from_id)
+                .collect::<InternedSeq<_>>(),
+        }),
+        line_header: decode_arena(arenas.line_headers, |header| optimized::LineHeader {
+            id: InternedStr::from_id(header.id),
+            name: InternedStr::from_id(header.name),
+            short_name: InternedStr::from_id(header.short_name),
+            mode: Known::from_id(header.mode),
+            network_id: InternedStr::from_id(header.network_id),
+        }),
+        impacted_object: decode_arena(arenas.impacted_objects, |object| {
+            optimized::ImpactedObject {
+                object: Interned::from_id(object.object),
+                disruption_ids: InternedSlice::from_id(object.disruption_ids),
+            }
+        }),
+        object: decode_arena(arenas.objects, |object| optimized::Object {
+            typ: InternedStr::from_id(object.typ),
+            id: InternedStr::from_id(object.id),
+            name: InternedStr::from_id(object.name),
+        }),
+        uuid_set: decode_sets(arenas.uuid_sets),
+        data: decode_arena(data, |x| x),
+    })
+}
+
+fn decode_arena<M, T: Eq + Hash, Storage: Borrow<T>>(
+    messages: Vec<M>,
+    f: impl Fn(M) -> Storage,
+) -> Arena<T, Storage> {
+    let mut arena = Arena::with_capacity(messages.len());
+    for message in messages {
+        arena.push_mut(f(message));
+    }
+    arena
+}
+
+fn decode_sets<H: Handle, const SORTED: bool>(lists: Vec<IdList>) -> ArenaSet<H, SORTED> {
+    let items = lists.iter().map(|list| list.ids.len()).sum();
+    let mut arena = ArenaSlice::with_capacity(lists.len(), items);
+    for list in lists {
+        let set: Vec<H> = list.ids.into_iter().map(H::from_id).collect();
+        arena.push_copy_mut(&set);
+    }
+    ArenaSet(arena)
+}
+
+fn decode_disruption(disruption: Disruption) -> optimized::Disruption {
+    optimized::Disruption {
+        id: Interned::from_id(disruption.id),
+        application_periods: InternedSlice::from_id(disruption.application_periods),
+        last_update: Interned::from_id(disruption.last_update),
+        cause: InternedStr::from_id(disruption.cause),
+        severity: Known::from_id(disruption.severity),
+        tags: disruption.tags.map(InternedSlice::from_id),
+        title: InternedStr::from_id(disruption.title),
+        message: disruption.message.map(InternedStr::from_id),
+        short_message: disruption.short_message.map(InternedStr::from_id),
+        disruption_id: disruption.disruption_id.map(Interned::from_id),
+    }
+}
+
+fn decode_data(data: Data) -> Option<optimized::Data> {
+    Some(match data.result? {
+        DataResult::Success(success) => optimized::Data::Success(optimized::DataSuccess {
+            disruptions: InternedSlice::from_id(success.disruptions),
+            lines: InternedSlice::from_id(success.lines),
+            last_updated_date: TimestampMillis(success.last_updated_date),
+        }),
+        DataResult::Error(error) => optimized::Data::Error(optimized::DataError {
+            status_code: error.status_code,
+            error: InternedStr::from_id(error.error),
+            message: InternedStr::from_id(error.message),
+        }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provenance::Provenance;
+    use crate::schema::generate::test_database;
+
+    #[test]
+    fn protobuf_round_trip_keeps_ids() {
+        let mut database = test_database(3);
+        database.provenance[0] = Some(Provenance {
+            checksum: 0x1234_5678,
+            modified: Some(1_600_000_000_000),
+            ingested: 1_700_000_000_000,
+        });
+        let crate::Database {
+            arenas,
+            datas,
+            paths,
+            provenance,
+        } = database;
+
+        let bytes = serialize(&arenas, &paths, &datas, &provenance).unwrap();
+        let (decoded, records) = read(&bytes).unwrap();
+        assert_eq!(decoded, arenas);
+        let expected: Vec<_> = paths.into_iter().zip(datas).zip(provenance).collect();
+        let records: Vec<_> = records
+            .into_iter()
+            .map(|(path, data, provenance)| ((path, data), provenance))
+            .collect();
+        assert_eq!(records, expected);
+
+        // The last occurrence of a scalar field wins, so appending a version overrides it.
+        let mut other_version = bytes.clone();
+        prost::encoding::uint32::encode(1, &5, &mut other_version);
+        assert!(read(&other_version).is_err());
+        assert!(read(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
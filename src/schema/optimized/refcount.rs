@@ -0,0 +1,226 @@
+// Reference counts of the interned values, i.e. how many snapshots and interned values refer to each
+// of them. A value referenced many times saves as many copies, whereas a value referenced once only
+// costs the overhead of its handle and of the arena's hash table.
+//
+// Counts are structural: a string used by a disruption counts once, regardless of how many
+// snapshots contain this disruption.
+
+use super::validate::{Id, PerArena, References, Visitor};
+use super::{ArenaSet, Arenas, Data};
+use crate::schema::archive::Handle;
+use crate::schema::introspect::Introspect;
+use blazinterner::{Arena, Interned, InternedSlice, InternedStr};
+use get_size2::GetSize;
+use std::borrow::Borrow;
+use std::convert::Infallible;
+use std::hash::Hash;
+use std::mem::size_of;
+
+// Maximum length of the values printed in a report.
+const MAX_VALUE_LEN: usize = 60;
+
+pub(super) type Counts = PerArena<u32>;
+
+impl Visitor for Counts {
+    type Error = Infallible;
+
+    fn visit<I: Id>(&mut self, id: I) -> Result<(), Infallible> {
+        I::slots(self)[id.raw_id() as usize] += 1;
+        Ok(())
+    }
+}
+
+impl Arenas {
+    /// Prints the `top` most referenced strings, disruptions and objects, along with `top` values
+    /// that are referenced only once.
+    pub fn print_reference_counts(&self, datas: &[Interned<Data>], top: usize) {
+        let counts = self.count_references(datas);
+
+        println!("Reference counts:");
+        print_counts("String", &counts.string, top, |i| {
+            truncated(format!("{:?}", self.string.lookup(InternedStr::from_id(i))))
+        });
+        print_counts("Disruption", &counts.disruption, top, |i| {
+            let disruption = self.disruption.lookup_ref(Interned::from_id(i));
+            let uuid = &self.uuid.lookup_ref(disruption.id).0;
+            truncated(format!("{uuid} {:?}", disruption.title(self)))
+        });
+        print_counts("Object", &counts.object, top, |i| {
+            let object = self.object.lookup_ref(Interned::from_id(i));
+            truncated(format!(
+                "{} {} {:?}",
+                self.string.lookup(object.typ),
+                self.string.lookup(object.id),
+                self.string.lookup(object.name),
+            ))
+        });
+    }
+
+    // Compares each arena with storing a copy of the value at each place that refers to it. The
+    // arenas only count their intern calls while values are being interned, so deduplication is
+    // measured on the references stored in the database, which also works once it is loaded.
+    pub(super) fn print_deduplication(&self, datas: &[Interned<Data>]) {
+        let counts = self.count_references(datas);
+
+        println!(
+            "Deduplication (references per unique value, and bytes vs. one copy per reference):"
+        );
+        let string_copy_bytes =
+            |i| size_of::<String>() + self.string.lookup(InternedStr::from_id(i)).len();
+        Deduplication::new(&counts.string, self.string.get_size(), string_copy_bytes)
+            .print("String");
+        Deduplication::of_arena(&counts.uuid, &self.uuid).print("Uuid");
+        Deduplication::of_arena(&counts.data, &self.data).print("Data");
+        Deduplication::of_arena_set(&counts.disruption_set, &self.disruption_set)
+            .print("InternedSeq<Disruption>");
+        Deduplication::of_arena(&counts.disruption, &self.disruption).print("Disruption");
+        Deduplication::of_arena_set(&counts.application_period_set, &self.application_period_set)
+            .print("InternedSet<ApplicationPeriod>");
+        Deduplication::of_arena(&counts.application_period, &self.application_period)
+            .print("ApplicationPeriod");
+        Deduplication::of_arena_set(&counts.string_set, &self.string_set)
+            .print("InternedSet<String>");
+        Deduplication::of_arena(&counts.timestamp, &self.timestamp).print("LocalTimestampSeconds");
+        Deduplication::of_arena_set(&counts.line_set, &self.line_set).print("InternedSeq<Line>");
+        Deduplication::of_arena(&counts.line, &self.line).print("Line");
+        Deduplication::of_arena(&counts.line_header, &self.line_header).print("LineHeader");
+        Deduplication::of_arena(&counts.impacted_object, &self.impacted_object)
+            .print("ImpactedObject");
+        Deduplication::of_arena(&counts.object, &self.object).print("Object");
+        Deduplication::of_arena_set(&counts.uuid_set, &self.uuid_set).print("InternedSet<Uuid>");
+    }
+
+    pub(super) fn count_references(&self, datas: &[Interned<Data>]) -> Counts {
+        let mut counts = Counts::new(self, 0);
+        let Ok(()) = counts.visit_all(datas.iter().copied());
+        count_arena(&self.data, &mut counts);
+        count_arena_set(&self.disruption_set, &mut counts);
+        count_arena(&self.disruption, &mut counts);
+        count_arena_set(&self.application_period_set, &mut counts);
+        count_arena_set(&self.string_set, &mut counts);
+        count_arena_set(&self.line_set, &mut counts);
+        count_arena(&self.line, &mut counts);
+        count_arena(&self.line_header, &mut counts);
+        count_arena(&self.impacted_object, &mut counts);
+        count_arena_set(&self.uuid_set, &mut counts);
+        count_arena(&self.object, &mut counts);
+        counts
+    }
+}
+
+fn count_arena<T: References + Eq + Hash>(arena: &Arena<T>, counts: &mut Counts) {
+    for value in arena.values() {
+        let Ok(()) = value.visit_ids(counts);
+    }
+}
+
+fn count_arena_set<H: Handle + Id, const SORTED: bool>(
+    arena: &ArenaSet<H, SORTED>,
+    counts: &mut Counts,
+) {
+    for set in arena.0.values() {
+        let Ok(()) = counts.visit_all(set.iter().copied());
+    }
+}
+
+// Sizes in bytes of an arena and of the values it would contain without interning.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) struct Deduplication {
+    pub(super) unique: usize,
+    pub(super) references: u64,
+    // Total size of a copy of each value for each of its references.
+    pub(super) copy_bytes: u64,
+    // Size of the arena and of the handles stored by the references.
+    pub(super) interned_bytes: u64,
+}
+
+impl Deduplication {
+    pub(super) fn new(
+        counts: &[u32],
+        arena_bytes: usize,
+        copy_bytes: impl Fn(u32) -> usize,
+    ) -> Self {
+        let references = counts.iter().map(|&count| count as u64).sum();
+        Self {
+            unique: counts.len(),
+            references,
+            copy_bytes: (0..counts.len() as u32)
+                .map(|i| counts[i as usize] as u64 * copy_bytes(i) as u64)
+                .sum(),
+            // All handles consist of a 32-bit ID.
+            interned_bytes: arena_bytes as u64 + references * size_of::<u32>() as u64,
+        }
+    }
+
+    fn of_arena<T: GetSize, Storage>(counts: &[u32], arena: &Arena<T, Storage>) -> Self
+    where
+        Storage: Borrow<T> + GetSize,
+    {
+        Self::new(counts, arena.get_size(), |i| {
+            arena.lookup_ref(Interned::from_id(i)).get_size()
+        })
+    }
+
+    fn of_arena_set<H: Handle, const SORTED: bool>(
+        counts: &[u32],
+        arena: &ArenaSet<H, SORTED>,
+    ) -> Self
+    where
+        ArenaSet<H, SORTED>: GetSize,
+    {
+        Self::new(counts, arena.get_size(), |i| {
+            let set = arena.lookup(InternedSlice::from_id(i)).0;
+            size_of::<Box<[H]>>() + size_of_val(set)
+        })
+    }
+
+    fn print(&self, title: &str) {
+        let saved_bytes = self.copy_bytes as i64 - self.interned_bytes as i64;
+        println!(
+            "  {title}: {} unique values for {} references ({:.02} refs/value) | {} bytes with one copy per reference vs. {} bytes interned, saving {saved_bytes} bytes ({:.02}%)",
+            self.unique,
+            self.references,
+            self.references as f64 / self.unique as f64,
+            self.copy_bytes,
+            self.interned_bytes,
+            saved_bytes as f64 * 100.0 / self.copy_bytes as f64,
+        );
+    }
+}
+
+fn print_counts(title: &str, counts: &[u32], top: usize, describe: impl Fn(u32) -> String) {
+    let singletons: Vec<u32> = (0..counts.len() as u32)
+        .filter(|&i| counts[i as usize] == 1)
+        .collect();
+    let unreferenced = counts.iter().filter(|&&count| count == 0).count();
+    let references: u64 = counts.iter().map(|&count| count as u64).sum();
+    println!(
+        "  {title}: {} values, {references} references, {} referenced once, {unreferenced} unreferenced",
+        counts.len(),
+        singletons.len(),
+    );
+
+    // Ties are listed by ID, i.e. in the order in which values were first interned.
+    let mut most_referenced: Vec<u32> = (0..counts.len() as u32).collect();
+    most_referenced.sort_by_key(|&i| std::cmp::Reverse(counts[i as usize]));
+    println!("    Most referenced:");
+    for &i in most_referenced.iter().take(top) {
+        println!("      {:>7} × {}", counts[i as usize], describe(i));
+    }
+    println!("    Referenced once:");
+    for &i in singletons.iter().take(top) {
+        println!("              {}", describe(i));
+    }
+}
+
+pub(super) fn truncated(mut value: String) -> String {
+    if value.len() > MAX_VALUE_LEN {
+        let mut end = MAX_VALUE_LEN;
+        while !value.is_char_boundary(end) {
+            end -= 1;
+        }
+        value.truncate(end);
+        value.push_str("...");
+    }
+    value
+}
@@ -0,0 +1,147 @@
+// Conversion of interned snapshots back into the source schema, so that the original JSON files can
+// be regenerated from a database. Sets are interned in sorted order, so the items of each array may
+// come out in a different order than in the original file.
+
+use super::{
+    ApplicationPeriod, Arenas, Data, DataError, DataSuccess, Disruption, ImpactedObject,
+    InternedSeq, Line, LocalTimestampSeconds,
+};
+use crate::schema::source::{self, Str};
+use blazinterner::Arena;
+
+impl Data {
+    /// Converts this snapshot back into the source schema.
+    pub fn to_source<'a>(&self, arenas: &'a Arenas) -> source::Data<'a> {
+        match self {
+            Data::Success(data) => data.to_source(arenas),
+            Data::Error(data) => data.to_source(arenas),
+        }
+    }
+}
+
+impl DataSuccess {
+    fn to_source<'a>(&self, arenas: &'a Arenas) -> source::Data<'a> {
+        source::Data {
+            disruptions: Some(
+                self.disruptions(arenas)
+                    .iter()
+                    .map(|x| arenas.disruption.lookup_ref(*x).to_source(arenas))
+                    .collect(),
+            ),
+            lines: Some(
+                self.lines(arenas)
+                    .iter()
+                    .map(|x| arenas.line.lookup_ref(*x).to_source(arenas))
+                    .collect(),
+            ),
+            last_updated_date: Some(self.last_updated_date().into()),
+            status_code: None,
+            error: None,
+            message: None,
+        }
+    }
+}
+
+impl DataError {
+    fn to_source<'a>(&self, arenas: &'a Arenas) -> source::Data<'a> {
+        source::Data {
+            disruptions: None,
+            lines: None,
+            last_updated_date: None,
+            status_code: Some(self.status_code),
+            error: Some(self.error(arenas).into()),
+            message: Some(self.message(arenas).into()),
+        }
+    }
+}
+
+impl Disruption {
+    fn to_source<'a>(&self, arenas: &'a Arenas) -> source::Disruption<'a> {
+        let string = |x| Str::from(arenas.string.lookup(x));
+        source::Disruption {
+            id: arenas.uuid.lookup_ref(self.id).clone(),
+            application_periods: arenas
+                .application_period_set
+                .lookup(self.application_periods)
+                .0
+                .iter()
+                .map(|x| arenas.application_period.lookup_ref(*x).to_source())
+                .collect(),
+            last_update: arenas
+                .timestamp
+                .lookup_ref(self.last_update)
+                .to_source()
+                .into(),
+            cause: string(self.cause),
+            severity: self.severity.as_str(&arenas.string).into(),
+            tags: self.tags.map(|tags| {
+                let tags = arenas.string_set.lookup(tags).0;
+                tags.iter().map(|x| string(*x)).collect()
+            }),
+            title: string(self.title),
+            message: self.message.map(string),
+            short_message: self.short_message.map(string),
+            disruption_id: self
+                .disruption_id
+                .map(|x| arenas.uuid.lookup_ref(x).clone()),
+        }
+    }
+}
+
+impl ApplicationPeriod {
+    fn to_source(&self) -> source::ApplicationPeriod<'static> {
+        source::ApplicationPeriod {
+            begin: self.begin.to_source().into(),
+            end: self.end.to_source().into(),
+        }
+    }
+}
+
+impl Line {
+    fn to_source<'a>(&self, arenas: &'a Arenas) -> source::Line<'a> {
+        let string = |x| Str::from(arenas.string.lookup(x));
+        let header = arenas.line_header.lookup_ref(self.header);
+        source::Line {
+            id: string(header.id),
+            name: string(header.name),
+            short_name: string(header.short_name),
+            mode: header.mode.as_str(&arenas.string).into(),
+            network_id: string(header.network_id),
+            impacted_objects: lookup_seq(&arenas.impacted_object, &self.impacted_objects)
+                .map(|x| x.to_source(arenas))
+                .collect(),
+        }
+    }
+}
+
+impl ImpactedObject {
+    fn to_source<'a>(&self, arenas: &'a Arenas) -> source::ImpactedObject<'a> {
+        let string = |x| Str::from(arenas.string.lookup(x));
+        let object = arenas.object.lookup_ref(self.object);
+        source::ImpactedObject {
+            typ: string(object.typ),
+            id: string(object.id),
+            name: string(object.name),
+            disruption_ids: arenas
+                .uuid_set
+                .lookup(self.disruption_ids)
+                .0
+                .iter()
+                .map(|x| arenas.uuid.lookup_ref(*x).clone())
+                .collect(),
+        }
+    }
+}
+
+impl LocalTimestampSeconds {
+    pub(super) fn to_source(&self) -> String {
+        self.to_formatted("%Y%m%dT%H%M%S")
+    }
+}
+
+fn lookup_seq<'a, T: 'a>(
+    arena: &'a Arena<T>,
+    seq: &'a InternedSeq<T>,
+) -> impl Iterator<Item = &'a T> + 'a {
+    seq.iter().map(|x| arena.lookup_ref(x))
+}
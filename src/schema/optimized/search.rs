@@ -0,0 +1,167 @@
+// Full-text search over the titles and messages of the disruptions. The index is built when searching
+// rather than stored in the database: each distinct string is tokenized once, however many
+// disruptions and snapshots refer to it, so building it only takes a pass over the string arena.
+//
+// Words are matched case-insensitively and in full. A disruption matches if each search term is a
+// word of either its title or its message, and each of its versions is reported once, along with
+// the dates of the first and last snapshots containing it and the lines that it impacted in them.
+
+use super::templates::tokenize;
+use super::{Arenas, Data, Disruption};
+use crate::schema::introspect::Introspect;
+use blazinterner::{Interned, InternedStr};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// Inverted index from the words of the disruption titles and messages to the strings containing
+/// them.
+pub struct SearchIndex {
+    words: HashMap<String, HashSet<InternedStr>>,
+}
+
+/// Version of a disruption matching a search.
+pub struct SearchHit<'a> {
+    pub disruption: Interned<Disruption>,
+    /// Update dates of the first and last snapshots containing this version.
+    pub first_seen: String,
+    pub last_seen: String,
+    /// Names of the lines that this version impacted, in any snapshot.
+    pub lines: BTreeSet<&'a str>,
+}
+
+// Returns the words of a text in lowercase, skipping HTML tags, whitespace and punctuation.
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    tokenize(text)
+        .into_iter()
+        .filter(|token| token.starts_with(char::is_alphanumeric))
+        .map(str::to_lowercase)
+}
+
+impl SearchIndex {
+    /// Indexes the distinct titles and messages of the disruptions.
+    pub fn new(arenas: &Arenas) -> Self {
+        let strings: HashSet<InternedStr> = arenas
+            .disruption
+            .values()
+            .flat_map(|disruption| [Some(disruption.title), disruption.message])
+            .flatten()
+            .collect();
+        let mut words: HashMap<String, HashSet<InternedStr>> = HashMap::new();
+        for string in strings {
+            for word in self::words(arenas.string.lookup(string)) {
+                words.entry(word).or_default().insert(string);
+            }
+        }
+        Self { words }
+    }
+
+    /// Returns the number of distinct indexed words.
+    pub fn word_count(&self) -> usize {
+        self.words.len()
+    }
+
+    // Returns whether the title or the message contains each of the words.
+    fn matches(&self, disruption: &Disruption, words: &[String]) -> bool {
+        words.iter().all(|word| {
+            self.words.get(word).is_some_and(|strings| {
+                strings.contains(&disruption.title)
+                    || disruption.message.is_some_and(|x| strings.contains(&x))
+            })
+        })
+    }
+}
+
+impl Arenas {
+    /// Returns the versions of the disruptions of the given snapshots that match all the words of
+    /// the query, in order of first appearance.
+    pub fn search<'a>(
+        &'a self,
+        index: &SearchIndex,
+        datas: &[Interned<Data>],
+        query: &str,
+    ) -> Vec<SearchHit<'a>> {
+        let query: Vec<String> = words(query).collect();
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        let mut matches: HashMap<Interned<Disruption>, bool> = HashMap::new();
+        let mut indices: HashMap<Interned<Disruption>, usize> = HashMap::new();
+        let mut hits: Vec<SearchHit> = Vec::new();
+        for data in datas {
+            let Data::Success(data) = self.data(*data) else {
+                continue;
+            };
+            let matching: Vec<Interned<Disruption>> = data
+                .disruptions(self)
+                .iter()
+                .copied()
+                .filter(|x| {
+                    *matches
+                        .entry(*x)
+                        .or_insert_with(|| index.matches(self.disruption(*x), &query))
+                })
+                .collect();
+            if matching.is_empty() {
+                continue;
+            }
+
+            let date = data.last_updated_date();
+            let lines = self.impacted_lines(data.lines(self));
+            for disruption in matching {
+                let i = *indices.entry(disruption).or_insert_with(|| {
+                    hits.push(SearchHit {
+                        disruption,
+                        first_seen: date.clone(),
+                        last_seen: date.clone(),
+                        lines: BTreeSet::new(),
+                    });
+                    hits.len() - 1
+                });
+                let hit = &mut hits[i];
+                hit.last_seen.clone_from(&date);
+                if let Some(headers) = lines.get(&self.disruption(disruption).id) {
+                    hit.lines.extend(headers.iter().map(|header| {
+                        self.string
+                            .lookup(self.line_header.lookup_ref(*header).name)
+                    }));
+                }
+            }
+        }
+        hits
+    }
+    /// Searches the query and prints the first `top` matching disruptions, with their lines and
+    /// application periods.
+    pub fn print_search(
+        &self,
+        index: &SearchIndex,
+        datas: &[Interned<Data>],
+        query: &str,
+        top: usize,
+    ) {
+        let hits = self.search(index, datas, query);
+        println!(
+            "{} disruptions match {query:?}{}",
+            hits.len(),
+            if hits.len() > top {
+                format!(", showing the first {top}")
+            } else {
+                String::new()
+            },
+        );
+        let resolver = self.resolver();
+        for hit in hits.iter().take(top) {
+            let disruption = resolver.disruption(hit.disruption);
+            println!(
+                "- {disruption} (seen from {} to {})",
+                hit.first_seen, hit.last_seen
+            );
+            if !hit.lines.is_empty() {
+                let lines: Vec<&str> = hit.lines.iter().copied().collect();
+                println!("  Lines: {}", lines.join(", "));
+            }
+            for period in disruption.application_periods() {
+                println!("  Period: {period}");
+            }
+        }
+    }
+}
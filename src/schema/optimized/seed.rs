@@ -0,0 +1,634 @@
+// Direct parsing of JSON files into the optimized representation. Values are interned in the arenas
+// as soon as they're parsed, instead of first building a `source::Data` and converting it, which
+// avoids allocating every string of the input.
+//
+// As a consequence, the arenas may end up containing values from a file that eventually fails to
+// parse.
+
+use super::known::Known;
+use super::validate::Id;
+use super::{
+    cautious_capacity, ApplicationPeriod, Arenas, Data, DataError, DataSuccess, Disruption,
+    ImpactedObject, Line, LineHeader, LocalTimestampSeconds, Object, TimestampMillis, TryIntern,
+};
+use blazinterner::{Arena, Interned};
+use serde::de::{DeserializeSeed, Error, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use std::borrow::Borrow;
+use std::hash::Hash;
+
+/// Parses a JSON file directly into an interned snapshot.
+pub fn from_slice(arenas: &Arenas, bytes: &[u8]) -> serde_json::Result<Data> {
+    let mut deserializer = serde_json::Deserializer::from_slice(bytes);
+    let data = DataSeed(arenas).deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(data)
+}
+
+// Field names, which must match the ones of the latest `source` schema. Missing nullable fields are
+// parsed as null, so files in the shape of older versions are accepted as well.
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "camelCase")]
+enum DataField {
+    Disruptions,
+    Lines,
+    LastUpdatedDate,
+    StatusCode,
+    Error,
+    Message,
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "camelCase")]
+enum DisruptionField {
+    Id,
+    ApplicationPeriods,
+    LastUpdate,
+    Cause,
+    Severity,
+    Tags,
+    Title,
+    Message,
+    ShortMessage,
+    #[serde(rename = "disruption_id")]
+    DisruptionId,
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "camelCase")]
+enum ApplicationPeriodField {
+    Begin,
+    End,
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "camelCase")]
+enum LineField {
+    Id,
+    Name,
+    ShortName,
+    Mode,
+    NetworkId,
+    ImpactedObjects,
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "camelCase")]
+enum ImpactedObjectField {
+    #[serde(rename = "type")]
+    Typ,
+    Id,
+    Name,
+    DisruptionIds,
+}
+
+struct DataSeed<'a>(&'a Arenas);
+
+impl<'de> DeserializeSeed<'de> for DataSeed<'_> {
+    type Value = Data;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for DataSeed<'_> {
+    type Value = Data;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a snapshot")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let arenas = self.0;
+        let string = StrSeed(|x: &str| arenas.string.try_intern(x));
+
+        let mut disruptions = None;
+        let mut lines = None;
+        let mut last_updated_date = None;
+        let mut status_code = None;
+        let mut error = None;
+        let mut message = None;
+
+        while let Some(field) = map.next_key()? {
+            match field {
+                DataField::Disruptions => set_once(
+                    &mut disruptions,
+                    "disruptions",
+                    map.next_value_seed(OptionSeed(SeqSeed(DisruptionSeed(arenas))))?,
+                )?,
+                DataField::Lines => set_once(
+                    &mut lines,
+                    "lines",
+                    map.next_value_seed(OptionSeed(SeqSeed(LineSeed(arenas))))?,
+                )?,
+                DataField::LastUpdatedDate => set_once(
+                    &mut last_updated_date,
+                    "lastUpdatedDate",
+                    map.next_value_seed(OptionSeed(StrSeed(TimestampMillis::from_rfc3339)))?,
+                )?,
+                DataField::StatusCode => set_once(
+                    &mut status_code,
+                    "statusCode",
+                    map.next_value::<Option<i32>>()?,
+                )?,
+                DataField::Error => set_once(
+                    &mut error,
+                    "error",
+                    map.next_value_seed(OptionSeed(string))?,
+                )?,
+                DataField::Message => set_once(
+                    &mut message,
+                    "message",
+                    map.next_value_seed(OptionSeed(string))?,
+                )?,
+            }
+        }
+
+        match (
+            disruptions.flatten(),
+            lines.flatten(),
+            last_updated_date.flatten(),
+            status_code.flatten(),
+            error.flatten(),
+            message.flatten(),
+        ) {
+            (Some(disruptions), Some(lines), Some(last_updated_date), None, None, None) => {
+                Ok(Data::Success(DataSuccess {
+                    disruptions: arenas
+                        .disruption_set
+                        .try_intern(disruptions)
+                        .map_err(A::Error::custom)?,
+                    lines: arenas
+                        .line_set
+                        .try_intern(lines)
+                        .map_err(A::Error::custom)?,
+                    last_updated_date,
+                }))
+            }
+            (None, None, None, Some(status_code), Some(error), Some(message)) => {
+                Ok(Data::Error(DataError {
+                    status_code,
+                    error,
+                    message,
+                }))
+            }
+            _ => Err(A::Error::custom(crate::error::Error::IncompleteData)),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct DisruptionSeed<'a>(&'a Arenas);
+
+impl<'de> DeserializeSeed<'de> for DisruptionSeed<'_> {
+    type Value = Interned<Disruption>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for DisruptionSeed<'_> {
+    type Value = Interned<Disruption>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a disruption")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let arenas = self.0;
+        let string = StrSeed(|x: &str| arenas.string.try_intern(x));
+        let known = StrSeed(|x: &str| Known::new(&arenas.string, x));
+        let uuid = InternSeed(&arenas.uuid);
+
+        let mut id = None;
+        let mut application_periods = None;
+        let mut last_update = None;
+        let mut cause = None;
+        let mut severity = None;
+        let mut tags = None;
+        let mut title = None;
+        let mut message = None;
+        let mut short_message = None;
+        let mut disruption_id = None;
+
+        while let Some(field) = map.next_key()? {
+            match field {
+                DisruptionField::Id => set_once(&mut id, "id", map.next_value_seed(uuid)?)?,
+                DisruptionField::ApplicationPeriods => set_once(
+                    &mut application_periods,
+                    "applicationPeriods",
+                    map.next_value_seed(SeqSeed(ApplicationPeriodSeed(arenas)))?,
+                )?,
+                DisruptionField::LastUpdate => set_once(
+                    &mut last_update,
+                    "lastUpdate",
+                    map.next_value_seed(StrSeed(|x: &str| {
+                        LocalTimestampSeconds::from_formatted(x, "%Y%m%dT%H%M%S")
+                            .and_then(|x| arenas.timestamp.try_intern(x))
+                    }))?,
+                )?,
+                DisruptionField::Cause => {
+                    set_once(&mut cause, "cause", map.next_value_seed(string)?)?
+                }
+                DisruptionField::Severity => {
+                    set_once(&mut severity, "severity", map.next_value_seed(known)?)?
+                }
+                DisruptionField::Tags => set_once(
+                    &mut tags,
+                    "tags",
+                    map.next_value_seed(OptionSeed(SeqSeed(string)))?,
+                )?,
+                DisruptionField::Title => {
+                    set_once(&mut title, "title", map.next_value_seed(string)?)?
+                }
+                DisruptionField::Message => set_once(
+                    &mut message,
+                    "message",
+                    map.next_value_seed(OptionSeed(string))?,
+                )?,
+                DisruptionField::ShortMessage => set_once(
+                    &mut short_message,
+                    "shortMessage",
+                    map.next_value_seed(OptionSeed(string))?,
+                )?,
+                DisruptionField::DisruptionId => set_once(
+                    &mut disruption_id,
+                    "disruption_id",
+                    map.next_value_seed(OptionSeed(uuid))?,
+                )?,
+            }
+        }
+
+        let disruption = Disruption {
+            id: id.ok_or_else(|| A::Error::missing_field("id"))?,
+            application_periods: arenas
+                .application_period_set
+                .try_intern(
+                    application_periods
+                        .ok_or_else(|| A::Error::missing_field("applicationPeriods"))?,
+                )
+                .map_err(A::Error::custom)?,
+            last_update: last_update.ok_or_else(|| A::Error::missing_field("lastUpdate"))?,
+            cause: cause.ok_or_else(|| A::Error::missing_field("cause"))?,
+            severity: severity.ok_or_else(|| A::Error::missing_field("severity"))?,
+            tags: (tags.flatten())
+                .map(|x| arenas.string_set.try_intern(x))
+                .transpose()
+                .map_err(A::Error::custom)?,
+            title: title.ok_or_else(|| A::Error::missing_field("title"))?,
+            message: message.flatten(),
+            short_message: short_message.flatten(),
+            disruption_id: disruption_id.flatten(),
+        };
+        arenas
+            .disruption
+            .try_intern(disruption)
+            .map_err(A::Error::custom)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct ApplicationPeriodSeed<'a>(&'a Arenas);
+
+impl<'de> DeserializeSeed<'de> for ApplicationPeriodSeed<'_> {
+    type Value = Interned<ApplicationPeriod>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ApplicationPeriodSeed<'_> {
+    type Value = Interned<ApplicationPeriod>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("an application period")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let timestamp =
+            StrSeed(|x: &str| LocalTimestampSeconds::from_formatted(x, "%Y%m%dT%H%M%S"));
+
+        let mut begin = None;
+        let mut end = None;
+
+        while let Some(field) = map.next_key()? {
+            match field {
+                ApplicationPeriodField::Begin => {
+                    set_once(&mut begin, "begin", map.next_value_seed(timestamp)?)?
+                }
+                ApplicationPeriodField::End => {
+                    set_once(&mut end, "end", map.next_value_seed(timestamp)?)?
+                }
+            }
+        }
+
+        let application_period = ApplicationPeriod {
+            begin: begin.ok_or_else(|| A::Error::missing_field("begin"))?,
+            end: end.ok_or_else(|| A::Error::missing_field("end"))?,
+        };
+        self.0
+            .application_period
+            .try_intern(application_period)
+            .map_err(A::Error::custom)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct LineSeed<'a>(&'a Arenas);
+
+impl<'de> DeserializeSeed<'de> for LineSeed<'_> {
+    type Value = Interned<Line>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for LineSeed<'_> {
+    type Value = Interned<Line>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a line")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let arenas = self.0;
+        let string = StrSeed(|x: &str| arenas.string.try_intern(x));
+        let known = StrSeed(|x: &str| Known::new(&arenas.string, x));
+
+        let mut id = None;
+        let mut name = None;
+        let mut short_name = None;
+        let mut mode = None;
+        let mut network_id = None;
+        let mut impacted_objects = None;
+
+        while let Some(field) = map.next_key()? {
+            match field {
+                LineField::Id => set_once(&mut id, "id", map.next_value_seed(string)?)?,
+                LineField::Name => set_once(&mut name, "name", map.next_value_seed(string)?)?,
+                LineField::ShortName => {
+                    set_once(&mut short_name, "shortName", map.next_value_seed(string)?)?
+                }
+                LineField::Mode => set_once(&mut mode, "mode", map.next_value_seed(known)?)?,
+                LineField::NetworkId => {
+                    set_once(&mut network_id, "networkId", map.next_value_seed(string)?)?
+                }
+                LineField::ImpactedObjects => set_once(
+                    &mut impacted_objects,
+                    "impactedObjects",
+                    map.next_value_seed(SeqSeed(ImpactedObjectSeed(arenas)))?,
+                )?,
+            }
+        }
+
+        let header = LineHeader {
+            id: id.ok_or_else(|| A::Error::missing_field("id"))?,
+            name: name.ok_or_else(|| A::Error::missing_field("name"))?,
+            short_name: short_name.ok_or_else(|| A::Error::missing_field("shortName"))?,
+            mode: mode.ok_or_else(|| A::Error::missing_field("mode"))?,
+            network_id: network_id.ok_or_else(|| A::Error::missing_field("networkId"))?,
+        };
+        let line = Line {
+            header: arenas
+                .line_header
+                .try_intern(header)
+                .map_err(A::Error::custom)?,
+            impacted_objects: impacted_objects
+                .ok_or_else(|| A::Error::missing_field("impactedObjects"))?
+                .into_iter()
+                .collect(),
+        };
+        arenas.line.try_intern(line).map_err(A::Error::custom)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct ImpactedObjectSeed<'a>(&'a Arenas);
+
+impl<'de> DeserializeSeed<'de> for ImpactedObjectSeed<'_> {
+    type Value = Interned<ImpactedObject>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ImpactedObjectSeed<'_> {
+    type Value = Interned<ImpactedObject>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("an impacted object")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let arenas = self.0;
+        let string = StrSeed(|x: &str| arenas.string.try_intern(x));
+
+        let mut typ = None;
+        let mut id = None;
+        let mut name = None;
+        let mut disruption_ids = None;
+
+        while let Some(field) = map.next_key()? {
+            match field {
+                ImpactedObjectField::Typ => {
+                    set_once(&mut typ, "type", map.next_value_seed(string)?)?
+                }
+                ImpactedObjectField::Id => set_once(&mut id, "id", map.next_value_seed(string)?)?,
+                ImpactedObjectField::Name => {
+                    set_once(&mut name, "name", map.next_value_seed(string)?)?
+                }
+                ImpactedObjectField::DisruptionIds => set_once(
+                    &mut disruption_ids,
+                    "disruptionIds",
+                    map.next_value_seed(SeqSeed(InternSeed(&arenas.uuid)))?,
+                )?,
+            }
+        }
+
+        let object = Object {
+            typ: typ.ok_or_else(|| A::Error::missing_field("type"))?,
+            id: id.ok_or_else(|| A::Error::missing_field("id"))?,
+            name: name.ok_or_else(|| A::Error::missing_field("name"))?,
+        };
+        let impacted_object = ImpactedObject {
+            object: arenas.object.try_intern(object).map_err(A::Error::custom)?,
+            disruption_ids: arenas
+                .uuid_set
+                .try_intern(disruption_ids.ok_or_else(|| A::Error::missing_field("disruptionIds"))?)
+                .map_err(A::Error::custom)?,
+        };
+        arenas
+            .impacted_object
+            .try_intern(impacted_object)
+            .map_err(A::Error::custom)
+    }
+}
+
+fn set_once<T, E: Error>(slot: &mut Option<T>, field: &'static str, value: T) -> Result<(), E> {
+    if slot.is_some() {
+        return Err(E::duplicate_field(field));
+    }
+    *slot = Some(value);
+    Ok(())
+}
+
+// Passes a string to the given fallible function, without allocating it when the deserializer can
+// borrow it from the input.
+#[derive(Clone, Copy)]
+struct StrSeed<F>(F);
+
+impl<'de, T, F: FnOnce(&str) -> Result<T, crate::error::Error>> DeserializeSeed<'de>
+    for StrSeed<F>
+{
+    type Value = T;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(self)
+    }
+}
+
+impl<'de, T, F: FnOnce(&str) -> Result<T, crate::error::Error>> Visitor<'de> for StrSeed<F> {
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a string")
+    }
+
+    fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+        (self.0)(v).map_err(E::custom)
+    }
+}
+
+// Deserializes a value and interns it in the given arena.
+struct InternSeed<'a, T, Storage = T>(&'a Arena<T, Storage>);
+
+impl<T, Storage> Clone for InternSeed<'_, T, Storage> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T, Storage> Copy for InternSeed<'_, T, Storage> {}
+
+impl<'de, T, Storage> DeserializeSeed<'de> for InternSeed<'_, T, Storage>
+where
+    T: Deserialize<'de> + Eq + Hash,
+    Storage: Borrow<T> + From<T>,
+    Interned<T, Storage>: Id,
+{
+    type Value = Interned<T, Storage>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        self.0
+            .try_intern(T::deserialize(deserializer)?)
+            .map_err(D::Error::custom)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct OptionSeed<S>(S);
+
+impl<'de, S: DeserializeSeed<'de>> DeserializeSeed<'de> for OptionSeed<S> {
+    type Value = Option<S::Value>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_option(self)
+    }
+}
+
+impl<'de, S: DeserializeSeed<'de>> Visitor<'de> for OptionSeed<S> {
+    type Value = Option<S::Value>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("an optional value")
+    }
+
+    fn visit_none<E: Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        self.0.deserialize(deserializer).map(Some)
+    }
+}
+
+// Collects a sequence of values, deserializing each of them with a copy of the given seed.
+#[derive(Clone, Copy)]
+struct SeqSeed<S>(S);
+
+impl<'de, S: DeserializeSeed<'de> + Clone> DeserializeSeed<'de> for SeqSeed<S> {
+    type Value = Vec<S::Value>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, S: DeserializeSeed<'de> + Clone> Visitor<'de> for SeqSeed<S> {
+    type Value = Vec<S::Value>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a sequence of values")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut values = Vec::with_capacity(cautious_capacity::<S::Value>(seq.size_hint()));
+        while let Some(x) = seq.next_element_seed(self.0.clone())? {
+            values.push(x);
+        }
+        Ok(values)
+    }
+}
@@ -0,0 +1,183 @@
+// Serialized sizes of the interned sets and sequences with each encoding of their IDs, and with each
+// serialization format. The sequences of impacted objects are serialized with a run-length encoding
+// of the differences between consecutive IDs, whereas the sets and sequences interned in arenas are
+// serialized as their plain IDs. Comparing both encodings, along with plain differences and roaring
+// bitmaps, on all of them tells which encoding is worth it for which kind of set.
+//
+// Like the set encodings, sets are measured as if they were serialized individually, so each size
+// includes the length of the set in the given format.
+
+use super::bitmap::RoaringSet;
+use super::{rle_encode_seq, rle_encode_set, ArenaSet, Arenas};
+use crate::schema::archive::Handle;
+use crate::schema::introspect::Introspect;
+use blazinterner::Interned;
+use serde::Serialize;
+
+const CODECS: [&str; 5] = ["Bincode", "CBOR", "JSON", "Postcard", "MessagePack"];
+
+/// Encoding of the IDs of a set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Encoding {
+    /// Plain IDs.
+    Ids,
+    /// Differences between consecutive IDs.
+    Deltas,
+    /// Run-length encoding of the differences, as used for `InternedSet` and `InternedSeq`.
+    RunLength,
+    /// Roaring bitmap, which doesn't keep the order or the duplicates of sequences.
+    Roaring,
+}
+
+impl Encoding {
+    const ALL: [Encoding; 4] = [
+        Encoding::Ids,
+        Encoding::Deltas,
+        Encoding::RunLength,
+        Encoding::Roaring,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Encoding::Ids => "IDs",
+            Encoding::Deltas => "deltas",
+            Encoding::RunLength => "run-length deltas",
+            Encoding::Roaring => "roaring",
+        }
+    }
+}
+
+/// Serialized sizes of a group of sets, for each codec and encoding.
+#[derive(Debug, Default)]
+pub(super) struct SetCodecs {
+    pub(super) sets: usize,
+    pub(super) items: usize,
+    /// Number of integers of the run-length encoding.
+    pub(super) run_length_integers: usize,
+    bytes: [[u64; Encoding::ALL.len()]; CODECS.len()],
+}
+
+impl SetCodecs {
+    /// Adds a set with the given IDs, which are in increasing order if `sorted` is true.
+    pub(super) fn add(&mut self, ids: &[u32], sorted: bool) {
+        let deltas: Vec<i32> = (ids.iter())
+            .scan(0u32, |prev, &id| {
+                let delta = id.wrapping_sub(*prev) as i32;
+                *prev = id;
+                Some(delta)
+            })
+            .collect();
+        let run_length = match sorted {
+            true => rle_encode_set(ids.iter().copied()),
+            false => rle_encode_seq(ids.iter().copied()),
+        };
+        let roaring: RoaringSet<u32> = ids.iter().map(|&id| Interned::from_id(id)).collect();
+
+        self.sets += 1;
+        self.items += ids.len();
+        self.run_length_integers += run_length.len();
+        for encoding in Encoding::ALL {
+            let sizes = match encoding {
+                Encoding::Ids => sizes(&ids),
+                Encoding::Deltas => sizes(&deltas),
+                Encoding::RunLength => sizes(&run_length),
+                Encoding::Roaring => sizes(&roaring),
+            };
+            for (bytes, size) in self.bytes.iter_mut().zip(sizes) {
+                bytes[encoding as usize] += size;
+            }
+        }
+    }
+
+    fn add_arena<H: Handle, const SORTED: bool>(mut self, arena: &ArenaSet<H, SORTED>) -> Self {
+        for set in arena.0.values() {
+            let ids: Vec<u32> = set.iter().map(|x| x.id()).collect();
+            self.add(&ids, SORTED);
+        }
+        self
+    }
+
+    /// Returns the total size of the sets serialized with the given codec and encoding.
+    pub(super) fn bytes(&self, codec: &str, encoding: Encoding) -> u64 {
+        let codec = CODECS.iter().position(|x| *x == codec).unwrap();
+        self.bytes[codec][encoding as usize]
+    }
+
+    fn print(&self, title: &str, stored: Encoding) {
+        println!(
+            "  {title} (serialized as {}): {} sets, {} items encoded as {} run-length integers ({:.02}%)",
+            stored.name(),
+            self.sets,
+            self.items,
+            self.run_length_integers,
+            self.run_length_integers as f64 * 100.0 / self.items as f64,
+        );
+        for codec in CODECS {
+            let bytes = |encoding| self.bytes(codec, encoding);
+            let sizes = (Encoding::ALL.iter())
+                .map(|&x| format!("{} {}", x.name(), bytes(x)))
+                .collect::<Vec<_>>()
+                .join(" | ");
+            let relative =
+                |encoding| bytes(Encoding::RunLength) as f64 * 100.0 / bytes(encoding) as f64;
+            println!(
+                "    {codec:<11} {sizes} bytes (run-length deltas = {:.02}% of IDs, {:.02}% of deltas)",
+                relative(Encoding::Ids),
+                relative(Encoding::Deltas),
+            );
+        }
+    }
+}
+
+// Returns the serialized size of the value with each codec.
+fn sizes<T: Serialize + ?Sized>(value: &T) -> [u64; CODECS.len()] {
+    let mut cbor = Vec::new();
+    ciborium::into_writer(value, &mut cbor).unwrap();
+    [
+        bincode::serialized_size(value).unwrap(),
+        cbor.len() as u64,
+        serde_json::to_vec(value).unwrap().len() as u64,
+        postcard::to_stdvec(value).unwrap().len() as u64,
+        rmp_serde::to_vec(value).unwrap().len() as u64,
+    ]
+}
+
+impl Arenas {
+    /// Prints the serialized sizes of all the sets and sequences, with each codec and encoding.
+    pub fn print_set_codecs(&self) {
+        println!("Set codecs (serialized bytes per codec and encoding of the IDs):");
+
+        let mut codecs = SetCodecs::default();
+        for line in self.line.values() {
+            let ids: Vec<u32> = line.impacted_objects.iter().map(|x| x.id()).collect();
+            codecs.add(&ids, false);
+        }
+        codecs.print("InternedSeq<ImpactedObject>", Encoding::RunLength);
+
+        let arenas = [
+            (
+                "InternedSeq<Disruption>",
+                SetCodecs::default().add_arena(&self.disruption_set),
+            ),
+            (
+                "InternedSet<ApplicationPeriod>",
+                SetCodecs::default().add_arena(&self.application_period_set),
+            ),
+            (
+                "InternedSet<String>",
+                SetCodecs::default().add_arena(&self.string_set),
+            ),
+            (
+                "InternedSeq<Line>",
+                SetCodecs::default().add_arena(&self.line_set),
+            ),
+            (
+                "InternedSet<Uuid>",
+                SetCodecs::default().add_arena(&self.uuid_set),
+            ),
+        ];
+        for (title, codecs) in arenas {
+            codecs.print(title, Encoding::Ids);
+        }
+    }
+}
@@ -0,0 +1,184 @@
+// Clusters of similar disruption titles and messages. Interning only deduplicates strings that are
+// exactly equal, whereas many messages are near-duplicates, e.g. the same announcement with another
+// date. Clustering them measures this redundancy, before deciding whether an encoding that exploits
+// it is worth it.
+//
+// Strings are compared by the Jaccard similarity of their sets of shingles, i.e. of the runs of
+// consecutive words that they contain. The similarity is estimated with MinHash signatures, and only
+// the pairs of strings whose signatures share a band of hashes are compared (locality-sensitive
+// hashing). Similar strings are then grouped transitively.
+
+use super::refcount::truncated;
+use super::templates::tokenize;
+use super::Arenas;
+use crate::schema::introspect::Introspect;
+use blazinterner::InternedStr;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, HashMap};
+use std::hash::{Hash, Hasher};
+
+// Number of consecutive words in a shingle.
+const SHINGLE_WORDS: usize = 3;
+// The signatures consist of BANDS bands of ROWS hashes each. Two strings of similarity s share a band
+// with probability 1 - (1 - s^ROWS)^BANDS, e.g. 99.98% for s = 0.8 and 64% for s = 0.5.
+const BANDS: usize = 16;
+const ROWS: usize = 4;
+const HASHES: usize = BANDS * ROWS;
+
+type Signature = [u64; HASHES];
+
+/// Returns the MinHash signature of the shingles of the given text.
+fn signature(text: &str) -> Signature {
+    let words: Vec<&str> = tokenize(text)
+        .into_iter()
+        .filter(|x| !x.trim().is_empty())
+        .collect();
+    let mut signature = [u64::MAX; HASHES];
+    for shingle in words.windows(SHINGLE_WORDS.min(words.len()).max(1)) {
+        // The default hasher is seeded with fixed keys, so signatures are reproducible.
+        let mut hasher = DefaultHasher::new();
+        shingle.hash(&mut hasher);
+        let hash = hasher.finish();
+        for (i, min) in signature.iter_mut().enumerate() {
+            *min = (*min).min(mix(
+                hash.wrapping_add((i as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15))
+            ));
+        }
+    }
+    signature
+}
+
+// Finalizer of SplitMix64, deriving the independent hash functions of the signatures.
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// Estimates the Jaccard similarity of the shingles of two strings.
+fn similarity(a: &Signature, b: &Signature) -> f64 {
+    a.iter().zip(b).filter(|(x, y)| x == y).count() as f64 / HASHES as f64
+}
+
+/// Groups the given strings into clusters of similar strings, and returns the cluster of each
+/// string, identified by the index of one of its strings.
+pub(super) fn clusters(texts: &[&str], threshold: f64) -> Vec<usize> {
+    let signatures: Vec<Signature> = texts.iter().map(|x| signature(x)).collect();
+
+    let mut parents: Vec<usize> = (0..texts.len()).collect();
+    fn find(parents: &mut [usize], mut i: usize) -> usize {
+        while parents[i] != i {
+            parents[i] = parents[parents[i]];
+            i = parents[i];
+        }
+        i
+    }
+
+    for band in 0..BANDS {
+        let mut buckets: HashMap<&[u64], Vec<usize>> = HashMap::new();
+        for (i, signature) in signatures.iter().enumerate() {
+            buckets
+                .entry(&signature[band * ROWS..(band + 1) * ROWS])
+                .or_default()
+                .push(i);
+        }
+        for bucket in buckets.values() {
+            for (k, &i) in bucket.iter().enumerate() {
+                for &j in &bucket[k + 1..] {
+                    let (a, b) = (find(&mut parents, i), find(&mut parents, j));
+                    if a != b && similarity(&signatures[i], &signatures[j]) >= threshold {
+                        parents[a.max(b)] = a.min(b);
+                    }
+                }
+            }
+        }
+    }
+    (0..texts.len()).map(|i| find(&mut parents, i)).collect()
+}
+
+impl Arenas {
+    /// Clusters the distinct titles and messages of the disruptions by similarity, and prints the
+    /// sizes of the clusters, along with the `top` largest ones.
+    pub fn print_similarity_clusters(&self, threshold: f64, top: usize) {
+        let distinct = |field: fn(&super::Disruption) -> Option<InternedStr>| {
+            let ids: BTreeSet<InternedStr> = self.disruption.values().filter_map(field).collect();
+            ids.into_iter()
+                .map(|x| self.string.lookup(x))
+                .collect::<Vec<_>>()
+        };
+        print_clusters(
+            "Disruption.title",
+            &distinct(|x| Some(x.title)),
+            threshold,
+            top,
+        );
+        print_clusters(
+            "Disruption.message",
+            &distinct(|x| x.message),
+            threshold,
+            top,
+        );
+    }
+}
+
+fn print_clusters(name: &str, texts: &[&str], threshold: f64, top: usize) {
+    let mut members: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, cluster) in clusters(texts, threshold).into_iter().enumerate() {
+        members.entry(cluster).or_default().push(i);
+    }
+    // Ties are listed by the ID of their first string.
+    let mut clusters: Vec<Vec<usize>> = members.into_values().collect();
+    clusters.sort_by_key(|x| (std::cmp::Reverse(x.len()), x[0]));
+
+    let bytes: usize = texts.iter().map(|x| x.len()).sum();
+    let singletons = clusters.iter().filter(|x| x.len() == 1).count();
+    println!(
+        "Similarity clusters of {name} (estimated Jaccard similarity of {SHINGLE_WORDS}-word shingles >= {threshold:.02}):"
+    );
+    println!(
+        "  {} distinct strings ({bytes} bytes) in {} clusters, of which {singletons} singletons",
+        texts.len(),
+        clusters.len(),
+    );
+
+    // Clusters are counted by powers of two of their size: 1, 2-3, 4-7, etc.
+    let mut sizes: Vec<(usize, usize)> = Vec::new();
+    for cluster in &clusters {
+        let i = cluster.len().ilog2() as usize;
+        if sizes.len() <= i {
+            sizes.resize(i + 1, (0, 0));
+        }
+        sizes[i].0 += 1;
+        sizes[i].1 += cluster.len();
+    }
+    for (i, (count, strings)) in sizes.iter().enumerate() {
+        if *count != 0 {
+            println!(
+                "  Clusters of {:>5} to {:>5} strings: {count:>6} clusters, {strings:>7} strings",
+                1 << i,
+                (2 << i) - 1,
+            );
+        }
+    }
+
+    // Bytes that an encoding of each string relative to a similar one could save, at most.
+    let redundant: usize = (clusters.iter())
+        .map(|x| {
+            let lengths = x.iter().map(|&i| texts[i].len());
+            lengths.clone().sum::<usize>() - lengths.max().unwrap()
+        })
+        .sum();
+    println!(
+        "  Near-duplicate bytes, beyond the longest string of each cluster: {redundant} ({:.02}%)",
+        redundant as f64 * 100.0 / bytes as f64,
+    );
+
+    println!("  Largest clusters:");
+    for cluster in clusters.iter().take(top).filter(|x| x.len() > 1) {
+        println!(
+            "    {:>7} × {}",
+            cluster.len(),
+            truncated(format!("{:?}", texts[cluster[0]])),
+        );
+    }
+}
@@ -0,0 +1,303 @@
+// Export of the optimized database to SQLite, so that it can be queried with SQL tools. Each arena
+// becomes a table whose primary key is the interned ID, and sets are stored in separate tables
+// linking the set to its items, along with their position. Sets are sorted by ID, whereas sequences
+// keep the order of the source. Fields with known values are stored as their string, as their
+// known values aren't in the string table.
+
+use super::{Arena, ArenaSet, Arenas, Data, Interned};
+use crate::schema::archive::Handle;
+use crate::schema::introspect::Introspect;
+use rusqlite::{params, Connection};
+use std::borrow::Borrow;
+use std::hash::Hash;
+use std::path::PathBuf;
+
+const SCHEMA: &str = "
+CREATE TABLE string (
+    id INTEGER PRIMARY KEY,
+    value TEXT NOT NULL
+);
+CREATE TABLE string_set (
+    id INTEGER PRIMARY KEY
+);
+CREATE TABLE string_set_item (
+    string_set INTEGER NOT NULL REFERENCES string_set(id),
+    string INTEGER NOT NULL REFERENCES string(id),
+    position INTEGER NOT NULL
+);
+CREATE TABLE uuid (
+    id INTEGER PRIMARY KEY,
+    value TEXT NOT NULL
+);
+CREATE TABLE uuid_set (
+    id INTEGER PRIMARY KEY
+);
+CREATE TABLE uuid_set_item (
+    uuid_set INTEGER NOT NULL REFERENCES uuid_set(id),
+    uuid INTEGER NOT NULL REFERENCES uuid(id),
+    position INTEGER NOT NULL
+);
+-- Timestamps are in seconds since the Unix epoch.
+CREATE TABLE timestamp (
+    id INTEGER PRIMARY KEY,
+    value INTEGER NOT NULL
+);
+CREATE TABLE application_period (
+    id INTEGER PRIMARY KEY,
+    begin INTEGER NOT NULL,
+    end INTEGER NOT NULL
+);
+CREATE TABLE application_period_set (
+    id INTEGER PRIMARY KEY
+);
+CREATE TABLE application_period_set_item (
+    application_period_set INTEGER NOT NULL REFERENCES application_period_set(id),
+    application_period INTEGER NOT NULL REFERENCES application_period(id),
+    position INTEGER NOT NULL
+);
+CREATE TABLE object (
+    id INTEGER PRIMARY KEY,
+    type INTEGER NOT NULL REFERENCES string(id),
+    object_id INTEGER NOT NULL REFERENCES string(id),
+    name INTEGER NOT NULL REFERENCES string(id)
+);
+CREATE TABLE impacted_object (
+    id INTEGER PRIMARY KEY,
+    object INTEGER NOT NULL REFERENCES object(id),
+    disruption_ids INTEGER NOT NULL REFERENCES uuid_set(id)
+);
+CREATE TABLE line_header (
+    id INTEGER PRIMARY KEY,
+    line_id INTEGER NOT NULL REFERENCES string(id),
+    name INTEGER NOT NULL REFERENCES string(id),
+    short_name INTEGER NOT NULL REFERENCES string(id),
+    mode TEXT NOT NULL,
+    network_id INTEGER NOT NULL REFERENCES string(id)
+);
+CREATE TABLE line (
+    id INTEGER PRIMARY KEY,
+    header INTEGER NOT NULL REFERENCES line_header(id)
+);
+CREATE TABLE line_impacted_object (
+    line INTEGER NOT NULL REFERENCES line(id),
+    impacted_object INTEGER NOT NULL REFERENCES impacted_object(id),
+    position INTEGER NOT NULL
+);
+CREATE TABLE line_set (
+    id INTEGER PRIMARY KEY
+);
+CREATE TABLE line_set_item (
+    line_set INTEGER NOT NULL REFERENCES line_set(id),
+    line INTEGER NOT NULL REFERENCES line(id),
+    position INTEGER NOT NULL
+);
+-- The last update is in seconds since the Unix epoch.
+CREATE TABLE disruption (
+    id INTEGER PRIMARY KEY,
+    uuid INTEGER NOT NULL REFERENCES uuid(id),
+    application_periods INTEGER NOT NULL REFERENCES application_period_set(id),
+    last_update INTEGER NOT NULL REFERENCES timestamp(id),
+    cause INTEGER NOT NULL REFERENCES string(id),
+    severity TEXT NOT NULL,
+    tags INTEGER REFERENCES string_set(id),
+    title INTEGER NOT NULL REFERENCES string(id),
+    message INTEGER REFERENCES string(id),
+    short_message INTEGER REFERENCES string(id),
+    disruption_id INTEGER REFERENCES uuid(id)
+);
+CREATE TABLE disruption_set (
+    id INTEGER PRIMARY KEY
+);
+CREATE TABLE disruption_set_item (
+    disruption_set INTEGER NOT NULL REFERENCES disruption_set(id),
+    disruption INTEGER NOT NULL REFERENCES disruption(id),
+    position INTEGER NOT NULL
+);
+-- Successful snapshots have disruptions, lines and a last updated date (in milliseconds since the
+-- Unix epoch), failed snapshots have a status code, an error and a message.
+CREATE TABLE data (
+    id INTEGER PRIMARY KEY,
+    disruptions INTEGER REFERENCES disruption_set(id),
+    lines INTEGER REFERENCES line_set(id),
+    last_updated_date INTEGER,
+    status_code INTEGER,
+    error INTEGER REFERENCES string(id),
+    message INTEGER REFERENCES string(id)
+);
+-- Identical snapshots refer to the same data.
+CREATE TABLE snapshot (
+    id INTEGER PRIMARY KEY,
+    path TEXT NOT NULL,
+    data INTEGER NOT NULL REFERENCES data(id)
+);
+";
+
+pub fn export(
+    connection: &mut Connection,
+    arenas: &Arenas,
+    datas: &[Interned<Data>],
+    paths: &[PathBuf],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let tx = connection.transaction()?;
+    tx.execute_batch(SCHEMA)?;
+
+    {
+        let mut insert = tx.prepare("INSERT INTO string VALUES (?1, ?2)")?;
+        for (id, string) in arenas.string.iter() {
+            insert.execute(params![id.id(), string])?;
+        }
+    }
+    export_set(&tx, &arenas.string_set, "string_set")?;
+
+    for_each(&arenas.uuid, |i, uuid| {
+        tx.prepare_cached("INSERT INTO uuid VALUES (?1, ?2)")?
+            .execute(params![i, uuid.0.to_string()])?;
+        Ok(())
+    })?;
+    export_set(&tx, &arenas.uuid_set, "uuid_set")?;
+
+    for_each(&arenas.timestamp, |i, timestamp| {
+        tx.prepare_cached("INSERT INTO timestamp VALUES (?1, ?2)")?
+            .execute(params![i, timestamp.0])?;
+        Ok(())
+    })?;
+
+    for_each(&arenas.application_period, |i, period| {
+        tx.prepare_cached("INSERT INTO application_period VALUES (?1, ?2, ?3)")?
+            .execute(params![i, period.begin.0, period.end.0])?;
+        Ok(())
+    })?;
+    export_set(
+        &tx,
+        &arenas.application_period_set,
+        "application_period_set",
+    )?;
+
+    for_each(&arenas.object, |i, object| {
+        tx.prepare_cached("INSERT INTO object VALUES (?1, ?2, ?3, ?4)")?
+            .execute(params![
+                i,
+                object.typ.id(),
+                object.id.id(),
+                object.name.id()
+            ])?;
+        Ok(())
+    })?;
+
+    for_each(&arenas.impacted_object, |i, impacted_object| {
+        tx.prepare_cached("INSERT INTO impacted_object VALUES (?1, ?2, ?3)")?
+            .execute(params![
+                i,
+                impacted_object.object.id(),
+                impacted_object.disruption_ids.id()
+            ])?;
+        Ok(())
+    })?;
+
+    for_each(&arenas.line_header, |i, header| {
+        tx.prepare_cached("INSERT INTO line_header VALUES (?1, ?2, ?3, ?4, ?5, ?6)")?
+            .execute(params![
+                i,
+                header.id.id(),
+                header.name.id(),
+                header.short_name.id(),
+                header.mode.as_str(&arenas.string),
+                header.network_id.id(),
+            ])?;
+        Ok(())
+    })?;
+
+    for_each(&arenas.line, |i, line| {
+        tx.prepare_cached("INSERT INTO line VALUES (?1, ?2)")?
+            .execute(params![i, line.header.id()])?;
+        for (position, impacted_object) in (0u32..).zip(line.impacted_objects.iter()) {
+            tx.prepare_cached("INSERT INTO line_impacted_object VALUES (?1, ?2, ?3)")?
+                .execute(params![i, impacted_object.id(), position])?;
+        }
+        Ok(())
+    })?;
+    export_set(&tx, &arenas.line_set, "line_set")?;
+
+    for_each(&arenas.disruption, |i, disruption| {
+        tx.prepare_cached(
+            "INSERT INTO disruption VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        )?
+        .execute(params![
+            i,
+            disruption.id.id(),
+            disruption.application_periods.id(),
+            disruption.last_update.id(),
+            disruption.cause.id(),
+            disruption.severity.as_str(&arenas.string),
+            disruption.tags.map(|x| x.id()),
+            disruption.title.id(),
+            disruption.message.map(|x| x.id()),
+            disruption.short_message.map(|x| x.id()),
+            disruption.disruption_id.map(|x| x.id()),
+        ])?;
+        Ok(())
+    })?;
+    export_set(&tx, &arenas.disruption_set, "disruption_set")?;
+
+    for_each(&arenas.data, |i, data| {
+        let mut insert =
+            tx.prepare_cached("INSERT INTO data VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)")?;
+        match data {
+            Data::Success(data) => insert.execute(params![
+                i,
+                data.disruptions.id(),
+                data.lines.id(),
+                data.last_updated_date.0,
+                None::<i32>,
+                None::<u32>,
+                None::<u32>,
+            ])?,
+            Data::Error(data) => insert.execute(params![
+                i,
+                None::<u32>,
+                None::<u32>,
+                None::<i64>,
+                data.status_code,
+                data.error.id(),
+                data.message.id(),
+            ])?,
+        };
+        Ok(())
+    })?;
+
+    {
+        let mut insert = tx.prepare("INSERT INTO snapshot VALUES (?1, ?2, ?3)")?;
+        for (i, (data, path)) in (0u32..).zip(datas.iter().zip(paths)) {
+            insert.execute(params![i, path.to_string_lossy(), data.id()])?;
+        }
+    }
+
+    tx.commit()?;
+    Ok(())
+}
+
+fn for_each<T: Eq + Hash, Storage: Borrow<T>>(
+    arena: &Arena<T, Storage>,
+    mut f: impl FnMut(u32, &T) -> Result<(), Box<dyn std::error::Error>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for (id, value) in arena.iter() {
+        f(id.id(), value)?;
+    }
+    Ok(())
+}
+
+fn export_set<H: Handle, const SORTED: bool>(
+    tx: &Connection,
+    arena: &ArenaSet<H, SORTED>,
+    table: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut insert_set = tx.prepare(&format!("INSERT INTO {table} VALUES (?1)"))?;
+    let mut insert_item = tx.prepare(&format!("INSERT INTO {table}_item VALUES (?1, ?2, ?3)"))?;
+    for (id, set) in arena.0.iter() {
+        insert_set.execute(params![id.id()])?;
+        for (position, x) in (0u32..).zip(set) {
+            insert_item.execute(params![id.id(), x.id(), position])?;
+        }
+    }
+    Ok(())
+}
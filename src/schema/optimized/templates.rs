@@ -0,0 +1,339 @@
+// Templates of the disruption messages. Messages are HTML blobs written from a few templates, which
+// only differ by dates, times or the names of stops and lines. Interning stores each variant in full,
+// so this estimates how much smaller the messages would be as a template ID and the parameters
+// filling the slots of the template.
+//
+// Messages are split into tokens (HTML tags, words, runs of whitespace and punctuation characters).
+// Each message is then aligned with the templates that start with the same tokens, keeping the
+// longest common subsequence of their tokens, weighted by their length. If enough bytes are common,
+// the message joins the template, whose tokens that the message doesn't have become slots.
+// Templates only lose tokens as messages join, so all their messages still match them.
+
+use super::refcount::truncated;
+use super::Arenas;
+use crate::schema::introspect::Introspect;
+use blazinterner::InternedStr;
+use std::collections::{BTreeSet, HashMap};
+
+// Number of leading tokens that a message must share with a template to be compared with it.
+const KEY_TOKENS: usize = 3;
+// Share of the bytes of both a message and a template that they must have in common for the message
+// to join the template, in percent.
+const MIN_SHARED_PERCENT: usize = 60;
+
+/// Piece of a template: either a literal string, or a slot filled by a parameter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum Piece<T> {
+    Literal(T),
+    Slot,
+}
+
+/// Template of messages.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) struct Template {
+    pub(super) pieces: Vec<Piece<String>>,
+    /// Number of messages written from this template.
+    pub(super) messages: usize,
+}
+
+impl Template {
+    fn literal_bytes(&self) -> usize {
+        (self.pieces.iter())
+            .map(|piece| match piece {
+                Piece::Literal(literal) => literal.len(),
+                Piece::Slot => 0,
+            })
+            .sum()
+    }
+
+    fn to_display(&self) -> String {
+        (self.pieces.iter())
+            .map(|piece| match piece {
+                Piece::Literal(literal) => literal.as_str(),
+                Piece::Slot => "{}",
+            })
+            .collect()
+    }
+}
+
+/// Message encoded as the ID of its template and the parameters of the slots of the template.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) struct EncodedMessage {
+    pub(super) template: u32,
+    pub(super) params: Vec<String>,
+}
+
+/// Templates detected among a list of messages.
+#[derive(Debug)]
+pub(super) struct Templates {
+    pub(super) templates: Vec<Template>,
+}
+
+impl Templates {
+    /// Detects the templates of the given messages, and returns them along with each message
+    /// encoded with its template.
+    pub(super) fn build<'a>(
+        messages: impl IntoIterator<Item = &'a str>,
+    ) -> (Self, Vec<EncodedMessage>) {
+        let messages: Vec<Vec<&str>> = messages.into_iter().map(tokenize).collect();
+
+        // Templates are compared with the new messages as patterns of tokens and slots.
+        let mut patterns: Vec<(Vec<Piece<&str>>, usize)> = Vec::new();
+        let mut clusters = Vec::with_capacity(messages.len());
+        let mut buckets: HashMap<&[&str], Vec<usize>> = HashMap::new();
+        for tokens in &messages {
+            let candidates = buckets
+                .entry(&tokens[..tokens.len().min(KEY_TOKENS)])
+                .or_default();
+            let best = (candidates.iter())
+                .filter_map(|&i| {
+                    let (pattern, bytes) = &patterns[i];
+                    merge(pattern, *bytes, tokens).map(|merged| (i, merged))
+                })
+                .max_by_key(|(_, (_, shared))| *shared);
+            match best {
+                Some((i, merged)) => {
+                    patterns[i] = merged;
+                    clusters.push(i);
+                }
+                None => {
+                    candidates.push(patterns.len());
+                    clusters.push(patterns.len());
+                    let pattern = tokens.iter().map(|&x| Piece::Literal(x)).collect();
+                    patterns.push((pattern, tokens.iter().map(|x| x.len()).sum()));
+                }
+            }
+        }
+
+        let mut templates: Vec<Template> = (patterns.iter())
+            .map(|(pattern, _)| Template {
+                pieces: concat_literals(pattern),
+                messages: 0,
+            })
+            .collect();
+        let encoded = (messages.iter().zip(clusters))
+            .map(|(tokens, i)| {
+                templates[i].messages += 1;
+                let captures = match_pattern(&patterns[i].0, tokens)
+                    .expect("messages match the templates that they joined");
+                EncodedMessage {
+                    template: i as u32,
+                    params: (captures.into_iter())
+                        .map(|(start, end)| tokens[start..end].concat())
+                        .collect(),
+                }
+            })
+            .collect();
+        (Templates { templates }, encoded)
+    }
+
+    /// Returns the message that the given one encodes.
+    pub(super) fn decode(&self, message: &EncodedMessage) -> String {
+        let mut params = message.params.iter();
+        (self.templates[message.template as usize].pieces.iter())
+            .map(|piece| match piece {
+                Piece::Literal(literal) => literal.as_str(),
+                Piece::Slot => params.next().unwrap(),
+            })
+            .collect()
+    }
+}
+
+/// Splits a message into HTML tags, words, runs of whitespace and other characters.
+pub(super) fn tokenize(text: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let len = if c == '<' {
+            rest.find('>').map_or(rest.len(), |i| i + 1)
+        } else if c.is_alphanumeric() {
+            rest.find(|c: char| !c.is_alphanumeric())
+                .unwrap_or(rest.len())
+        } else if c.is_whitespace() {
+            rest.find(|c: char| !c.is_whitespace())
+                .unwrap_or(rest.len())
+        } else {
+            c.len_utf8()
+        };
+        let (token, tail) = rest.split_at(len);
+        tokens.push(token);
+        rest = tail;
+    }
+    tokens
+}
+
+// Aligns a pattern of `pattern_bytes` literal bytes with the tokens of a message, keeping their
+// common tokens with the most bytes. Returns the pattern generalized to the message, with one slot
+// for each run of pieces or tokens that only one of them has, along with the number of common
+// bytes, unless they have too few bytes in common.
+fn merge<'a>(
+    pattern: &[Piece<&'a str>],
+    pattern_bytes: usize,
+    tokens: &[&'a str],
+) -> Option<(Vec<Piece<&'a str>>, usize)> {
+    let token_bytes: usize = tokens.iter().map(|x| x.len()).sum();
+    let min_shared = pattern_bytes.max(token_bytes) * MIN_SHARED_PERCENT;
+    if pattern_bytes.min(token_bytes) * 100 < min_shared {
+        return None;
+    }
+
+    // shared[i][j] is the number of common bytes of pattern[i..] and tokens[j..].
+    let (n, m) = (pattern.len(), tokens.len());
+    let mut shared = vec![0; (n + 1) * (m + 1)];
+    let at = |i: usize, j: usize| i * (m + 1) + j;
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            let skip = shared[at(i + 1, j)].max(shared[at(i, j + 1)]);
+            shared[at(i, j)] = match pattern[i] {
+                Piece::Literal(x) if x == tokens[j] => skip.max(x.len() + shared[at(i + 1, j + 1)]),
+                _ => skip,
+            };
+        }
+    }
+    if shared[at(0, 0)] * 100 < min_shared {
+        return None;
+    }
+
+    let mut merged = Vec::new();
+    let (mut i, mut j, mut gap) = (0, 0, false);
+    while i < n && j < m {
+        match pattern[i] {
+            Piece::Literal(x)
+                if x == tokens[j] && shared[at(i, j)] == x.len() + shared[at(i + 1, j + 1)] =>
+            {
+                if gap {
+                    merged.push(Piece::Slot);
+                    gap = false;
+                }
+                merged.push(Piece::Literal(x));
+                i += 1;
+                j += 1;
+            }
+            _ => {
+                // Skipping a slot of the pattern keeps a slot, as its messages have something
+                // there.
+                gap = true;
+                if shared[at(i + 1, j)] >= shared[at(i, j + 1)] {
+                    i += 1;
+                } else {
+                    j += 1;
+                }
+            }
+        }
+    }
+    if gap || i < n || j < m {
+        merged.push(Piece::Slot);
+    }
+    Some((merged, shared[at(0, 0)]))
+}
+
+// Matches the tokens of a message with a pattern, in which each slot matches any number of tokens.
+// Returns the range of tokens matched by each slot, the earlier slots matching as few tokens as
+// possible.
+fn match_pattern(pattern: &[Piece<&str>], tokens: &[&str]) -> Option<Vec<(usize, usize)>> {
+    let mut captures: Vec<(usize, usize)> = Vec::new();
+    // Position after the last slot, and first token that the slot doesn't match yet.
+    let mut last_slot: Option<(usize, usize)> = None;
+    let (mut i, mut j) = (0, 0);
+    while j < tokens.len() {
+        match pattern.get(i) {
+            Some(Piece::Literal(x)) if *x == tokens[j] => {
+                i += 1;
+                j += 1;
+            }
+            Some(Piece::Slot) => {
+                captures.push((j, j));
+                last_slot = Some((i + 1, j));
+                i += 1;
+            }
+            _ => {
+                // Backtrack to the last slot, making it match one more token.
+                let (slot_end, slot_token) = last_slot?;
+                let slot = slot_token + 1;
+                captures.last_mut().unwrap().1 = slot;
+                last_slot = Some((slot_end, slot));
+                i = slot_end;
+                j = slot;
+            }
+        }
+    }
+    for piece in &pattern[i..] {
+        match piece {
+            Piece::Slot => captures.push((j, j)),
+            Piece::Literal(_) => return None,
+        }
+    }
+    Some(captures)
+}
+
+// Concatenates the consecutive literals of a pattern.
+fn concat_literals(pattern: &[Piece<&str>]) -> Vec<Piece<String>> {
+    let mut pieces: Vec<Piece<String>> = Vec::new();
+    for piece in pattern {
+        match (piece, pieces.last_mut()) {
+            (Piece::Literal(x), Some(Piece::Literal(literal))) => literal.push_str(x),
+            (Piece::Literal(x), _) => pieces.push(Piece::Literal(x.to_string())),
+            (Piece::Slot, _) => pieces.push(Piece::Slot),
+        }
+    }
+    pieces
+}
+
+impl Arenas {
+    /// Detects the templates of the distinct disruption messages, and prints how many messages
+    /// collapse into a template, how many bytes the templates and their parameters take, and the
+    /// `top` templates with the most messages.
+    pub fn print_message_templates(&self, top: usize) {
+        let ids: BTreeSet<InternedStr> = (self.disruption.values())
+            .filter_map(|disruption| disruption.message)
+            .collect();
+        let messages: Vec<&str> = ids.iter().map(|&x| self.string.lookup(x)).collect();
+        let (templates, encoded) = Templates::build(messages.iter().copied());
+
+        let mismatches = (messages.iter().zip(&encoded))
+            .filter(|(message, encoded)| templates.decode(encoded) != **message)
+            .count();
+        let message_bytes: usize = messages.iter().map(|x| x.len()).sum();
+        let template_bytes: usize = templates
+            .templates
+            .iter()
+            .map(Template::literal_bytes)
+            .sum();
+        let params: usize = encoded.iter().map(|x| x.params.len()).sum();
+        let param_bytes: usize = (encoded.iter())
+            .flat_map(|x| &x.params)
+            .map(|x| x.len())
+            .sum();
+        let shared: Vec<&Template> = (templates.templates.iter())
+            .filter(|x| x.messages > 1)
+            .collect();
+        let collapsed: usize = shared.iter().map(|x| x.messages).sum();
+
+        println!(
+            "Message templates: {} distinct messages ({message_bytes} bytes) written from {} templates, of which {} are shared by {collapsed} messages ({:.02}%)",
+            messages.len(),
+            templates.templates.len(),
+            shared.len(),
+            collapsed as f64 * 100.0 / messages.len() as f64,
+        );
+        println!(
+            "  Templates: {template_bytes} bytes | parameters: {params} ({param_bytes} bytes) | {} bytes in total (relative size = {:.02}%), excluding the IDs and lengths",
+            template_bytes + param_bytes,
+            (template_bytes + param_bytes) as f64 * 100.0 / message_bytes as f64,
+        );
+        if mismatches != 0 {
+            println!("  {mismatches} messages don't round-trip through their template");
+        }
+
+        let mut largest: Vec<&Template> = shared;
+        largest.sort_by_key(|x| std::cmp::Reverse(x.messages));
+        println!("  Most used templates:");
+        for template in largest.iter().take(top) {
+            println!(
+                "    {:>7} × {}",
+                template.messages,
+                truncated(format!("{:?}", template.to_display())),
+            );
+        }
+    }
+}
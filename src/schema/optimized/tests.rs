@@ -0,0 +1,1900 @@
+// Property-based tests of the interning primitives and of their serialization.
+
+use super::active::parse_instant;
+use super::bitmap::RoaringSet;
+use super::churn::{Churn, ChurnStep};
+use super::front_coding::FrontCodedArenas;
+use super::gtfs::FeedMessage;
+use super::invariants::{check_positions, Problem, Violation};
+use super::known::{Known, KnownOr, Mode, Severity};
+use super::lifetime::Lifetime;
+use super::refcount::Deduplication;
+use super::search::SearchIndex;
+use super::{
+    check_room, ArchiveError, ArchivedArenas, ArchivedData, Arenas, Data, DataError, DataSuccess,
+    FromSource, InternedSeq, InternedSet, InternedStrSet, LineHeader, LocalDatetimes,
+    LocalTimestampSeconds, TimestampMillis, CONCURRENT_ROOM, DEFAULT_TIMEZONE, DISPLAY_FORMAT,
+};
+use crate::cli::{AmbiguousDatetimes, DatabaseArgs, Format};
+use crate::codec;
+use crate::commands::verify::verify_db;
+use crate::compare::EqWith;
+use crate::database::{save_database, Database};
+use crate::error::Error;
+use crate::schema::introspect::Introspect;
+use crate::schema::Uuid;
+use blazinterner::{ArenaStr, Interned, InternedSlice, InternedStr};
+use get_size2::GetSize;
+use proptest::prelude::*;
+use rkyv::util::AlignedVec;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+
+// Serializes and deserializes the value with every serde codec supported by the database.
+fn serde_round_trips<T>(value: &T)
+where
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    let bincode: T = bincode::deserialize(&bincode::serialize(value).unwrap()).unwrap();
+    assert_eq!(&bincode, value, "bincode");
+
+    let config = bincode2::config::standard();
+    let bytes = bincode2::serde::encode_to_vec(value, config).unwrap();
+    let (bincode2, _): (T, _) = bincode2::serde::decode_from_slice(&bytes, config).unwrap();
+    assert_eq!(&bincode2, value, "bincode 2");
+
+    let bitcode: T = bitcode::deserialize(&bitcode::serialize(value).unwrap()).unwrap();
+    assert_eq!(&bitcode, value, "bitcode");
+
+    let mut cbor_bytes = Vec::new();
+    ciborium::into_writer(value, &mut cbor_bytes).unwrap();
+    let cbor: T = ciborium::from_reader(&cbor_bytes[..]).unwrap();
+    assert_eq!(&cbor, value, "CBOR");
+
+    let json: T = serde_json::from_slice(&serde_json::to_vec(value).unwrap()).unwrap();
+    assert_eq!(&json, value, "JSON");
+
+    let postcard: T = postcard::from_bytes(&postcard::to_stdvec(value).unwrap()).unwrap();
+    assert_eq!(&postcard, value, "Postcard");
+
+    let frame = codec::to_cobs_frame(value).unwrap();
+    let postcard_cobs: T = codec::from_cobs_frame(&frame).unwrap();
+    assert_eq!(&postcard_cobs, value, "Postcard COBS");
+
+    let messagepack: T = rmp_serde::from_slice(&rmp_serde::to_vec(value).unwrap()).unwrap();
+    assert_eq!(&messagepack, value, "MessagePack");
+}
+
+fn rkyv_round_trip<T>(value: &T) -> T
+where
+    T: rkyv::Archive
+        + for<'a> rkyv::Serialize<
+            rkyv::api::high::HighSerializer<
+                AlignedVec,
+                rkyv::ser::allocator::ArenaHandle<'a>,
+                rkyv::rancor::Error,
+            >,
+        >,
+    T::Archived: for<'a> rkyv::bytecheck::CheckBytes<rkyv::api::high::HighValidator<'a, rkyv::rancor::Error>>
+        + rkyv::Deserialize<T, rkyv::api::high::HighDeserializer<rkyv::rancor::Error>>,
+{
+    let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(value).unwrap();
+    rkyv::from_bytes::<T, rkyv::rancor::Error>(&bytes).unwrap()
+}
+
+fn uuid() -> impl Strategy<Value = Uuid> {
+    any::<[u8; 16]>().prop_map(|bytes| Uuid(uuid::Uuid::from_bytes(bytes)))
+}
+
+// Sorted sets of IDs, mixing arbitrary IDs with runs of consecutive IDs, which the serde encoding
+// compresses as streaks. IDs are below 2^31, as gaps between consecutive IDs are encoded as `i32`.
+fn id_set() -> impl Strategy<Value = Vec<u32>> {
+    let max_id = i32::MAX as u32;
+    let arbitrary = prop::collection::vec(0..=max_id, 0..100);
+    let small = prop::collection::vec(0..20u32, 0..100);
+    let streaks = prop::collection::vec((0..1000u32, 1..200u32), 0..10).prop_map(|runs| {
+        let mut ids = Vec::new();
+        let mut next = 0;
+        for (gap, len) in runs {
+            next += gap;
+            ids.extend(next..next + len);
+            next += len;
+        }
+        ids
+    });
+    prop_oneof![arbitrary, small, streaks].prop_map(|mut ids| {
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    })
+}
+
+// Strings that often share a prefix with each other, including multi-byte characters.
+fn prefixed_string() -> impl Strategy<Value = String> {
+    prop_oneof![".*", "(line:IDFM:|stop_area:|été)?[a-cé]{0,4}"]
+}
+
+proptest! {
+    #[test]
+    fn string_intern_lookup_is_identity(strings in prop::collection::vec(".*", 0..50)) {
+        let arenas = Arenas::default();
+        let interned: Vec<_> = strings.iter().map(|x| arenas.string.intern(x.as_str())).collect();
+        for (x, id) in strings.iter().zip(&interned) {
+            prop_assert_eq!(arenas.string.lookup(*id), x.as_str());
+            // Interning the same value again returns the same handle.
+            prop_assert_eq!(arenas.string.intern(x.as_str()), *id);
+        }
+    }
+
+    #[test]
+    fn string_introspection_follows_id_order(strings in prop::collection::vec(".*", 0..50)) {
+        let arenas = Arenas::default();
+        let mut distinct: Vec<&str> = Vec::new();
+        for x in &strings {
+            arenas.string.intern(x.as_str());
+            if !distinct.contains(&x.as_str()) {
+                distinct.push(x);
+            }
+        }
+        prop_assert_eq!(arenas.string.values().collect::<Vec<_>>(), distinct);
+        for (id, x) in arenas.string.iter() {
+            prop_assert_eq!(arenas.string.position(x), Some(id));
+        }
+        if !strings.iter().any(|x| x == "not interned") {
+            prop_assert_eq!(arenas.string.position("not interned"), None);
+        }
+    }
+
+    #[test]
+    fn uuid_intern_lookup_is_identity(uuids in prop::collection::vec(uuid(), 0..50)) {
+        let arenas = Arenas::default();
+        let interned: Vec<_> = uuids.iter().map(|x| arenas.uuid.intern(x.clone())).collect();
+        for (x, id) in uuids.iter().zip(&interned) {
+            prop_assert_eq!(arenas.uuid.lookup_ref(*id), x);
+            // Interning the same value again by reference returns the same handle.
+            prop_assert_eq!(arenas.uuid.intern(x), *id);
+        }
+    }
+
+    #[test]
+    fn arenas_round_trip(
+        strings in prop::collection::vec(".*", 0..50),
+        uuids in prop::collection::vec(uuid(), 0..50),
+    ) {
+        let arenas = Arenas::default();
+        for x in &strings {
+            arenas.string.intern(x.as_str());
+        }
+        for x in uuids {
+            arenas.uuid.intern(x);
+        }
+
+        serde_round_trips(&arenas);
+        prop_assert_eq!(rkyv_round_trip(&arenas), arenas);
+    }
+
+    #[test]
+    fn front_coded_arenas_round_trip(strings in prop::collection::vec(prefixed_string(), 0..50)) {
+        let arenas = Arenas::default();
+        for x in &strings {
+            arenas.string.intern(x.as_str());
+        }
+
+        serde_round_trips(&FrontCodedArenas(arenas));
+    }
+
+    #[test]
+    fn interned_set_round_trip(ids in id_set()) {
+        let set: InternedSet<Uuid> = ids.iter().map(|&id| Interned::from_id(id)).collect();
+        prop_assert_eq!(set.iter().map(|x| x.id()).collect::<Vec<_>>(), ids);
+
+        serde_round_trips(&set);
+        prop_assert_eq!(rkyv_round_trip(&set), set);
+    }
+
+    #[test]
+    fn set_eq_by_key_compares_multisets(
+        (lhs, rhs) in prop::collection::vec(0u8..8, 0..10).prop_flat_map(|lhs| {
+            let shuffled = Just(lhs.clone()).prop_shuffle();
+            (Just(lhs), prop_oneof![shuffled, prop::collection::vec(0u8..8, 0..10)])
+        }),
+    ) {
+        // The item 7 has no handle, as if it wasn't interned.
+        let key = |x: &u8| (*x != 7).then_some(*x);
+        let sorted = |x: &[u8]| {
+            let mut x = x.to_vec();
+            x.sort_unstable();
+            x
+        };
+        let expected = !rhs.contains(&7) && sorted(&lhs) == sorted(&rhs);
+        prop_assert_eq!(super::set_eq_by_key(&lhs, &rhs, key), expected);
+    }
+
+    #[test]
+    fn message_templates_round_trip(
+        messages in prop::collection::vec("(<p>|a|b|c| |,|é){0,12}", 0..20),
+    ) {
+        let (templates, encoded) =
+            super::templates::Templates::build(messages.iter().map(String::as_str));
+        for (message, encoded) in messages.iter().zip(&encoded) {
+            prop_assert_eq!(&templates.decode(encoded), message);
+        }
+    }
+
+    #[test]
+    fn interned_set_decoder_accepts_strictly_increasing_ids_only(
+        encoded in prop::collection::vec(prop_oneof![-20i32..20, -1000i32..0, 0..=i32::MAX], 0..20),
+    ) {
+        let json = serde_json::to_string(&encoded).unwrap();
+        if let Ok(set) = serde_json::from_str::<InternedSet<Uuid>>(&json) {
+            let ids: Vec<u32> = set.iter().map(|x| x.id()).collect();
+            prop_assert!(ids.windows(2).all(|x| x[0] < x[1]));
+            serde_round_trips(&set);
+        }
+    }
+
+    #[test]
+    fn interned_seq_decoder_is_consistent(
+        encoded in prop::collection::vec(prop_oneof![-20i32..20, -1000i32..0, 0..=i32::MAX], 0..20),
+    ) {
+        let json = serde_json::to_string(&encoded).unwrap();
+        if let Ok(seq) = serde_json::from_str::<InternedSeq<Uuid>>(&json) {
+            serde_round_trips(&seq);
+        }
+    }
+
+    #[test]
+    fn roaring_set_round_trip(ids in id_set()) {
+        let set: RoaringSet<Uuid> = ids.iter().map(|&id| Interned::from_id(id)).collect();
+        let mut distinct = ids.clone();
+        distinct.dedup();
+        prop_assert_eq!(set.iter().map(|x| x.id()).collect::<Vec<_>>(), distinct);
+
+        serde_round_trips(&set);
+    }
+}
+
+#[test]
+fn interned_seq_round_trip() {
+    for ids in [
+        vec![],
+        vec![0],
+        vec![3, 1, 2, 1],
+        vec![5, 5, 6, 7, 8, 2, 3, 4],
+        vec![u32::MAX, 0, u32::MAX],
+        (0..1000).collect(),
+        (0..1000).rev().collect(),
+    ] {
+        let seq: InternedSeq<Uuid> = ids.iter().map(|&id| Interned::from_id(id)).collect();
+        assert_eq!(seq.iter().map(|x| x.id()).collect::<Vec<_>>(), ids);
+        serde_round_trips(&seq);
+        assert_eq!(rkyv_round_trip(&seq), seq);
+    }
+}
+
+#[test]
+fn interned_set_edge_cases() {
+    for ids in [
+        vec![],
+        vec![0],
+        vec![0, 0],
+        vec![0, 1, 2, 3],
+        vec![5, 5, 5, 6, 7, 7],
+        (0..10_000).collect(),
+        vec![0, i32::MAX as u32],
+    ] {
+        let set: InternedSet<Uuid> = ids.iter().map(|&id| Interned::from_id(id)).collect();
+        assert!(set.iter().map(|x| x.id()).is_sorted_by(|x, y| x < y));
+        serde_round_trips(&set);
+        assert_eq!(rkyv_round_trip(&set), set);
+    }
+}
+
+#[test]
+fn malformed_interned_sets_are_rejected() {
+    let set = |json: &str| {
+        serde_json::from_str::<InternedSet<Uuid>>(json)
+            .map(|set| set.iter().map(|x| x.id()).collect::<Vec<_>>())
+            .map_err(|e| e.to_string())
+    };
+    assert_eq!(set("[0, 2, -3, 4]"), Ok(vec![0, 2, 3, 4, 5, 9]));
+    assert!(set("[2, -3, 0, 4]")
+        .unwrap_err()
+        .contains("difference of 0 after ID 5 duplicates it"));
+    assert!(set("[0, 0]")
+        .unwrap_err()
+        .contains("difference of 0 after ID 0 duplicates it"));
+    assert!(set("[-2, 5]")
+        .unwrap_err()
+        .contains("streak of consecutive IDs before the first ID"));
+    assert!(set("[2147483647, 2147483647, -10]")
+        .unwrap_err()
+        .contains("streak of 10 consecutive IDs after ID 4294967294 overflows"));
+    assert!(set("[2147483647, 2147483647, 2]")
+        .unwrap_err()
+        .contains("difference of 2 after ID 4294967294 overflows"));
+    assert!(set("[1, \"a\"]").is_err());
+    assert!(set("[0, -2147483648]")
+        .unwrap_err()
+        .contains("streak of 2147483648 consecutive IDs after 1 IDs exceeds the maximum"));
+    assert_eq!(set("[0, -16777215]").map(|ids| ids.len()), Ok(1 << 24));
+
+    let seq = |json: &str| {
+        serde_json::from_str::<InternedSeq<Uuid>>(json)
+            .map(|seq| seq.iter().map(|x| x.id()).collect::<Vec<_>>())
+            .map_err(|e| e.to_string())
+    };
+    assert_eq!(seq("[5, 0, -2, -2]"), Ok(vec![5, 3, 4, 5]));
+    assert!(seq("[-1]")
+        .unwrap_err()
+        .contains("streak of consecutive IDs before the first ID"));
+    assert!(seq("[3, 0]")
+        .unwrap_err()
+        .contains("escaped difference missing at the end"));
+    assert!(seq("[3, 0, 2]")
+        .unwrap_err()
+        .contains("escaped difference 2 is positive"));
+    assert!(seq("[1, -16777215, -1]")
+        .unwrap_err()
+        .contains("streak of 1 consecutive IDs after 16777216 IDs exceeds the maximum"));
+}
+
+#[test]
+fn size_hints_of_truncated_inputs_are_not_trusted() {
+    // Sequences claiming 2^60 elements, without any of them.
+    const BINCODE: [u8; 8] = (1u64 << 60).to_le_bytes();
+    // CBOR array whose length is given by the next 8 bytes.
+    const CBOR: [u8; 9] = [0x9b, 0x10, 0, 0, 0, 0, 0, 0, 0];
+    // Postcard varint.
+    const POSTCARD: [u8; 9] = [0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x10];
+
+    fn rejected<T: serde::de::DeserializeOwned>() -> [bool; 3] {
+        [
+            bincode::deserialize::<T>(&BINCODE).is_err(),
+            ciborium::from_reader::<T, _>(&CBOR[..]).is_err(),
+            postcard::from_bytes::<T>(&POSTCARD).is_err(),
+        ]
+    }
+    assert_eq!(rejected::<InternedSet<Uuid>>(), [true; 3]);
+    assert_eq!(rejected::<InternedSeq<Uuid>>(), [true; 3]);
+    assert_eq!(rejected::<InternedStrSet>(), [true; 3]);
+}
+
+#[test]
+fn check_room_reports_full_arenas() {
+    assert_eq!(check_room("string", 1, "strings", 1), Ok(()));
+
+    // With the room kept for other threads, one more string would make the arena reach 2^32 + 1
+    // strings.
+    let len = u32::MAX as usize - CONCURRENT_ROOM;
+    assert_eq!(
+        check_room("string", len, "strings", 1),
+        Err(Error::ArenaFull {
+            arena: "string",
+            len,
+            unit: "strings",
+        })
+    );
+    assert_eq!(check_room("string", len - 1, "strings", 1), Ok(()));
+    // A string takes as much room as its length in the byte offsets.
+    assert_eq!(check_room("string", len - 10, "bytes", 10), Ok(()));
+    assert!(check_room("string", len - 10, "bytes", 11).is_err());
+}
+
+#[test]
+fn validate_reports_dangling_ids() {
+    let data = |arenas: &Arenas, message| {
+        arenas
+            .intern_data(Data::Error(DataError {
+                status_code: 500,
+                error: arenas.string.intern("error"),
+                message,
+            }))
+            .unwrap()
+    };
+
+    let arenas = Arenas::default();
+    let snapshot = data(&arenas, InternedStr::from_id(0));
+    assert_eq!(arenas.validate(), Ok(()));
+    assert_eq!(arenas.validate_snapshot(snapshot, 0), Ok(()));
+    assert_eq!(
+        arenas
+            .validate_snapshot(Interned::from_id(1), 3)
+            .unwrap_err()
+            .to_string(),
+        "snapshot #3 refers to data #1, but the data arena only contains 1 values",
+    );
+
+    let error = arenas.string.intern("error");
+    arenas.line_header.intern(LineHeader {
+        id: error,
+        name: error,
+        short_name: InternedStr::from_id(7),
+        mode: Known::new(&arenas.string, "Metro").unwrap(),
+        network_id: error,
+    });
+    assert_eq!(
+        arenas.validate().unwrap_err().to_string(),
+        "line_header #0 refers to string #7, but the string arena only contains 1 values",
+    );
+
+    let arenas = Arenas::default();
+    data(&arenas, InternedStr::from_id(1));
+    assert_eq!(
+        arenas.validate().unwrap_err().to_string(),
+        "data #0 refers to string #1, but the string arena only contains 1 values",
+    );
+}
+
+#[test]
+fn local_timestamps_out_of_range_are_rejected() {
+    // The latest instant that chrono represents is valid in UTC, but not in a timezone ahead of it.
+    let latest = chrono::DateTime::<chrono::Utc>::MAX_UTC.timestamp();
+    let arenas = Arenas::default();
+    arenas
+        .timestamp
+        .intern(LocalTimestampSeconds(latest - 86400));
+    assert_eq!(arenas.check_timestamps(), Ok(()));
+
+    arenas.timestamp.intern(LocalTimestampSeconds(latest));
+    assert_eq!(
+        arenas.check_timestamps().unwrap_err().to_string(),
+        format!("timestamp #1: timestamp {latest} is out of range"),
+    );
+}
+
+#[test]
+fn check_invariants_reports_broken_arenas() {
+    let arenas = Arenas::default();
+    let a = arenas.string.intern("a");
+    let b = arenas.string.intern("b");
+    arenas.string_set.intern([b, a, a]);
+    arenas
+        .timestamp
+        .intern(LocalTimestampSeconds(1_700_000_000));
+    assert_eq!(arenas.check_invariants(), []);
+
+    // Sets interned in the arena are sorted, but not those of a hand-edited database.
+    arenas.string_set.0.intern_copy(&[a, b, a]);
+    arenas.timestamp.intern(LocalTimestampSeconds(i64::MAX));
+    let violations: Vec<String> = (arenas.check_invariants().iter())
+        .map(ToString::to_string)
+        .collect();
+    assert_eq!(
+        violations,
+        [
+            "string_set #1: items 1 and 2 aren't sorted",
+            "timestamp #1: timestamp 9223372036854775807 is out of range",
+        ],
+    );
+
+    assert_eq!(
+        arenas.check_timestamps().unwrap_err().to_string(),
+        "timestamp #1: timestamp 9223372036854775807 is out of range",
+    );
+
+    // Deserializing an arena doesn't deduplicate its values, so the second one can't be looked up.
+    let string: ArenaStr = serde_json::from_str(r#"[[1, 1], "aa"]"#).unwrap();
+    let mut violations = Vec::new();
+    check_positions(&string, &mut violations);
+    assert_eq!(
+        violations,
+        [Violation {
+            arena: "string",
+            id: 1,
+            problem: Problem::Misplaced { found: Some(0) },
+        }],
+    );
+}
+
+#[test]
+fn verify_db_checks_set_order() {
+    let path = std::env::temp_dir().join(format!(
+        "rust-interning-verify-db-{}/stream.db",
+        std::process::id()
+    ));
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let args = DatabaseArgs {
+        path: path.clone(),
+        format: None,
+    };
+
+    // Sets are sorted multisets, which keep the repeated items of their source array.
+    let arenas = Arenas::default();
+    let a = arenas.string.intern("a");
+    let b = arenas.string.intern("b");
+    let set = arenas.string_set.intern([b, a, a]);
+    assert_eq!(arenas.string_set.lookup(set).0, [a, a, b]);
+    save_database(&Database::new(arenas), &path, Format::Stream).unwrap();
+    assert!(verify_db(&args).is_ok());
+
+    let arenas = Arenas::default();
+    let a = arenas.string.intern("a");
+    let b = arenas.string.intern("b");
+    arenas.string_set.0.intern_copy(&[b, a]);
+    save_database(&Database::new(arenas), &path, Format::Stream).unwrap();
+    assert_eq!(
+        verify_db(&args).unwrap_err().to_string(),
+        "invalid database: 1 invariant violations"
+    );
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+// Archives are queried in place, so their accessors report the values that they can't read rather
+// than panicking.
+#[test]
+fn archived_accessors_report_invalid_values() {
+    let arenas = Arenas::default();
+    let data = arenas
+        .intern_data(Data::Success(DataSuccess {
+            disruptions: InternedSlice::from_id(0),
+            lines: InternedSlice::from_id(3),
+            last_updated_date: TimestampMillis(i64::MAX),
+        }))
+        .unwrap();
+    let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&arenas).unwrap();
+    let archived = rkyv::access::<ArchivedArenas, rkyv::rancor::Error>(&bytes).unwrap();
+
+    assert_eq!(
+        archived.data(1).err().unwrap().to_string(),
+        "invalid database: data #1 is missing, the data arena only contains 1 values",
+    );
+    let ArchivedData::Success(success) = archived.data(data.id()).unwrap() else {
+        panic!("expected a successful snapshot");
+    };
+    assert_eq!(
+        success.disruptions(archived).unwrap_err(),
+        ArchiveError::MissingValue {
+            arena: "disruption_set",
+            id: 0,
+            arena_len: 0,
+        },
+    );
+    assert_eq!(
+        success.line_disruptions(archived, "1").unwrap_err(),
+        ArchiveError::MissingValue {
+            arena: "line_set",
+            id: 3,
+            arena_len: 0,
+        },
+    );
+    assert_eq!(
+        success.last_updated_date().unwrap_err(),
+        ArchiveError::InvalidTimestamp {
+            timestamp: i64::MAX,
+        },
+    );
+}
+
+#[test]
+fn known_values_fall_back_to_strings() {
+    let arenas = Arenas::default();
+    let metro = Known::<Mode>::new(&arenas.string, "Metro").unwrap();
+    assert_eq!(metro.get(), KnownOr::Known(Mode::Metro));
+    assert_eq!(metro.other(), None);
+    assert_eq!(arenas.strings().count(), 0);
+
+    let cable = Known::<Mode>::new(&arenas.string, "Cable").unwrap();
+    assert_eq!(cable.get(), KnownOr::Other(InternedStr::from_id(0)));
+    assert_eq!(cable.as_str(&arenas.string), "Cable");
+    assert_ne!(cable, metro);
+
+    // Strings interned before are decoded as known values.
+    let bloquante = arenas.string.intern("BLOQUANTE");
+    let severity = Known::<Severity>::from_interned(&arenas.string, bloquante);
+    assert_eq!(severity.get(), KnownOr::Known(Severity::Blocking));
+    assert_eq!(severity.as_str(&arenas.string), "BLOQUANTE");
+
+    // Known values are serialized as their index, and other strings after them.
+    assert_eq!(serde_json::to_value(metro).unwrap(), serde_json::json!(0));
+    assert_eq!(serde_json::to_value(cable).unwrap(), serde_json::json!(6));
+    for value in [metro, cable] {
+        serde_round_trips(&value);
+        assert_eq!(rkyv_round_trip(&value), value);
+    }
+}
+
+#[test]
+fn front_coded_strings_are_checked() {
+    // Front-coded arenas differ from the default encoding only by their strings, so the other
+    // arenas can be copied from an empty one.
+    let empty = serde_json::to_value(Arenas::default()).unwrap();
+    let arenas = |strings: serde_json::Value| {
+        let mut value = empty.clone();
+        value[0] = strings;
+        serde_json::from_value::<FrontCodedArenas>(value).map_err(|e| e.to_string())
+    };
+
+    let valid = arenas(serde_json::json!([[1, 0], [[0, "line:1"], [5, "2"]]])).unwrap();
+    assert_eq!(valid.0.string.lookup(InternedStr::from_id(0)), "line:2");
+    assert_eq!(valid.0.string.lookup(InternedStr::from_id(1)), "line:1");
+
+    assert_eq!(
+        arenas(serde_json::json!([[0], []])).unwrap_err(),
+        "front-coded strings: 1 IDs for 0 strings",
+    );
+    assert_eq!(
+        arenas(serde_json::json!([[0, 0], [[0, "a"], [1, "b"]]])).unwrap_err(),
+        "front-coded strings: invalid or duplicate ID 0",
+    );
+    assert_eq!(
+        arenas(serde_json::json!([[0, 1], [[0, "é"], [1, "b"]]])).unwrap_err(),
+        "front-coded strings: invalid prefix length 1 after \"é\"",
+    );
+}
+
+#[test]
+fn compact_drops_unreferenced_values() {
+    let snapshot = |disruption_id: &str, line_id: &str| {
+        crate::schema::source::Data::deserialize(serde_json::json!({
+            "disruptions": [{
+                "id": disruption_id,
+                "applicationPeriods": [{"begin": "20240101T100000", "end": "20240102T100000"}],
+                "lastUpdate": "20240101T090000",
+                "cause": "TRAVAUX",
+                "severity": "BLOQUANTE",
+                "tags": ["a"],
+                "title": "T",
+                "message": "m",
+                "shortMessage": null,
+                "disruption_id": null,
+            }],
+            "lines": [{
+                "id": line_id,
+                "name": "Line",
+                "shortName": "1",
+                "mode": "Metro",
+                "networkId": "N",
+                "impactedObjects": [{
+                    "type": "line",
+                    "id": line_id,
+                    "name": "Line",
+                    "disruptionIds": [disruption_id],
+                }],
+            }],
+            "lastUpdatedDate": "2024-01-01T10:00:00.000Z",
+        }))
+        .unwrap()
+    };
+    let kept_source = snapshot("11111111-1111-1111-1111-111111111111", "line:IDFM:1");
+    let dropped_source = snapshot("22222222-2222-2222-2222-222222222222", "line:IDFM:2");
+
+    let arenas = Arenas::default();
+    let dropped = Data::from_source(&arenas, &dropped_source).unwrap();
+    arenas.intern_data(dropped).unwrap();
+    let kept = Data::from_source(&arenas, &kept_source).unwrap();
+    let kept = arenas.intern_data(kept).unwrap();
+
+    // Only the values shared with the dropped snapshot survive from it.
+    let (compacted, mapping) = arenas.compact(&[kept]).unwrap();
+    let reference = Arenas::default();
+    let expected = Data::from_source(&reference, &kept_source).unwrap();
+    reference.intern_data(expected).unwrap();
+    assert_eq!(compacted.value_count(), reference.value_count());
+    assert!(compacted.value_count() < arenas.value_count());
+
+    assert_eq!(compacted.validate(), Ok(()));
+    let kept = mapping.data(kept);
+    assert_eq!(compacted.validate_snapshot(kept, 0), Ok(()));
+    assert!(compacted.data(kept).eq_with(&kept_source, &compacted));
+
+    // Compacting arenas without unreferenced values keeps them as is.
+    let (recompacted, remapping) = compacted.compact(&[kept]).unwrap();
+    assert_eq!(recompacted, compacted);
+    assert_eq!(remapping.data(kept), kept);
+}
+
+#[test]
+fn short_message_is_preserved() {
+    let json = serde_json::json!({
+        "disruptions": [{
+            "id": "11111111-1111-1111-1111-111111111111",
+            "applicationPeriods": [],
+            "lastUpdate": "20240101T090000",
+            "cause": "TRAVAUX",
+            "severity": "BLOQUANTE",
+            "tags": null,
+            "title": "T",
+            "message": "m",
+            "shortMessage": "short",
+            "disruption_id": null,
+        }],
+        "lines": [],
+        "lastUpdatedDate": "2024-01-01T10:00:00.000Z",
+    });
+    let mut source: crate::schema::source::Data =
+        crate::schema::source::Data::deserialize(json.clone()).unwrap();
+
+    let arenas = Arenas::default();
+    let data = arenas
+        .intern_data(Data::from_source(&arenas, &source).unwrap())
+        .unwrap();
+    let direct = super::seed::from_slice(&arenas, json.to_string().as_bytes()).unwrap();
+    assert_eq!(arenas.intern_data(direct).unwrap(), data);
+
+    let data = arenas.data(data);
+    let disruption = arenas.disruption(Interned::from_id(0));
+    assert_eq!(
+        disruption.short_message.map(|x| arenas.string.lookup(x)),
+        Some("short"),
+    );
+    let disruptions = data.to_source(&arenas).disruptions.unwrap();
+    assert_eq!(disruptions[0].short_message.as_deref(), Some("short"));
+
+    assert!(data.eq_with(&source, &arenas));
+    source.disruptions.as_mut().unwrap()[0].short_message = None;
+    assert!(!data.eq_with(&source, &arenas));
+}
+
+#[test]
+fn source_order_is_preserved() {
+    let disruption = |id: &str| {
+        serde_json::json!({
+            "id": id,
+            "applicationPeriods": [],
+            "lastUpdate": "20240101T090000",
+            "cause": "TRAVAUX",
+            "severity": "BLOQUANTE",
+            "tags": null,
+            "title": id,
+            "message": null,
+            "shortMessage": null,
+            "disruption_id": null,
+        })
+    };
+    let object = |id: &str| {
+        serde_json::json!({
+            "type": "stop_area",
+            "id": id,
+            "name": id,
+            "disruptionIds": [],
+        })
+    };
+    let line = |id: &str, objects: &[&str]| {
+        serde_json::json!({
+            "id": id,
+            "name": id,
+            "shortName": id,
+            "mode": "Bus",
+            "networkId": "N",
+            "impactedObjects": objects.iter().map(|x| object(x)).collect::<Vec<_>>(),
+        })
+    };
+    let snapshot = |disruptions: &[&str], lines: serde_json::Value| {
+        serde_json::json!({
+            "disruptions": disruptions.iter().map(|x| disruption(x)).collect::<Vec<_>>(),
+            "lines": lines,
+            "lastUpdatedDate": "2024-01-01T10:00:00.000Z",
+        })
+    };
+    let first = snapshot(
+        &[
+            "11111111-1111-1111-1111-111111111111",
+            "22222222-2222-2222-2222-222222222222",
+        ],
+        serde_json::json!([line("a", &["x", "y"]), line("b", &["y", "x"])]),
+    );
+    let second = snapshot(
+        &[
+            "22222222-2222-2222-2222-222222222222",
+            "11111111-1111-1111-1111-111111111111",
+        ],
+        serde_json::json!([line("b", &["y", "x"]), line("a", &["x", "y"])]),
+    );
+
+    let arenas = Arenas::default();
+    let mut datas = Vec::new();
+    for json in [&first, &second] {
+        let source: crate::schema::source::Data =
+            crate::schema::source::Data::deserialize(json.clone()).unwrap();
+        let data = arenas
+            .intern_data(Data::from_source(&arenas, &source).unwrap())
+            .unwrap();
+        let direct = super::seed::from_slice(&arenas, json.to_string().as_bytes()).unwrap();
+        assert_eq!(arenas.intern_data(direct).unwrap(), data);
+        assert_eq!(
+            serde_json::to_value(arenas.data(data).to_source(&arenas)).unwrap(),
+            *json,
+        );
+        datas.push(data);
+    }
+
+    // The same items in a different order make different sequences.
+    assert_ne!(datas[0], datas[1]);
+    assert_eq!(arenas.disruption.len(), 2);
+    assert_eq!(arenas.line.len(), 2);
+    let source: crate::schema::source::Data =
+        crate::schema::source::Data::deserialize(second).unwrap();
+    assert!(!arenas.data(datas[0]).eq_with(&source, &arenas));
+}
+
+#[test]
+fn equal_tag_sets_are_interned_once() {
+    let disruption = |id: &str, tags: &[&str]| {
+        serde_json::json!({
+            "id": id,
+            "applicationPeriods": [],
+            "lastUpdate": "20240101T090000",
+            "cause": "TRAVAUX",
+            "severity": "BLOQUANTE",
+            "tags": tags,
+            "title": "T",
+            "message": null,
+            "shortMessage": null,
+            "disruption_id": null,
+        })
+    };
+    let json = serde_json::json!({
+        "disruptions": [
+            disruption("11111111-1111-1111-1111-111111111111", &["a", "b"]),
+            disruption("22222222-2222-2222-2222-222222222222", &["b", "a"]),
+            disruption("33333333-3333-3333-3333-333333333333", &["a"]),
+        ],
+        "lines": [],
+        "lastUpdatedDate": "2024-01-01T10:00:00.000Z",
+    });
+    let mut source: crate::schema::source::Data =
+        crate::schema::source::Data::deserialize(json.clone()).unwrap();
+
+    let arenas = Arenas::default();
+    let data = arenas
+        .intern_data(Data::from_source(&arenas, &source).unwrap())
+        .unwrap();
+    let direct = super::seed::from_slice(&arenas, json.to_string().as_bytes()).unwrap();
+    assert_eq!(arenas.intern_data(direct).unwrap(), data);
+
+    // Sets are sorted when interned, so the order of the tags doesn't matter.
+    assert_eq!(arenas.string_set.len(), 2);
+    let tags = |i| arenas.disruption(Interned::from_id(i)).tags.unwrap();
+    assert_eq!(tags(0), tags(1));
+    assert_ne!(tags(0), tags(2));
+
+    let data = arenas.data(data);
+    assert!(data.eq_with(&source, &arenas));
+    source.disruptions.as_mut().unwrap()[2].tags = Some(vec!["b".into()]);
+    assert!(!data.eq_with(&source, &arenas));
+
+    // Comparing with a tag that was never interned doesn't intern it.
+    let strings = arenas.string.len();
+    source.disruptions.as_mut().unwrap()[2].tags = Some(vec!["c".into()]);
+    assert!(!data.eq_with(&source, &arenas));
+    assert_eq!(arenas.string.len(), strings);
+}
+
+#[test]
+fn string_domains_tag_each_referring_field() {
+    use super::domains::Domain;
+
+    let json = serde_json::json!({
+        "disruptions": [{
+            "id": "11111111-1111-1111-1111-111111111111",
+            "applicationPeriods": [],
+            "lastUpdate": "20240101T090000",
+            "cause": "TRAVAUX",
+            "severity": "BLOQUANTE",
+            "tags": ["T"],
+            "title": "T",
+            "message": "M",
+            "shortMessage": "M",
+            "disruption_id": null,
+        }],
+        "lines": [],
+        "lastUpdatedDate": "2024-01-01T10:00:00.000Z",
+    });
+    let source = crate::schema::source::Data::deserialize(json).unwrap();
+    let arenas = Arenas::default();
+    arenas
+        .intern_data(Data::from_source(&arenas, &source).unwrap())
+        .unwrap();
+    arenas.string.intern("unreferenced");
+
+    let domains = arenas.string_domains();
+    let domains_of = |x| domains[arenas.string.find(x).unwrap().id() as usize];
+    assert_eq!(domains_of("TRAVAUX"), Domain::DisruptionCause.bit());
+    assert_eq!(
+        domains_of("T"),
+        Domain::DisruptionTags.bit() | Domain::DisruptionTitle.bit()
+    );
+    assert_eq!(
+        domains_of("M"),
+        Domain::DisruptionMessage.bit() | Domain::DisruptionShortMessage.bit()
+    );
+    assert_eq!(domains_of("unreferenced"), 0);
+    // Known severities aren't interned.
+    assert_eq!(arenas.string.find("BLOQUANTE"), None);
+}
+
+#[test]
+fn string_lengths_are_bucketed_by_powers_of_two() {
+    use super::domains::{length_histogram, LengthBucket};
+
+    let bucket = |min, max, strings, bytes| LengthBucket {
+        min,
+        max,
+        strings,
+        bytes,
+    };
+    assert_eq!(length_histogram([].into_iter()), []);
+    assert_eq!(
+        length_histogram([3, 0, 4, 7, 2].into_iter()),
+        [
+            bucket(0, 1, 1, 0),
+            bucket(1, 2, 0, 0),
+            bucket(2, 4, 2, 5),
+            bucket(4, 8, 2, 11),
+        ]
+    );
+}
+
+#[test]
+fn messages_are_encoded_with_their_template() {
+    use super::templates::{Piece, Templates};
+
+    let messages = [
+        "<p>Suite à un incident à Nation, le trafic est perturbé jusqu'à 18h30.</p>",
+        "<p>Travaux de nuit du 12/03 au 14/03.</p>",
+        "<p>Suite à un incident à Gare de Lyon, le trafic est perturbé jusqu'à 19h00.</p>",
+        "<p>Suite à un incident à Bastille, le trafic est perturbé jusqu'à 20h15.</p>",
+        "",
+    ];
+    let (templates, encoded) = Templates::build(messages);
+    for (message, encoded) in messages.iter().zip(&encoded) {
+        assert_eq!(templates.decode(encoded), *message);
+    }
+
+    assert_eq!(templates.templates.len(), 3);
+    let incident = &templates.templates[0];
+    assert_eq!(incident.messages, 3);
+    assert_eq!(
+        incident.pieces,
+        [
+            Piece::Literal("<p>Suite à un incident à ".to_owned()),
+            Piece::Slot,
+            Piece::Literal(", le trafic est perturbé jusqu'à ".to_owned()),
+            Piece::Slot,
+            Piece::Literal(".</p>".to_owned()),
+        ]
+    );
+    assert_eq!(encoded[2].template, 0);
+    assert_eq!(encoded[2].params, ["Gare de Lyon", "19h00"]);
+    assert_eq!(encoded[1].template, 1);
+    assert!(encoded[1].params.is_empty());
+}
+
+#[test]
+fn similar_messages_are_clustered() {
+    let messages = [
+        "<p>En raison de travaux, le trafic est interrompu entre Nation et Bastille du lundi au vendredi à partir de 22h00.</p>",
+        "Arrêt non desservi",
+        "<p>En raison de travaux, le trafic est interrompu entre Nation et Bastille du lundi au vendredi à partir de 22h30.</p>",
+        "<p>Suite à un incident voyageur, le trafic est perturbé sur l'ensemble de la ligne.</p>",
+        "<p>En raison de travaux, le trafic est interrompu entre Nation et Bastille du lundi au vendredi à partir de 21h45.</p>",
+        "",
+    ];
+    let clusters = super::similarity::clusters(&messages, 0.6);
+    assert_eq!(clusters, [0, 1, 0, 3, 0, 5]);
+    // Only identical sets of shingles are clustered with a threshold of 1.
+    assert_eq!(
+        super::similarity::clusters(&messages, 1.0),
+        [0, 1, 2, 3, 4, 5]
+    );
+}
+
+#[test]
+fn set_codecs_count_run_length_integers() {
+    use super::set_codecs::{Encoding, SetCodecs};
+
+    let mut codecs = SetCodecs::default();
+    // Encoded as [1, -3, 6].
+    codecs.add(&[1, 2, 3, 4, 10], true);
+    assert_eq!((codecs.sets, codecs.items), (1, 5));
+    assert_eq!(codecs.run_length_integers, 3);
+    assert_eq!(codecs.bytes("Postcard", Encoding::Ids), 6);
+    assert_eq!(codecs.bytes("Postcard", Encoding::RunLength), 4);
+    assert_eq!(codecs.bytes("Bincode", Encoding::Ids), 8 + 5 * 4);
+    assert_eq!(codecs.bytes("Bincode", Encoding::RunLength), 8 + 3 * 4);
+
+    // Encoded as [5, 0, -2, -2]: going back to 3 is escaped, and 4, 5 is a streak.
+    codecs.add(&[5, 3, 4, 5], false);
+    assert_eq!((codecs.sets, codecs.items), (2, 9));
+    assert_eq!(codecs.run_length_integers, 3 + 4);
+    assert_eq!(codecs.bytes("Bincode", Encoding::Deltas), 2 * 8 + 9 * 4);
+}
+
+#[test]
+fn equal_last_updates_are_interned_once() {
+    let disruption = |id: &str, last_update: &str| {
+        serde_json::json!({
+            "id": id,
+            "applicationPeriods": [],
+            "lastUpdate": last_update,
+            "cause": "TRAVAUX",
+            "severity": "BLOQUANTE",
+            "tags": null,
+            "title": "T",
+            "message": null,
+            "shortMessage": null,
+            "disruption_id": null,
+        })
+    };
+    let json = serde_json::json!({
+        "disruptions": [
+            disruption("11111111-1111-1111-1111-111111111111", "20240101T090000"),
+            disruption("22222222-2222-2222-2222-222222222222", "20240101T090000"),
+            disruption("33333333-3333-3333-3333-333333333333", "20240101T090001"),
+        ],
+        "lines": [],
+        "lastUpdatedDate": "2024-01-01T10:00:00.000Z",
+    });
+    let source: crate::schema::source::Data =
+        crate::schema::source::Data::deserialize(json.clone()).unwrap();
+
+    let arenas = Arenas::default();
+    let data = arenas
+        .intern_data(Data::from_source(&arenas, &source).unwrap())
+        .unwrap();
+    let direct = super::seed::from_slice(&arenas, json.to_string().as_bytes()).unwrap();
+    assert_eq!(arenas.intern_data(direct).unwrap(), data);
+
+    assert_eq!(arenas.timestamp.len(), 2);
+    let last_update = |i| arenas.disruption(Interned::from_id(i)).last_update;
+    assert_eq!(last_update(0), last_update(1));
+    assert_ne!(last_update(0), last_update(2));
+    assert!(arenas.data(data).eq_with(&source, &arenas));
+}
+
+#[test]
+fn nonexistent_local_datetimes_are_rejected() {
+    // Clocks skip from 02:00 to 03:00 in the default timezone on the last Sunday of March.
+    let value = "20240331T023000";
+    assert_eq!(
+        LocalTimestampSeconds::from_formatted(value, "%Y%m%dT%H%M%S"),
+        Err(Error::NonexistentLocalDatetime {
+            value: value.to_owned(),
+            timezone: DEFAULT_TIMEZONE,
+        }),
+    );
+    let timestamp = LocalTimestampSeconds::from_formatted("20240331T033000", "%Y%m%dT%H%M%S");
+    assert_eq!(
+        timestamp.unwrap().to_formatted(DISPLAY_FORMAT),
+        "2024-03-31 03:30:00"
+    );
+}
+
+#[test]
+fn ambiguous_local_datetimes_round_trip() {
+    // Clocks go back from 03:00 to 02:00 in the default timezone on the last Sunday of October.
+    let value = "20241027T023000";
+    let parse = |ambiguous| {
+        LocalTimestampSeconds::from_formatted_in(
+            value,
+            "%Y%m%dT%H%M%S",
+            LocalDatetimes {
+                timezone: DEFAULT_TIMEZONE,
+                ambiguous,
+            },
+        )
+    };
+
+    let earliest = parse(AmbiguousDatetimes::Earliest).unwrap();
+    let latest = parse(AmbiguousDatetimes::Latest).unwrap();
+    assert_eq!(latest.0 - earliest.0, 3600);
+    assert_eq!(earliest.to_formatted("%Y%m%dT%H%M%S"), value);
+    assert_eq!(latest.to_formatted("%Y%m%dT%H%M%S"), value);
+    assert_eq!(
+        parse(AmbiguousDatetimes::Reject),
+        Err(Error::AmbiguousLocalDatetime {
+            value: value.to_owned(),
+            timezone: DEFAULT_TIMEZONE,
+        }),
+    );
+}
+
+#[test]
+fn older_source_versions_are_detected() {
+    use crate::schema::source;
+
+    let disruption = serde_json::json!({
+        "id": "11111111-1111-1111-1111-111111111111",
+        "applicationPeriods": [{"begin": "20240101T080000", "end": "20240101T180000"}],
+        "lastUpdate": "20240101T090000",
+        "cause": "TRAVAUX",
+        "severity": "BLOQUANTE",
+        "tags": ["Actualité"],
+        "title": "T",
+        "message": "m",
+    });
+    let json = serde_json::json!({
+        "disruptions": [disruption],
+        "lines": [],
+        "lastUpdatedDate": "2024-01-01T10:00:00.000Z",
+    })
+    .to_string();
+
+    // Version 1 disruptions lack fields that are always present in version 2.
+    assert!(serde_json::from_str::<source::Data>(&json).is_err());
+    let data = source::from_slice(json.as_bytes()).unwrap();
+    let converted = &data.disruptions.as_ref().unwrap()[0];
+    assert_eq!(converted.message.as_deref(), Some("m"));
+    assert_eq!(converted.short_message, None);
+    assert_eq!(converted.disruption_id, None);
+
+    // Parsing directly into the arenas accepts both versions.
+    let arenas = Arenas::default();
+    let expected = arenas
+        .intern_data(Data::from_source(&arenas, &data).unwrap())
+        .unwrap();
+    let direct = super::seed::from_slice(&arenas, json.as_bytes()).unwrap();
+    assert_eq!(arenas.intern_data(direct).unwrap(), expected);
+
+    // Files that match no version are reported against the latest one.
+    let error = source::from_slice(br#"{"disruptions": [], "lines": [], "extra": 1}"#);
+    assert!(error
+        .unwrap_err()
+        .to_string()
+        .contains("unknown field `extra`"));
+}
+
+#[test]
+fn tolerant_parsing_drops_unknown_fields() {
+    use crate::schema::source;
+
+    let json = serde_json::json!({
+        "disruptions": [{
+            "id": "11111111-1111-1111-1111-111111111111",
+            "applicationPeriods": [{"begin": "20240101T080000", "end": "20240101T180000", "note": 1}],
+            "lastUpdate": "20240101T090000",
+            "cause": "TRAVAUX",
+            "severity": "BLOQUANTE",
+            "tags": null,
+            "title": "T",
+            "message": null,
+            "shortMessage": null,
+            "disruption_id": null,
+            "newField": "x",
+        }],
+        "lines": [],
+        "lastUpdatedDate": "2024-01-01T10:00:00.000Z",
+        "extra": [1, 2],
+    })
+    .to_string();
+
+    assert!(source::from_slice(json.as_bytes()).is_err());
+    let (data, extras) = source::from_slice_tolerant(json.as_bytes()).unwrap();
+    assert_eq!(&*data.disruptions.unwrap()[0].title, "T");
+
+    let mut paths: Vec<&str> = extras.keys().map(String::as_str).collect();
+    paths.sort_unstable();
+    assert_eq!(
+        paths,
+        [
+            "disruptions[0].applicationPeriods[0].note",
+            "disruptions[0].newField",
+            "extra",
+        ]
+    );
+    assert_eq!(extras["extra"], serde_json::json!([1, 2]));
+    assert_eq!(
+        crate::schema::field_name("disruptions[0].applicationPeriods[12].note"),
+        "disruptions[].applicationPeriods[].note"
+    );
+}
+
+#[test]
+fn unescaped_strings_borrow_from_the_input() {
+    use crate::schema::source;
+
+    let json = br#"{"error": "Not found", "message": "line\nbreak", "statusCode": 404}"#;
+    let data = source::from_slice(json).unwrap();
+    assert_eq!(data.error.as_ref().unwrap().get_heap_size(), 0);
+    assert_eq!(&**data.message.as_ref().unwrap(), "line\nbreak");
+    assert!(data.message.as_ref().unwrap().get_heap_size() > 0);
+
+    let data = data.into_owned();
+    assert!(data.error.as_ref().unwrap().get_heap_size() > 0);
+}
+
+#[test]
+fn malformed_datetimes_are_conversion_errors() {
+    let snapshot = |last_update: &str, last_updated_date: &str| {
+        serde_json::json!({
+            "disruptions": [{
+                "id": "11111111-1111-1111-1111-111111111111",
+                "applicationPeriods": [],
+                "lastUpdate": last_update,
+                "cause": "TRAVAUX",
+                "severity": "BLOQUANTE",
+                "tags": null,
+                "title": "T",
+                "message": null,
+                "shortMessage": null,
+                "disruption_id": null,
+            }],
+            "lines": [],
+            "lastUpdatedDate": last_updated_date,
+        })
+    };
+
+    let arenas = Arenas::default();
+    for (json, expected) in [
+        (
+            snapshot("2024-13-45", "2024-01-01T10:00:00.000Z"),
+            Error::ParseDatetime {
+                value: "2024-13-45".to_owned(),
+                format: "%Y%m%dT%H%M%S",
+            },
+        ),
+        (
+            snapshot("20240101T090000", "yesterday"),
+            Error::ParseDatetime {
+                value: "yesterday".to_owned(),
+                format: "RFC 3339",
+            },
+        ),
+    ] {
+        let source: crate::schema::source::Data =
+            crate::schema::source::Data::deserialize(json.clone()).unwrap();
+        assert_eq!(Data::from_source(&arenas, &source), Err(expected.clone()));
+        // The streaming path reports the same error, as a deserialization error.
+        let error = super::seed::from_slice(&arenas, json.to_string().as_bytes()).unwrap_err();
+        assert!(error.to_string().starts_with(&expected.to_string()));
+    }
+}
+
+#[test]
+fn generated_snapshots_are_valid() {
+    use crate::schema::generate::{Generator, TEST_CONFIG};
+
+    let snapshots: Vec<_> = Generator::new(TEST_CONFIG).take(5).collect();
+    let again: Vec<_> = Generator::new(TEST_CONFIG).take(5).collect();
+    assert_eq!(
+        serde_json::to_value(&snapshots).unwrap(),
+        serde_json::to_value(&again).unwrap(),
+    );
+
+    let arenas = Arenas::default();
+    for source in &snapshots {
+        assert_eq!(
+            source.disruptions.as_ref().unwrap().len(),
+            TEST_CONFIG.disruptions
+        );
+        let data = Data::from_source(&arenas, source).unwrap();
+        assert!(data.eq_with(source, &arenas));
+        arenas.intern_data(data).unwrap();
+    }
+    // Snapshots share most of their disruptions with the previous one.
+    assert!(arenas.disruption.len() < 5 * TEST_CONFIG.disruptions);
+}
+
+#[test]
+fn direct_parsing_matches_conversion() {
+    use crate::schema::generate::{Generator, TEST_CONFIG};
+
+    let direct_arenas = Arenas::default();
+    let converted_arenas = Arenas::default();
+    let mut direct_datas = Vec::new();
+    let mut converted_datas = Vec::new();
+    for source in Generator::new(TEST_CONFIG).take(20) {
+        let json = serde_json::to_vec(&source).unwrap();
+        let direct = super::seed::from_slice(&direct_arenas, &json).unwrap();
+        assert!(direct.eq_with(&source, &direct_arenas));
+        direct_datas.push(direct_arenas.intern_data(direct).unwrap());
+
+        let converted = Data::from_source(&converted_arenas, &source).unwrap();
+        converted_datas.push(converted_arenas.intern_data(converted).unwrap());
+    }
+
+    // Once compacted, the IDs only depend on the snapshots, so both paths give the same arenas.
+    let (direct_arenas, _) = direct_arenas.compact(&direct_datas).unwrap();
+    let (converted_arenas, _) = converted_arenas.compact(&converted_datas).unwrap();
+    assert_eq!(
+        bincode::serialize(&direct_arenas).unwrap(),
+        bincode::serialize(&converted_arenas).unwrap(),
+    );
+}
+
+#[test]
+fn views_serialize_like_regenerated_snapshots() {
+    use crate::schema::generate::intern_test_snapshots;
+
+    let arenas = Arenas::default();
+    let mut datas = intern_test_snapshots(&arenas, 3);
+    datas.push(
+        arenas
+            .intern_data(Data::Error(DataError {
+                status_code: 503,
+                error: arenas.string.intern("Service Unavailable"),
+                message: arenas.string.intern("Try again later"),
+            }))
+            .unwrap(),
+    );
+
+    let resolver = arenas.resolver();
+    for data in datas {
+        let view = resolver.data(data);
+        assert_eq!(
+            serde_json::to_string(&view).unwrap(),
+            serde_json::to_string(&arenas.data(data).to_source(&arenas)).unwrap(),
+        );
+    }
+    assert_eq!(
+        resolver.data(Interned::from_id(3)).to_string(),
+        "error 503 | Service Unavailable: Try again later",
+    );
+}
+
+#[test]
+fn count_references_of_shared_values() {
+    let arenas = Arenas::default();
+    let error = arenas.string.intern("error");
+    let data = |message: &str| {
+        arenas
+            .intern_data(Data::Error(DataError {
+                status_code: 500,
+                error,
+                message: arenas.string.intern(message),
+            }))
+            .unwrap()
+    };
+    let first = data("first");
+    let second = data("second");
+    arenas.string.intern("unused");
+
+    let counts = arenas.count_references(&[first, second, first]);
+    assert_eq!(&*counts.data, &[2, 1]);
+    assert_eq!(&*counts.string, &[2, 1, 1, 0]);
+}
+
+#[test]
+fn deduplication_counts_one_copy_per_reference() {
+    let counts = [3, 1, 0];
+    let dedup = Deduplication::new(&counts, 100, |i| [10, 20, 30][i as usize]);
+    assert_eq!(
+        dedup,
+        Deduplication {
+            unique: 3,
+            references: 4,
+            copy_bytes: 3 * 10 + 20,
+            interned_bytes: 100 + 4 * 4,
+        }
+    );
+}
+
+#[test]
+fn interner_stats_cover_all_arenas() {
+    use crate::schema::generate::intern_test_snapshots;
+
+    let arenas = Arenas::default();
+    intern_test_snapshots(&arenas, 3);
+
+    let stats = arenas.interner_stats();
+    assert_eq!(stats.len(), 15);
+    assert_eq!(stats[0].objects, arenas.strings().count());
+    let disruptions = stats.iter().find(|x| x.name == "Disruption").unwrap();
+    assert_eq!(disruptions.objects, arenas.disruption.len());
+    // The arenas only add their own fields to the sizes of the individual arenas.
+    let bytes: usize = stats.iter().map(|x| x.bytes).sum();
+    assert!(bytes <= arenas.get_size());
+    assert!(bytes > arenas.get_size() - std::mem::size_of::<Arenas>());
+}
+
+#[test]
+fn diff_pairs_equal_values_regardless_of_ids() {
+    use crate::schema::generate::{Generator, TEST_CONFIG};
+
+    let sources: Vec<_> = Generator::new(TEST_CONFIG).take(3).collect();
+    let intern = |arenas: &Arenas, source| {
+        arenas
+            .intern_data(Data::from_source(arenas, source).unwrap())
+            .unwrap()
+    };
+
+    // The shared snapshot is interned first on the left and last on the right, so that its values
+    // have different IDs on each side.
+    let left = Arenas::default();
+    let left_shared = intern(&left, &sources[1]);
+    let left_only = intern(&left, &sources[0]);
+    let right = Arenas::default();
+    let right_only = intern(&right, &sources[2]);
+    let right_shared = intern(&right, &sources[1]);
+    assert_ne!(left_shared, right_shared);
+
+    let diff = left.diff(&right).unwrap();
+    assert!(diff.same_data(left_shared, right_shared));
+    assert!(!diff.same_data(left_only, right_shared));
+    assert!(!diff.same_data(left_shared, right_only));
+    assert_eq!(diff.interners.len(), 15);
+    let data = diff
+        .interners
+        .iter()
+        .find(|x| x.left.name == "Data")
+        .unwrap();
+    assert_eq!((data.only_left, data.only_right), (1, 1));
+
+    // Each arena is identical to itself, however its values were interned.
+    let diff = left.diff(&left).unwrap();
+    assert!(diff.same_data(left_only, left_only));
+    assert!(diff
+        .interners
+        .iter()
+        .all(|x| x.only_left == 0 && x.only_right == 0 && x.left.objects == x.right.objects));
+}
+
+#[test]
+fn churn_compares_consecutive_successful_snapshots() {
+    let disruption = |id: &str, title: &str| {
+        serde_json::json!({
+            "id": id,
+            "applicationPeriods": [{"begin": "20240101T100000", "end": "20240102T100000"}],
+            "lastUpdate": "20240101T090000",
+            "cause": "TRAVAUX",
+            "severity": "BLOQUANTE",
+            "tags": null,
+            "title": title,
+            "message": "m",
+            "shortMessage": null,
+            "disruption_id": null,
+        })
+    };
+    let snapshot = |disruptions: Vec<serde_json::Value>| {
+        crate::schema::source::Data::deserialize(serde_json::json!({
+            "disruptions": disruptions,
+            "lines": [],
+            "lastUpdatedDate": "2024-01-01T10:00:00.000Z",
+        }))
+        .unwrap()
+    };
+    let a = "11111111-1111-1111-1111-111111111111";
+    let b = "22222222-2222-2222-2222-222222222222";
+    let c = "33333333-3333-3333-3333-333333333333";
+    let error = crate::schema::source::Data::deserialize(
+        serde_json::json!({"error": "Not found", "message": "m", "statusCode": 404}),
+    )
+    .unwrap();
+    let sources = [
+        snapshot(vec![disruption(a, "A"), disruption(b, "B")]),
+        error,
+        snapshot(vec![disruption(a, "A2"), disruption(c, "C")]),
+        snapshot(vec![disruption(a, "A2"), disruption(c, "C")]),
+    ];
+
+    let arenas = Arenas::default();
+    let datas: Vec<_> = sources
+        .iter()
+        .map(|source| {
+            arenas
+                .intern_data(Data::from_source(&arenas, source).unwrap())
+                .unwrap()
+        })
+        .collect();
+    let steps = arenas.churn(&datas);
+
+    // The error snapshot is skipped rather than treated as having no disruptions.
+    let pairs: Vec<_> = steps.iter().map(|x| (x.previous, x.index)).collect();
+    assert_eq!(pairs, [(0, 2), (2, 3)]);
+    assert_eq!(
+        steps[0].churn,
+        Churn {
+            appeared: 1,
+            disappeared: 1,
+            changed: 1,
+            unchanged: 0,
+        }
+    );
+    assert_eq!(
+        steps[1].churn,
+        Churn {
+            unchanged: 2,
+            ..Churn::default()
+        }
+    );
+    assert_eq!(
+        ChurnStep::total(&steps),
+        Churn {
+            appeared: 1,
+            disappeared: 1,
+            changed: 1,
+            unchanged: 2,
+        }
+    );
+}
+
+#[test]
+fn lifetimes_track_versions_and_application_periods() {
+    let disruption = |id: &str, title: &str, end: &str| {
+        serde_json::json!({
+            "id": id,
+            "applicationPeriods": [{"begin": "20240101T100000", "end": end}],
+            "lastUpdate": "20240101T090000",
+            "cause": "TRAVAUX",
+            "severity": "BLOQUANTE",
+            "tags": null,
+            "title": title,
+            "message": "m",
+            "shortMessage": null,
+            "disruption_id": null,
+        })
+    };
+    let snapshot = |date: &str, disruptions: Vec<serde_json::Value>| {
+        crate::schema::source::Data::deserialize(serde_json::json!({
+            "disruptions": disruptions,
+            "lines": [],
+            "lastUpdatedDate": date,
+        }))
+        .unwrap()
+    };
+    let a = "11111111-1111-1111-1111-111111111111";
+    let b = "22222222-2222-2222-2222-222222222222";
+    let error = crate::schema::source::Data::deserialize(
+        serde_json::json!({"error": "Not found", "message": "m", "statusCode": 404}),
+    )
+    .unwrap();
+    let sources = [
+        snapshot(
+            "2024-01-01T10:00:00.000Z",
+            vec![disruption(a, "A", "20240102T100000")],
+        ),
+        error,
+        snapshot(
+            "2024-01-01T10:02:00.000Z",
+            vec![
+                disruption(a, "A2", "20240102T100000"),
+                disruption(b, "B", "20240102T100000"),
+            ],
+        ),
+        snapshot(
+            "2024-01-01T10:04:00.000Z",
+            vec![disruption(b, "B", "20240103T100000")],
+        ),
+        snapshot(
+            "2024-01-01T10:06:00.000Z",
+            vec![disruption(a, "A", "20240102T100000")],
+        ),
+    ];
+
+    let arenas = Arenas::default();
+    let datas: Vec<_> = sources
+        .iter()
+        .map(|source| {
+            arenas
+                .intern_data(Data::from_source(&arenas, source).unwrap())
+                .unwrap()
+        })
+        .collect();
+    let lifetimes = arenas.lifetimes(&datas);
+    assert_eq!(lifetimes.len(), 2);
+
+    // The error snapshot doesn't interrupt the first disruption, which changed its title and came
+    // back to its first version after missing from a successful snapshot.
+    let a = &lifetimes[0];
+    assert_eq!(a.id, "11111111-1111-1111-1111-111111111111");
+    assert_eq!((a.first_snapshot, a.last_snapshot), (0, 4));
+    assert_eq!(a.duration_seconds, 360);
+    assert_eq!(
+        (a.snapshots, a.reappearances, a.versions, a.period_changes),
+        (3, 1, 2, 0)
+    );
+
+    let b = &lifetimes[1];
+    assert_eq!((b.first_snapshot, b.last_snapshot), (2, 3));
+    assert_eq!(
+        (b.snapshots, b.reappearances, b.versions, b.period_changes),
+        (2, 0, 2, 1)
+    );
+    assert_eq!(b.first_periods, "2024-01-01T10:00:00/2024-01-02T10:00:00");
+    assert_eq!(b.last_periods, "2024-01-01T10:00:00/2024-01-03T10:00:00");
+
+    let mut csv = Vec::new();
+    Lifetime::write_csv(&lifetimes, &mut csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    let header_fields = csv.lines().next().unwrap().split(',').count();
+    assert_eq!(csv.lines().count(), 3);
+    assert!(csv
+        .lines()
+        .all(|line| line.split(',').count() == header_fields));
+}
+
+#[test]
+fn active_disruptions_are_grouped_by_line_and_severity() {
+    let disruption = |id: &str, severity: &str, begin: &str, end: &str| {
+        serde_json::json!({
+            "id": id,
+            "applicationPeriods": [{"begin": begin, "end": end}],
+            "lastUpdate": "20240101T090000",
+            "cause": "TRAVAUX",
+            "severity": severity,
+            "tags": null,
+            "title": id,
+            "message": "m",
+            "shortMessage": null,
+            "disruption_id": null,
+        })
+    };
+    let line = |id: &str, disruption_ids: &[&str]| {
+        serde_json::json!({
+            "id": id,
+            "name": id,
+            "shortName": id,
+            "mode": "Metro",
+            "networkId": "N",
+            "impactedObjects": [{
+                "type": "line",
+                "id": id,
+                "name": id,
+                "disruptionIds": disruption_ids,
+            }],
+        })
+    };
+    let a = "11111111-1111-1111-1111-111111111111";
+    let b = "22222222-2222-2222-2222-222222222222";
+    let c = "33333333-3333-3333-3333-333333333333";
+    let d = "44444444-4444-4444-4444-444444444444";
+    let snapshot = |date: &str| {
+        crate::schema::source::Data::deserialize(serde_json::json!({
+            "disruptions": [
+                disruption(a, "BLOQUANTE", "20240101T100000", "20240101T120000"),
+                disruption(b, "PERTURBEE", "20240101T100000", "20240101T120000"),
+                disruption(c, "BLOQUANTE", "20240101T130000", "20240101T140000"),
+                disruption(d, "PERTURBEE", "20240101T100000", "20240101T120000"),
+            ],
+            "lines": [line("L1", &[a, b, c]), line("L2", &[a])],
+            "lastUpdatedDate": date,
+        }))
+        .unwrap()
+    };
+    let sources = [
+        snapshot("2024-01-01T08:00:00.000Z"),
+        snapshot("2024-01-01T10:00:00.000Z"),
+    ];
+
+    let arenas = Arenas::default();
+    let datas: Vec<_> = sources
+        .iter()
+        .map(|source| {
+            arenas
+                .intern_data(Data::from_source(&arenas, source).unwrap())
+                .unwrap()
+        })
+        .collect();
+
+    // 11:00 in Paris is 10:00 UTC.
+    let instant = parse_instant("20240101T110000").unwrap();
+    assert_eq!(instant, parse_instant("2024-01-01T10:00:00Z").unwrap());
+    assert!(parse_instant("yesterday").is_err());
+
+    let active = arenas.active_at(&datas, instant).unwrap();
+    assert_eq!(active.snapshot, 1);
+    assert_eq!((active.active.len(), active.disruptions), (3, 4));
+    let groups: Vec<_> = active
+        .by_line
+        .iter()
+        .flat_map(|(line, severities)| {
+            severities.iter().map(move |(severity, disruptions)| {
+                (line.map(|(id, _)| id), *severity, disruptions.len())
+            })
+        })
+        .collect();
+    assert_eq!(
+        groups,
+        [
+            (None, "PERTURBEE", 1),
+            (Some("L1"), "BLOQUANTE", 1),
+            (Some("L1"), "PERTURBEE", 1),
+            (Some("L2"), "BLOQUANTE", 1),
+        ]
+    );
+
+    assert!(arenas.active_at(&[], instant).is_none());
+}
+
+#[test]
+fn search_matches_words_of_titles_and_messages() {
+    let disruption = |id: &str, title: &str, message: &str| {
+        serde_json::json!({
+            "id": id,
+            "applicationPeriods": [{"begin": "20240101T100000", "end": "20240102T100000"}],
+            "lastUpdate": "20240101T090000",
+            "cause": "TRAVAUX",
+            "severity": "BLOQUANTE",
+            "tags": null,
+            "title": title,
+            "message": message,
+            "shortMessage": null,
+            "disruption_id": null,
+        })
+    };
+    let a = "11111111-1111-1111-1111-111111111111";
+    let b = "22222222-2222-2222-2222-222222222222";
+    let snapshot = |date: &str, disruptions: Vec<serde_json::Value>| {
+        crate::schema::source::Data::deserialize(serde_json::json!({
+            "disruptions": disruptions,
+            "lines": [{
+                "id": "line:IDFM:1",
+                "name": "Métro 1",
+                "shortName": "1",
+                "mode": "Metro",
+                "networkId": "N",
+                "impactedObjects": [{
+                    "type": "line",
+                    "id": "line:IDFM:1",
+                    "name": "Métro 1",
+                    "disruptionIds": [a],
+                }],
+            }],
+            "lastUpdatedDate": date,
+        }))
+        .unwrap()
+    };
+    let sources = [
+        snapshot(
+            "2024-01-01T10:00:00.000Z",
+            vec![
+                disruption(a, "Travaux", "<p>Trafic interrompu à Châtelet</p>"),
+                disruption(b, "Colis suspect", "Trafic perturbé"),
+            ],
+        ),
+        snapshot(
+            "2024-01-01T10:02:00.000Z",
+            vec![disruption(
+                a,
+                "Travaux",
+                "<p>Trafic interrompu à Châtelet</p>",
+            )],
+        ),
+    ];
+
+    let arenas = Arenas::default();
+    let datas: Vec<_> = sources
+        .iter()
+        .map(|source| {
+            arenas
+                .intern_data(Data::from_source(&arenas, source).unwrap())
+                .unwrap()
+        })
+        .collect();
+    let index = SearchIndex::new(&arenas);
+    let search = |query: &str| -> Vec<&str> {
+        arenas
+            .search(&index, &datas, query)
+            .iter()
+            .map(|hit| arenas.disruption(hit.disruption).title(&arenas))
+            .collect()
+    };
+
+    // Words are matched regardless of case, in either the title or the message.
+    assert_eq!(search("trafic"), ["Travaux", "Colis suspect"]);
+    assert_eq!(search("TRAVAUX châtelet"), ["Travaux"]);
+    assert_eq!(search("colis interrompu"), Vec::<&str>::new());
+    // HTML tags and partial words aren't indexed.
+    assert_eq!(search("p"), Vec::<&str>::new());
+    assert_eq!(search("Châtel"), Vec::<&str>::new());
+    assert_eq!(search(""), Vec::<&str>::new());
+
+    let hits = arenas.search(&index, &datas, "travaux");
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].first_seen, "2024-01-01T10:00:00.000Z");
+    assert_eq!(hits[0].last_seen, "2024-01-01T10:02:00.000Z");
+    assert_eq!(
+        hits[0].lines.iter().copied().collect::<Vec<_>>(),
+        ["Métro 1"]
+    );
+    let hits = arenas.search(&index, &datas, "colis");
+    assert!(hits[0].lines.is_empty());
+}
+
+#[test]
+fn disruption_versions_are_found_by_uuid() {
+    use crate::schema::generate::intern_test_snapshots;
+
+    let arenas = Arenas::default();
+    intern_test_snapshots(&arenas, 3);
+
+    for (handle, disruption) in arenas.disruption.iter() {
+        let id = arenas.uuid.lookup_ref(disruption.id);
+        let parsed: Uuid = id.0.to_string().parse().unwrap();
+        assert!(arenas.disruption_versions(&parsed).contains(&handle));
+    }
+    assert!(arenas.disruption_versions(&Uuid::default()).is_empty());
+    assert!("not a uuid".parse::<Uuid>().is_err());
+
+    let headers = arenas.line_headers();
+    assert_eq!(headers.len(), arenas.line_header.len());
+    assert!(headers.is_sorted());
+}
+
+#[test]
+fn gtfs_feeds_list_each_disruption_once() {
+    use crate::schema::generate::intern_test_snapshots;
+    use prost::Message;
+
+    let arenas = Arenas::default();
+    let datas = intern_test_snapshots(&arenas, 3);
+
+    let mut all_ids = Vec::new();
+    for data in &datas {
+        let Data::Success(success) = arenas.data(*data) else {
+            assert!(arenas.gtfs_feed(*data).is_none());
+            continue;
+        };
+        let feed = FeedMessage::decode(arenas.gtfs_feed(*data).unwrap().as_slice()).unwrap();
+        assert_eq!(feed.header.gtfs_realtime_version, "2.0");
+        assert_eq!(
+            feed.header.timestamp,
+            Some((success.last_updated_date.0 / 1000) as u64)
+        );
+
+        let mut ids: Vec<String> = Vec::new();
+        for x in success.disruptions(&arenas) {
+            let id = arenas.disruption(*x).id;
+            let id = arenas.uuid.lookup_ref(id).0.to_string();
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+        let feed_ids: Vec<&str> = feed.entity.iter().map(|x| x.id.as_str()).collect();
+        assert_eq!(feed_ids, ids);
+        for entity in &feed.entity {
+            let alert = entity.alert.as_ref().unwrap();
+            assert!(!alert.active_period.is_empty());
+            assert!(alert.header_text.is_some());
+        }
+        for id in ids {
+            if !all_ids.contains(&id) {
+                all_ids.push(id);
+            }
+        }
+    }
+
+    let merged = FeedMessage::decode(arenas.gtfs_merged_feed(&datas).unwrap().as_slice()).unwrap();
+    let merged_ids: Vec<&str> = merged.entity.iter().map(|x| x.id.as_str()).collect();
+    assert_eq!(merged_ids, all_ids);
+    assert!(arenas.gtfs_merged_feed(&[]).is_none());
+}
+
+#[test]
+fn anonymization_preserves_the_interning_structure() {
+    use crate::schema::anonymize::Anonymizer;
+    use crate::schema::generate::{Generator, TEST_CONFIG};
+
+    let sources: Vec<_> = Generator::new(TEST_CONFIG).take(3).collect();
+    let anonymizer = Anonymizer::new("key", false);
+
+    let arenas = Arenas::default();
+    let anonymized = Arenas::default();
+    for source in &sources {
+        arenas
+            .intern_data(Data::from_source(&arenas, source).unwrap())
+            .unwrap();
+        let mut source = source.clone();
+        anonymizer.anonymize(&mut source);
+        anonymized
+            .intern_data(Data::from_source(&anonymized, &source).unwrap())
+            .unwrap();
+    }
+    for (left, right) in arenas
+        .interner_stats()
+        .iter()
+        .zip(anonymized.interner_stats())
+    {
+        assert_eq!(left.objects, right.objects, "{}", left.name);
+    }
+    for (uuid, pseudonym) in arenas.uuid.values().zip(anonymized.uuid.values()) {
+        assert_ne!(uuid, pseudonym);
+        assert_eq!(anonymizer.uuid(uuid), *pseudonym);
+    }
+    let id = arenas.uuid.values().next().unwrap();
+    assert_ne!(
+        anonymizer.uuid(id),
+        Anonymizer::new("other", false).uuid(id)
+    );
+
+    let mut source = sources[0].clone();
+    Anonymizer::new("key", true).anonymize(&mut source);
+    for disruption in source.disruptions.iter().flatten() {
+        for message in [&disruption.message, &disruption.short_message] {
+            assert!(message.as_ref().is_none_or(|x| x.starts_with("[redacted ")));
+        }
+    }
+}
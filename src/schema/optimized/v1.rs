@@ -0,0 +1,84 @@
+// Layout of the arenas in version 1 of the databases, kept to upgrade older databases. Disruptions
+// stored their set of application periods inline, whereas version 2 interns these sets in their own
+// arena, like the other sets.
+//
+// Version 1 databases are upgraded to the layout of version 2, and from there to the current one.
+
+use super::{
+    v2, v3, v5, ApplicationPeriod, ArenaSet, Data, DisruptionUuid, ImpactedObject, InternedSet,
+    InternedStrSet, LocalTimestampSeconds, Object, UuidStorage,
+};
+use crate::schema::introspect::Introspect;
+use crate::schema::Uuid;
+use blazinterner::{Arena, ArenaStr, Interned, InternedStr};
+use serde_tuple::Deserialize_tuple;
+
+#[derive(Deserialize_tuple)]
+pub struct Arenas {
+    string: ArenaStr,
+    uuid: Arena<Uuid, UuidStorage>,
+    disruption_set: ArenaSet<Interned<super::Disruption>>,
+    disruption: Arena<Disruption>,
+    application_period: Arena<ApplicationPeriod>,
+    line_set: ArenaSet<Interned<super::Line>>,
+    line: Arena<v5::Line>,
+    line_header: Arena<v3::LineHeader>,
+    impacted_object: Arena<ImpactedObject>,
+    object: Arena<Object>,
+    uuid_set: ArenaSet<DisruptionUuid>,
+    data: Arena<Data>,
+}
+
+#[derive(Hash, PartialEq, Eq, Deserialize_tuple)]
+struct Disruption {
+    id: DisruptionUuid,
+    application_periods: InternedSet<ApplicationPeriod>,
+    last_update: LocalTimestampSeconds,
+    cause: InternedStr,
+    severity: InternedStr,
+    tags: Option<InternedStrSet>,
+    title: InternedStr,
+    message: Option<InternedStr>,
+    short_message: Option<InternedStr>,
+    disruption_id: Option<DisruptionUuid>,
+}
+
+impl From<Arenas> for v2::Arenas {
+    // Disruptions are re-interned in the same order, so they keep their IDs, and so do the sets of
+    // disruptions and the snapshots that refer to them.
+    fn from(arenas: Arenas) -> Self {
+        let application_period_set = ArenaSet::default();
+        let disruption = Arena::default();
+        for x in arenas.disruption.values() {
+            disruption.intern(v2::Disruption {
+                id: x.id,
+                application_periods: application_period_set.intern(x.application_periods.iter()),
+                last_update: x.last_update.clone(),
+                cause: x.cause,
+                severity: x.severity,
+                tags: (x.tags.as_ref()).map(|tags| InternedStrSet {
+                    set: tags.set.clone(),
+                }),
+                title: x.title,
+                message: x.message,
+                short_message: x.short_message,
+                disruption_id: x.disruption_id,
+            });
+        }
+        v2::Arenas {
+            string: arenas.string,
+            uuid: arenas.uuid,
+            disruption_set: arenas.disruption_set,
+            disruption,
+            application_period_set,
+            application_period: arenas.application_period,
+            line_set: arenas.line_set,
+            line: arenas.line,
+            line_header: arenas.line_header,
+            impacted_object: arenas.impacted_object,
+            object: arenas.object,
+            uuid_set: arenas.uuid_set,
+            data: arenas.data,
+        }
+    }
+}
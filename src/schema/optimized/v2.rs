@@ -0,0 +1,83 @@
+// Layout of the arenas in version 2 of the databases, kept to upgrade older databases. Disruptions
+// stored their set of tags inline, whereas version 3 interns these sets in the `string_set` arena.
+//
+// Version 2 databases are upgraded to the layout of version 3, and from there to the current one.
+
+use super::{
+    v3, v5, ApplicationPeriod, ArenaSet, Data, DisruptionUuid, ImpactedObject, InternedStrSet,
+    LocalTimestampSeconds, Object, UuidStorage,
+};
+use crate::schema::introspect::Introspect;
+use crate::schema::Uuid;
+use blazinterner::{Arena, ArenaStr, Interned, InternedSlice, InternedStr};
+use serde_tuple::Deserialize_tuple;
+
+#[derive(Deserialize_tuple)]
+pub struct Arenas {
+    pub(super) string: ArenaStr,
+    pub(super) uuid: Arena<Uuid, UuidStorage>,
+    pub(super) disruption_set: ArenaSet<Interned<super::Disruption>>,
+    pub(super) disruption: Arena<Disruption>,
+    pub(super) application_period_set: ArenaSet<Interned<ApplicationPeriod>>,
+    pub(super) application_period: Arena<ApplicationPeriod>,
+    pub(super) line_set: ArenaSet<Interned<super::Line>>,
+    pub(super) line: Arena<v5::Line>,
+    pub(super) line_header: Arena<v3::LineHeader>,
+    pub(super) impacted_object: Arena<ImpactedObject>,
+    pub(super) object: Arena<Object>,
+    pub(super) uuid_set: ArenaSet<DisruptionUuid>,
+    pub(super) data: Arena<Data>,
+}
+
+#[derive(Hash, PartialEq, Eq, Deserialize_tuple)]
+pub(super) struct Disruption {
+    pub(super) id: DisruptionUuid,
+    pub(super) application_periods: InternedSlice<Interned<ApplicationPeriod>>,
+    pub(super) last_update: LocalTimestampSeconds,
+    pub(super) cause: InternedStr,
+    pub(super) severity: InternedStr,
+    pub(super) tags: Option<InternedStrSet>,
+    pub(super) title: InternedStr,
+    pub(super) message: Option<InternedStr>,
+    pub(super) short_message: Option<InternedStr>,
+    pub(super) disruption_id: Option<DisruptionUuid>,
+}
+
+impl From<Arenas> for v3::Arenas {
+    // As in the upgrade from version 1, disruptions are re-interned in the same order so that they
+    // keep their IDs.
+    fn from(arenas: Arenas) -> Self {
+        let string_set = ArenaSet::default();
+        let disruption = Arena::default();
+        for x in arenas.disruption.values() {
+            disruption.intern(v3::Disruption {
+                id: x.id,
+                application_periods: x.application_periods,
+                last_update: x.last_update.clone(),
+                cause: x.cause,
+                severity: x.severity,
+                tags: (x.tags.as_ref()).map(|tags| string_set.intern(tags.set.iter().copied())),
+                title: x.title,
+                message: x.message,
+                short_message: x.short_message,
+                disruption_id: x.disruption_id,
+            });
+        }
+        v3::Arenas {
+            string: arenas.string,
+            uuid: arenas.uuid,
+            disruption_set: arenas.disruption_set,
+            disruption,
+            application_period_set: arenas.application_period_set,
+            application_period: arenas.application_period,
+            string_set,
+            line_set: arenas.line_set,
+            line: arenas.line,
+            line_header: arenas.line_header,
+            impacted_object: arenas.impacted_object,
+            object: arenas.object,
+            uuid_set: arenas.uuid_set,
+            data: arenas.data,
+        }
+    }
+}
@@ -0,0 +1,104 @@
+// Layout of the arenas in version 3 of the databases, kept to upgrade older databases. The mode of
+// lines and the severity of disruptions were interned strings, whereas version 4 stores their known
+// values as enum variants.
+
+use super::known::Known;
+use super::{
+    v4, v5, ApplicationPeriod, ArenaSet, Data, DisruptionUuid, ImpactedObject,
+    LocalTimestampSeconds, Object, UuidStorage,
+};
+use crate::schema::introspect::Introspect;
+use crate::schema::Uuid;
+use blazinterner::{Arena, ArenaStr, Interned, InternedSlice, InternedStr};
+use serde_tuple::Deserialize_tuple;
+
+#[derive(Deserialize_tuple)]
+pub struct Arenas {
+    pub(super) string: ArenaStr,
+    pub(super) uuid: Arena<Uuid, UuidStorage>,
+    pub(super) disruption_set: ArenaSet<Interned<super::Disruption>>,
+    pub(super) disruption: Arena<Disruption>,
+    pub(super) application_period_set: ArenaSet<Interned<ApplicationPeriod>>,
+    pub(super) application_period: Arena<ApplicationPeriod>,
+    pub(super) string_set: ArenaSet<InternedStr>,
+    pub(super) line_set: ArenaSet<Interned<super::Line>>,
+    pub(super) line: Arena<v5::Line>,
+    pub(super) line_header: Arena<LineHeader>,
+    pub(super) impacted_object: Arena<ImpactedObject>,
+    pub(super) object: Arena<Object>,
+    pub(super) uuid_set: ArenaSet<DisruptionUuid>,
+    pub(super) data: Arena<Data>,
+}
+
+#[derive(Hash, PartialEq, Eq, Deserialize_tuple)]
+pub(super) struct Disruption {
+    pub(super) id: DisruptionUuid,
+    pub(super) application_periods: InternedSlice<Interned<ApplicationPeriod>>,
+    pub(super) last_update: LocalTimestampSeconds,
+    pub(super) cause: InternedStr,
+    pub(super) severity: InternedStr,
+    pub(super) tags: Option<InternedSlice<InternedStr>>,
+    pub(super) title: InternedStr,
+    pub(super) message: Option<InternedStr>,
+    pub(super) short_message: Option<InternedStr>,
+    pub(super) disruption_id: Option<DisruptionUuid>,
+}
+
+#[derive(Hash, PartialEq, Eq, Deserialize_tuple)]
+pub(super) struct LineHeader {
+    id: InternedStr,
+    name: InternedStr,
+    short_name: InternedStr,
+    mode: InternedStr,
+    network_id: InternedStr,
+}
+
+impl From<Arenas> for v4::Arenas {
+    // Line headers and disruptions are re-interned in the same order, so that they keep their IDs.
+    // Distinct strings are encoded into distinct values, so no two of them are merged. The strings
+    // of known values stay in the string arena until the database is compacted.
+    fn from(arenas: Arenas) -> Self {
+        let strings = &arenas.string;
+        let line_header = Arena::default();
+        for x in arenas.line_header.values() {
+            line_header.intern(super::LineHeader {
+                id: x.id,
+                name: x.name,
+                short_name: x.short_name,
+                mode: Known::from_interned(strings, x.mode),
+                network_id: x.network_id,
+            });
+        }
+        let disruption = Arena::default();
+        for x in arenas.disruption.values() {
+            disruption.intern(v4::Disruption {
+                id: x.id,
+                application_periods: x.application_periods,
+                last_update: x.last_update.clone(),
+                cause: x.cause,
+                severity: Known::from_interned(strings, x.severity),
+                tags: x.tags,
+                title: x.title,
+                message: x.message,
+                short_message: x.short_message,
+                disruption_id: x.disruption_id,
+            });
+        }
+        v4::Arenas {
+            string: arenas.string,
+            uuid: arenas.uuid,
+            disruption_set: arenas.disruption_set,
+            disruption,
+            application_period_set: arenas.application_period_set,
+            application_period: arenas.application_period,
+            string_set: arenas.string_set,
+            line_set: arenas.line_set,
+            line: arenas.line,
+            line_header,
+            impacted_object: arenas.impacted_object,
+            object: arenas.object,
+            uuid_set: arenas.uuid_set,
+            data: arenas.data,
+        }
+    }
+}
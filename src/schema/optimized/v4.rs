@@ -0,0 +1,85 @@
+// Layout of the arenas in version 4 of the databases, kept to upgrade older databases. The last
+// update of each disruption was stored inline, whereas version 5 interns it in an arena of
+// timestamps.
+
+use super::known::{Known, Severity};
+use super::{
+    v5, ApplicationPeriod, ArenaSet, Data, DisruptionUuid, ImpactedObject, LineHeader,
+    LocalTimestampSeconds, Object, UuidStorage,
+};
+use crate::schema::introspect::Introspect;
+use crate::schema::Uuid;
+use blazinterner::{Arena, ArenaStr, Interned, InternedSlice, InternedStr};
+use serde_tuple::Deserialize_tuple;
+
+#[derive(Deserialize_tuple)]
+pub struct Arenas {
+    pub(super) string: ArenaStr,
+    pub(super) uuid: Arena<Uuid, UuidStorage>,
+    pub(super) disruption_set: ArenaSet<Interned<super::Disruption>>,
+    pub(super) disruption: Arena<Disruption>,
+    pub(super) application_period_set: ArenaSet<Interned<ApplicationPeriod>>,
+    pub(super) application_period: Arena<ApplicationPeriod>,
+    pub(super) string_set: ArenaSet<InternedStr>,
+    pub(super) line_set: ArenaSet<Interned<super::Line>>,
+    pub(super) line: Arena<v5::Line>,
+    pub(super) line_header: Arena<LineHeader>,
+    pub(super) impacted_object: Arena<ImpactedObject>,
+    pub(super) object: Arena<Object>,
+    pub(super) uuid_set: ArenaSet<DisruptionUuid>,
+    pub(super) data: Arena<Data>,
+}
+
+#[derive(Hash, PartialEq, Eq, Deserialize_tuple)]
+pub(super) struct Disruption {
+    pub(super) id: DisruptionUuid,
+    pub(super) application_periods: InternedSlice<Interned<ApplicationPeriod>>,
+    pub(super) last_update: LocalTimestampSeconds,
+    pub(super) cause: InternedStr,
+    pub(super) severity: Known<Severity>,
+    pub(super) tags: Option<InternedSlice<InternedStr>>,
+    pub(super) title: InternedStr,
+    pub(super) message: Option<InternedStr>,
+    pub(super) short_message: Option<InternedStr>,
+    pub(super) disruption_id: Option<DisruptionUuid>,
+}
+
+impl From<Arenas> for v5::Arenas {
+    // Disruptions are re-interned in the same order, so that they keep their IDs. Timestamps are
+    // interned along the way, in the order of the disruptions that first refer to them.
+    fn from(arenas: Arenas) -> Self {
+        let timestamp = Arena::default();
+        let disruption = Arena::default();
+        for x in arenas.disruption.values() {
+            disruption.intern(super::Disruption {
+                id: x.id,
+                application_periods: x.application_periods,
+                last_update: timestamp.intern(x.last_update.clone()),
+                cause: x.cause,
+                severity: x.severity,
+                tags: x.tags,
+                title: x.title,
+                message: x.message,
+                short_message: x.short_message,
+                disruption_id: x.disruption_id,
+            });
+        }
+        v5::Arenas {
+            string: arenas.string,
+            uuid: arenas.uuid,
+            disruption_set: arenas.disruption_set,
+            disruption,
+            application_period_set: arenas.application_period_set,
+            application_period: arenas.application_period,
+            string_set: arenas.string_set,
+            timestamp,
+            line_set: arenas.line_set,
+            line: arenas.line,
+            line_header: arenas.line_header,
+            impacted_object: arenas.impacted_object,
+            object: arenas.object,
+            uuid_set: arenas.uuid_set,
+            data: arenas.data,
+        }
+    }
+}
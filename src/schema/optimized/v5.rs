@@ -0,0 +1,68 @@
+// Layout of the arenas in version 5 of the databases, kept to upgrade older databases. The
+// snapshots and lines stored their disruptions, lines and impacted objects as sorted sets, whereas
+// version 6 keeps them in the order of the source. Sorted sets are kept as they are, since their
+// original order is lost.
+
+use super::{
+    ApplicationPeriod, ArenaSet, Data, DisruptionUuid, ImpactedObject, InternedSet, LineHeader,
+    LocalTimestampSeconds, Object, UuidStorage,
+};
+use crate::schema::introspect::Introspect;
+use crate::schema::Uuid;
+use blazinterner::{Arena, ArenaStr, Interned, InternedStr};
+use serde_tuple::Deserialize_tuple;
+
+#[derive(Deserialize_tuple)]
+pub struct Arenas {
+    pub(super) string: ArenaStr,
+    pub(super) uuid: Arena<Uuid, UuidStorage>,
+    pub(super) disruption_set: ArenaSet<Interned<super::Disruption>>,
+    pub(super) disruption: Arena<super::Disruption>,
+    pub(super) application_period_set: ArenaSet<Interned<ApplicationPeriod>>,
+    pub(super) application_period: Arena<ApplicationPeriod>,
+    pub(super) string_set: ArenaSet<InternedStr>,
+    pub(super) timestamp: Arena<LocalTimestampSeconds>,
+    pub(super) line_set: ArenaSet<Interned<super::Line>>,
+    pub(super) line: Arena<Line>,
+    pub(super) line_header: Arena<LineHeader>,
+    pub(super) impacted_object: Arena<ImpactedObject>,
+    pub(super) object: Arena<Object>,
+    pub(super) uuid_set: ArenaSet<DisruptionUuid>,
+    pub(super) data: Arena<Data>,
+}
+
+#[derive(Hash, PartialEq, Eq, Deserialize_tuple)]
+pub(super) struct Line {
+    pub(super) header: Interned<LineHeader>,
+    pub(super) impacted_objects: InternedSet<ImpactedObject>,
+}
+
+impl From<Arenas> for super::Arenas {
+    // Lines are re-interned in the same order, so that they keep their IDs.
+    fn from(arenas: Arenas) -> Self {
+        let line = Arena::default();
+        for x in arenas.line.values() {
+            line.intern(super::Line {
+                header: x.header,
+                impacted_objects: x.impacted_objects.iter().collect(),
+            });
+        }
+        super::Arenas {
+            string: arenas.string,
+            uuid: arenas.uuid,
+            disruption_set: ArenaSet(arenas.disruption_set.0),
+            disruption: arenas.disruption,
+            application_period_set: arenas.application_period_set,
+            application_period: arenas.application_period,
+            string_set: arenas.string_set,
+            timestamp: arenas.timestamp,
+            line_set: ArenaSet(arenas.line_set.0),
+            line,
+            line_header: arenas.line_header,
+            impacted_object: arenas.impacted_object,
+            object: arenas.object,
+            uuid_set: arenas.uuid_set,
+            data: arenas.data,
+        }
+    }
+}
@@ -0,0 +1,322 @@
+// Validation of the interned IDs of a deserialized database. Looking up an ID that is out of the
+// bounds of its arena panics, so a corrupted or hand-edited database is checked upfront, to report
+// which value refers to a missing one rather than crashing on the first lookup.
+//
+// Each value only needs its own IDs to be in bounds, so arenas can be validated in any order.
+//
+// The IDs contained in each value are enumerated by the `References` trait, which compaction and
+// reference counting also use to walk the references between arenas.
+
+use super::{
+    ApplicationPeriod, ArenaSet, Arenas, Data, Disruption, DisruptionUuid, ImpactedObject, Line,
+    LineHeader, LocalTimestampSeconds, Object,
+};
+use crate::schema::archive::Handle;
+use crate::schema::introspect::Introspect;
+use blazinterner::{Arena, Interned, InternedSlice, InternedStr};
+use std::fmt::{Display, Formatter};
+use std::hash::Hash;
+
+/// Interned ID that doesn't refer to any value of its arena.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DanglingId {
+    referrer: Referrer,
+    arena: &'static str,
+    id: u32,
+    arena_len: usize,
+}
+
+// Value containing a dangling ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Referrer {
+    // Value of the given arena, with the given ID.
+    Arena { arena: &'static str, id: u32 },
+    // Snapshot at the given index of the database.
+    Snapshot(usize),
+}
+
+impl Display for DanglingId {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self.referrer {
+            Referrer::Arena { arena, id } => write!(f, "{arena} #{id}")?,
+            Referrer::Snapshot(index) => write!(f, "snapshot #{index}")?,
+        }
+        write!(
+            f,
+            " refers to {} #{}, but the {} arena only contains {} values",
+            self.arena, self.id, self.arena, self.arena_len,
+        )
+    }
+}
+
+impl std::error::Error for DanglingId {}
+
+// Handle of a value interned in one of the arenas.
+pub(super) trait Id: Copy {
+    const ARENA: &'static str;
+    fn raw_id(self) -> u32;
+    fn arena_len(arenas: &Arenas) -> usize;
+    fn slots<T>(per_arena: &mut PerArena<T>) -> &mut [T];
+}
+
+macro_rules! impl_id {
+    ($($ty:ty => $arena:ident,)*) => {
+        $(
+            impl Id for $ty {
+                const ARENA: &'static str = stringify!($arena);
+
+                fn raw_id(self) -> u32 {
+                    self.id()
+                }
+
+                fn arena_len(arenas: &Arenas) -> usize {
+                    arenas.$arena.len()
+                }
+
+                fn slots<T>(per_arena: &mut PerArena<T>) -> &mut [T] {
+                    &mut per_arena.$arena
+                }
+            }
+        )*
+    };
+}
+
+impl_id! {
+    DisruptionUuid => uuid,
+    InternedSlice<Interned<Disruption>> => disruption_set,
+    Interned<Disruption> => disruption,
+    InternedSlice<Interned<ApplicationPeriod>> => application_period_set,
+    Interned<ApplicationPeriod> => application_period,
+    InternedSlice<InternedStr> => string_set,
+    Interned<LocalTimestampSeconds> => timestamp,
+    InternedSlice<Interned<Line>> => line_set,
+    Interned<Line> => line,
+    Interned<LineHeader> => line_header,
+    Interned<ImpactedObject> => impacted_object,
+    Interned<Object> => object,
+    InternedSlice<DisruptionUuid> => uuid_set,
+    Interned<Data> => data,
+}
+
+impl Id for InternedStr {
+    const ARENA: &'static str = "string";
+
+    fn raw_id(self) -> u32 {
+        self.id()
+    }
+
+    fn arena_len(arenas: &Arenas) -> usize {
+        arenas.string.strings()
+    }
+
+    fn slots<T>(per_arena: &mut PerArena<T>) -> &mut [T] {
+        &mut per_arena.string
+    }
+}
+
+// One value of type `T` for each value of each arena.
+pub(super) struct PerArena<T> {
+    pub(super) string: Box<[T]>,
+    pub(super) uuid: Box<[T]>,
+    pub(super) disruption_set: Box<[T]>,
+    pub(super) disruption: Box<[T]>,
+    pub(super) application_period_set: Box<[T]>,
+    pub(super) application_period: Box<[T]>,
+    pub(super) string_set: Box<[T]>,
+    pub(super) timestamp: Box<[T]>,
+    pub(super) line_set: Box<[T]>,
+    pub(super) line: Box<[T]>,
+    pub(super) line_header: Box<[T]>,
+    pub(super) impacted_object: Box<[T]>,
+    pub(super) object: Box<[T]>,
+    pub(super) uuid_set: Box<[T]>,
+    pub(super) data: Box<[T]>,
+}
+
+impl<T: Clone> PerArena<T> {
+    pub(super) fn new(arenas: &Arenas, value: T) -> Self {
+        Self {
+            string: vec![value.clone(); arenas.string.strings()].into(),
+            uuid: vec![value.clone(); arenas.uuid.len()].into(),
+            disruption_set: vec![value.clone(); arenas.disruption_set.len()].into(),
+            disruption: vec![value.clone(); arenas.disruption.len()].into(),
+            application_period_set: vec![value.clone(); arenas.application_period_set.len()].into(),
+            application_period: vec![value.clone(); arenas.application_period.len()].into(),
+            string_set: vec![value.clone(); arenas.string_set.len()].into(),
+            timestamp: vec![value.clone(); arenas.timestamp.len()].into(),
+            line_set: vec![value.clone(); arenas.line_set.len()].into(),
+            line: vec![value.clone(); arenas.line.len()].into(),
+            line_header: vec![value.clone(); arenas.line_header.len()].into(),
+            impacted_object: vec![value.clone(); arenas.impacted_object.len()].into(),
+            object: vec![value.clone(); arenas.object.len()].into(),
+            uuid_set: vec![value.clone(); arenas.uuid_set.len()].into(),
+            data: vec![value; arenas.data.len()].into(),
+        }
+    }
+}
+
+impl<H: Handle, const SORTED: bool> ArenaSet<H, SORTED> {
+    pub(super) fn len(&self) -> usize {
+        self.0.slices()
+    }
+}
+
+// Checks the IDs contained in a given value.
+struct Checker<'a> {
+    arenas: &'a Arenas,
+    referrer: Referrer,
+}
+
+// Receives the IDs contained in a value.
+pub(super) trait Visitor {
+    type Error;
+
+    fn visit<I: Id>(&mut self, id: I) -> Result<(), Self::Error>;
+
+    fn visit_all<I: Id>(&mut self, ids: impl IntoIterator<Item = I>) -> Result<(), Self::Error> {
+        ids.into_iter().try_for_each(|id| self.visit(id))
+    }
+}
+
+impl Visitor for Checker<'_> {
+    type Error = DanglingId;
+
+    fn visit<I: Id>(&mut self, id: I) -> Result<(), DanglingId> {
+        let arena_len = I::arena_len(self.arenas);
+        if (id.raw_id() as usize) < arena_len {
+            Ok(())
+        } else {
+            Err(DanglingId {
+                referrer: self.referrer,
+                arena: I::ARENA,
+                id: id.raw_id(),
+                arena_len,
+            })
+        }
+    }
+}
+
+// Values that contain IDs of other values.
+pub(super) trait References {
+    fn visit_ids<V: Visitor>(&self, visitor: &mut V) -> Result<(), V::Error>;
+}
+
+impl Arenas {
+    /// Checks that all the IDs contained in the arenas refer to existing values.
+    pub fn validate(&self) -> Result<(), DanglingId> {
+        self.validate_arena(&self.disruption)?;
+        self.validate_arena(&self.line)?;
+        self.validate_arena(&self.line_header)?;
+        self.validate_arena(&self.impacted_object)?;
+        self.validate_arena(&self.object)?;
+        self.validate_arena_set(&self.disruption_set)?;
+        self.validate_arena_set(&self.application_period_set)?;
+        self.validate_arena_set(&self.string_set)?;
+        self.validate_arena_set(&self.line_set)?;
+        self.validate_arena_set(&self.uuid_set)?;
+        self.validate_arena(&self.data)?;
+        Ok(())
+    }
+
+    /// Checks that the handle of the snapshot at the given index of the database refers to an
+    /// existing snapshot of the arenas.
+    pub fn validate_snapshot(&self, data: Interned<Data>, index: usize) -> Result<(), DanglingId> {
+        let mut checker = Checker {
+            arenas: self,
+            referrer: Referrer::Snapshot(index),
+        };
+        checker.visit(data)
+    }
+
+    fn validate_arena<T: References + Eq + Hash>(&self, arena: &Arena<T>) -> Result<(), DanglingId>
+    where
+        Interned<T>: Id,
+    {
+        arena.iter().try_for_each(|(id, value)| {
+            let mut checker = Checker {
+                arenas: self,
+                referrer: Referrer::Arena {
+                    arena: <Interned<T>>::ARENA,
+                    id: id.id(),
+                },
+            };
+            value.visit_ids(&mut checker)
+        })
+    }
+
+    fn validate_arena_set<H: Handle + Id, const SORTED: bool>(
+        &self,
+        arena: &ArenaSet<H, SORTED>,
+    ) -> Result<(), DanglingId>
+    where
+        InternedSlice<H>: Id,
+    {
+        arena.0.iter().try_for_each(|(id, set)| {
+            let mut checker = Checker {
+                arenas: self,
+                referrer: Referrer::Arena {
+                    arena: <InternedSlice<H>>::ARENA,
+                    id: id.id(),
+                },
+            };
+            checker.visit_all(set.iter().copied())
+        })
+    }
+}
+
+impl References for Data {
+    fn visit_ids<V: Visitor>(&self, visitor: &mut V) -> Result<(), V::Error> {
+        match self {
+            Data::Success(data) => {
+                visitor.visit(data.disruptions)?;
+                visitor.visit(data.lines)
+            }
+            Data::Error(data) => {
+                visitor.visit(data.error)?;
+                visitor.visit(data.message)
+            }
+        }
+    }
+}
+
+impl References for Disruption {
+    fn visit_ids<V: Visitor>(&self, visitor: &mut V) -> Result<(), V::Error> {
+        visitor.visit(self.id)?;
+        visitor.visit(self.application_periods)?;
+        visitor.visit(self.last_update)?;
+        visitor.visit(self.cause)?;
+        visitor.visit_all(self.severity.other())?;
+        visitor.visit_all(self.tags)?;
+        visitor.visit(self.title)?;
+        visitor.visit_all(self.message)?;
+        visitor.visit_all(self.short_message)?;
+        visitor.visit_all(self.disruption_id)
+    }
+}
+
+impl References for Line {
+    fn visit_ids<V: Visitor>(&self, visitor: &mut V) -> Result<(), V::Error> {
+        visitor.visit(self.header)?;
+        visitor.visit_all(self.impacted_objects.iter())
+    }
+}
+
+impl References for LineHeader {
+    fn visit_ids<V: Visitor>(&self, visitor: &mut V) -> Result<(), V::Error> {
+        visitor.visit_all([self.id, self.name, self.short_name, self.network_id])?;
+        visitor.visit_all(self.mode.other())
+    }
+}
+
+impl References for ImpactedObject {
+    fn visit_ids<V: Visitor>(&self, visitor: &mut V) -> Result<(), V::Error> {
+        visitor.visit(self.object)?;
+        visitor.visit(self.disruption_ids)
+    }
+}
+
+impl References for Object {
+    fn visit_ids<V: Visitor>(&self, visitor: &mut V) -> Result<(), V::Error> {
+        visitor.visit_all([self.typ, self.id, self.name])
+    }
+}
@@ -0,0 +1,330 @@
+// Views of interned values that resolve their handles on access, so that consumers can print or
+// re-export snapshots without passing the arenas to each accessor. Views serialize to the source
+// schema, like the snapshots regenerated by `Data::to_source`, but read the strings in place rather
+// than copying them out of the arenas.
+
+use super::known::{Known, KnownValues};
+use super::{
+    ApplicationPeriod, Arenas, Data, Disruption, DisruptionUuid, ImpactedObject, Line,
+    LocalTimestampSeconds, DISPLAY_FORMAT,
+};
+use crate::schema::Uuid;
+use blazinterner::{Interned, InternedStr};
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use std::fmt::{self, Display, Formatter};
+
+/// Resolves the handles of values interned in some arenas.
+#[derive(Clone, Copy)]
+pub struct Resolver<'a> {
+    arenas: &'a Arenas,
+}
+
+impl Arenas {
+    pub fn resolver(&self) -> Resolver<'_> {
+        Resolver { arenas: self }
+    }
+}
+
+impl<'a> Resolver<'a> {
+    /// Returns a view of a value whose handles refer to these arenas.
+    pub fn view<T>(self, value: &'a T) -> View<'a, T> {
+        View {
+            resolver: self,
+            value,
+        }
+    }
+
+    pub fn data(self, data: Interned<Data>) -> DataView<'a> {
+        self.view(self.arenas.data.lookup_ref(data))
+    }
+
+    pub fn disruption(self, disruption: Interned<Disruption>) -> DisruptionView<'a> {
+        self.view(self.arenas.disruption.lookup_ref(disruption))
+    }
+
+    fn string(self, string: InternedStr) -> &'a str {
+        self.arenas.string.lookup(string)
+    }
+
+    fn known<K: KnownValues>(self, value: Known<K>) -> &'a str {
+        value.as_str(&self.arenas.string)
+    }
+
+    fn timestamp(self, timestamp: Interned<LocalTimestampSeconds>) -> &'a LocalTimestampSeconds {
+        self.arenas.timestamp.lookup_ref(timestamp)
+    }
+
+    fn uuid(self, uuid: DisruptionUuid) -> &'a Uuid {
+        self.arenas.uuid.lookup_ref(uuid)
+    }
+}
+
+/// Interned value along with the arenas that its handles refer to.
+pub struct View<'a, T> {
+    resolver: Resolver<'a>,
+    value: &'a T,
+}
+
+impl<T> Clone for View<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for View<'_, T> {}
+
+pub type DataView<'a> = View<'a, Data>;
+pub type DisruptionView<'a> = View<'a, Disruption>;
+pub type ApplicationPeriodView<'a> = View<'a, ApplicationPeriod>;
+pub type LineView<'a> = View<'a, Line>;
+pub type ImpactedObjectView<'a> = View<'a, ImpactedObject>;
+
+impl<'a> DisruptionView<'a> {
+    pub fn title(&self) -> &'a str {
+        self.resolver.string(self.value.title)
+    }
+
+    pub fn severity(&self) -> &'a str {
+        self.resolver.known(self.value.severity)
+    }
+
+    fn tags(&self) -> impl Iterator<Item = &'a str> + Clone {
+        let resolver = self.resolver;
+        let tags = (self.value.tags).map_or(&[][..], |x| resolver.arenas.string_set.lookup(x).0);
+        tags.iter().map(move |x| resolver.string(*x))
+    }
+
+    pub fn application_periods(&self) -> impl Iterator<Item = ApplicationPeriodView<'a>> + Clone {
+        let resolver = self.resolver;
+        let arenas = resolver.arenas;
+        (arenas.application_period_set)
+            .lookup(self.value.application_periods)
+            .0
+            .iter()
+            .map(move |x| resolver.view(arenas.application_period.lookup_ref(*x)))
+    }
+}
+
+impl ApplicationPeriodView<'_> {
+    /// Returns the beginning of this period, in local time.
+    pub fn begin(&self) -> String {
+        self.value.begin.to_formatted(DISPLAY_FORMAT)
+    }
+
+    /// Returns the end of this period, in local time.
+    pub fn end(&self) -> String {
+        self.value.end.to_formatted(DISPLAY_FORMAT)
+    }
+}
+
+impl<'a> LineView<'a> {
+    fn impacted_objects(&self) -> impl Iterator<Item = ImpactedObjectView<'a>> + Clone {
+        let resolver = self.resolver;
+        self.value
+            .impacted_objects
+            .iter()
+            .map(move |x| resolver.view(resolver.arenas.impacted_object.lookup_ref(x)))
+    }
+}
+
+impl Display for DataView<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let arenas = self.resolver.arenas;
+        match self.value {
+            Data::Success(data) => write!(
+                f,
+                "{} | {} disruptions | {} lines",
+                data.last_updated_date(),
+                data.disruptions(arenas).len(),
+                data.lines(arenas).len(),
+            ),
+            Data::Error(data) => write!(
+                f,
+                "error {} | {}: {}",
+                data.status_code(),
+                data.error(arenas),
+                data.message(arenas),
+            ),
+        }
+    }
+}
+
+impl Display for DisruptionView<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.severity(), self.title())
+    }
+}
+
+impl Display for ApplicationPeriodView<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} -> {}", self.begin(), self.end())
+    }
+}
+
+impl Display for LineView<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let header = self
+            .resolver
+            .arenas
+            .line_header
+            .lookup_ref(self.value.header);
+        write!(
+            f,
+            "{} {}: {} ({})",
+            self.resolver.known(header.mode),
+            self.resolver.string(header.short_name),
+            self.resolver.string(header.name),
+            self.resolver.string(header.id),
+        )
+    }
+}
+
+impl Display for ImpactedObjectView<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let object = self.resolver.arenas.object.lookup_ref(self.value.object);
+        write!(
+            f,
+            "{} {}: {}",
+            self.resolver.string(object.typ),
+            self.resolver.string(object.id),
+            self.resolver.string(object.name),
+        )
+    }
+}
+
+// The fields and their names follow the source schema, so that views serialize to the same JSON as
+// the snapshots that they were interned from.
+
+impl Serialize for DataView<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let resolver = self.resolver;
+        let arenas = resolver.arenas;
+        let mut state = serializer.serialize_struct("Data", 3)?;
+        match self.value {
+            Data::Success(data) => {
+                state.serialize_field(
+                    "disruptions",
+                    &Seq(data
+                        .disruptions(arenas)
+                        .iter()
+                        .map(|x| resolver.disruption(*x))),
+                )?;
+                state.serialize_field(
+                    "lines",
+                    &Seq(data
+                        .lines(arenas)
+                        .iter()
+                        .map(|x| resolver.view(arenas.line.lookup_ref(*x)))),
+                )?;
+                state.serialize_field("lastUpdatedDate", &data.last_updated_date())?;
+            }
+            Data::Error(data) => {
+                state.serialize_field("statusCode", &data.status_code())?;
+                state.serialize_field("error", data.error(arenas))?;
+                state.serialize_field("message", data.message(arenas))?;
+            }
+        }
+        state.end()
+    }
+}
+
+impl Serialize for DisruptionView<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let resolver = self.resolver;
+        let disruption = self.value;
+        let mut state = serializer.serialize_struct("Disruption", 10)?;
+        state.serialize_field("id", resolver.uuid(disruption.id))?;
+        state.serialize_field("applicationPeriods", &Seq(self.application_periods()))?;
+        state.serialize_field(
+            "lastUpdate",
+            &resolver.timestamp(disruption.last_update).to_source(),
+        )?;
+        state.serialize_field("cause", resolver.string(disruption.cause))?;
+        state.serialize_field("severity", self.severity())?;
+        state.serialize_field("tags", &disruption.tags.map(|_| Seq(self.tags())))?;
+        state.serialize_field("title", self.title())?;
+        state.serialize_field("message", &disruption.message.map(|x| resolver.string(x)))?;
+        state.serialize_field(
+            "shortMessage",
+            &disruption.short_message.map(|x| resolver.string(x)),
+        )?;
+        state.serialize_field(
+            "disruption_id",
+            &disruption.disruption_id.map(|x| resolver.uuid(x)),
+        )?;
+        state.end()
+    }
+}
+
+impl Serialize for ApplicationPeriodView<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("ApplicationPeriod", 2)?;
+        state.serialize_field("begin", &self.value.begin.to_source())?;
+        state.serialize_field("end", &self.value.end.to_source())?;
+        state.end()
+    }
+}
+
+impl Serialize for LineView<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let resolver = self.resolver;
+        let header = resolver.arenas.line_header.lookup_ref(self.value.header);
+        let mut state = serializer.serialize_struct("Line", 6)?;
+        state.serialize_field("id", resolver.string(header.id))?;
+        state.serialize_field("name", resolver.string(header.name))?;
+        state.serialize_field("shortName", resolver.string(header.short_name))?;
+        state.serialize_field("mode", resolver.known(header.mode))?;
+        state.serialize_field("networkId", resolver.string(header.network_id))?;
+        state.serialize_field("impactedObjects", &Seq(self.impacted_objects()))?;
+        state.end()
+    }
+}
+
+impl Serialize for ImpactedObjectView<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let resolver = self.resolver;
+        let object = resolver.arenas.object.lookup_ref(self.value.object);
+        let disruption_ids = resolver.arenas.uuid_set.lookup(self.value.disruption_ids).0;
+        let mut state = serializer.serialize_struct("ImpactedObject", 4)?;
+        state.serialize_field("type", resolver.string(object.typ))?;
+        state.serialize_field("id", resolver.string(object.id))?;
+        state.serialize_field("name", resolver.string(object.name))?;
+        state.serialize_field(
+            "disruptionIds",
+            &Seq(disruption_ids.iter().map(|x| resolver.uuid(*x))),
+        )?;
+        state.end()
+    }
+}
+
+// Serializes the items of an iterator as a sequence, without collecting them.
+struct Seq<I>(I);
+
+impl<I> Serialize for Seq<I>
+where
+    I: Iterator + Clone,
+    I::Item: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(self.0.clone())
+    }
+}
@@ -1,68 +1,230 @@
-use super::Uuid;
-use get_size2::GetSize;
-use serde::Deserialize;
-
-#[derive(Clone, Debug, Deserialize, GetSize)]
-#[serde(deny_unknown_fields)]
-pub struct Data {
-    // Success case.
-    pub disruptions: Option<Vec<Disruption>>,
-    pub lines: Option<Vec<Line>>,
-    #[serde(rename = "lastUpdatedDate")]
-    pub last_updated_date: Option<String>,
-    // Error case.
-    #[serde(rename = "statusCode")]
-    pub status_code: Option<i32>,
-    pub error: Option<String>,
-    pub message: Option<String>,
-}
-
-#[derive(Clone, Debug, Deserialize, GetSize)]
-#[serde(deny_unknown_fields)]
-pub struct Disruption {
-    pub id: Uuid,
-    #[serde(rename = "applicationPeriods")]
-    pub application_periods: Vec<ApplicationPeriod>,
-    #[serde(rename = "lastUpdate")]
-    pub last_update: String,
-    pub cause: String,
-    pub severity: String,
-    pub tags: Option<Vec<String>>,
-    pub title: String,
-    pub message: Option<String>,
-    #[serde(rename = "shortMessage")]
-    pub short_message: Option<String>,
-    pub disruption_id: Option<Uuid>,
-}
-
-#[derive(Clone, Debug, Deserialize, GetSize)]
-#[serde(deny_unknown_fields)]
-pub struct ApplicationPeriod {
-    pub begin: String,
-    pub end: String,
-}
-
-#[derive(Clone, Debug, Deserialize, GetSize)]
-#[serde(deny_unknown_fields)]
-pub struct Line {
-    pub id: String,
-    pub name: String,
-    #[serde(rename = "shortName")]
-    pub short_name: String,
-    pub mode: String,
-    #[serde(rename = "networkId")]
-    pub network_id: String,
-    #[serde(rename = "impactedObjects")]
-    pub impacted_objects: Vec<ImpactedObject>,
-}
-
-#[derive(Clone, Debug, Deserialize, GetSize)]
-#[serde(deny_unknown_fields)]
-pub struct ImpactedObject {
-    #[serde(rename = "type")]
-    pub typ: String,
-    pub id: String,
-    pub name: String,
-    #[serde(rename = "disruptionIds")]
-    pub disruption_ids: Vec<Uuid>,
+// Schemas of the JSON files returned by the upstream API. The API occasionally adds or renames
+// fields, so each shape of the responses has its own module, and older shapes are converted into the
+// latest one, which is re-exported here. A single database can therefore span several versions of
+// the API, and snapshots are always regenerated in the latest shape.
+//
+// Strings are borrowed from the input file when they don't contain escape sequences, so that
+// converting a snapshot only allocates for the strings that aren't interned yet.
+//
+// The schemas reject unknown fields. In tolerant mode, the fields that the latest schema doesn't
+// know are moved out of the JSON value before converting it, so that a new upstream field doesn't
+// make every file fail to parse.
+
+pub mod v1;
+pub mod v2;
+
+pub use v2::*;
+
+use super::Extras;
+use get_size2::{GetSize, GetSizeTracker};
+use serde::de::value::Error as ValueError;
+use serde::de::{Deserialize, Deserializer, Error as _, Visitor};
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Deref;
+
+/// String of a parsed snapshot, borrowed from the input file unless it had to be unescaped.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Str<'a>(Cow<'a, str>);
+
+impl Str<'_> {
+    pub fn into_owned(self) -> Str<'static> {
+        Str(Cow::Owned(self.0.into_owned()))
+    }
+}
+
+impl Deref for Str<'_> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for Str<'_> {
+    fn from(string: String) -> Self {
+        Str(Cow::Owned(string))
+    }
+}
+
+impl<'a> From<&'a str> for Str<'a> {
+    fn from(string: &'a str) -> Self {
+        Str(Cow::Borrowed(string))
+    }
+}
+
+impl fmt::Display for Str<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self)
+    }
+}
+
+impl GetSize for Str<'_> {
+    fn get_heap_size_with_tracker<T: GetSizeTracker>(&self, tracker: T) -> (usize, T) {
+        match &self.0 {
+            Cow::Borrowed(_) => (0, tracker),
+            Cow::Owned(string) => string.get_heap_size_with_tracker(tracker),
+        }
+    }
+}
+
+impl Serialize for Str<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self)
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for Str<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(StrVisitor)
+    }
+}
+
+struct StrVisitor;
+
+impl<'de> Visitor<'de> for StrVisitor {
+    type Value = Str<'de>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a string")
+    }
+
+    fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Str<'de>, E> {
+        Ok(Str(Cow::Borrowed(v)))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Str<'de>, E> {
+        Ok(Str(Cow::Owned(v.to_owned())))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Str<'de>, E> {
+        Ok(Str(Cow::Owned(v)))
+    }
+}
+
+/// Parses a JSON file in any version of the schema, trying the newest version first, and converts
+/// it into the latest version.
+pub fn from_slice(bytes: &[u8]) -> serde_json::Result<Data<'_>> {
+    let error = match serde_json::from_slice::<Data>(bytes) {
+        Ok(data) => return Ok(data),
+        Err(error) => error,
+    };
+    match serde_json::from_slice::<v1::Data>(bytes) {
+        Ok(data) => {
+            tracing::debug!("Parsed snapshot in the version 1 schema");
+            Ok(data.into())
+        }
+        // Files of an unknown shape are reported against the latest version.
+        Err(_) => Err(error),
+    }
+}
+
+/// Parses a JSON file like `from_slice`, but moves the fields that the schema doesn't know into the
+/// returned extras instead of failing. The strings are copied out of the intermediate JSON value.
+pub fn from_slice_tolerant(bytes: &[u8]) -> serde_json::Result<(Data<'static>, Extras)> {
+    let mut value: Value = serde_json::from_slice(bytes)?;
+    let extras = take_extras(&mut value);
+    let error = match Data::deserialize(&value) {
+        Ok(data) => return Ok((data.into_owned(), extras)),
+        Err(error) => error,
+    };
+    match v1::Data::deserialize(&value) {
+        Ok(data) => {
+            tracing::debug!("Parsed snapshot in the version 1 schema");
+            Ok((Data::from(data).into_owned(), extras))
+        }
+        Err(_) => Err(error),
+    }
+}
+
+// Removes the unknown fields of each object of the snapshot. Fields of older versions are a subset
+// of the latest ones, so only the latest schema is checked.
+fn take_extras(data: &mut Value) -> Extras {
+    let mut extras = Extras::new();
+    take_unknown_fields::<Data>(data, "", &mut extras);
+    for (i, disruption) in items(data, "disruptions") {
+        let path = format!("disruptions[{i}]");
+        take_unknown_fields::<Disruption>(disruption, &path, &mut extras);
+        for (j, period) in items(disruption, "applicationPeriods") {
+            let path = format!("{path}.applicationPeriods[{j}]");
+            take_unknown_fields::<ApplicationPeriod>(period, &path, &mut extras);
+        }
+    }
+    for (i, line) in items(data, "lines") {
+        let path = format!("lines[{i}]");
+        take_unknown_fields::<Line>(line, &path, &mut extras);
+        for (j, object) in items(line, "impactedObjects") {
+            let path = format!("{path}.impactedObjects[{j}]");
+            take_unknown_fields::<ImpactedObject>(object, &path, &mut extras);
+        }
+    }
+    extras
+}
+
+fn take_unknown_fields<'de, T: Deserialize<'de>>(
+    value: &mut Value,
+    path: &str,
+    extras: &mut Extras,
+) {
+    let Value::Object(object) = value else {
+        return;
+    };
+    let fields = fields::<T>();
+    object.retain(|key, value| {
+        let known = fields.contains(&key.as_str());
+        if !known {
+            let path = if path.is_empty() {
+                key.clone()
+            } else {
+                format!("{path}.{key}")
+            };
+            extras.insert(path, value.take());
+        }
+        known
+    });
+}
+
+fn items<'a>(value: &'a mut Value, key: &str) -> impl Iterator<Item = (usize, &'a mut Value)> {
+    value
+        .get_mut(key)
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+        .enumerate()
+}
+
+// Returns the names of the fields of a struct, as declared to serde by its derived `Deserialize`
+// implementation, i.e. after renaming. This keeps the known fields in sync with the schema.
+fn fields<'de, T: Deserialize<'de>>() -> &'static [&'static str] {
+    let mut fields: &'static [&'static str] = &[];
+    // The deserializer always fails once it has captured the fields.
+    let _ = T::deserialize(FieldNames(&mut fields));
+    fields
+}
+
+struct FieldNames<'a>(&'a mut &'static [&'static str]);
+
+impl<'de> Deserializer<'de> for FieldNames<'_> {
+    type Error = ValueError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, ValueError> {
+        Err(ValueError::custom("expected a struct"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, ValueError> {
+        *self.0 = fields;
+        Err(ValueError::custom("only the names of the fields are read"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option
+        unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier ignored_any
+    }
 }
@@ -0,0 +1,69 @@
+// Schema of the responses of version 1 of the API, whose disruptions don't have the `shortMessage`
+// and `disruption_id` fields yet. The other objects are unchanged in version 2.
+
+use super::v2::{self, ApplicationPeriod, Line};
+use super::Str;
+use crate::schema::Uuid;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, bound(deserialize = "'de: 'a"))]
+pub struct Data<'a> {
+    // Success case.
+    pub disruptions: Option<Vec<Disruption<'a>>>,
+    pub lines: Option<Vec<Line<'a>>>,
+    #[serde(rename = "lastUpdatedDate")]
+    pub last_updated_date: Option<Str<'a>>,
+    // Error case.
+    #[serde(rename = "statusCode")]
+    pub status_code: Option<i32>,
+    pub error: Option<Str<'a>>,
+    pub message: Option<Str<'a>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, bound(deserialize = "'de: 'a"))]
+pub struct Disruption<'a> {
+    pub id: Uuid,
+    #[serde(rename = "applicationPeriods")]
+    pub application_periods: Vec<ApplicationPeriod<'a>>,
+    #[serde(rename = "lastUpdate")]
+    pub last_update: Str<'a>,
+    pub cause: Str<'a>,
+    pub severity: Str<'a>,
+    pub tags: Option<Vec<Str<'a>>>,
+    pub title: Str<'a>,
+    pub message: Option<Str<'a>>,
+}
+
+impl<'a> From<Data<'a>> for v2::Data<'a> {
+    fn from(data: Data<'a>) -> Self {
+        Self {
+            disruptions: data
+                .disruptions
+                .map(|disruptions| disruptions.into_iter().map(Into::into).collect()),
+            lines: data.lines,
+            last_updated_date: data.last_updated_date,
+            status_code: data.status_code,
+            error: data.error,
+            message: data.message,
+        }
+    }
+}
+
+impl<'a> From<Disruption<'a>> for v2::Disruption<'a> {
+    fn from(disruption: Disruption<'a>) -> Self {
+        Self {
+            id: disruption.id,
+            application_periods: disruption.application_periods,
+            last_update: disruption.last_update,
+            cause: disruption.cause,
+            severity: disruption.severity,
+            tags: disruption.tags,
+            title: disruption.title,
+            message: disruption.message,
+            short_message: None,
+            disruption_id: None,
+        }
+    }
+}
@@ -0,0 +1,167 @@
+// Schema of the responses of version 2 of the API, which the rest of the program works with.
+
+use super::Str;
+use crate::schema::Uuid;
+use get_size2::GetSize;
+use serde::{Deserialize, Deserializer, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize, GetSize)]
+#[serde(deny_unknown_fields, bound(deserialize = "'de: 'a"))]
+pub struct Data<'a> {
+    // Success case.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disruptions: Option<Vec<Disruption<'a>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lines: Option<Vec<Line<'a>>>,
+    #[serde(rename = "lastUpdatedDate", skip_serializing_if = "Option::is_none")]
+    pub last_updated_date: Option<Str<'a>>,
+    // Error case.
+    #[serde(rename = "statusCode", skip_serializing_if = "Option::is_none")]
+    pub status_code: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<Str<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<Str<'a>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, GetSize)]
+#[serde(deny_unknown_fields, bound(deserialize = "'de: 'a"))]
+pub struct Disruption<'a> {
+    pub id: Uuid,
+    #[serde(rename = "applicationPeriods")]
+    pub application_periods: Vec<ApplicationPeriod<'a>>,
+    #[serde(rename = "lastUpdate")]
+    pub last_update: Str<'a>,
+    pub cause: Str<'a>,
+    pub severity: Str<'a>,
+    pub tags: Option<Vec<Str<'a>>>,
+    pub title: Str<'a>,
+    pub message: Option<Str<'a>>,
+    #[serde(rename = "shortMessage", deserialize_with = "nullable")]
+    pub short_message: Option<Str<'a>>,
+    #[serde(deserialize_with = "nullable")]
+    pub disruption_id: Option<Uuid>,
+}
+
+// Deserializes a field that may be null but must be present. Serde otherwise treats a missing
+// `Option` field as `None`, which would make version 1 disruptions parse as version 2 ones.
+fn nullable<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::deserialize(deserializer)
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, GetSize)]
+#[serde(deny_unknown_fields, bound(deserialize = "'de: 'a"))]
+pub struct ApplicationPeriod<'a> {
+    pub begin: Str<'a>,
+    pub end: Str<'a>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, GetSize)]
+#[serde(deny_unknown_fields, bound(deserialize = "'de: 'a"))]
+pub struct Line<'a> {
+    pub id: Str<'a>,
+    pub name: Str<'a>,
+    #[serde(rename = "shortName")]
+    pub short_name: Str<'a>,
+    pub mode: Str<'a>,
+    #[serde(rename = "networkId")]
+    pub network_id: Str<'a>,
+    #[serde(rename = "impactedObjects")]
+    pub impacted_objects: Vec<ImpactedObject<'a>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, GetSize)]
+#[serde(deny_unknown_fields, bound(deserialize = "'de: 'a"))]
+pub struct ImpactedObject<'a> {
+    #[serde(rename = "type")]
+    pub typ: Str<'a>,
+    pub id: Str<'a>,
+    pub name: Str<'a>,
+    #[serde(rename = "disruptionIds")]
+    pub disruption_ids: Vec<Uuid>,
+}
+
+impl Data<'_> {
+    /// Copies the strings borrowed from the input file, so that the snapshot outlives it.
+    pub fn into_owned(self) -> Data<'static> {
+        Data {
+            disruptions: self.disruptions.map(|disruptions| {
+                disruptions
+                    .into_iter()
+                    .map(Disruption::into_owned)
+                    .collect()
+            }),
+            lines: self
+                .lines
+                .map(|lines| lines.into_iter().map(Line::into_owned).collect()),
+            last_updated_date: self.last_updated_date.map(Str::into_owned),
+            status_code: self.status_code,
+            error: self.error.map(Str::into_owned),
+            message: self.message.map(Str::into_owned),
+        }
+    }
+}
+
+impl Disruption<'_> {
+    fn into_owned(self) -> Disruption<'static> {
+        Disruption {
+            id: self.id,
+            application_periods: self
+                .application_periods
+                .into_iter()
+                .map(ApplicationPeriod::into_owned)
+                .collect(),
+            last_update: self.last_update.into_owned(),
+            cause: self.cause.into_owned(),
+            severity: self.severity.into_owned(),
+            tags: self
+                .tags
+                .map(|tags| tags.into_iter().map(Str::into_owned).collect()),
+            title: self.title.into_owned(),
+            message: self.message.map(Str::into_owned),
+            short_message: self.short_message.map(Str::into_owned),
+            disruption_id: self.disruption_id,
+        }
+    }
+}
+
+impl ApplicationPeriod<'_> {
+    fn into_owned(self) -> ApplicationPeriod<'static> {
+        ApplicationPeriod {
+            begin: self.begin.into_owned(),
+            end: self.end.into_owned(),
+        }
+    }
+}
+
+impl Line<'_> {
+    fn into_owned(self) -> Line<'static> {
+        Line {
+            id: self.id.into_owned(),
+            name: self.name.into_owned(),
+            short_name: self.short_name.into_owned(),
+            mode: self.mode.into_owned(),
+            network_id: self.network_id.into_owned(),
+            impacted_objects: self
+                .impacted_objects
+                .into_iter()
+                .map(ImpactedObject::into_owned)
+                .collect(),
+        }
+    }
+}
+
+impl ImpactedObject<'_> {
+    fn into_owned(self) -> ImpactedObject<'static> {
+        ImpactedObject {
+            typ: self.typ.into_owned(),
+            id: self.id.into_owned(),
+            name: self.name.into_owned(),
+            disruption_ids: self.disruption_ids,
+        }
+    }
+}
@@ -0,0 +1,67 @@
+// Storage of interned values tagged with a marker type. Handles are generic over the storage type
+// of their arena, so handles into two arenas of the same values but with different tags are
+// distinct types, and looking up a handle in the wrong arena fails to compile.
+//
+// The tag only exists at the type level: values are stored, serialized and archived as is.
+
+use get_size2::{GetSize, GetSizeTracker};
+use serde::{Deserialize, Serialize};
+use std::borrow::Borrow;
+use std::fmt::{Debug, Formatter};
+use std::marker::PhantomData;
+
+#[derive(Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Tagged<T, Tag> {
+    value: T,
+    #[serde(skip)]
+    _tag: PhantomData<fn() -> Tag>,
+}
+
+impl<T, Tag> From<T> for Tagged<T, Tag> {
+    fn from(value: T) -> Self {
+        Self {
+            value,
+            _tag: PhantomData,
+        }
+    }
+}
+
+// Allows interning a value by reference, which only clones it if it isn't already interned.
+impl<T: Clone, Tag> From<&T> for Tagged<T, Tag> {
+    fn from(value: &T) -> Self {
+        value.clone().into()
+    }
+}
+
+impl<T, Tag> Borrow<T> for Tagged<T, Tag> {
+    fn borrow(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: Clone, Tag> Clone for Tagged<T, Tag> {
+    fn clone(&self) -> Self {
+        self.value.clone().into()
+    }
+}
+
+impl<T: PartialEq, Tag> PartialEq for Tagged<T, Tag> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<T: Eq, Tag> Eq for Tagged<T, Tag> {}
+
+impl<T: Debug, Tag> Debug for Tagged<T, Tag> {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+impl<T: GetSize, Tag> GetSize for Tagged<T, Tag> {
+    fn get_heap_size_with_tracker<Tr: GetSizeTracker>(&self, tracker: Tr) -> (usize, Tr) {
+        self.value.get_heap_size_with_tracker(tracker)
+    }
+}
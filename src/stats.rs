@@ -0,0 +1,260 @@
+// Machine-readable statistics of the commands that serialize databases, written as `stats.json` in
+// the output directory. They contain the same numbers as the printed tables, so that sizes and
+// timings can be tracked across runs. Timings are sampled over several repetitions of each round
+// trip, of which the minimum, median and maximum are kept.
+
+mod html;
+
+use crate::cli::ReportFormat;
+use crate::report::{Failure, Stage};
+use serde::ser::SerializeStruct;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+// Names of the statistics files in the output directory.
+const STATS_FILE: &str = "stats.json";
+const HTML_REPORT_FILE: &str = "report.html";
+
+#[derive(Debug, Default, Serialize)]
+pub struct StatsReport {
+    /// Counts of input files, if the command read any.
+    pub files: Option<FileCounts>,
+    pub totals: Totals,
+    /// Compression level of the `zstd` sizes and times.
+    pub zstd_level: Option<u32>,
+    /// Breakdown of the arenas of the optimized schema, in the order of the printed summary.
+    pub interners: Vec<InternerStats>,
+    /// Sizes and times of each serialized database.
+    pub formats: Vec<FormatStats>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct FileCounts {
+    pub parsed: usize,
+    pub verified: usize,
+    /// Files skipped because the database already contained them.
+    pub skipped: usize,
+    pub failed: BTreeMap<Stage, usize>,
+    /// Occurrences of each field that the schema doesn't know, dropped in tolerant mode.
+    pub unknown_fields: BTreeMap<String, usize>,
+}
+
+impl FileCounts {
+    pub fn new(parsed: usize, verified: usize, skipped: usize, failures: &[Failure]) -> Self {
+        let mut failed = BTreeMap::new();
+        for failure in failures {
+            *failed.entry(failure.stage).or_insert(0) += 1;
+        }
+        Self {
+            parsed,
+            verified,
+            skipped,
+            failed,
+            unknown_fields: BTreeMap::new(),
+        }
+    }
+}
+
+/// Total sizes in bytes. Sizes that the command doesn't compute are null.
+#[derive(Debug, Default, Serialize)]
+pub struct Totals {
+    pub input_bytes: usize,
+    pub parsed_bytes: Option<usize>,
+    pub optimized_bytes: Option<usize>,
+    pub arenas_bytes: Option<usize>,
+    pub optimized_json_bytes: Option<usize>,
+    pub jinterners_bytes: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InternerStats {
+    pub name: &'static str,
+    pub objects: usize,
+    /// Total number of items of the interned slices, for arenas of slices.
+    pub items: Option<usize>,
+    pub bytes: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FormatStats {
+    /// Name of the file within the output directory.
+    pub file: String,
+    /// Representation of the serialized database, e.g. with front-coded strings.
+    pub variant: &'static str,
+    pub format: &'static str,
+    #[serde(flatten)]
+    pub stats: Stats,
+}
+
+/// Converts a duration to a (fractional) number of milliseconds, as reported in the statistics.
+pub fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Writes the statistics to the output directory, along with a report in the given format if any.
+pub fn write_stats(
+    output_dir: &Path,
+    stats: &StatsReport,
+    report: Option<ReportFormat>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let path = output_dir.join(STATS_FILE);
+    tracing::info!(?path, "Writing statistics");
+    serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), stats)?;
+
+    match report {
+        Some(ReportFormat::Html) => {
+            let path = output_dir.join(HTML_REPORT_FILE);
+            tracing::info!(?path, "Writing HTML report");
+            std::fs::write(path, html::render(stats))?;
+        }
+        None => (),
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+pub struct Stats {
+    pub serialized: CodecStats,
+    pub gzip: CodecStats,
+    pub xz: CodecStats,
+    pub brotli: CodecStats,
+    pub zstd: CodecStats,
+}
+
+#[derive(Debug)]
+pub struct CodecStats {
+    pub encoded_size: usize,
+    pub encode: Samples,
+    pub decode: Samples,
+}
+
+// The median times are reported as `encode_ms` and `decode_ms`, next to their spread over the
+// iterations of the benchmark.
+impl Serialize for CodecStats {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("CodecStats", 7)?;
+        s.serialize_field("bytes", &self.encoded_size)?;
+        s.serialize_field("encode_ms", &millis(self.encode.median))?;
+        s.serialize_field("encode_min_ms", &millis(self.encode.min))?;
+        s.serialize_field("encode_max_ms", &millis(self.encode.max))?;
+        s.serialize_field("decode_ms", &millis(self.decode.median))?;
+        s.serialize_field("decode_min_ms", &millis(self.decode.min))?;
+        s.serialize_field("decode_max_ms", &millis(self.decode.max))?;
+        s.end()
+    }
+}
+
+// Durations of the repeated runs of an encoder or a decoder.
+#[derive(Clone, Copy, Debug)]
+pub struct Samples {
+    pub min: Duration,
+    pub median: Duration,
+    pub max: Duration,
+}
+
+impl Samples {
+    // Runs the operation the given number of times, preceded by a warmup run if there are several,
+    // and returns the output of the last run.
+    pub fn measure<R>(
+        iterations: u32,
+        mut run: impl FnMut() -> Result<R, Box<dyn std::error::Error>>,
+    ) -> Result<(R, Self), Box<dyn std::error::Error>> {
+        if iterations > 1 {
+            run()?;
+        }
+        let mut output = None;
+        let mut durations = Vec::with_capacity(iterations as usize);
+        for _ in 0..iterations {
+            let start = Instant::now();
+            let result = run()?;
+            durations.push(Instant::now().duration_since(start));
+            // The output of the previous run is dropped outside of the measurement.
+            output = Some(result);
+        }
+        durations.sort_unstable();
+        let n = durations.len();
+        let samples = Samples {
+            min: durations[0],
+            median: (durations[(n - 1) / 2] + durations[n / 2]) / 2,
+            max: durations[n - 1],
+        };
+        Ok((output.expect("At least one iteration is run"), samples))
+    }
+
+    // Throughput of the median run over the given number of bytes, in MB/s.
+    pub fn mb_per_sec(&self, bytes: usize) -> f64 {
+        bytes as f64 / (1_000_000.0 * self.median.as_secs_f64())
+    }
+}
+
+impl Stats {
+    pub fn print_sizes(&self, title: &str, total_bytes: usize) {
+        println!(
+            "| {title:<13} | {:>9} |{:>5.02}% | {:>9} |{:>5.02}% | {:>9} |{:>5.02}% | {:>9} |{:>5.02}% | {:>9} |{:>5.02}% |",
+            self.serialized.encoded_size,
+            self.serialized.encoded_size as f64 * 100.0 / total_bytes as f64,
+            self.gzip.encoded_size,
+            self.gzip.encoded_size as f64 * 100.0 / total_bytes as f64,
+            self.xz.encoded_size,
+            self.xz.encoded_size as f64 * 100.0 / total_bytes as f64,
+            self.brotli.encoded_size,
+            self.brotli.encoded_size as f64 * 100.0 / total_bytes as f64,
+            self.zstd.encoded_size,
+            self.zstd.encoded_size as f64 * 100.0 / total_bytes as f64,
+        );
+    }
+
+    // Prints the difference of sizes with the baseline, relative to the baseline.
+    pub fn print_size_deltas(&self, title: &str, baseline: &Stats) {
+        let delta = |stats: &CodecStats, baseline: &CodecStats| {
+            let delta = stats.encoded_size as i64 - baseline.encoded_size as i64;
+            (delta, delta as f64 * 100.0 / baseline.encoded_size as f64)
+        };
+        let (serialized, serialized_pct) = delta(&self.serialized, &baseline.serialized);
+        let (gzip, gzip_pct) = delta(&self.gzip, &baseline.gzip);
+        let (xz, xz_pct) = delta(&self.xz, &baseline.xz);
+        let (brotli, brotli_pct) = delta(&self.brotli, &baseline.brotli);
+        let (zstd, zstd_pct) = delta(&self.zstd, &baseline.zstd);
+        println!(
+            "| {title:<13} | {serialized:>+9} |{serialized_pct:>+5.01}% | {gzip:>+9} |{gzip_pct:>+5.01}% | {xz:>+9} |{xz_pct:>+5.01}% | {brotli:>+9} |{brotli_pct:>+5.01}% | {zstd:>+9} |{zstd_pct:>+5.01}% |",
+        );
+    }
+
+    // Prints the given statistic of the encoding and decoding times of each codec.
+    pub fn print_times(&self, title: &str, time: impl Fn(&Samples) -> Duration) {
+        let [a, b, c, d, e, f, g, h, i, j] =
+            self.samples().map(|samples| time(samples).as_millis());
+        println!(
+            "| {title:<13} |{a:>5} ms |{b:>5} ms |{c:>5} ms |{d:>5} ms |{e:>5} ms |{f:>5} ms |{g:>5} ms |{h:>5} ms |{i:>5} ms |{j:>5} ms |",
+        );
+    }
+
+    // Prints the throughput of each codec, relative to the size of the serialized database.
+    pub fn print_throughputs(&self, title: &str) {
+        let bytes = self.serialized.encoded_size;
+        let [a, b, c, d, e, f, g, h, i, j] =
+            self.samples().map(|samples| samples.mb_per_sec(bytes));
+        println!(
+            "| {title:<13} |{a:>4.0} MB/s|{b:>4.0} MB/s|{c:>4.0} MB/s|{d:>4.0} MB/s|{e:>4.0} MB/s|{f:>4.0} MB/s|{g:>4.0} MB/s|{h:>4.0} MB/s|{i:>4.0} MB/s|{j:>4.0} MB/s|",
+        );
+    }
+
+    pub fn samples(&self) -> [&Samples; 10] {
+        [
+            &self.serialized.encode,
+            &self.serialized.decode,
+            &self.gzip.encode,
+            &self.gzip.decode,
+            &self.xz.encode,
+            &self.xz.decode,
+            &self.brotli.encode,
+            &self.brotli.decode,
+            &self.zstd.encode,
+            &self.zstd.decode,
+        ]
+    }
+}
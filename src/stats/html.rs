@@ -0,0 +1,255 @@
+// Rendering of the statistics as a self-contained HTML page, which can be shared as a single file.
+// Charts are horizontal bars drawn with CSS, so that the page doesn't need any script nor external
+// resource.
+
+use super::{FormatStats, StatsReport};
+use crate::CodecStats;
+use std::fmt::Write;
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { padding: 0.2em 0.6em; text-align: right; border-bottom: 1px solid #ddd; }
+th:first-child, td:first-child { text-align: left; }
+td.chart { width: 30em; text-align: left; }
+.bar { height: 0.9em; background: #4a7bb7; display: inline-block; vertical-align: middle; }
+.bar.alt { background: #e08a3c; }
+";
+
+/// Renders the statistics as an HTML page.
+pub fn render(stats: &StatsReport) -> String {
+    let mut html = String::new();
+    writeln!(html, "<!DOCTYPE html>").unwrap();
+    writeln!(
+        html,
+        "<html><head><meta charset=\"utf-8\"><title>Interning statistics</title><style>{STYLE}</style></head><body>"
+    )
+    .unwrap();
+    writeln!(html, "<h1>Interning statistics</h1>").unwrap();
+
+    render_totals(&mut html, stats);
+    if !stats.interners.is_empty() {
+        render_interners(&mut html, stats);
+    }
+
+    let zstd = match stats.zstd_level {
+        Some(level) => format!("zstd -{level}"),
+        None => "zstd".to_owned(),
+    };
+    let codecs = ["Bytes", "gzip -6", "xz -6", "brotli -6", &zstd];
+    let mut variants: Vec<&str> = stats.formats.iter().map(|x| x.variant).collect();
+    variants.dedup();
+    for variant in variants {
+        let formats: Vec<&FormatStats> = stats
+            .formats
+            .iter()
+            .filter(|x| x.variant == variant)
+            .collect();
+        render_sizes(
+            &mut html,
+            variant,
+            &codecs,
+            &formats,
+            stats.totals.input_bytes,
+        );
+        render_times(&mut html, variant, &codecs, &formats);
+    }
+
+    writeln!(html, "</body></html>").unwrap();
+    html
+}
+
+fn render_totals(html: &mut String, stats: &StatsReport) {
+    if let Some(files) = &stats.files {
+        let failed: usize = files.failed.values().sum();
+        writeln!(
+            html,
+            "<p>Parsed {} files ({} verified, {} skipped, {failed} failed).</p>",
+            files.parsed, files.verified, files.skipped,
+        )
+        .unwrap();
+        if !files.unknown_fields.is_empty() {
+            let fields: Vec<String> = files
+                .unknown_fields
+                .iter()
+                .map(|(field, count)| format!("<code>{}</code> ({count})", escape(field)))
+                .collect();
+            writeln!(
+                html,
+                "<p>Dropped unknown fields: {}.</p>",
+                fields.join(", ")
+            )
+            .unwrap();
+        }
+    }
+
+    let totals = &stats.totals;
+    let sizes: Vec<(&str, usize)> = [
+        ("Input", Some(totals.input_bytes)),
+        ("Parsed in memory", totals.parsed_bytes),
+        ("Optimized", totals.optimized_bytes),
+        ("Arenas", totals.arenas_bytes),
+        ("Optimized JSON", totals.optimized_json_bytes),
+        ("Jinterners", totals.jinterners_bytes),
+    ]
+    .into_iter()
+    .filter_map(|(name, bytes)| Some((name, bytes?)))
+    .collect();
+    let max = sizes.iter().map(|(_, bytes)| *bytes).max().unwrap_or(0);
+
+    writeln!(html, "<h2>Size breakdown</h2>").unwrap();
+    writeln!(
+        html,
+        "<table><tr><th></th><th>Bytes</th><th>Relative size</th><th></th></tr>"
+    )
+    .unwrap();
+    for (name, bytes) in sizes {
+        writeln!(
+            html,
+            "<tr><td>{name}</td><td>{bytes}</td><td>{}</td><td class=\"chart\">{}</td></tr>",
+            percent(bytes, totals.input_bytes),
+            bar(bytes, max, ""),
+        )
+        .unwrap();
+    }
+    writeln!(html, "</table>").unwrap();
+}
+
+fn render_interners(html: &mut String, stats: &StatsReport) {
+    let total: usize = stats.interners.iter().map(|x| x.bytes).sum();
+    let max = stats.interners.iter().map(|x| x.bytes).max().unwrap_or(0);
+
+    writeln!(html, "<h2>Interners</h2>").unwrap();
+    writeln!(
+        html,
+        "<table><tr><th>Interner</th><th>Objects</th><th>Items</th><th>Bytes</th><th>Bytes/object</th><th>Share</th><th></th></tr>"
+    )
+    .unwrap();
+    for interner in &stats.interners {
+        let items = match interner.items {
+            Some(items) => items.to_string(),
+            None => String::new(),
+        };
+        writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td>{items}</td><td>{}</td><td>{:.02}</td><td>{}</td><td class=\"chart\">{}</td></tr>",
+            escape(interner.name),
+            interner.objects,
+            interner.bytes,
+            interner.bytes as f64 / interner.objects as f64,
+            percent(interner.bytes, total),
+            bar(interner.bytes, max, ""),
+        )
+        .unwrap();
+    }
+    writeln!(html, "</table>").unwrap();
+}
+
+// Sizes of each format, with one bar per codec, relative to the input size.
+fn render_sizes(
+    html: &mut String,
+    variant: &str,
+    codecs: &[&str],
+    formats: &[&FormatStats],
+    input_bytes: usize,
+) {
+    let max = formats
+        .iter()
+        .flat_map(|x| codec_stats(x))
+        .map(|x| x.encoded_size)
+        .max()
+        .unwrap_or(0);
+
+    writeln!(html, "<h2>Sizes: {}</h2>", variant_title(variant)).unwrap();
+    writeln!(
+        html,
+        "<table><tr><th>Format</th><th>Codec</th><th>Bytes</th><th>Relative size</th><th></th></tr>"
+    )
+    .unwrap();
+    for format in formats {
+        for (codec, stats) in codecs.iter().zip(codec_stats(format)) {
+            writeln!(
+                html,
+                "<tr><td>{}</td><td>{codec}</td><td>{}</td><td>{}</td><td class=\"chart\">{}</td></tr>",
+                escape(format.format),
+                stats.encoded_size,
+                percent(stats.encoded_size, input_bytes),
+                bar(stats.encoded_size, max, ""),
+            )
+            .unwrap();
+        }
+    }
+    writeln!(html, "</table>").unwrap();
+}
+
+// Encoding and decoding times of each format, as two bars per codec.
+fn render_times(html: &mut String, variant: &str, codecs: &[&str], formats: &[&FormatStats]) {
+    let max = formats
+        .iter()
+        .flat_map(|x| codec_stats(x))
+        .map(|x| x.encode.median.max(x.decode.median).as_micros() as usize)
+        .max()
+        .unwrap_or(0);
+
+    writeln!(html, "<h2>Times: {}</h2>", variant_title(variant)).unwrap();
+    writeln!(
+        html,
+        "<table><tr><th>Format</th><th>Codec</th><th>Encode</th><th>Decode</th><th></th></tr>"
+    )
+    .unwrap();
+    for format in formats {
+        for (codec, stats) in codecs.iter().zip(codec_stats(format)) {
+            writeln!(
+                html,
+                "<tr><td>{}</td><td>{codec}</td><td>{:.02} ms</td><td>{:.02} ms</td><td class=\"chart\">{}<br>{}</td></tr>",
+                escape(format.format),
+                stats.encode.median.as_secs_f64() * 1000.0,
+                stats.decode.median.as_secs_f64() * 1000.0,
+                bar(stats.encode.median.as_micros() as usize, max, ""),
+                bar(stats.decode.median.as_micros() as usize, max, "alt"),
+            )
+            .unwrap();
+        }
+    }
+    writeln!(html, "</table>").unwrap();
+}
+
+fn codec_stats(format: &FormatStats) -> [&CodecStats; 5] {
+    let stats = &format.stats;
+    [
+        &stats.serialized,
+        &stats.gzip,
+        &stats.xz,
+        &stats.brotli,
+        &stats.zstd,
+    ]
+}
+
+fn variant_title(variant: &str) -> &str {
+    match variant {
+        "optimized" => "optimized schema",
+        "front_coded" => "optimized schema with front-coded strings",
+        "json" => "interned JSON",
+        "json_optimized" => "interned JSON with optimized interners",
+        _ => variant,
+    }
+}
+
+fn bar(value: usize, max: usize, class: &str) -> String {
+    let width = if max == 0 {
+        0.0
+    } else {
+        value as f64 * 100.0 / max as f64
+    };
+    format!("<span class=\"bar {class}\" style=\"width: {width:.02}%\"></span>")
+}
+
+fn percent(value: usize, total: usize) -> String {
+    format!("{:.02}%", value as f64 * 100.0 / total as f64)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
@@ -0,0 +1,107 @@
+// Streaming serialization of the optimized database. Each snapshot is appended to the output file
+// as soon as it's interned, so that only the arenas need to be kept in memory. The arenas are
+// written once all the inputs are processed, followed by a trailer containing their offset.
+//
+// File layout:
+// - the binary header with the version of the database,
+// - a sequence of records in the order in which they were processed, each made of its length as a
+//   little-endian u32 and a bincode-encoded `(path, data, provenance)` tuple where `data` is the
+//   handle of the snapshot in the arenas, followed by a checksum,
+// - the arenas, encoded with bincode, followed by a checksum,
+// - a trailer: the offset of the arenas after the header, as a little-endian u64, followed by a
+//   checksum.
+
+use crate::checksum::{self, ChecksumWriter, CHECKSUM_LEN};
+use crate::provenance::Provenance;
+use crate::schema::optimized::{Arenas, Data};
+use crate::schema::{Disruptions, Schema};
+use crate::version;
+use blazinterner::Interned;
+use std::fs::File;
+use std::io::{BufWriter, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Snapshot along with the path of the input file that it was parsed from, and its provenance if
+/// it was recorded.
+pub type Record<S = Disruptions> = (
+    PathBuf,
+    Interned<<S as Schema>::Optimized>,
+    Option<Provenance>,
+);
+
+pub struct StreamWriter {
+    file: Mutex<BufWriter<File>>,
+}
+
+impl StreamWriter {
+    pub fn create(path: &Path) -> std::io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(&version::header())?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Appends a snapshot to the file. This can be called concurrently from multiple threads.
+    pub fn write(
+        &self,
+        path: &Path,
+        data: Interned<Data>,
+        provenance: Option<Provenance>,
+    ) -> std::io::Result<()> {
+        // Encode outside of the lock, to only serialize the actual write between threads.
+        let mut record = vec![0; 4];
+        bincode::serialize_into(&mut record, &(path, data, provenance))
+            .map_err(std::io::Error::other)?;
+        let len = u32::try_from(record.len() - 4).map_err(std::io::Error::other)?;
+        record[..4].copy_from_slice(&len.to_le_bytes());
+        checksum::seal(&mut record, 0);
+        self.file.lock().unwrap().write_all(&record)
+    }
+
+    /// Writes the arenas and the trailer, returning the total size of the file.
+    pub fn finish(self, arenas: &Arenas) -> Result<u64, Box<dyn std::error::Error>> {
+        let mut file = self.file.into_inner().unwrap();
+        let arenas_offset = file.stream_position()? - version::HEADER_LEN as u64;
+        let mut writer = ChecksumWriter::new(&mut file);
+        bincode::serialize_into(&mut writer, arenas)?;
+        writer.finish()?;
+        file.write_all(&checksum::sealed(arenas_offset.to_le_bytes().to_vec()))?;
+        let total_bytes = file.stream_position()?;
+        file.into_inner()?.sync_all()?;
+        Ok(total_bytes)
+    }
+}
+
+/// Reads back a file written by [`StreamWriter`]. Records are returned in the order in which they
+/// were written.
+pub fn read(bytes: &[u8]) -> Result<(Arenas, Vec<Record>), Box<dyn std::error::Error>> {
+    let (body, trailer) = version::split_header(bytes)?
+        .split_last_chunk::<{ 8 + CHECKSUM_LEN }>()
+        .ok_or("Stream database is too short to contain a trailer")?;
+    let trailer = checksum::verify(trailer, "the trailer")?;
+    let arenas_offset = u64::from_le_bytes(trailer.try_into().unwrap()) as usize;
+    if arenas_offset > body.len() {
+        return Err(format!("Invalid arenas offset in stream database: {arenas_offset}").into());
+    }
+    let (mut records, arenas) = body.split_at(arenas_offset);
+
+    let arenas = bincode::deserialize(checksum::verify(arenas, "the arenas")?)?;
+    let mut values = Vec::new();
+    while let Some((len, _)) = records.split_first_chunk::<4>() {
+        let i = values.len();
+        let record_len = 4 + u32::from_le_bytes(*len) as usize + CHECKSUM_LEN;
+        if record_len > records.len() {
+            return Err(format!("Record {i} is truncated in stream database").into());
+        }
+        let (record, rest) = records.split_at(record_len);
+        let record = checksum::verify(record, format_args!("record {i}"))?;
+        values.push(bincode::deserialize(&record[4..])?);
+        records = rest;
+    }
+    if !records.is_empty() {
+        return Err(format!("Record {} is truncated in stream database", values.len()).into());
+    }
+    Ok((arenas, values))
+}
@@ -0,0 +1,146 @@
+// Time spent in each phase of the ingestion of input files, to find out which phase dominates the
+// runtime of a build. Files are ingested in parallel, so the times add up the work of all threads
+// rather than the elapsed time.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Phase of the ingestion of an input file.
+#[derive(Debug, Clone, Copy)]
+pub enum Phase {
+    /// Reading the file from disk or from an archive.
+    Read,
+    /// Parsing the JSON into the source schema.
+    Parse,
+    /// Converting the source schema into the optimized one, and interning it.
+    Convert,
+    /// Checking the converted data against the source, for the sampled files.
+    Verify,
+    /// Estimating the heap size of the parsed and converted data.
+    Estimate,
+    /// Parsing and interning the file as generic JSON.
+    Json,
+}
+
+impl Phase {
+    const ALL: [Phase; 6] = [
+        Phase::Read,
+        Phase::Parse,
+        Phase::Convert,
+        Phase::Verify,
+        Phase::Estimate,
+        Phase::Json,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Phase::Read => "read",
+            Phase::Parse => "parse",
+            Phase::Convert => "convert",
+            Phase::Verify => "verify",
+            Phase::Estimate => "estimate",
+            Phase::Json => "json",
+        }
+    }
+}
+
+#[derive(Default)]
+struct FileTiming {
+    path: PathBuf,
+    bytes: usize,
+    durations: [Duration; Phase::ALL.len()],
+}
+
+impl FileTiming {
+    fn total(&self) -> Duration {
+        self.durations.iter().sum()
+    }
+}
+
+/// Time spent in each phase, for each of the ingested files.
+#[derive(Default)]
+pub struct Timings(Mutex<Vec<FileTiming>>);
+
+impl Timings {
+    /// Starts timing the phases of the given file, which are recorded once the returned timer is
+    /// dropped, including when the file fails at some phase.
+    pub fn file(&self, path: &Path) -> FileTimer<'_> {
+        FileTimer {
+            timings: self,
+            timing: FileTiming {
+                path: path.to_owned(),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Prints the time spent in each phase across all files, along with the throughput of input
+    /// bytes per thread, followed by the slowest files.
+    pub fn print_summary(self, outlier_count: usize) {
+        let mut files = self.0.into_inner().unwrap();
+        if files.is_empty() {
+            return;
+        }
+
+        let total: Duration = files.iter().map(FileTiming::total).sum();
+        let total_bytes: usize = files.iter().map(|file| file.bytes).sum();
+        println!(
+            "Time spent per phase over {} files, summed across threads ({total:.2?} in total):",
+            files.len()
+        );
+        for (i, phase) in Phase::ALL.iter().enumerate() {
+            let phase_total: Duration = files.iter().map(|file| file.durations[i]).sum();
+            let slowest = files.iter().max_by_key(|file| file.durations[i]).unwrap();
+            println!(
+                "- {:<8} {phase_total:>10.2?} ({:>5.02}%) | {:>9.02} MB/s | mean {:>9.2?} per file | max {:>9.2?} for {:?}",
+                phase.name(),
+                phase_total.as_secs_f64() * 100.0 / total.as_secs_f64(),
+                total_bytes as f64 / (1_000_000.0 * phase_total.as_secs_f64()),
+                phase_total / files.len() as u32,
+                slowest.durations[i],
+                slowest.path,
+            );
+        }
+
+        files.sort_unstable_by_key(|file| std::cmp::Reverse(file.total()));
+        println!("Slowest files:");
+        for file in files.iter().take(outlier_count) {
+            let phases = Phase::ALL
+                .iter()
+                .zip(&file.durations)
+                .map(|(phase, duration)| format!("{} {duration:.2?}", phase.name()))
+                .collect::<Vec<_>>()
+                .join(", ");
+            println!("- {:?}: {:.2?} ({phases})", file.path, file.total());
+        }
+    }
+}
+
+/// Timer of the phases of a single file.
+pub struct FileTimer<'a> {
+    timings: &'a Timings,
+    timing: FileTiming,
+}
+
+impl FileTimer<'_> {
+    /// Sets the size of the file, once read.
+    pub fn set_bytes(&mut self, bytes: usize) {
+        self.timing.bytes = bytes;
+    }
+
+    /// Runs the given function, adding the time it takes to the given phase.
+    pub fn time<T>(&mut self, phase: Phase, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.timing.durations[phase as usize] += start.elapsed();
+        result
+    }
+}
+
+impl Drop for FileTimer<'_> {
+    fn drop(&mut self) {
+        let timing = std::mem::take(&mut self.timing);
+        self.timings.0.lock().unwrap().push(timing);
+    }
+}
@@ -0,0 +1,417 @@
+// Versioning of the serialized databases. Each database records the version of its layout, i.e. of
+// the optimized schema and of the arenas, so that files written by an older build can be upgraded
+// when loading them, and files of unknown versions are rejected with a clear error instead of
+// failing to deserialize halfway, or deserializing into garbage.
+//
+// The serde formats start with the version, as the first element of a `(version, database)` tuple.
+// The other formats start with a binary header made of the magic bytes `RIDB` and the version.
+//
+// Since version 8, the header also records the timezone of the local datetimes, which the timestamps
+// are relative to: the tuple becomes `(version, timezone, database)` and the binary header holds the
+// name of the timezone after the version. A database is rejected when loaded with another timezone,
+// which would shift all its local datetimes. Older databases are read in the configured timezone.
+//
+// When the layout changes:
+// - bump `CURRENT_VERSION`,
+// - keep the types of the previous layout that changed in a `vN` module,
+// - add a variant to `Layout` decoding them, and a step to `Layout::upgrade` converting them into
+//   the next version.
+
+use crate::schema::optimized::timezone;
+use crate::Database;
+use chrono_tz::Tz;
+use serde::de::{self, SeqAccess, Visitor};
+use serde::ser::SerializeTuple;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{self, Display};
+
+/// Version of the databases written by this build.
+pub const CURRENT_VERSION: u32 = 8;
+// Oldest version that can still be upgraded to the current one.
+const OLDEST_VERSION: u32 = 1;
+
+// First version that records the timezone.
+const TIMEZONE_VERSION: u32 = 8;
+
+const MAGIC: &[u8; 4] = b"RIDB";
+/// Length of the binary header. The payload that follows stays 16-byte aligned, as required by rkyv
+/// archives.
+pub const HEADER_LEN: usize = 48;
+// Offset of the timezone name in the binary header, padded with zeros. The longest IANA names are
+// about 32 bytes long.
+const TIMEZONE_OFFSET: usize = 8;
+
+/// Error returned for a database of a version that this build can't read.
+#[derive(Debug)]
+pub struct UnsupportedVersion(pub u32);
+
+impl Display for UnsupportedVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let version = self.0;
+        if version > CURRENT_VERSION {
+            write!(
+                f,
+                "database version {version} was written by a newer build, this build supports versions {OLDEST_VERSION} to {CURRENT_VERSION}"
+            )
+        } else {
+            write!(
+                f,
+                "database version {version} is too old to be upgraded, this build supports versions {OLDEST_VERSION} to {CURRENT_VERSION}"
+            )
+        }
+    }
+}
+
+impl std::error::Error for UnsupportedVersion {}
+
+/// Error returned for a database whose local datetimes aren't in the configured timezone.
+#[derive(Debug)]
+pub enum TimezoneMismatch {
+    /// The database was built with another timezone.
+    Other(Tz),
+    /// The recorded timezone isn't known to this build.
+    Unknown(String),
+}
+
+impl Display for TimezoneMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimezoneMismatch::Other(recorded) => write!(
+                f,
+                "database was built with the {recorded} timezone but the {} timezone is configured, pass `--timezone {recorded}` to read it",
+                timezone(),
+            ),
+            TimezoneMismatch::Unknown(name) => {
+                write!(f, "database was built with an unknown timezone {name:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TimezoneMismatch {}
+
+/// Checks that the timezone recorded in a database is the configured one.
+pub fn check_timezone(name: &str) -> Result<(), TimezoneMismatch> {
+    let recorded: Tz = name
+        .parse()
+        .map_err(|_| TimezoneMismatch::Unknown(name.to_owned()))?;
+    if recorded == timezone() {
+        Ok(())
+    } else {
+        Err(TimezoneMismatch::Other(recorded))
+    }
+}
+
+fn check(version: u32) -> Result<(), UnsupportedVersion> {
+    if (OLDEST_VERSION..=CURRENT_VERSION).contains(&version) {
+        Ok(())
+    } else {
+        Err(UnsupportedVersion(version))
+    }
+}
+
+// Database as laid out in each supported version. The arenas hold many hash tables, so layouts are
+// boxed to keep the variants small.
+enum Layout {
+    V1(Box<v1::Database>),
+    V2(Box<v2::Database>),
+    V3(Box<v3::Database>),
+    V4(Box<v4::Database>),
+    V5(Box<v5::Database>),
+    V6(Box<v6::Database>),
+    V7(Box<Database>),
+    V8(Box<Database>),
+}
+
+mod v1 {
+    use crate::schema::optimized::{self, v1};
+    use blazinterner::Interned;
+    use serde::Deserialize;
+    use std::path::PathBuf;
+
+    // Version 1 stored the application periods of each disruption inline, rather than as a handle
+    // to an interned set.
+    #[derive(Deserialize)]
+    pub struct Database {
+        pub arenas: v1::Arenas,
+        pub datas: Vec<Interned<optimized::Data>>,
+        pub paths: Vec<PathBuf>,
+    }
+}
+
+mod v2 {
+    use crate::schema::optimized::{self, v2};
+    use blazinterner::Interned;
+    use serde::Deserialize;
+    use std::path::PathBuf;
+
+    // Version 2 stored the tags of each disruption inline, rather than as a handle to an interned
+    // set of strings.
+    #[derive(Deserialize)]
+    pub struct Database {
+        pub arenas: v2::Arenas,
+        pub datas: Vec<Interned<optimized::Data>>,
+        pub paths: Vec<PathBuf>,
+    }
+}
+
+mod v3 {
+    use crate::schema::optimized::{self, v3};
+    use blazinterner::Interned;
+    use serde::Deserialize;
+    use std::path::PathBuf;
+
+    // Version 3 stored the mode of lines and the severity of disruptions as interned strings,
+    // rather than as known values with a fallback to interned strings.
+    #[derive(Deserialize)]
+    pub struct Database {
+        pub arenas: v3::Arenas,
+        pub datas: Vec<Interned<optimized::Data>>,
+        pub paths: Vec<PathBuf>,
+    }
+}
+
+mod v4 {
+    use crate::schema::optimized::{self, v4};
+    use blazinterner::Interned;
+    use serde::Deserialize;
+    use std::path::PathBuf;
+
+    // Version 4 stored the last update of each disruption inline, rather than as a handle to an
+    // interned timestamp.
+    #[derive(Deserialize)]
+    pub struct Database {
+        pub arenas: v4::Arenas,
+        pub datas: Vec<Interned<optimized::Data>>,
+        pub paths: Vec<PathBuf>,
+    }
+}
+
+mod v5 {
+    use crate::schema::optimized::{self, v5};
+    use blazinterner::Interned;
+    use serde::Deserialize;
+    use std::path::PathBuf;
+
+    // Version 5 sorted the disruptions and lines of each snapshot, and the impacted objects of each
+    // line, rather than keeping them in the order of the source. Upgraded databases keep the sorted
+    // order, so `verify` reports the snapshots whose input files had them in another order.
+    #[derive(Deserialize)]
+    pub struct Database {
+        pub arenas: v5::Arenas,
+        pub datas: Vec<Interned<optimized::Data>>,
+        pub paths: Vec<PathBuf>,
+    }
+}
+
+mod v6 {
+    use crate::schema::optimized::{self, Arenas};
+    use blazinterner::Interned;
+    use serde::Deserialize;
+    use std::path::PathBuf;
+
+    // Version 6 didn't record the provenance of the snapshots.
+    #[derive(Deserialize)]
+    pub struct Database {
+        pub arenas: Arenas,
+        pub datas: Vec<Interned<optimized::Data>>,
+        pub paths: Vec<PathBuf>,
+    }
+}
+
+impl Layout {
+    // Decodes the next element of the sequence as a database of the given version.
+    fn decode<'de, A: SeqAccess<'de>>(version: u32, seq: &mut A) -> Result<Option<Self>, A::Error> {
+        match version {
+            1 => Ok(seq.next_element()?.map(|x| Layout::V1(Box::new(x)))),
+            2 => Ok(seq.next_element()?.map(|x| Layout::V2(Box::new(x)))),
+            3 => Ok(seq.next_element()?.map(|x| Layout::V3(Box::new(x)))),
+            4 => Ok(seq.next_element()?.map(|x| Layout::V4(Box::new(x)))),
+            5 => Ok(seq.next_element()?.map(|x| Layout::V5(Box::new(x)))),
+            6 => Ok(seq.next_element()?.map(|x| Layout::V6(Box::new(x)))),
+            7 => Ok(seq.next_element()?.map(|x| Layout::V7(Box::new(x)))),
+            8 => Ok(seq.next_element()?.map(|x| Layout::V8(Box::new(x)))),
+            _ => Err(de::Error::custom(UnsupportedVersion(version))),
+        }
+    }
+
+    // Upgrades the database one version at a time, up to the current version.
+    fn upgrade(self) -> Database {
+        match self {
+            Layout::V1(database) => Layout::V2(Box::new(v2::Database {
+                arenas: database.arenas.into(),
+                datas: database.datas,
+                paths: database.paths,
+            }))
+            .upgrade(),
+            Layout::V2(database) => Layout::V3(Box::new(v3::Database {
+                arenas: database.arenas.into(),
+                datas: database.datas,
+                paths: database.paths,
+            }))
+            .upgrade(),
+            Layout::V3(database) => Layout::V4(Box::new(v4::Database {
+                arenas: database.arenas.into(),
+                datas: database.datas,
+                paths: database.paths,
+            }))
+            .upgrade(),
+            Layout::V4(database) => Layout::V5(Box::new(v5::Database {
+                arenas: database.arenas.into(),
+                datas: database.datas,
+                paths: database.paths,
+            }))
+            .upgrade(),
+            Layout::V5(database) => Layout::V6(Box::new(v6::Database {
+                arenas: database.arenas.into(),
+                datas: database.datas,
+                paths: database.paths,
+            }))
+            .upgrade(),
+            Layout::V6(database) => Layout::V7(Box::new(Database {
+                provenance: vec![None; database.datas.len()],
+                arenas: database.arenas,
+                datas: database.datas,
+                paths: database.paths,
+            }))
+            .upgrade(),
+            // Version 7 had the same layout, but didn't record the timezone.
+            Layout::V7(database) => Layout::V8(database).upgrade(),
+            Layout::V8(database) => *database,
+        }
+    }
+}
+
+/// Serializes a database along with the current version, for the serde formats.
+pub struct Versioned<'a>(pub &'a Database);
+
+impl Serialize for Versioned<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut tuple = serializer.serialize_tuple(3)?;
+        tuple.serialize_element(&CURRENT_VERSION)?;
+        tuple.serialize_element(timezone().name())?;
+        tuple.serialize_element(self.0)?;
+        tuple.end()
+    }
+}
+
+/// Database deserialized from any supported version with the serde formats, and upgraded to the
+/// current version.
+pub struct Upgraded(pub Database);
+
+impl<'de> Deserialize<'de> for Upgraded {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_tuple(3, UpgradedVisitor)
+    }
+}
+
+struct UpgradedVisitor;
+
+impl<'de> Visitor<'de> for UpgradedVisitor {
+    type Value = Upgraded;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a database preceded by its version")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let version = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let mut len = 1;
+        if version >= TIMEZONE_VERSION {
+            let name: String = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+            check_timezone(&name).map_err(de::Error::custom)?;
+            len += 1;
+        }
+        let layout = Layout::decode(version, &mut seq)?
+            .ok_or_else(|| de::Error::invalid_length(len, &self))?;
+        Ok(Upgraded(layout.upgrade()))
+    }
+}
+
+/// Returns the binary header of the current version, with the configured timezone.
+pub fn header() -> [u8; HEADER_LEN] {
+    let mut header = [0; HEADER_LEN];
+    header[..4].copy_from_slice(MAGIC);
+    header[4..8].copy_from_slice(&CURRENT_VERSION.to_le_bytes());
+    let name = timezone().name().as_bytes();
+    header[TIMEZONE_OFFSET..TIMEZONE_OFFSET + name.len()].copy_from_slice(name);
+    header
+}
+
+/// Checks the binary header, returning the payload that follows it. These formats are read in
+/// place or decoded piecewise, so only the current version can be read.
+pub fn split_header(bytes: &[u8]) -> Result<&[u8], Box<dyn std::error::Error>> {
+    let (magic, rest) = bytes
+        .split_first_chunk::<4>()
+        .ok_or("Database is too short to contain a header")?;
+    if magic != MAGIC {
+        return Err("Invalid magic bytes, the database was written without a version".into());
+    }
+    let version = u32::from_le_bytes(
+        *rest
+            .first_chunk::<4>()
+            .ok_or("Database is too short to contain a header")?,
+    );
+    check(version)?;
+    if version != CURRENT_VERSION {
+        return Err(format!(
+            "database version {version} can only be upgraded from the serde formats (bincode, CBOR, JSON, Postcard and MessagePack)"
+        )
+        .into());
+    }
+    let (header, payload) = bytes
+        .split_first_chunk::<HEADER_LEN>()
+        .ok_or("Database is too short to contain a header")?;
+    let name = &header[TIMEZONE_OFFSET..];
+    let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+    check_timezone(&String::from_utf8_lossy(name)).map_err(|e| e.to_string())?;
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::optimized::Arenas;
+
+    #[test]
+    fn timezone_is_checked_on_load() {
+        let database = Database::new(Arenas::default());
+        let mut json = serde_json::to_value(Versioned(&database)).unwrap();
+        assert_eq!(json[0], CURRENT_VERSION);
+        assert_eq!(json[1], timezone().name());
+        let Upgraded(decoded) = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(decoded, database);
+
+        json[1] = "America/New_York".into();
+        let error = serde_json::from_value::<Upgraded>(json.clone())
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(
+            error.contains("pass `--timezone America/New_York`"),
+            "{error}"
+        );
+        json[1] = "Mars/Olympus_Mons".into();
+        let error = serde_json::from_value::<Upgraded>(json).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            r#"database was built with an unknown timezone "Mars/Olympus_Mons""#,
+        );
+
+        // Version 7 didn't record the timezone.
+        let v7 = serde_json::json!([7, database]);
+        let Upgraded(upgraded) = serde_json::from_value(v7).unwrap();
+        assert_eq!(upgraded, database);
+
+        let mut header = header();
+        assert!(split_header(&header).unwrap().is_empty());
+        header[TIMEZONE_OFFSET..].fill(0);
+        header[TIMEZONE_OFFSET..TIMEZONE_OFFSET + 3].copy_from_slice(b"UTC");
+        let error = split_header(&header).unwrap_err().to_string();
+        assert!(error.contains("pass `--timezone UTC`"), "{error}");
+    }
+}
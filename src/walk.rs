@@ -0,0 +1,158 @@
+// Traversal of the input directories, visiting each input file (or archive entry) on the thread
+// pool so that files are parsed in parallel.
+
+use crate::cli::{InputFormat, ParseArgs};
+use crate::input;
+use crate::input::{ArchiveFormat, InputFile};
+use crate::progress::Progress;
+use crate::provenance::Provenance;
+use crate::schema::{Extras, Schema};
+use paralight::prelude::*;
+use std::fmt::Debug;
+use std::fs::{read_dir, DirEntry};
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, info_span, warn};
+
+// Input directories of a command, along with how to traverse them.
+pub struct Inputs<'a> {
+    pub thread_pool: &'a RayonThreadPool<'a>,
+    pub dirs: &'a [PathBuf],
+    pub format: InputFormat,
+    pub quiet: bool,
+    pub parsing: ParseArgs,
+    pub provenance: bool,
+}
+
+impl<'a> Inputs<'a> {
+    pub fn new(
+        thread_pool: &'a RayonThreadPool<'a>,
+        dirs: &'a [PathBuf],
+        format: InputFormat,
+        quiet: bool,
+    ) -> Self {
+        Self {
+            thread_pool,
+            dirs,
+            format,
+            quiet,
+            parsing: ParseArgs::default(),
+            provenance: false,
+        }
+    }
+
+    // Sets how the input files are parsed into the source schema.
+    pub fn parsing(self, parsing: ParseArgs) -> Self {
+        Self { parsing, ..self }
+    }
+
+    // Sets whether the provenance of the ingested snapshots is recorded.
+    pub fn recording_provenance(self, provenance: bool) -> Self {
+        Self { provenance, ..self }
+    }
+
+    // Returns the provenance of a snapshot parsed from the given contents of an input file, if it's
+    // recorded.
+    pub fn provenance(
+        &self,
+        input: &InputFile,
+        bytes: &[u8],
+    ) -> std::io::Result<Option<Provenance>> {
+        self.provenance
+            .then(|| Provenance::new(input, bytes))
+            .transpose()
+    }
+
+    pub fn parse<'b, S: Schema>(
+        &self,
+        bytes: &'b [u8],
+    ) -> serde_json::Result<(S::Source<'b>, Extras)> {
+        self.parsing.parse::<S>(bytes)
+    }
+
+    // Calls the callback on every file of the input directories in parallel, displaying the
+    // progress. Archives are traversed as directories, and the callback is called on each record
+    // of NDJSON files.
+    pub fn visit(
+        &self,
+        callback: &(impl Fn(&InputFile) -> std::io::Result<()> + Sync),
+    ) -> std::io::Result<()> {
+        let progress = Progress::scan(self.dirs, self.quiet)?;
+        for directory in self.dirs {
+            info!(?directory, "Visiting directory");
+            visit_dirs(self, &progress, directory, callback)?;
+        }
+        progress.finish();
+        Ok(())
+    }
+
+    // Calls the callback on the file, or in parallel on each of its records if it's an NDJSON file.
+    fn visit_file(
+        &self,
+        input: &InputFile,
+        callback: &(impl Fn(&InputFile) -> std::io::Result<()> + Sync),
+    ) -> std::io::Result<()> {
+        if !input.is_ndjson(self.format) {
+            return callback(input);
+        }
+        let bytes = input.read()?;
+        input::ndjson_records(input.path(), &bytes)
+            .par_iter()
+            .with_thread_pool(self.thread_pool)
+            .try_for_each(|(path, line)| callback(&InputFile::record(path, line)))
+    }
+}
+
+fn visit_dirs(
+    inputs: &Inputs,
+    progress: &Progress,
+    dir: impl AsRef<Path> + Debug,
+    callback: &(impl Fn(&InputFile) -> std::io::Result<()> + Sync),
+) -> std::io::Result<()> {
+    // Sort entries by path for reproducibility.
+    let mut entries: Vec<DirEntry> = read_dir(dir)?.collect::<Result<_, _>>()?;
+    entries.sort_unstable_by_key(|x| x.path());
+    entries
+        .par_iter()
+        .with_thread_pool(inputs.thread_pool)
+        .try_for_each(|entry| -> std::io::Result<()> {
+            let mut path = entry.path();
+            let mut file_type = entry.file_type()?;
+
+            // Resolve symbolic links.
+            if file_type.is_symlink() {
+                let target = std::fs::canonicalize(&path)?;
+                debug!(?path, ?target, "Resolved symlink");
+                path = target;
+                file_type = path.metadata()?.file_type();
+            }
+
+            if file_type.is_dir() {
+                visit_dirs(inputs, progress, path, callback)?;
+            } else if let Some(format) = file_type
+                .is_file()
+                .then(|| ArchiveFormat::detect(&path))
+                .flatten()
+            {
+                let _span = info_span!("archive", ?path).entered();
+                input::read_archive(&path, format, |entries| {
+                    entries
+                        .par_iter()
+                        .with_thread_pool(inputs.thread_pool)
+                        .try_for_each(|(entry_path, contents)| {
+                            inputs.visit_file(&InputFile::archived(entry_path, contents), callback)
+                        })
+                })?;
+                progress.inc(path.metadata()?.len());
+            } else if file_type.is_file() {
+                let _span = info_span!("file", ?path).entered();
+                inputs.visit_file(&InputFile::on_disk(&path), callback)?;
+                progress.inc(path.metadata()?.len());
+            } else {
+                warn!(?path, ?file_type, "Skipping path of unknown file type");
+            }
+
+            Ok(())
+        })?;
+
+    Ok(())
+}